@@ -7,6 +7,8 @@ pub struct BundleHandler {
     decoder: Box<dyn Decoder>,
     decode_ctx: Arc<dyn DecodeCtx>,
     request_tx: Sender<Request>,
+    /// Shared with the connection, which resets it each tick
+    bytes_received: Arc<AtomicU64>,
 }
 
 impl BundleHandler {
@@ -15,34 +17,34 @@ impl BundleHandler {
         decoder: Box<dyn Decoder>,
         decode_ctx: Arc<dyn DecodeCtx>,
         request_tx: Sender<Request>,
+        bytes_received: Arc<AtomicU64>,
     ) -> Self {
         Self {
             connection_key,
             decoder,
             decode_ctx,
             request_tx,
+            bytes_received,
         }
     }
 }
 
 impl InboundBundleHandler for BundleHandler {
     fn handle(&mut self, data: &[u8]) {
-        match self
+        self.bytes_received.fetch_add(data.len() as u64, SeqCst);
+        let results = self
             .decoder
-            .decode(self.decode_ctx.as_ref(), data.to_owned())
-        {
-            Ok(requests) => {
-                requests.into_iter().for_each(|request| {
-                    if let Err(e) = self.request_tx.send(request) {
-                        warn!("failed to handle data for {:?}: {}", self.connection_key, e);
-                    }
-                });
-            }
-            Err(e) => {
+            .decode(self.decode_ctx.as_ref(), data.to_owned());
+        for result in results {
+            let request = result.unwrap_or_else(|e| {
                 warn!(
-                    "can't decode inbound bundle: {} on {:?}",
+                    "can't decode inbound message: {} on {:?}",
                     e, self.connection_key
                 );
+                Request::Malformed(e)
+            });
+            if let Err(e) = self.request_tx.send(request) {
+                warn!("failed to handle data for {:?}: {}", self.connection_key, e);
             }
         }
     }
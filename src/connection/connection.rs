@@ -1,10 +1,46 @@
 use super::*;
+use std::collections::VecDeque;
+
+/// How many malformed messages in a row a client can send before it is disconnected
+const MAX_MALFORMED_MESSAGES: u32 = 10;
+/// Seconds clients disconnected for sending too many malformed messages are told to wait before
+/// reconnecting, so a broken client doesn't hammer the server
+const MALFORMED_MESSAGES_RETRY_AFTER: u64 = 30;
+/// How many events in a row can fail to encode before the connection is assumed to be broken and
+/// closed
+const MAX_ENCODE_FAILURES: u64 = 10;
+/// How many bundles can wait for a blocked session before the client is assumed to be stuck and
+/// is disconnected
+const MAX_UNSENT_BUNDLES: usize = 10_000;
 
 new_key_type! {
     /// A handle to a client connection
     pub struct ConnectionKey;
 }
 
+impl ConnectionKey {
+    /// The number used to identify this connection to clients and the game
+    pub fn to_id(self) -> u64 {
+        slotmap::KeyData::from(self).as_ffi()
+    }
+
+    /// The inverse of to_id(), the result may not refer to a real connection
+    pub fn from_id(id: u64) -> Self {
+        slotmap::KeyData::from_ffi(id).into()
+    }
+}
+
+/// Number of bytes sent to and received from a client
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Bandwidth {
+    pub total_sent: u64,
+    pub total_received: u64,
+    /// Sent during the most recent tick
+    pub tick_sent: u64,
+    /// Received during the most recent tick
+    pub tick_received: u64,
+}
+
 /// Manages a single client connection. Both the session type (TCP, WebRTC, etc) and the format
 /// (JSON, etc) are abstracted.
 pub trait Connection {
@@ -16,18 +52,56 @@ pub trait Connection {
     fn flush(&mut self, handler: &mut dyn RequestHandler) -> Result<(), ()>;
     /// Called just after connection is removed from the connection map before it is dropped
     fn finalize(&mut self, handler: &mut dyn RequestHandler);
+    /// Human-readable info about the client, such as the session type and address
+    fn description(&self) -> String;
+    /// Tell the client why and start closing the connection, the next flush() will return Err
+    fn close(&self, reason: String);
+    /// Bytes sent and received, updated on each flush()
+    fn bandwidth(&self) -> Bandwidth;
+    /// Removes entities exists() returns false for from the object map, returns how many
+    fn purge_destroyed_entities(&self, exists: &dyn Fn(EntityKey) -> bool) -> usize;
 }
 
 /// The main Connection implementation
+/// A "ping" action on the root object is answered by the connection itself with a "pong" signal
+/// carrying the same value, so clients can measure their round trip time. The client does not
+/// need to subscribe to "pong".
+/// If an object ID store is set, an "identify" action on the root object with a stable client
+/// identity makes the connection's object IDs match earlier connections with that identity.
+/// A Root request is answered with the root object, so clients don't need to assume its ID.
+/// If debug actions are enabled, an "object_map" action on the root object is answered with an
+/// "object_map" signal listing the [object ID, entity] pairs the connection currently knows.
 pub struct ConnectionImpl {
     self_key: ConnectionKey,
+    root_entity: EntityKey,
     encoder: Box<dyn Encoder>,
     obj_map: Arc<dyn ObjectMap>,
     session: Mutex<Box<dyn Session>>,
     request_rx: Receiver<Request>,
     pending_get_requests: HashSet<(EntityKey, String)>,
     subscriptions: HashMap<(EntityKey, String), Box<dyn Any>>,
+    /// Options subscriptions were made with (if not the default), also used for the initial value
+    subscription_options: HashMap<(EntityKey, String), SubscribeOptions>,
+    /// Malformed messages received since the last well-formed one
+    malformed_message_count: u32,
+    /// Events in a row that failed to encode and were skipped
+    encode_failure_count: AtomicU64,
     should_close: AtomicBool,
+    /// Bytes sent since the last flush
+    bytes_sent: AtomicU64,
+    /// Bytes received since the last flush, added to by the bundle handler
+    bytes_received: Arc<AtomicU64>,
+    bandwidth: Bandwidth,
+    config: ConnectionConfig,
+    /// Transient send errors since the last successful send
+    send_failures: AtomicU64,
+    /// Game time as of the last process_requests(), which doesn't change until the next tick
+    game_time: f64,
+    /// Encoded events to send on the next flush
+    pending_bundles: Mutex<Vec<Vec<u8>>>,
+    /// Bundles the session would have blocked on, in order. Retried on each flush, and anything
+    /// sent in the mean time waits behind them.
+    unsent_bundles: Mutex<VecDeque<Vec<u8>>>,
 }
 
 impl ConnectionImpl {
@@ -35,6 +109,7 @@ impl ConnectionImpl {
         self_key: ConnectionKey,
         root_entity: EntityKey,
         session_builder: Box<dyn SessionBuilder>,
+        config: ConnectionConfig,
     ) -> Result<Self, Box<dyn Error>> {
         let obj_map = Arc::new(ObjectMapImpl::new());
         let root_obj_id = obj_map.get_or_create_object(root_entity);
@@ -45,24 +120,91 @@ impl ConnectionImpl {
                 self_key, root_obj_id
             );
         }
-        // TODO: let the client choose the format in the first message
-        let (encoder, decoder) = json_protocol_impls();
+        let (encoder, mut decoder) = protocol_impls_for(session_builder.format(), config.delimiter);
+        decoder.set_limits(config.decode_limits);
         let (request_tx, request_rx) = channel();
-        let handler = BundleHandler::new(self_key, decoder, obj_map.clone(), request_tx);
+        let bytes_received = Arc::new(AtomicU64::new(0));
+        let handler = BundleHandler::new(
+            self_key,
+            decoder,
+            obj_map.clone(),
+            request_tx,
+            bytes_received.clone(),
+        );
         let session = session_builder.build(Box::new(handler))?;
         info!("created connection {:?} on {:?}", self_key, session);
         Ok(Self {
             self_key,
+            root_entity,
             encoder,
             obj_map,
             session: Mutex::new(session),
             request_rx,
             pending_get_requests: HashSet::new(),
             subscriptions: HashMap::new(),
+            subscription_options: HashMap::new(),
+            malformed_message_count: 0,
+            encode_failure_count: AtomicU64::new(0),
             should_close: AtomicBool::new(false),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received,
+            bandwidth: Bandwidth::default(),
+            config,
+            send_failures: AtomicU64::new(0),
+            game_time: 0.0,
+            pending_bundles: Mutex::new(Vec::new()),
+            unsent_bundles: Mutex::new(VecDeque::new()),
         })
     }
 
+    fn identify(&self, store: Arc<dyn ObjectIdStore>, identity: Value) -> RequestResult<()> {
+        let identity: String = Into::<RequestResult<String>>::into(identity)?;
+        validate_identity(&identity)?;
+        self.obj_map.persist(store, identity)
+    }
+
+    /// The object map as [object ID, entity] pairs. Entities are sent as their debug
+    /// representation, since encoding them would just turn them back into object IDs.
+    fn object_map_value(&self) -> Value {
+        Value::Array(
+            self.obj_map
+                .objects()
+                .into_iter()
+                .map(|(object, entity)| {
+                    Value::Array(vec![
+                        Value::Integer(object as i64),
+                        format!("{:?}", entity).into(),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    /// Removes and returns the get requests to respond to this flush, respecting
+    /// max_values_per_flush. Properties of the root object are sent first since clients generally
+    /// need them to find everything else, then the rest in order of the handler's send priority
+    /// (ex nearby objects first).
+    fn take_get_requests(&mut self, handler: &dyn RequestHandler) -> Vec<(EntityKey, String)> {
+        let mut requests: Vec<(EntityKey, String)> = self.pending_get_requests.drain().collect();
+        if self.config.max_values_per_flush > 0 && requests.len() > self.config.max_values_per_flush
+        {
+            let mut priorities = HashMap::new();
+            for (entity, _) in &requests {
+                priorities.entry(*entity).or_insert_with(|| {
+                    if *entity == self.root_entity {
+                        f64::INFINITY
+                    } else {
+                        handler.send_priority(self.self_key, *entity)
+                    }
+                });
+            }
+            requests.sort_by(|(a, _), (b, _)| priorities[b].total_cmp(&priorities[a]));
+            self.pending_get_requests
+                .extend(requests.split_off(self.config.max_values_per_flush));
+        }
+        requests
+    }
+
     fn process_request_method(
         &mut self,
         handler: &mut dyn RequestHandler,
@@ -71,6 +213,11 @@ impl ConnectionImpl {
         method: RequestMethod,
     ) -> RequestResult<()> {
         use std::collections::hash_map::Entry;
+        if self.config.read_only {
+            if let RequestMethod::Action(_) | RequestMethod::Set(_) = method {
+                return Err(BadRequest("server is read-only".into()));
+            }
+        }
         match method {
             RequestMethod::Action(value) => {
                 handler.fire_action(self.self_key, entity, property, value)?;
@@ -81,22 +228,67 @@ impl ConnectionImpl {
             RequestMethod::Get => {
                 // it doesn't matter if it's already there or not, it's not an error to make two
                 // get requests but it will only result in one response.
-                self.pending_get_requests.insert((entity, property.into()));
+                let key = (entity, property.to_string());
+                if self.config.max_pending_gets > 0
+                    && self.pending_get_requests.len() >= self.config.max_pending_gets
+                    && !self.pending_get_requests.contains(&key)
+                {
+                    return Err(BadRequest(format!(
+                        "too many get requests, at most {} can wait for a tick",
+                        self.config.max_pending_gets
+                    )));
+                }
+                self.pending_get_requests.insert(key);
             }
-            RequestMethod::Subscribe => {
+            RequestMethod::GetOnce => {
+                let value = handler.get_property(self.self_key, entity, property)?;
+                self.send_event(Event::value(entity, property.into(), value));
+            }
+            RequestMethod::Subscribe(options) => {
+                if self.config.max_subscriptions > 0
+                    && self.subscriptions.len() >= self.config.max_subscriptions
+                {
+                    return Err(BadRequest(format!(
+                        "can not have more than {} subscriptions",
+                        self.config.max_subscriptions
+                    )));
+                }
                 match self.subscriptions.entry((entity, property.to_string())) {
                     Entry::Occupied(_) => {
                         return Err(BadRequest("tried to subscribe multiple times".into()))
                     }
                     Entry::Vacant(entry) => {
-                        let sub = handler.subscribe(self.self_key, entity, property)?;
+                        let sub = if options.is_default() {
+                            handler.subscribe(self.self_key, entity, property)
+                        } else {
+                            handler.subscribe_with_options(
+                                self.self_key,
+                                entity,
+                                property,
+                                &options,
+                            )
+                        }
+                        .map_err(|e| match e {
+                            // Without this the client wouldn't find out, since the following
+                            // get request ignores errors (it's expected to fail for signals)
+                            BadName(_, name) => BadRequest(format!(
+                                "can not subscribe to unknown property or signal {:?}",
+                                name
+                            )),
+                            e => e,
+                        })?;
                         entry.insert(sub);
+                        if !options.is_default() {
+                            self.subscription_options
+                                .insert((entity, property.into()), options);
+                        }
                         self.pending_get_requests.insert((entity, property.into()));
                     }
                 }
             }
             RequestMethod::Unsubscribe => {
                 let key = (entity, property.to_string());
+                self.subscription_options.remove(&key);
                 match self.subscriptions.remove(&key) {
                     Some(entry) => handler.unsubscribe(entry)?,
                     None => {
@@ -116,21 +308,122 @@ impl ConnectionImpl {
         if self.should_close.load(SeqCst) {
             return;
         }
+        let mut unsent = self.unsent_bundles.lock().unwrap();
+        if unsent.is_empty() && self.yeet_bundle(&data) {
+            return;
+        }
+        if unsent.len() >= MAX_UNSENT_BUNDLES {
+            warn!(
+                "closing {:?} after {} bundles were blocked",
+                self.self_key,
+                unsent.len()
+            );
+            self.should_close.store(true, SeqCst);
+            self.session.lock().unwrap().close();
+            return;
+        }
+        unsent.push_back(data);
+    }
+
+    /// Sends the bundle, closing the connection if that fails. Returns false if the session would
+    /// block or had a transient error, in which case the bundle should be retried later. Only
+    /// max_send_failures transient errors in a row are retried.
+    fn yeet_bundle(&self, data: &[u8]) -> bool {
+        use std::io::ErrorKind::{Interrupted, TimedOut, WouldBlock};
         let mut session = self.session.lock().unwrap();
-        if let Err(e) = session.yeet_bundle(&data) {
-            warn!("closing session due to problem sending bundle: {}", e);
+        let result = session.yeet_bundle(data);
+        let kind = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.downcast_ref::<std::io::Error>())
+            .map(std::io::Error::kind);
+        match result {
+            Ok(()) => {
+                self.bytes_sent.fetch_add(data.len() as u64, SeqCst);
+                self.send_failures.store(0, SeqCst);
+                true
+            }
+            Err(_) if kind == Some(WouldBlock) => false,
+            Err(e)
+                if (kind == Some(Interrupted) || kind == Some(TimedOut))
+                    && self.send_failures.fetch_add(1, SeqCst) + 1
+                        < self.config.max_send_failures =>
+            {
+                warn!("retrying bundle after transient problem sending it: {}", e);
+                false
+            }
+            Err(e) => {
+                warn!("closing session due to problem sending bundle: {}", e);
+                self.should_close.store(true, SeqCst);
+                session.close();
+                true
+            }
+        }
+    }
+
+    /// Lets the session send data it's holding on to, closing the connection if that fails
+    fn flush_session(&self) {
+        let mut session = self.session.lock().unwrap();
+        if let Err(e) = session.flush() {
+            warn!("closing session due to problem flushing it: {}", e);
             self.should_close.store(true, SeqCst);
             session.close();
         }
     }
+
+    fn retry_unsent_bundles(&self) {
+        let mut unsent = self.unsent_bundles.lock().unwrap();
+        while let Some(data) = unsent.front() {
+            if self.should_close.load(SeqCst) || !self.yeet_bundle(data) {
+                return;
+            }
+            unsent.pop_front();
+        }
+    }
 }
 
 impl Connection for ConnectionImpl {
     fn process_requests(&mut self, handler: &mut dyn RequestHandler) {
         use std::sync::mpsc::TryRecvError;
+        self.game_time = handler.time();
         loop {
-            match self.request_rx.try_recv() {
+            let request = self.request_rx.try_recv();
+            if let Ok(Request::Method(..) | Request::Root) = request {
+                self.malformed_message_count = 0;
+            }
+            match request {
+                Ok(Request::Method(entity, property, RequestMethod::Action(token)))
+                    if entity == self.root_entity && property == "ping" =>
+                {
+                    // Answered here instead of by the game so the pong goes out right away
+                    // rather than waiting for the game to update
+                    self.send_event(Event::signal(entity, "pong".to_string(), token));
+                }
+                Ok(Request::Method(entity, property, RequestMethod::Action(_)))
+                    if entity == self.root_entity
+                        && property == "object_map"
+                        && self.config.debug_actions =>
+                {
+                    self.send_event(Event::signal(entity, property, self.object_map_value()));
+                }
+                Ok(Request::Method(entity, property, RequestMethod::Action(identity)))
+                    if entity == self.root_entity
+                        && property == "identify"
+                        && self.config.object_id_store.is_some() =>
+                {
+                    let store = self.config.object_id_store.clone().unwrap();
+                    if let Err(e) = self.identify(store, identity) {
+                        warn!("{:?} failed to identify: {}", self.self_key, e);
+                        self.send_event(Event::Error(e.to_string()));
+                    }
+                }
                 Ok(Request::Method(entity, property, method)) => {
+                    if let Some(recorder) = &self.config.recorder {
+                        recorder.record(
+                            self.self_key,
+                            &Request::Method(entity, property.clone(), method.clone()),
+                        );
+                    }
                     if let Err(e) =
                         self.process_request_method(handler, entity, &property, method.clone())
                     {
@@ -138,8 +431,25 @@ impl Connection for ConnectionImpl {
                             "failed to process {:?} on {:?}::{:?}.{}: {}",
                             method, self.self_key, entity, property, e
                         );
-                        // TODO: send error to client
+                        self.send_event(Event::Error(e.to_string()));
+                    }
+                }
+                Ok(Request::Root) => self.send_event(Event::Root(self.root_entity)),
+                Ok(Request::Malformed(e)) => {
+                    self.malformed_message_count += 1;
+                    if self.malformed_message_count > MAX_MALFORMED_MESSAGES {
+                        warn!(
+                            "closing {:?} after {} malformed messages in a row",
+                            self.self_key, self.malformed_message_count
+                        );
+                        self.send_event(Event::FatalError(
+                            format!("too many malformed messages, last was: {}", e),
+                            Some(MALFORMED_MESSAGES_RETRY_AFTER),
+                        ));
+                        self.should_close.store(true, SeqCst);
+                        return;
                     }
+                    self.send_event(Event::Error(e.to_string()));
                 }
                 Ok(Request::Close) | Err(TryRecvError::Disconnected) => {
                     self.should_close.store(true, SeqCst);
@@ -151,34 +461,84 @@ impl Connection for ConnectionImpl {
     }
 
     fn send_event(&self, event: Event) {
-        let buffer = match self
+        let destroyed = match event {
+            Event::Destroyed(entity) => Some(entity),
+            _ => None,
+        };
+        let event = if self.config.timestamp_events {
+            Event::Timestamped(self.game_time, Box::new(event))
+        } else {
+            event
+        };
+        match self
             .encoder
             .encode_event(self.obj_map.as_encode_ctx(), &event)
         {
-            Ok(buffer) => buffer,
+            Ok(buffer) => {
+                // Reporting a failure isn't a sign things are working again
+                if !matches!(event, Event::Error(_)) {
+                    self.encode_failure_count.store(0, SeqCst);
+                }
+                match self.config.flush_strategy.timing(event.kind()) {
+                    FlushTiming::Immediate => self.queue_message(buffer),
+                    FlushTiming::PerTick => self.pending_bundles.lock().unwrap().push(buffer),
+                }
+            }
             Err(e) => {
-                error!("failed to encode {:?}: {}", event, e);
-                self.should_close.store(true, SeqCst);
-                return;
+                // A single bad event is skipped, but if they keep coming something is wrong
+                let failures = self.encode_failure_count.fetch_add(1, SeqCst) + 1;
+                if failures > MAX_ENCODE_FAILURES {
+                    error!(
+                        "closing {:?} after {} events failed to encode, last was {:?}: {}",
+                        self.self_key, failures, event, e
+                    );
+                    self.should_close.store(true, SeqCst);
+                } else {
+                    error!("failed to encode {:?}, skipping it: {}", event, e);
+                    // Don't report failing to encode an error, it would probably fail again
+                    if !matches!(event, Event::Error(_)) {
+                        self.send_event(Event::Error(format!("failed to encode event: {}", e)));
+                    }
+                }
             }
-        };
-        self.queue_message(buffer);
+        }
 
-        if let Event::Destroyed(entity) = event {
+        if let Some(entity) = destroyed {
             self.obj_map.remove_entity(entity);
         }
     }
 
     fn flush(&mut self, handler: &mut dyn RequestHandler) -> Result<(), ()> {
-        let get_requests = std::mem::replace(&mut self.pending_get_requests, HashSet::new());
-        for (entity, property) in get_requests.into_iter() {
+        if !self.should_close.load(SeqCst) {
+            self.flush_session();
+        }
+        self.retry_unsent_bundles();
+        for (entity, property) in self.take_get_requests(handler) {
             // When a client subscribes to a signal, we have no way of knowing it's a signal and
             // not a property, so it goes in the pending get requests list and is processed here.
             // That fails, and so we simply ignore errors here. There's probably a better way.
-            if let Ok(value) = handler.get_property(self.self_key, entity, &property) {
+            let value = match self.subscription_options.get(&(entity, property.clone())) {
+                Some(options) => {
+                    handler.get_property_with_options(self.self_key, entity, &property, options)
+                }
+                None => handler.get_property(self.self_key, entity, &property),
+            };
+            if let Ok(value) = value {
                 self.send_event(Event::value(entity, property, value));
             }
         }
+        let pending = std::mem::take(&mut *self.pending_bundles.lock().unwrap());
+        for buffer in pending {
+            self.queue_message(buffer);
+        }
+        let sent = self.bytes_sent.swap(0, SeqCst);
+        let received = self.bytes_received.swap(0, SeqCst);
+        self.bandwidth = Bandwidth {
+            total_sent: self.bandwidth.total_sent + sent,
+            total_received: self.bandwidth.total_received + received,
+            tick_sent: sent,
+            tick_received: received,
+        };
         if self.should_close.load(SeqCst) {
             Err(())
         } else {
@@ -186,10 +546,29 @@ impl Connection for ConnectionImpl {
         }
     }
 
+    fn description(&self) -> String {
+        format!("{:?}", self.session.lock().unwrap())
+    }
+
+    fn close(&self, reason: String) {
+        info!("closing {:?}: {}", self.self_key, reason);
+        self.send_event(Event::FatalError(reason, None));
+        self.should_close.store(true, SeqCst);
+    }
+
+    fn bandwidth(&self) -> Bandwidth {
+        self.bandwidth
+    }
+
+    fn purge_destroyed_entities(&self, exists: &dyn Fn(EntityKey) -> bool) -> usize {
+        self.obj_map.retain_entities(exists)
+    }
+
     fn finalize(&mut self, handler: &mut dyn RequestHandler) {
         let mut session = self.session.lock().unwrap();
         info!("finalized connection {:?} on {:?}", self.self_key, session,);
         session.close();
+        self.subscription_options.clear();
         for ((entity, prop), subscription) in self.subscriptions.drain() {
             if let Err(e) = handler.unsubscribe(subscription) {
                 warn!(
@@ -205,6 +584,9 @@ impl Connection for ConnectionImpl {
 mod test_common {
     use super::*;
 
+    /// MockEncoder always fails to encode events on members with this name
+    pub const UNENCODABLE: &str = "unencodable";
+
     pub struct MockEncoder {
         should_error: bool,
     }
@@ -222,7 +604,11 @@ mod test_common {
             _: &dyn EncodeCtx,
             event: &Event,
         ) -> Result<Vec<u8>, Box<dyn Error>> {
-            if self.should_error {
+            let unencodable = match event {
+                Event::Method(_, member, _, _) => member == UNENCODABLE,
+                _ => false,
+            };
+            if self.should_error || unencodable {
                 Err("MockEncoder error".into())
             } else {
                 Ok(format!("{:?}", event).as_bytes().into())
@@ -249,11 +635,19 @@ mod test_common {
             panic!("unexpected call");
         }
 
-        fn as_encode_ctx(&self) -> &dyn EncodeCtx {
-            self
+        fn persist(&self, _: Arc<dyn ObjectIdStore>, _: String) -> RequestResult<()> {
+            panic!("unexpected call");
         }
 
-        fn as_decode_ctx(&self) -> &dyn DecodeCtx {
+        fn retain_entities(&self, _: &dyn Fn(EntityKey) -> bool) -> usize {
+            panic!("unexpected call");
+        }
+
+        fn objects(&self) -> Vec<(ObjectId, EntityKey)> {
+            panic!("unexpected call");
+        }
+
+        fn as_encode_ctx(&self) -> &dyn EncodeCtx {
             self
         }
     }
@@ -267,13 +661,25 @@ mod test_common {
         let (request_tx, request_rx) = channel();
         let conn = ConnectionImpl {
             self_key: ConnectionKey::null(),
+            root_entity: mock_keys(1)[0],
             encoder: Box::new(encoder),
             obj_map: Arc::new(MockObjectMap),
             session: Mutex::new(Box::new(session.clone())),
             request_rx,
             pending_get_requests: HashSet::new(),
             subscriptions: HashMap::new(),
+            subscription_options: HashMap::new(),
+            malformed_message_count: 0,
+            encode_failure_count: AtomicU64::new(0),
             should_close: AtomicBool::new(false),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: Arc::new(AtomicU64::new(0)),
+            bandwidth: Bandwidth::default(),
+            config: ConnectionConfig::default(),
+            send_failures: AtomicU64::new(0),
+            game_time: 0.0,
+            pending_bundles: Mutex::new(Vec::new()),
+            unsent_bundles: Mutex::new(VecDeque::new()),
         };
         (conn, session, request_tx)
     }
@@ -299,13 +705,131 @@ mod event_tests {
     }
 
     #[test]
-    fn is_closed_when_encoding_fails() {
-        let (mut conn, _, _tx) = setup(true, false);
+    fn events_carry_game_time_when_timestamps_enabled() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        conn.config.timestamp_events = true;
         let e = mock_keys(1);
         let ev = Event::signal(e[0], "foo".to_string(), 12.5.into());
         let mut handler = MockRequestHandler::new(Ok(()));
+        handler.set_time(31.0);
         conn.process_requests(&mut handler);
+        conn.send_event(ev.clone());
+        conn.flush(&mut handler).unwrap();
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::Timestamped(31.0, Box::new(ev))
+        )]);
+    }
+
+    #[test]
+    fn immediate_events_are_sent_before_flush() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        let mut strategy = FlushStrategy::new(FlushTiming::PerTick);
+        strategy.set(EventKind::Signal, FlushTiming::Immediate);
+        conn.config.flush_strategy = strategy;
+        let e = mock_keys(1);
+        let update = Event::update(e[0], "foo".to_string(), 1.5.into());
+        let signal = Event::signal(e[0], "bar".to_string(), 2.5.into());
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.send_event(update.clone());
+        conn.send_event(signal.clone());
+        sesh.assert_bundles_eq(vec![format!("{:?}", signal)]);
+        conn.flush(&mut handler).unwrap();
+        sesh.assert_bundles_eq(vec![format!("{:?}", signal), format!("{:?}", update)]);
+    }
+
+    #[test]
+    fn blocked_bundles_are_sent_in_order_on_flush() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        let e = mock_keys(1);
+        let ev0 = Event::update(e[0], "foo".to_string(), 1.5.into());
+        let ev1 = Event::update(e[0], "bar".to_string(), 2.5.into());
+        let mut handler = MockRequestHandler::new(Ok(()));
+        sesh.set_would_block(1);
+        conn.send_event(ev0.clone());
+        conn.send_event(ev1.clone());
+        sesh.assert_bundles_eq(vec![]);
+        conn.flush(&mut handler).unwrap();
+        sesh.assert_bundles_eq(vec![format!("{:?}", ev0), format!("{:?}", ev1)]);
+        assert!(!sesh.is_closed());
+    }
+
+    #[test]
+    fn session_is_flushed_even_with_nothing_to_send() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.flush(&mut handler).unwrap();
+        conn.flush(&mut handler).unwrap();
+        assert_eq!(sesh.flush_count(), 2);
+        sesh.assert_bundles_eq(vec![]);
+    }
+
+    #[test]
+    fn counts_sent_bytes() {
+        let (mut conn, _sesh, _tx) = setup(false, false);
+        let e = mock_keys(1);
+        let ev = Event::signal(e[0], "foo".to_string(), 12.5.into());
+        let len = format!("{:?}", ev).len() as u64;
+        let mut handler = MockRequestHandler::new(Ok(()));
         conn.send_event(ev);
+        conn.flush(&mut handler).unwrap();
+        assert_eq!(conn.bandwidth().total_sent, len);
+        assert_eq!(conn.bandwidth().tick_sent, len);
+        conn.flush(&mut handler).unwrap();
+        assert_eq!(conn.bandwidth().total_sent, len);
+        assert_eq!(conn.bandwidth().tick_sent, 0);
+    }
+
+    #[test]
+    fn is_closed_when_encoding_keeps_failing() {
+        let (mut conn, _, _tx) = setup(true, false);
+        let e = mock_keys(1);
+        let ev = Event::signal(e[0], "foo".to_string(), 12.5.into());
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.process_requests(&mut handler);
+        conn.send_event(ev.clone());
+        assert!(conn.flush(&mut handler).is_ok());
+        for _ in 0..MAX_ENCODE_FAILURES {
+            conn.send_event(ev.clone());
+        }
+        assert!(conn.flush(&mut handler).is_err());
+    }
+
+    #[test]
+    fn event_that_fails_to_encode_is_skipped() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        let e = mock_keys(1);
+        let ev = Event::signal(e[0], "foo".to_string(), 12.5.into());
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.process_requests(&mut handler);
+        conn.send_event(Event::signal(e[0], UNENCODABLE.to_string(), ().into()));
+        conn.send_event(ev.clone());
+        assert!(conn.flush(&mut handler).is_ok());
+        sesh.assert_bundles_eq(vec![
+            format!(
+                "{:?}",
+                Event::Error("failed to encode event: MockEncoder error".to_string())
+            ),
+            format!("{:?}", ev),
+        ]);
+    }
+
+    #[test]
+    fn only_encode_failures_in_a_row_close_connection() {
+        let (mut conn, _sesh, _tx) = setup(false, false);
+        let e = mock_keys(1);
+        let unencodable = Event::signal(e[0], UNENCODABLE.to_string(), ().into());
+        let ev = Event::signal(e[0], "foo".to_string(), 12.5.into());
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.process_requests(&mut handler);
+        for _ in 0..MAX_ENCODE_FAILURES * 2 {
+            conn.send_event(unencodable.clone());
+            conn.send_event(ev.clone());
+        }
+        assert!(conn.flush(&mut handler).is_ok());
+        for _ in 0..=MAX_ENCODE_FAILURES {
+            conn.send_event(unencodable.clone());
+        }
         assert!(conn.flush(&mut handler).is_err());
     }
 
@@ -320,6 +844,54 @@ mod event_tests {
         assert!(conn.flush(&mut handler).is_err());
     }
 
+    #[test]
+    fn survives_transient_send_failures_until_limit() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        conn.config.max_send_failures = 3;
+        let e = mock_keys(1);
+        let ev = Event::signal(e[0], "foo".to_string(), 12.5.into());
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.process_requests(&mut handler);
+        sesh.set_transient_errors(2);
+        // The first try is when the event is sent, and each flush tries again
+        conn.send_event(ev.clone());
+        assert!(conn.flush(&mut handler).is_ok());
+        sesh.assert_bundles_eq(Vec::new());
+        assert!(conn.flush(&mut handler).is_ok());
+        sesh.assert_bundles_eq(vec![format!("{:?}", ev)]);
+        sesh.set_transient_errors(3);
+        conn.send_event(ev);
+        assert!(conn.flush(&mut handler).is_ok());
+        assert!(!sesh.is_closed());
+        assert!(conn.flush(&mut handler).is_err());
+        assert!(sesh.is_closed());
+    }
+
+    #[test]
+    fn zero_max_send_failures_closes_on_first_failure() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        conn.config.max_send_failures = 0;
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.process_requests(&mut handler);
+        sesh.set_transient_errors(1);
+        conn.send_event(Event::signal(e[0], "foo".to_string(), 12.5.into()));
+        assert!(conn.flush(&mut handler).is_err());
+        assert!(sesh.is_closed());
+    }
+
+    #[test]
+    fn fatal_send_error_closes_despite_retries() {
+        let (mut conn, sesh, _tx) = setup(false, true);
+        conn.config.max_send_failures = 3;
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.process_requests(&mut handler);
+        conn.send_event(Event::signal(e[0], "foo".to_string(), 12.5.into()));
+        assert!(conn.flush(&mut handler).is_err());
+        assert!(sesh.is_closed());
+    }
+
     #[test]
     fn does_not_keep_sending_events_after_sending_fails() {
         let (mut conn, sesh, _tx) = setup(false, true);
@@ -337,6 +909,19 @@ mod event_tests {
         sesh.assert_bundles_eq(vec![format!("{:?}", ev0)]);
     }
 
+    #[test]
+    fn close_sends_reason_and_results_in_flush_returning_err() {
+        let (mut conn, sesh, _tx) = setup(false, false);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        conn.process_requests(&mut handler);
+        conn.close("go away".to_string());
+        assert!(conn.flush(&mut handler).is_err());
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::FatalError("go away".to_string(), None)
+        )]);
+    }
+
     #[test]
     fn finalize_closes_session() {
         let (mut conn, session, _tx) = setup(false, true);
@@ -376,6 +961,49 @@ mod request_tests {
         handler.assert_requests_eq(vec![sub_rq, Request::get(e[0], "prop".to_string())]);
     }
 
+    #[test]
+    fn sub_request_is_passed_options() {
+        let (mut conn, _, tx) = setup(false, false);
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let mut properties = HashMap::new();
+        properties.insert("class".to_string(), Value::Text("ship".to_string()));
+        let options = SubscribeOptions {
+            filter: Some(EntityFilter::new(properties)),
+            ..SubscribeOptions::default()
+        };
+        let sub_rq = Request::subscribe_with_options(e[0], "prop".to_string(), options);
+        tx.send(sub_rq.clone()).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        handler.assert_requests_eq(vec![sub_rq, Request::get(e[0], "prop".to_string())]);
+    }
+
+    #[test]
+    fn subscriptions_past_max_are_rejected_until_one_is_freed() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        conn.config.max_subscriptions = 2;
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let sub = |name: &str| Request::subscribe(e[0], name.to_string());
+        tx.send(sub("a")).unwrap();
+        tx.send(sub("b")).unwrap();
+        tx.send(sub("c")).unwrap();
+        conn.process_requests(&mut handler);
+        handler.assert_requests_eq(vec![sub("a"), sub("b")]);
+        assert_eq!(sesh.bundles().len(), 1);
+        tx.send(Request::unsubscribe(e[0], "a".to_string()))
+            .unwrap();
+        tx.send(sub("c")).unwrap();
+        conn.process_requests(&mut handler);
+        handler.assert_requests_eq(vec![
+            sub("a"),
+            sub("b"),
+            Request::unsubscribe(e[0], "a".to_string()),
+            sub("c"),
+        ]);
+    }
+
     #[test]
     fn get_request_works() {
         let (mut conn, _, tx) = setup(false, false);
@@ -388,6 +1016,65 @@ mod request_tests {
         handler.assert_requests_eq(vec![rq]);
     }
 
+    #[test]
+    fn get_once_sends_one_value_without_subscribing() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let rq = Request::get_once(e[0], "prop".to_string());
+        tx.send(rq).unwrap();
+        conn.process_requests(&mut handler);
+        let value = Event::value(
+            e[0],
+            "prop".to_string(),
+            Value::Text("MockRequestHandler get response value".to_string()),
+        );
+        sesh.assert_bundles_eq(vec![format!("{:?}", value)]);
+        conn.flush(&mut handler).unwrap();
+        conn.flush(&mut handler).unwrap();
+        handler.assert_requests_eq(vec![Request::get(e[0], "prop".to_string())]);
+        sesh.assert_bundles_eq(vec![format!("{:?}", value)]);
+    }
+
+    #[test]
+    fn get_once_on_signal_reports_not_available() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let e = mock_keys(1);
+        let not_available = NotAvailable(e[0], "sig".to_string());
+        let mut handler = MockRequestHandler::new(Err(not_available.clone()));
+        tx.send(Request::get_once(e[0], "sig".to_string())).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::Error(not_available.to_string())
+        )]);
+    }
+
+    #[test]
+    fn gets_past_max_pending_are_rejected() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        conn.config.max_pending_gets = 2;
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let get = |name: &str| Request::get(e[0], name.to_string());
+        tx.send(get("a")).unwrap();
+        tx.send(get("b")).unwrap();
+        tx.send(get("a")).unwrap();
+        tx.send(get("c")).unwrap();
+        conn.process_requests(&mut handler);
+        assert_eq!(sesh.bundles().len(), 1);
+        conn.flush(&mut handler).unwrap();
+        let mut served = handler.requests();
+        served.sort_by_key(|rq| format!("{:?}", rq));
+        assert_eq!(served, vec![get("a"), get("b")]);
+        tx.send(get("c")).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        assert_eq!(handler.requests().last(), Some(&get("c")));
+        assert_eq!(sesh.bundles().len(), 4);
+    }
+
     #[test]
     fn does_not_sub_multiple_times_in_one_tick() {
         let (mut conn, _, tx) = setup(false, false);
@@ -455,6 +1142,330 @@ mod request_tests {
         ]);
     }
 
+    #[test]
+    fn set_is_rejected_when_read_only() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        conn.config.read_only = true;
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        tx.send(Request::set(e[0], "prop".to_string(), 7.into()))
+            .unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        handler.assert_requests_eq(vec![]);
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::Error(BadRequest("server is read-only".into()).to_string())
+        )]);
+    }
+
+    #[test]
+    fn action_is_rejected_when_read_only() {
+        let (mut conn, _, tx) = setup(false, false);
+        conn.config.read_only = true;
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        tx.send(Request::action(e[0], "act".to_string(), 7.into()))
+            .unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        handler.assert_requests_eq(vec![]);
+    }
+
+    #[test]
+    fn get_and_subscribe_work_when_read_only() {
+        let (mut conn, _, tx) = setup(false, false);
+        conn.config.read_only = true;
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let get_rq = Request::get(e[0], "foo".to_string());
+        let sub_rq = Request::subscribe(e[0], "bar".to_string());
+        tx.send(get_rq.clone()).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        tx.send(sub_rq.clone()).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        handler.assert_requests_eq(vec![get_rq, sub_rq, Request::get(e[0], "bar".to_string())]);
+    }
+
+    #[test]
+    fn initial_values_are_spread_across_flushes() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        conn.config.max_values_per_flush = 2;
+        let e = mock_keys(2);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        for name in &["a", "b", "c", "d", "e"] {
+            tx.send(Request::subscribe(e[1], name.to_string())).unwrap();
+        }
+        conn.process_requests(&mut handler);
+        let mut sent = Vec::new();
+        for _ in 0..3 {
+            conn.flush(&mut handler).unwrap();
+            let total = sesh.bundles().len();
+            sent.push(total - sent.iter().sum::<usize>());
+        }
+        assert_eq!(sent, vec![2, 2, 1]);
+        conn.flush(&mut handler).unwrap();
+        assert_eq!(sesh.bundles().len(), 5);
+    }
+
+    #[test]
+    fn root_values_are_sent_first() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        conn.config.max_values_per_flush = 1;
+        let e = mock_keys(2);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        tx.send(Request::get(e[1], "other".to_string())).unwrap();
+        tx.send(Request::get(e[0], "root".to_string())).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        handler.assert_requests_eq(vec![Request::get(e[0], "root".to_string())]);
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::value(
+                e[0],
+                "root".to_string(),
+                Value::Text("MockRequestHandler get response value".to_string())
+            )
+        )]);
+    }
+
+    #[test]
+    fn higher_priority_values_are_sent_first() {
+        let (mut conn, _, tx) = setup(false, false);
+        conn.config.max_values_per_flush = 1;
+        let e = mock_keys(4);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        handler.set_priority(e[1], -10.0);
+        handler.set_priority(e[2], 5.0);
+        handler.set_priority(e[3], -1.0);
+        for entity in &e[1..] {
+            tx.send(Request::get(*entity, "x".to_string())).unwrap();
+        }
+        tx.send(Request::get(e[0], "root".to_string())).unwrap();
+        conn.process_requests(&mut handler);
+        let gets = handler.requests().len();
+        for _ in 0..4 {
+            conn.flush(&mut handler).unwrap();
+        }
+        assert_eq!(
+            handler.requests()[gets..].to_vec(),
+            vec![
+                Request::get(e[0], "root".to_string()),
+                Request::get(e[2], "x".to_string()),
+                Request::get(e[3], "x".to_string()),
+                Request::get(e[1], "x".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn recorded_requests_replay_to_same_state() {
+        struct Counter(Element<i64>);
+
+        fn counter_state() -> (State, EntityKey) {
+            let mut state = State::new();
+            let entity = state.create_entity().unwrap();
+            state.install_component(entity, Counter(Element::new(0)));
+            RWConduit::new(
+                move |state| Ok(&state.component::<Counter>(entity)?.0),
+                move |state, value| Ok(state.component_mut::<Counter>(entity)?.0.set(value)),
+            )
+            .install_property(&mut state, entity, "count");
+            ActionConduit::new(move |state, amount: i64| {
+                let count = &mut state.component_mut::<Counter>(entity)?.0;
+                count.set(**count + amount);
+                Ok(())
+            })
+            .install_action(&mut state, entity, "add");
+            (state, entity)
+        }
+
+        fn count(state: &State, entity: EntityKey) -> i64 {
+            *state.component::<Counter>(entity).unwrap().0
+        }
+
+        let (mut conn, _, tx) = setup(false, false);
+        let buffer = SharedBuffer::default();
+        let recorder = Arc::new(RequestRecorder::new(Box::new(buffer.clone())));
+        conn.config.recorder = Some(recorder.clone());
+        let (mut state, entity) = counter_state();
+        let mut counts = Vec::new();
+
+        tx.send(Request::set(entity, "count".to_string(), 5.into()))
+            .unwrap();
+        tx.send(Request::action(entity, "add".to_string(), 2.into()))
+            .unwrap();
+        conn.process_requests(&mut state);
+        counts.push(count(&state, entity));
+        recorder.next_tick();
+        conn.process_requests(&mut state);
+        counts.push(count(&state, entity));
+        recorder.next_tick();
+        tx.send(Request::subscribe(entity, "count".to_string()))
+            .unwrap();
+        tx.send(Request::action(entity, "add".to_string(), 10.into()))
+            .unwrap();
+        conn.process_requests(&mut state);
+        counts.push(count(&state, entity));
+        conn.finalize(&mut state);
+        assert_eq!(counts, vec![7, 7, 17]);
+
+        let mut replay = RequestReplay::from_reader(&buffer.contents()[..]).unwrap();
+        let (mut replayed_state, replayed_entity) = counter_state();
+        assert_eq!(replayed_entity, entity);
+        let mut replayed_counts = Vec::new();
+        for _ in 0..3 {
+            replay.replay_tick(&mut replayed_state);
+            replayed_counts.push(count(&replayed_state, replayed_entity));
+        }
+        assert!(replay.is_done());
+        assert_eq!(replayed_counts, counts);
+    }
+
+    #[test]
+    fn ping_results_in_pong_with_same_token() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let root = mock_keys(1)[0];
+        let mut handler = MockRequestHandler::new(Ok(()));
+        tx.send(Request::action(
+            root,
+            "ping".to_string(),
+            Value::Text("abc".to_string()),
+        ))
+        .unwrap();
+        conn.process_requests(&mut handler);
+        // The pong must not wait for the flush at the end of the tick
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::signal(root, "pong".to_string(), Value::Text("abc".to_string()))
+        )]);
+        conn.flush(&mut handler).unwrap();
+        handler.assert_requests_eq(vec![]);
+    }
+
+    #[test]
+    fn object_map_action_lists_live_objects() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        conn.config.debug_actions = true;
+        conn.obj_map = Arc::new(ObjectMapImpl::new());
+        let e = mock_keys(3);
+        let o: Vec<ObjectId> = e
+            .iter()
+            .map(|entity| conn.obj_map.get_or_create_object(*entity))
+            .collect();
+        conn.purge_destroyed_entities(&|entity| entity != e[1]);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        tx.send(Request::action(e[0], "object_map".to_string(), Value::Null))
+            .unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        let pair = |object: ObjectId, entity: EntityKey| {
+            Value::Array(vec![
+                Value::Integer(object as i64),
+                format!("{:?}", entity).into(),
+            ])
+        };
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::signal(
+                e[0],
+                "object_map".to_string(),
+                Value::Array(vec![pair(o[0], e[0]), pair(o[2], e[2])])
+            )
+        )]);
+        handler.assert_requests_eq(vec![]);
+    }
+
+    #[test]
+    fn object_map_action_goes_to_handler_without_debug_actions() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let root = mock_keys(1)[0];
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let rq = Request::action(root, "object_map".to_string(), Value::Null);
+        tx.send(rq.clone()).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        sesh.assert_bundles_eq(vec![]);
+        handler.assert_requests_eq(vec![rq]);
+    }
+
+    #[test]
+    fn ping_on_other_object_goes_to_handler() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let e = mock_keys(2);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let rq = Request::action(e[1], "ping".to_string(), Value::Text("abc".to_string()));
+        tx.send(rq.clone()).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        sesh.assert_bundles_eq(vec![]);
+        handler.assert_requests_eq(vec![rq]);
+    }
+
+    #[test]
+    fn malformed_request_is_reported_and_skipped() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        let err = BadMessage("mock bad message".to_string());
+        let rq = Request::action(e[0], "act".to_string(), 7.into());
+        tx.send(Request::Malformed(err.clone())).unwrap();
+        tx.send(rq.clone()).unwrap();
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        sesh.assert_bundles_eq(vec![format!("{:?}", Event::Error(err.to_string()))]);
+        handler.assert_requests_eq(vec![rq]);
+    }
+
+    #[test]
+    fn closed_after_too_many_malformed_requests() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        for _ in 0..MAX_MALFORMED_MESSAGES {
+            tx.send(Request::Malformed(BadMessage("mock".to_string())))
+                .unwrap();
+        }
+        conn.process_requests(&mut handler);
+        conn.flush(&mut handler).unwrap();
+        tx.send(Request::Malformed(BadMessage("mock".to_string())))
+            .unwrap();
+        conn.process_requests(&mut handler);
+        assert!(conn.flush(&mut handler).is_err());
+        let last = sesh.bundles().pop().unwrap();
+        assert_eq!(
+            String::from_utf8(last).unwrap(),
+            format!(
+                "{:?}",
+                Event::FatalError(
+                    format!(
+                        "too many malformed messages, last was: {}",
+                        BadMessage("mock".to_string())
+                    ),
+                    Some(MALFORMED_MESSAGES_RETRY_AFTER)
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn malformed_request_count_resets_after_well_formed_request() {
+        let (mut conn, _, tx) = setup(false, false);
+        let e = mock_keys(1);
+        let mut handler = MockRequestHandler::new(Ok(()));
+        for _ in 0..3 {
+            for _ in 0..MAX_MALFORMED_MESSAGES {
+                tx.send(Request::Malformed(BadMessage("mock".to_string())))
+                    .unwrap();
+            }
+            tx.send(Request::action(e[0], "act".to_string(), 7.into()))
+                .unwrap();
+        }
+        conn.process_requests(&mut handler);
+        assert!(conn.flush(&mut handler).is_ok());
+    }
+
     #[test]
     fn close_request_results_in_flush_returning_err() {
         let (mut conn, _, tx) = setup(false, false);
@@ -496,4 +1507,36 @@ mod request_tests {
         conn.process_requests(&mut handler);
         conn.flush(&mut handler).unwrap();
     }
+
+    #[test]
+    fn subscribing_to_unknown_property_reports_error() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let mut state = State::new();
+        let entity = state.create_entity().unwrap();
+        tx.send(Request::subscribe(entity, "foo".to_string()))
+            .unwrap();
+        conn.process_requests(&mut state);
+        conn.flush(&mut state).unwrap();
+        sesh.assert_bundles_eq(vec![format!(
+            "{:?}",
+            Event::Error("can not subscribe to unknown property or signal \"foo\"".to_string())
+        )]);
+    }
+
+    #[test]
+    fn subscribing_to_signal_does_not_report_error() {
+        let (mut conn, sesh, tx) = setup(false, false);
+        let mut state = State::new();
+        let entity = state.create_entity().unwrap();
+        let mut signal = Signal::<i64>::new();
+        signal
+            .conduit(&state.notif_queue)
+            .install_signal(&mut state, entity, "foo");
+        tx.send(Request::subscribe(entity, "foo".to_string()))
+            .unwrap();
+        conn.process_requests(&mut state);
+        conn.flush(&mut state).unwrap();
+        sesh.assert_bundles_eq(vec![]);
+        conn.finalize(&mut state);
+    }
 }
@@ -1,4 +1,8 @@
 use super::*;
+use std::time::Instant;
+
+/// Seconds clients turned away because the server is full are told to wait before reconnecting
+const SERVER_FULL_RETRY_AFTER: u64 = 10;
 
 /// See try_to_build_connection for why this is needed
 struct StubConnection;
@@ -16,6 +20,18 @@ impl Connection for StubConnection {
     fn finalize(&mut self, _: &mut dyn RequestHandler) {
         error!("StubConnection::finalize() called");
     }
+    fn description(&self) -> String {
+        "StubConnection".to_string()
+    }
+    fn close(&self, _: String) {
+        error!("StubConnection::close() called");
+    }
+    fn bandwidth(&self) -> Bandwidth {
+        Bandwidth::default()
+    }
+    fn purge_destroyed_entities(&self, _: &dyn Fn(EntityKey) -> bool) -> usize {
+        0
+    }
 }
 
 struct NullRequestHandler;
@@ -49,9 +65,70 @@ impl RequestHandler for NullRequestHandler {
     ) -> RequestResult<Box<dyn Any>> {
         Ok(Box::new(()))
     }
+    fn get_property_with_options(
+        &self,
+        _: ConnectionKey,
+        _: EntityKey,
+        _: &str,
+        _: &SubscribeOptions,
+    ) -> RequestResult<Value> {
+        Ok(Value::Null)
+    }
+    fn subscribe_with_options(
+        &mut self,
+        _: ConnectionKey,
+        _: EntityKey,
+        _: &str,
+        _: &SubscribeOptions,
+    ) -> RequestResult<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
     fn unsubscribe(&mut self, _: Box<dyn Any>) -> RequestResult<()> {
         Ok(())
     }
+    fn take_disconnect_requests(&mut self) -> Vec<ConnectionKey> {
+        Vec::new()
+    }
+    fn take_reconcile_request(&mut self) -> bool {
+        false
+    }
+    fn take_connection_list_requests(&mut self) -> Vec<ConnectionKey> {
+        Vec::new()
+    }
+    fn connection_closed(&mut self, _: ConnectionKey) {}
+    fn entity_exists(&self, _: EntityKey) -> bool {
+        true
+    }
+    fn send_priority(&self, _: ConnectionKey, _: EntityKey) -> f64 {
+        0.0
+    }
+
+    fn time(&self) -> f64 {
+        0.0
+    }
+}
+
+/// How each connection is described to clients allowed to see the connection list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    /// The session type and peer address
+    pub address: String,
+    pub bandwidth: Bandwidth,
+}
+
+/// Sent as [ID, address, [total sent, total received], [tick sent, tick received]]
+impl From<ConnectionInfo> for Value {
+    fn from(info: ConnectionInfo) -> Self {
+        let bandwidth = info.bandwidth;
+        (
+            info.id,
+            info.address,
+            (bandwidth.total_sent, bandwidth.total_received),
+            (bandwidth.tick_sent, bandwidth.tick_received),
+        )
+            .into()
+    }
 }
 
 /// Holds all the active connections for a game. process_requests() should be called by the game
@@ -62,6 +139,18 @@ pub struct ConnectionCollection {
     new_session_rx: Receiver<Box<dyn SessionBuilder>>,
     max_connections: usize,
     set_max_connections: bool,
+    /// If set, sent to each new connection
+    server_info: Option<ServerInfo>,
+    /// Cloned into every new connection
+    connection_config: ConnectionConfig,
+    /// If set, connections not reached this long after flushing starts wait for the next tick
+    flush_budget: Option<Duration>,
+    /// Index of the connection to flush first on the next tick. Rotates each tick, and starts
+    /// at the first connection skipped for being over the budget if any were.
+    next_flush: usize,
+    /// Descriptions of connections finalize() hasn't finished with, shared so they can be
+    /// reported if shutdown hangs
+    unfinished: Arc<Mutex<Vec<String>>>,
 }
 
 impl ConnectionCollection {
@@ -69,6 +158,7 @@ impl ConnectionCollection {
         new_session_rx: Receiver<Box<dyn SessionBuilder>>,
         root_entity: EntityKey,
         max_connections: usize,
+        connection_config: ConnectionConfig,
     ) -> Self {
         Self {
             root_entity,
@@ -76,9 +166,25 @@ impl ConnectionCollection {
             new_session_rx,
             max_connections,
             set_max_connections: true,
+            server_info: None,
+            connection_config,
+            flush_budget: None,
+            next_flush: 0,
+            unfinished: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// Limit how long flushing can take each tick. At least one connection is always flushed, and
+    /// connections that don't fit go first on the next tick.
+    pub fn set_flush_budget(&mut self, budget: Duration) {
+        self.flush_budget = Some(budget);
+    }
+
+    /// Send the given info to every connection as soon as it is created
+    pub fn set_server_info(&mut self, info: ServerInfo) {
+        self.server_info = Some(info);
+    }
+
     /// Handle incoming connection requests and messages from clients on the current thread. Should
     /// be called at the start of each network tick.
     pub fn process_inbound_messages(&mut self, handler: &mut dyn RequestHandler) {
@@ -95,38 +201,131 @@ impl ConnectionCollection {
             self.set_max_connections = false;
         }
         // Build sessions for any new clients that are trying to connect
+        let conn_count = self.connections.len();
         while let Ok(session_builder) = self.new_session_rx.try_recv() {
             self.try_to_build_connection(session_builder);
-            handler
-                .set_property(
-                    ConnectionKey::null(),
-                    self.root_entity,
-                    "conn_count",
-                    Value::Integer(self.connections.len() as i64),
-                )
-                .or_log_error("setting connection count property");
+        }
+        if self.connections.len() != conn_count {
+            self.update_connection_count(handler);
         }
         // Process requests on all connections
         for connection in self.connections.values_mut() {
             connection.process_requests(handler);
         }
+        // Start closing any connections the game wants gone, they are removed on flush
+        for key in handler.take_disconnect_requests() {
+            match self.connections.get(key) {
+                Some(connection) => connection.close("disconnected by the server".to_string()),
+                None => warn!("can not disconnect {:?} as it does not exist", key),
+            }
+        }
+        if handler.take_reconcile_request() {
+            self.reconcile_object_maps(handler);
+        }
+        let list_requests = handler.take_connection_list_requests();
+        if !list_requests.is_empty() {
+            let list: Value = self.connection_list().into();
+            for key in list_requests {
+                match self.connections.get(key) {
+                    Some(connection) => connection.send_event(Event::signal(
+                        self.root_entity,
+                        "connections".to_string(),
+                        list.clone(),
+                    )),
+                    None => warn!(
+                        "can not send connection list to {:?} as it does not exist",
+                        key
+                    ),
+                }
+            }
+        }
     }
 
-    /// Called after game state has been fully updated before waiting for the next tick
-    pub fn flush_outbound_messages(&mut self, handler: &mut dyn RequestHandler) {
-        let failed_connections: Vec<ConnectionKey> = self
+    /// Removes entities that no longer exist from the object map of every connection, and reports
+    /// how many were removed in the purged_object_count property
+    fn reconcile_object_maps(&self, handler: &mut dyn RequestHandler) {
+        let purged: usize = self
             .connections
-            .iter_mut()
-            .filter_map(|(key, connection)| match connection.flush(handler) {
-                Ok(()) => None,
-                Err(()) => Some(key),
+            .values()
+            .map(|connection| {
+                connection.purge_destroyed_entities(&|entity| handler.entity_exists(entity))
             })
-            .collect();
+            .sum();
+        info!("purged {} destroyed entities from object maps", purged);
+        handler
+            .set_property(
+                ConnectionKey::null(),
+                self.root_entity,
+                "purged_object_count",
+                Value::Integer(purged as i64),
+            )
+            .or_log_error("setting purged object count property");
+    }
+
+    /// Updates the connection count property on the root entity, should be called when connections
+    /// are added or removed
+    fn update_connection_count(&self, handler: &mut dyn RequestHandler) {
+        handler
+            .set_property(
+                ConnectionKey::null(),
+                self.root_entity,
+                "conn_count",
+                Value::Integer(self.connections.len() as i64),
+            )
+            .or_log_error("setting connection count property");
+    }
+
+    /// Describes every connection, including peer addresses and traffic. Only sent to connections
+    /// the game has allowed to see it.
+    fn connection_list(&self) -> Vec<ConnectionInfo> {
+        self.connections
+            .iter()
+            .map(|(key, connection)| ConnectionInfo {
+                id: key.to_id(),
+                address: connection.description(),
+                bandwidth: connection.bandwidth(),
+            })
+            .collect()
+    }
+
+    /// If no clients are connected
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// Called after game state has been fully updated before waiting for the next tick
+    pub fn flush_outbound_messages(&mut self, handler: &mut dyn RequestHandler) {
+        let start = Instant::now();
+        let keys: Vec<ConnectionKey> = self.connections.keys().collect();
+        let first = self.next_flush % keys.len().max(1);
+        self.next_flush = first + 1;
+        let mut failed_connections = Vec::new();
+        for (i, key) in keys[first..].iter().chain(&keys[..first]).enumerate() {
+            if let Some(budget) = self.flush_budget {
+                if i > 0 && start.elapsed() >= budget {
+                    warn!(
+                        "flushing took over {:?}, {} connections will be flushed next tick",
+                        budget,
+                        keys.len() - i
+                    );
+                    self.next_flush = first + i;
+                    break;
+                }
+            }
+            if self.connections[*key].flush(handler).is_err() {
+                failed_connections.push(*key);
+            }
+        }
+        if failed_connections.is_empty() {
+            return;
+        }
         for key in failed_connections {
             if let Some(mut connection) = self.connections.remove(key) {
                 connection.finalize(handler);
+                handler.connection_closed(key);
             }
         }
+        self.update_connection_count(handler);
     }
 
     fn try_to_build_connection(&mut self, builder: Box<dyn SessionBuilder>) {
@@ -137,12 +336,17 @@ impl ConnectionCollection {
                 builder
             );
             // Build a temporary connection in order to report the error to the client
-            match ConnectionImpl::new(ConnectionKey::null(), self.root_entity, builder) {
+            match ConnectionImpl::new(
+                ConnectionKey::null(),
+                self.root_entity,
+                builder,
+                self.connection_config.clone(),
+            ) {
                 Ok(mut conn) => {
-                    conn.send_event(Event::FatalError(format!(
-                        "server full (max {} connections)",
-                        self.max_connections
-                    )));
+                    conn.send_event(Event::FatalError(
+                        format!("server full (max {} connections)", self.max_connections),
+                        Some(SERVER_FULL_RETRY_AFTER),
+                    ));
                     conn.finalize(&mut NullRequestHandler);
                 }
                 Err(e) => error!("failed to build connection: {}", e),
@@ -155,9 +359,16 @@ impl ConnectionCollection {
         // stub connection in that case (and then immediately remove it). A mess, I know.
         let mut failed_to_build = false;
         let root_entity = self.root_entity;
+        let server_info = &self.server_info;
+        let connection_config = &self.connection_config;
         let key = self.connections.insert_with_key(|key| {
-            match ConnectionImpl::new(key, root_entity, builder) {
-                Ok(conn) => Box::new(conn),
+            match ConnectionImpl::new(key, root_entity, builder, connection_config.clone()) {
+                Ok(conn) => {
+                    if let Some(info) = server_info {
+                        conn.send_event(Event::Info(info.clone()));
+                    }
+                    Box::new(conn)
+                }
                 Err(e) => {
                     failed_to_build = true;
                     error!("failed to build connection: {}", e);
@@ -170,11 +381,25 @@ impl ConnectionCollection {
         }
     }
 
+    /// Descriptions of connections that finalize() has not finished closing. Filled in when it
+    /// starts, and each is removed once that connection is closed.
+    pub fn unfinished_connections(&self) -> Arc<Mutex<Vec<String>>> {
+        self.unfinished.clone()
+    }
+
     pub fn finalize(&mut self, handler: &mut dyn RequestHandler) {
-        for (_, mut connection) in self.connections.drain() {
-            connection.send_event(Event::FatalError("server has shut down".to_string()));
+        let connections: Vec<Box<dyn Connection>> =
+            self.connections.drain().map(|(_, c)| c).collect();
+        *self.unfinished.lock().unwrap() = connections.iter().map(|c| c.description()).collect();
+        for mut connection in connections {
+            let description = connection.description();
+            connection.send_event(Event::Shutdown);
             let _ = connection.flush(handler);
             connection.finalize(handler);
+            let mut unfinished = self.unfinished.lock().unwrap();
+            if let Some(i) = unfinished.iter().position(|d| *d == description) {
+                unfinished.remove(i);
+            }
         }
     }
 }
@@ -195,6 +420,7 @@ impl EventHandler for ConnectionCollection {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::connection::event::PROTOCOL_VERSION;
 
     #[derive(Debug)]
     struct MockSession;
@@ -228,28 +454,97 @@ mod tests {
         }
     }
 
+    /// Builds a session that records everything sent to it
+    #[derive(Debug)]
+    struct RecordingSessionBuilder(crate::helpers::MockSession);
+
+    impl SessionBuilder for RecordingSessionBuilder {
+        fn build(
+            self: Box<Self>,
+            handler: Box<dyn InboundBundleHandler>,
+        ) -> Result<Box<dyn Session>, Box<dyn Error>> {
+            Ok(Box::new(RecordingSession {
+                session: self.0,
+                _handler: handler,
+            }))
+        }
+    }
+
+    /// Holds on to the inbound handler, as the connection closes itself once it's dropped
+    struct RecordingSession {
+        session: crate::helpers::MockSession,
+        _handler: Box<dyn InboundBundleHandler>,
+    }
+
+    impl Debug for RecordingSession {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            self.session.fmt(f)
+        }
+    }
+
+    impl Session for RecordingSession {
+        fn yeet_bundle(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
+            self.session.yeet_bundle(data)
+        }
+
+        fn max_packet_len(&self) -> usize {
+            self.session.max_packet_len()
+        }
+
+        fn close(&mut self) {
+            self.session.close()
+        }
+    }
+
+    #[derive(Default)]
     struct MockConnection {
         flush_succeeds: bool,
+        /// How long each flush takes
+        flush_delay: Duration,
+        flushes: Arc<AtomicU64>,
+        /// If set finalize() doesn't return until something is sent
+        finalize_blocks_on: Option<Receiver<()>>,
     }
 
     impl Connection for MockConnection {
         fn process_requests(&mut self, _: &mut dyn RequestHandler) {}
         fn send_event(&self, _: Event) {}
         fn flush(&mut self, _: &mut dyn RequestHandler) -> Result<(), ()> {
+            std::thread::sleep(self.flush_delay);
+            self.flushes.fetch_add(1, SeqCst);
             if self.flush_succeeds {
                 Ok(())
             } else {
                 Err(())
             }
         }
-        fn finalize(&mut self, _: &mut dyn RequestHandler) {}
+        fn finalize(&mut self, _: &mut dyn RequestHandler) {
+            if let Some(rx) = &self.finalize_blocks_on {
+                let _ = rx.recv();
+            }
+        }
+        fn description(&self) -> String {
+            if self.finalize_blocks_on.is_some() {
+                "HangingConnection".to_string()
+            } else {
+                "MockConnection".to_string()
+            }
+        }
+        fn close(&self, _: String) {}
+        fn bandwidth(&self) -> Bandwidth {
+            Bandwidth::default()
+        }
+        fn purge_destroyed_entities(&self, _: &dyn Fn(EntityKey) -> bool) -> usize {
+            0
+        }
     }
 
     #[test]
     fn can_create_connection_from_session_builder() {
         let e = mock_keys(1);
         let (session_tx, session_rx) = channel();
-        let mut cc = ConnectionCollection::new(session_rx, e[0], usize::MAX);
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
         let builder = Box::new(MockSessionBuilder(true));
         session_tx
             .send(builder)
@@ -264,7 +559,8 @@ mod tests {
     fn does_not_create_connection_when_building_session_fails() {
         let e = mock_keys(1);
         let (session_tx, session_rx) = channel();
-        let mut cc = ConnectionCollection::new(session_rx, e[0], usize::MAX);
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
         // False means building session will fail vvvvv
         let builder = Box::new(MockSessionBuilder(false));
         session_tx
@@ -279,7 +575,7 @@ mod tests {
     fn building_connections_fail_after_max_connections_reached() {
         let e = mock_keys(1);
         let (session_tx, session_rx) = channel();
-        let mut cc = ConnectionCollection::new(session_rx, e[0], 2);
+        let mut cc = ConnectionCollection::new(session_rx, e[0], 2, ConnectionConfig::default());
         session_tx
             .send(Box::new(MockSessionBuilder(true)))
             .expect("failed to send connection builder");
@@ -303,9 +599,11 @@ mod tests {
     fn does_not_remove_connections_that_succeed_to_flush() {
         let e = mock_keys(1);
         let (_, session_rx) = channel();
-        let mut cc = ConnectionCollection::new(session_rx, e[0], usize::MAX);
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
         cc.connections.insert(Box::new(MockConnection {
             flush_succeeds: true,
+            ..Default::default()
         }));
         assert_eq!(cc.connections.len(), 1);
         let mut handler = MockRequestHandler::new(Ok(()));
@@ -317,15 +615,317 @@ mod tests {
     fn removes_connections_that_fail_to_flush() {
         let e = mock_keys(1);
         let (_, session_rx) = channel();
-        let mut cc = ConnectionCollection::new(session_rx, e[0], usize::MAX);
-        cc.connections.insert(Box::new(MockConnection {
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        let key = cc.connections.insert(Box::new(MockConnection {
             flush_succeeds: false,
+            ..Default::default()
         }));
         assert_eq!(cc.connections.len(), 1);
         let mut handler = MockRequestHandler::new(Ok(()));
         cc.flush_outbound_messages(&mut handler);
         assert_eq!(cc.connections.len(), 0);
+        assert_eq!(handler.closed_connections(), vec![key]);
+    }
+
+    #[test]
+    fn slow_connection_does_not_starve_others_of_flush_budget() {
+        let e = mock_keys(1);
+        let (_, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        cc.set_flush_budget(Duration::from_millis(10));
+        let flushes: Vec<Arc<AtomicU64>> = (0..3).map(|_| Arc::new(AtomicU64::new(0))).collect();
+        for (i, flushes) in flushes.iter().enumerate() {
+            cc.connections.insert(Box::new(MockConnection {
+                flush_succeeds: true,
+                flush_delay: Duration::from_millis(if i == 0 { 30 } else { 0 }),
+                flushes: flushes.clone(),
+                ..Default::default()
+            }));
+        }
+        let counts = || -> Vec<u64> { flushes.iter().map(|f| f.load(SeqCst)).collect() };
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.flush_outbound_messages(&mut handler);
+        // The slow connection used up the budget, so the rest wait
+        assert_eq!(counts(), vec![1, 0, 0]);
+        cc.flush_outbound_messages(&mut handler);
+        // And go first on the next tick, which leaves time for the slow one too
+        assert_eq!(counts(), vec![2, 1, 1]);
+        assert_eq!(cc.connections.len(), 3);
+    }
+
+    #[test]
+    fn shutdown_watchdog_reports_connection_that_never_finishes() {
+        let e = mock_keys(1);
+        let (_, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        let (release_tx, release_rx) = channel();
+        cc.connections.insert(Box::new(MockConnection {
+            flush_succeeds: true,
+            finalize_blocks_on: Some(release_rx),
+            ..Default::default()
+        }));
+        cc.connections.insert(Box::new(MockConnection {
+            flush_succeeds: true,
+            ..Default::default()
+        }));
+        let unfinished = cc.unfinished_connections();
+        let (reported_tx, reported_rx) = channel();
+        let start = Instant::now();
+        let _watchdog = ShutdownWatchdog::start(Duration::from_millis(50), move || {
+            reported_tx
+                .send(unfinished.lock().unwrap().clone())
+                .unwrap();
+            // Stands in for exiting the process
+            release_tx.send(()).unwrap();
+        });
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.finalize(&mut handler);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50));
+        assert!(elapsed < Duration::from_secs(5));
+        let reported = reported_rx.recv().unwrap();
+        assert!(reported.contains(&"HangingConnection".to_string()));
+        assert!(cc.unfinished_connections().lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn connection_count_is_only_set_when_it_changes() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc = ConnectionCollection::new(session_rx, e[0], 5, ConnectionConfig::default());
+        let session = crate::helpers::MockSession::new(false);
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(session)))
+            .expect("failed to send connection builder");
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        cc.flush_outbound_messages(&mut handler);
+        cc.process_inbound_messages(&mut handler);
+        cc.flush_outbound_messages(&mut handler);
+        handler.assert_requests_eq(vec![
+            Request::set(e[0], "max_conn_count".to_string(), Value::Integer(5)),
+            Request::set(e[0], "conn_count".to_string(), Value::Integer(1)),
+        ]);
+    }
+
+    #[test]
+    fn connection_info_is_sent_as_array() {
+        let info = ConnectionInfo {
+            id: 7,
+            address: "TCP 10.0.0.1".to_string(),
+            bandwidth: Bandwidth {
+                total_sent: 1,
+                total_received: 2,
+                tick_sent: 3,
+                tick_received: 4,
+            },
+        };
+        let expected = Value::Array(vec![
+            Value::Integer(7),
+            "TCP 10.0.0.1".to_string().into(),
+            Value::Array(vec![Value::Integer(1), Value::Integer(2)]),
+            Value::Array(vec![Value::Integer(3), Value::Integer(4)]),
+        ]);
+        assert_eq!(Value::from(info), expected);
+    }
+
+    #[test]
+    fn connection_list_is_only_sent_to_requester() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc = ConnectionCollection::new(session_rx, e[0], 5, ConnectionConfig::default());
+        let requester_session = crate::helpers::MockSession::new(false);
+        let other_session = crate::helpers::MockSession::new(false);
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(requester_session.clone())))
+            .unwrap();
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(other_session.clone())))
+            .unwrap();
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        let keys: Vec<ConnectionKey> = cc.connections.keys().collect();
+        handler.request_connection_list(keys[0]);
+        cc.process_inbound_messages(&mut handler);
+        cc.flush_outbound_messages(&mut handler);
+        let sent = |session: &crate::helpers::MockSession| {
+            session
+                .take_bundles()
+                .iter()
+                .map(|bundle| String::from_utf8_lossy(bundle).to_string())
+                .collect::<String>()
+        };
+        let requested = sent(&requester_session);
+        assert!(requested.contains("\"connections\""), "{}", requested);
+        assert!(
+            requested.contains(&format!("[{},", keys[1].to_id())),
+            "{}",
+            requested
+        );
+        assert!(!sent(&other_session).contains("\"connections\""));
+    }
+
+    #[test]
+    fn disconnect_request_results_in_flush_returning_err() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        session_tx
+            .send(Box::new(MockSessionBuilder(true)))
+            .expect("failed to send connection builder");
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        let key = cc.connections.keys().next().unwrap();
+        handler.request_disconnect(key);
+        cc.process_inbound_messages(&mut handler);
+        assert!(cc.connections[key].flush(&mut handler).is_err());
+    }
+
+    #[test]
+    fn connection_is_removed_after_disconnect_request() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        session_tx
+            .send(Box::new(MockSessionBuilder(true)))
+            .expect("failed to send connection builder");
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        let key = cc.connections.keys().next().unwrap();
+        handler.request_disconnect(key);
+        cc.process_inbound_messages(&mut handler);
+        cc.flush_outbound_messages(&mut handler);
+        assert_eq!(cc.connections.len(), 0);
+    }
+
+    #[test]
+    fn only_overload_closes_carry_retry_hint() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc = ConnectionCollection::new(session_rx, e[0], 1, ConnectionConfig::default());
+        let accepted = crate::helpers::MockSession::new(false);
+        let rejected = crate::helpers::MockSession::new(false);
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(accepted.clone())))
+            .expect("failed to send connection builder");
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(rejected.clone())))
+            .expect("failed to send connection builder");
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        let full: serde_json::Value = serde_json::from_slice(&rejected.bundles()[0]).unwrap();
+        assert_eq!(full["mtype"], "error");
+        assert!(full["retry_after"].as_u64().unwrap() > 0);
+
+        let key = cc.connections.keys().next().unwrap();
+        handler.request_disconnect(key);
+        cc.process_inbound_messages(&mut handler);
+        cc.flush_outbound_messages(&mut handler);
+        let closed: serde_json::Value =
+            serde_json::from_slice(accepted.bundles().last().unwrap()).unwrap();
+        assert_eq!(closed["mtype"], "error");
+        assert!(closed.get("retry_after").is_none());
     }
 
     // TODO: test connections are finalized
+
+    #[test]
+    fn first_event_on_new_connection_is_server_info() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        cc.set_server_info(ServerInfo::new("dev".to_string(), 20.0, e[0]));
+        let session = crate::helpers::MockSession::new(false);
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(session.clone())))
+            .expect("failed to send connection builder");
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        let bundles = session.bundles();
+        assert!(!bundles.is_empty());
+        let info: serde_json::Value = serde_json::from_slice(&bundles[0]).unwrap();
+        assert_eq!(info["mtype"], "info");
+        assert_eq!(info["server_name"], "dev");
+        assert_eq!(info["server_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(info["protocol_version"], PROTOCOL_VERSION);
+        assert_eq!(info["tick_rate"], 20.0);
+        assert_eq!(info["root"], 1);
+    }
+
+    #[test]
+    fn server_info_is_not_sent_unless_set() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        let session = crate::helpers::MockSession::new(false);
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(session.clone())))
+            .expect("failed to send connection builder");
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        session.assert_bundles_eq(vec![]);
+    }
+
+    #[test]
+    fn finalize_sends_shutdown_before_closing() {
+        let e = mock_keys(1);
+        let (session_tx, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, e[0], usize::MAX, ConnectionConfig::default());
+        let session = crate::helpers::MockSession::new(false);
+        session_tx
+            .send(Box::new(RecordingSessionBuilder(session.clone())))
+            .expect("failed to send connection builder");
+        let mut handler = MockRequestHandler::new(Ok(()));
+        cc.process_inbound_messages(&mut handler);
+        cc.finalize(&mut handler);
+        // MockSession panics if sent a bundle after being closed, so the order is checked too
+        assert!(session.is_closed());
+        let bundles = session.bundles();
+        let last: serde_json::Value = serde_json::from_slice(bundles.last().unwrap()).unwrap();
+        assert_eq!(last["mtype"], "shutdown");
+    }
+
+    #[test]
+    fn reconcile_removes_destroyed_entities_missed_by_connections() {
+        struct Purged(Element<u64>);
+
+        let mut state = State::new();
+        let root = state.root_entity();
+        state.install_component(root, Purged(Element::new(0)));
+        RWConduit::new(
+            move |state| Ok(&state.component::<Purged>(root)?.0),
+            move |state, value| Ok(state.component_mut::<Purged>(root)?.0.set(value)),
+        )
+        .install_property(&mut state, root, "purged_object_count");
+        let (session_tx, session_rx) = channel();
+        let mut cc =
+            ConnectionCollection::new(session_rx, root, usize::MAX, ConnectionConfig::default());
+        session_tx
+            .send(Box::new(MockSessionBuilder(true)))
+            .expect("failed to send connection builder");
+        cc.process_inbound_messages(&mut state);
+        let key = cc.connections.keys().next().unwrap();
+        let purged = |state: &State| *state.component::<Purged>(root).unwrap().0;
+
+        // Sending the entity to the client gives it an object ID
+        let entity = state.create_entity().unwrap();
+        cc.event(key, Event::value(root, "target".to_string(), entity.into()));
+        // Simulate missing the destruction, the connection is never told
+        state.destroy_entity(entity).unwrap();
+
+        state.reconcile_object_maps();
+        cc.process_inbound_messages(&mut state);
+        assert_eq!(purged(&state), 1);
+        state.reconcile_object_maps();
+        cc.process_inbound_messages(&mut state);
+        assert_eq!(purged(&state), 0);
+    }
 }
@@ -0,0 +1,54 @@
+use super::*;
+
+/// Settings every client connection is created with, generally loaded from the config
+#[derive(Clone)]
+pub struct ConnectionConfig {
+    /// Reject requests that could change the game (sets and actions), clients can still get and
+    /// subscribe
+    pub read_only: bool,
+    /// Let clients inspect connection internals with debugging actions on the root object
+    pub debug_actions: bool,
+    /// Send every event with the game time it was generated at
+    pub timestamp_events: bool,
+    /// If not 0, at most this many get responses are sent per flush and the rest wait for later
+    /// flushes. Keeps the initial state of a big world from being sent all in one tick.
+    pub max_values_per_flush: usize,
+    /// If not 0, subscribe requests past this many active subscriptions are rejected
+    pub max_subscriptions: usize,
+    /// If not 0, get requests for new members are rejected once this many are waiting for a flush
+    pub max_pending_gets: usize,
+    /// Transient send errors in a row that are retried before the connection is closed. 0 behaves
+    /// the same as 1, closing on the first failure. Fatal errors (such as a broken pipe) always
+    /// close the connection.
+    pub max_send_failures: u64,
+    /// Limits how deeply nested and how big values sent by clients can be
+    pub decode_limits: DecodeLimits,
+    /// Messages to and from clients are split on this byte instead of requests being
+    /// newline-delimited and events being framed by the session
+    pub delimiter: Option<u8>,
+    /// If set, every request from every client is recorded
+    pub recorder: Option<Arc<RequestRecorder>>,
+    /// If set, clients can identify themselves to keep their object IDs between connections
+    pub object_id_store: Option<Arc<dyn ObjectIdStore>>,
+    /// Decides which events are sent right away and which wait for the end of the tick
+    pub flush_strategy: FlushStrategy,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            read_only: false,
+            debug_actions: false,
+            timestamp_events: false,
+            max_values_per_flush: 0,
+            max_subscriptions: 0,
+            max_pending_gets: 0,
+            max_send_failures: 1,
+            decode_limits: DecodeLimits::default(),
+            delimiter: None,
+            recorder: None,
+            object_id_store: None,
+            flush_strategy: FlushStrategy::default(),
+        }
+    }
+}
@@ -1,5 +1,32 @@
 use super::*;
 
+/// Incremented whenever a change to the protocol would break existing clients
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// Information clients can use to check compatibility as soon as they connect
+#[derive(Debug, PartialEq, Clone)]
+pub struct ServerInfo {
+    /// Identifies this server instance, so clients can tell which one they're connected to
+    pub server_name: String,
+    pub server_version: String,
+    pub protocol_version: u64,
+    /// Physics ticks per second
+    pub tick_rate: f64,
+    pub root_entity: EntityKey,
+}
+
+impl ServerInfo {
+    pub fn new(server_name: String, tick_rate: f64, root_entity: EntityKey) -> Self {
+        Self {
+            server_name,
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            tick_rate,
+            root_entity,
+        }
+    }
+}
+
 /// The data for a method event. That is, an event for an object member.
 #[derive(Debug, PartialEq, Clone)]
 pub enum EventMethod {
@@ -11,6 +38,36 @@ pub enum EventMethod {
     Signal,
 }
 
+/// The kinds of events, without their data. Used to choose when each kind is flushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Value,
+    Update,
+    Signal,
+    Destroyed,
+    FatalError,
+    Error,
+    Delta,
+    Info,
+    Root,
+    Shutdown,
+}
+
+impl PropertyEnum for EventKind {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (EventKind::Value, "value"),
+        (EventKind::Update, "update"),
+        (EventKind::Signal, "signal"),
+        (EventKind::Destroyed, "destroyed"),
+        (EventKind::FatalError, "fatal_error"),
+        (EventKind::Error, "error"),
+        (EventKind::Delta, "delta"),
+        (EventKind::Info, "info"),
+        (EventKind::Root, "root"),
+        (EventKind::Shutdown, "shutdown"),
+    ];
+}
+
 /// Represents a message from the server to a client
 #[derive(Debug, PartialEq, Clone)]
 pub enum Event {
@@ -21,8 +78,22 @@ pub enum Event {
     #[allow(dead_code)]
     Destroyed(EntityKey),
     /// Some problem has caused the server or connection to fail. This should be the last event
-    /// before the session is closed. The message should be user-readable.
-    FatalError(String),
+    /// before the session is closed. The message should be user-readable. If the server is
+    /// overloaded, also has how many seconds the client should wait before reconnecting.
+    FatalError(String, Option<u64>),
+    /// A problem with something the client sent. Unlike FatalError the connection stays open.
+    Error(String),
+    /// An update to a subscribed list property with only the elements that were added and removed
+    Delta(EntityKey, String, Vec<Value>, Vec<Value>),
+    /// Sent as the first event on a new connection (if enabled)
+    Info(ServerInfo),
+    /// The root object, in response to a Root request
+    Root(EntityKey),
+    /// The server is intentionally shutting down and the connection is about to close. Unlike a
+    /// network failure, clients should not try to reconnect.
+    Shutdown,
+    /// Another event along with the game time it was generated at. Never nested.
+    Timestamped(f64, Box<Event>),
 }
 
 impl Event {
@@ -37,4 +108,24 @@ impl Event {
     pub fn signal(entity: EntityKey, name: String, value: Value) -> Self {
         Self::Method(entity, name, EventMethod::Signal, value)
     }
+
+    pub fn delta(entity: EntityKey, name: String, added: Vec<Value>, removed: Vec<Value>) -> Self {
+        Self::Delta(entity, name, added, removed)
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Method(_, _, EventMethod::Value, _) => EventKind::Value,
+            Self::Method(_, _, EventMethod::Update, _) => EventKind::Update,
+            Self::Method(_, _, EventMethod::Signal, _) => EventKind::Signal,
+            Self::Destroyed(_) => EventKind::Destroyed,
+            Self::FatalError(..) => EventKind::FatalError,
+            Self::Error(_) => EventKind::Error,
+            Self::Delta(..) => EventKind::Delta,
+            Self::Info(_) => EventKind::Info,
+            Self::Root(_) => EventKind::Root,
+            Self::Shutdown => EventKind::Shutdown,
+            Self::Timestamped(_, event) => event.kind(),
+        }
+    }
 }
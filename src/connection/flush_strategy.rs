@@ -0,0 +1,77 @@
+use super::*;
+
+/// When an event is sent to the session
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FlushTiming {
+    /// As soon as it happens
+    #[default]
+    Immediate,
+    /// With everything else at the end of the tick, when the connection is flushed
+    PerTick,
+}
+
+impl PropertyEnum for FlushTiming {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (FlushTiming::Immediate, "immediate"),
+        (FlushTiming::PerTick, "per_tick"),
+    ];
+}
+
+/// Chooses when each kind of event is sent. Latency-sensitive events (like signals) can be sent
+/// immediately while the rest wait for the end of the tick.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlushStrategy {
+    default: FlushTiming,
+    overrides: HashMap<EventKind, FlushTiming>,
+}
+
+impl FlushStrategy {
+    /// Uses the given timing for every kind of event that isn't overridden
+    pub fn new(default: FlushTiming) -> Self {
+        Self {
+            default,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Uses the given timing for one kind of event
+    pub fn set(&mut self, kind: EventKind, timing: FlushTiming) {
+        self.overrides.insert(kind, timing);
+    }
+
+    pub fn timing(&self, kind: EventKind) -> FlushTiming {
+        match kind {
+            // These come right before the connection is closed, so they can't wait for a flush
+            EventKind::FatalError | EventKind::Shutdown => FlushTiming::Immediate,
+            kind => *self.overrides.get(&kind).unwrap_or(&self.default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_take_precedence_over_default() {
+        let mut strategy = FlushStrategy::new(FlushTiming::PerTick);
+        strategy.set(EventKind::Signal, FlushTiming::Immediate);
+        assert_eq!(strategy.timing(EventKind::Signal), FlushTiming::Immediate);
+        assert_eq!(strategy.timing(EventKind::Update), FlushTiming::PerTick);
+        assert_eq!(
+            FlushStrategy::default().timing(EventKind::Update),
+            FlushTiming::Immediate
+        );
+    }
+
+    #[test]
+    fn closing_events_are_always_immediate() {
+        let mut strategy = FlushStrategy::new(FlushTiming::PerTick);
+        strategy.set(EventKind::Shutdown, FlushTiming::PerTick);
+        assert_eq!(strategy.timing(EventKind::Shutdown), FlushTiming::Immediate);
+        assert_eq!(
+            strategy.timing(EventKind::FatalError),
+            FlushTiming::Immediate
+        );
+    }
+}
@@ -20,7 +20,108 @@ pub trait DecodeCtx: Send + Sync {
     fn entity_for(&self, object: ObjectId) -> RequestResult<EntityKey>;
 }
 
+/// Bounds on decoded values, so a malicious message can't overflow the stack or use up memory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeLimits {
+    /// How many arrays and maps deep a value can be nested
+    pub max_depth: usize,
+    /// How many values (including nested ones) a single message can contain
+    pub max_elements: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_elements: 10_000,
+        }
+    }
+}
+
 /// Decodes a stream of bytes from the session into requests
 pub trait Decoder: Send {
-    fn decode(&mut self, ctx: &dyn DecodeCtx, bytes: Vec<u8>) -> RequestResult<Vec<Request>>;
+    /// Messages with values that exceed these limits fail to decode
+    fn set_limits(&mut self, limits: DecodeLimits);
+    /// Returns a result for each complete message. A message that fails to decode does not effect
+    /// the ones around it.
+    fn decode(&mut self, ctx: &dyn DecodeCtx, bytes: Vec<u8>) -> Vec<RequestResult<Request>>;
+}
+
+/// Formats a session layer can negotiate by name (ex as a WebSocket subprotocol)
+#[allow(clippy::type_complexity)]
+const NAMED_FORMATS: &[(&str, fn(Option<u8>) -> (Box<dyn Encoder>, Box<dyn Decoder>))] = &[
+    ("json", json_protocol_impls),
+    ("msgpack", msgpack_protocol_impls),
+];
+
+/// Returns the first of the requested format names that is supported, or None if none are
+pub fn select_format<'a>(requested: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    requested.into_iter().find_map(|name| {
+        NAMED_FORMATS
+            .iter()
+            .find(|(supported, _)| *supported == name)
+            .map(|(supported, _)| *supported)
+    })
+}
+
+/// Returns the encoder and decoder for the named format, which split datagrams on the delimiter
+/// if one is given. Falls back to JSON if no format was negotiated or the name is unknown.
+pub fn protocol_impls_for(
+    format: Option<&str>,
+    delimiter: Option<u8>,
+) -> (Box<dyn Encoder>, Box<dyn Decoder>) {
+    NAMED_FORMATS
+        .iter()
+        .find(|(name, _)| Some(*name) == format)
+        .map(|(_, impls)| impls(delimiter))
+        .unwrap_or_else(|| json_protocol_impls(delimiter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selects_json() {
+        assert_eq!(select_format(vec!["json"]), Some("json"));
+    }
+
+    #[test]
+    fn selects_first_supported_format() {
+        assert_eq!(select_format(vec!["foo", "json", "bar"]), Some("json"));
+    }
+
+    #[test]
+    fn selects_msgpack() {
+        assert_eq!(select_format(vec!["msgpack"]), Some("msgpack"));
+    }
+
+    #[test]
+    fn selects_nothing_if_no_format_is_supported() {
+        assert_eq!(select_format(vec!["foo"]), None);
+        assert_eq!(select_format(Vec::new()), None);
+    }
+
+    #[test]
+    fn msgpack_format_uses_msgpack_codec() {
+        let map = ObjectMapImpl::new();
+        let (encoder, mut decoder) = protocol_impls_for(Some("msgpack"), None);
+        let event = encoder.encode_event(&map, &Event::Shutdown).unwrap();
+        let event: serde_json::Value = rmp_serde::from_slice(&event).unwrap();
+        assert_eq!(event, serde_json::json!({"mtype": "shutdown"}));
+        let request = rmp_serde::to_vec(&serde_json::json!({"mtype": "root"})).unwrap();
+        assert_eq!(decoder.decode(&map, request), vec![Ok(Request::Root)]);
+    }
+
+    #[test]
+    fn unknown_format_uses_json_codec() {
+        let map = ObjectMapImpl::new();
+        let (encoder, mut decoder) = protocol_impls_for(Some("foo"), None);
+        let event = encoder.encode_event(&map, &Event::Shutdown).unwrap();
+        assert_eq!(event, br#"{"mtype":"shutdown"}"#.to_vec());
+        assert_eq!(
+            decoder.decode(&map, b"{\"mtype\": \"root\"}\n".to_vec()),
+            vec![Ok(Request::Root)]
+        );
+    }
 }
@@ -6,12 +6,19 @@ const MAX_DATAGRAM_LEN: usize = 10_000_000;
 
 pub struct JsonDecoder {
     splitter: DatagramSplitter,
+    limits: DecodeLimits,
 }
 
 impl JsonDecoder {
     pub fn new() -> Self {
+        Self::with_delimiter(b'\n')
+    }
+
+    /// Splits datagrams on the given byte instead of a newline
+    pub fn with_delimiter(delimiter: u8) -> Self {
         Self {
-            splitter: DatagramSplitter::new(b'\n', MAX_DATAGRAM_LEN), // Cap
+            splitter: DatagramSplitter::new(delimiter, MAX_DATAGRAM_LEN),
+            limits: DecodeLimits::default(),
         }
     }
 
@@ -20,6 +27,8 @@ impl JsonDecoder {
         &self,
         ctx: &dyn DecodeCtx,
         array: &[serde_json::Value],
+        depth: usize,
+        count: &mut usize,
     ) -> RequestResult<Value> {
         match array.len() {
             3 => {
@@ -42,9 +51,14 @@ impl JsonDecoder {
                     // An array-wrapped array is an actual array
                     let result: Result<Vec<_>, _> = array
                         .iter()
-                        .map(|value| self.decode_value(ctx, value))
+                        .map(|value| self.decode_nested(ctx, value, depth + 1, count))
                         .collect();
                     Ok(Value::Array(result?))
+                } else if let Some(text) = array[0].as_str() {
+                    // An array-wrapped string is base64 encoded bytes
+                    base64::decode(text)
+                        .map(Value::Bytes)
+                        .map_err(|e| BadMessage(format!("{:?} is invalid base64: {}", text, e)))
                 } else {
                     Err(BadMessage(format!(
                         "{} is an array-wrapped value, but not an object ID",
@@ -66,14 +80,44 @@ impl JsonDecoder {
         ctx: &dyn DecodeCtx,
         serde_val: &serde_json::Value,
     ) -> RequestResult<Value> {
+        self.decode_nested(ctx, serde_val, 0, &mut 0)
+    }
+
+    /// Decodes a value inside depth arrays and maps. count is the number of values decoded so far
+    /// in the whole message, and is used to enforce the element limit.
+    fn decode_nested(
+        &self,
+        ctx: &dyn DecodeCtx,
+        serde_val: &serde_json::Value,
+        depth: usize,
+        count: &mut usize,
+    ) -> RequestResult<Value> {
+        if depth > self.limits.max_depth {
+            return Err(BadMessage(format!(
+                "values nested more than {} deep",
+                self.limits.max_depth
+            )));
+        }
+        *count += 1;
+        if *count > self.limits.max_elements {
+            return Err(BadMessage(format!(
+                "message has more than {} values",
+                self.limits.max_elements
+            )));
+        }
         match serde_val {
             serde_json::Value::Null => Ok(Value::Null),
-            serde_json::Value::Bool(_) => {
-                Err(InternalError("decoding bool not implemented".to_string()))
-            }
+            serde_json::Value::Bool(value) => Ok(Value::Bool(*value)),
             serde_json::Value::Number(n) => {
+                // With arbitrary_precision, numbers keep the literal they were written as, so an
+                // integer too big for an i64 can be told apart from a large float
+                let is_integer_literal = !n.to_string().contains(&['.', 'e', 'E'][..]);
                 if let Some(i) = n.as_i64() {
                     Ok(Value::Integer(i))
+                } else if n.is_u64() {
+                    Err(BadMessage(format!("{} is too large for an integer", n)))
+                } else if is_integer_literal {
+                    Err(BadMessage(format!("{} is out of range for an integer", n)))
                 } else if let Some(f) = n.as_f64() {
                     Ok(Value::Scalar(f))
                 } else {
@@ -81,9 +125,18 @@ impl JsonDecoder {
                 }
             }
             serde_json::Value::String(text) => Ok(Value::Text(text.to_string())),
-            serde_json::Value::Array(array) => self.decode_wrapper_array(ctx, array),
-            serde_json::Value::Object(_) => {
-                Err(InternalError("decoding map not implemented".to_string()))
+            serde_json::Value::Array(array) => self.decode_wrapper_array(ctx, array, depth, count),
+            serde_json::Value::Object(object) => {
+                let result: RequestResult<HashMap<String, Value>> = object
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok((
+                            key.clone(),
+                            self.decode_nested(ctx, value, depth + 1, count)?,
+                        ))
+                    })
+                    .collect();
+                Ok(Value::Map(result?))
             }
         }
     }
@@ -100,6 +153,38 @@ impl JsonDecoder {
         ctx.entity_for(obj).map_err(Into::into)
     }
 
+    /// Decodes the optional filter and frame of a subscribe request. The filter must be an object
+    /// and the frame an object ID.
+    fn decode_subscribe_options(
+        &self,
+        ctx: &dyn DecodeCtx,
+        datagram: &serde_json::map::Map<String, serde_json::Value>,
+    ) -> RequestResult<SubscribeOptions> {
+        let filter = match datagram.get("filter") {
+            None => None,
+            Some(filter) if filter.is_object() => match self.decode_value(ctx, filter)? {
+                Value::Map(properties) => Some(EntityFilter::new(properties)),
+                _ => {
+                    return Err(InternalError(
+                        "filter object did not decode to a map".into(),
+                    ))
+                }
+            },
+            Some(_) => return Err(BadMessage("subscribe filter is not an object".into())),
+        };
+        let frame = match datagram.get("frame") {
+            None => None,
+            Some(frame) => Some(
+                ctx.entity_for(
+                    frame
+                        .as_u64()
+                        .ok_or_else(|| BadMessage("frame not an object ID".into()))?,
+                )?,
+            ),
+        };
+        Ok(SubscribeOptions { filter, frame })
+    }
+
     fn decode_name(
         datagram: &serde_json::map::Map<String, serde_json::Value>,
     ) -> RequestResult<String> {
@@ -119,9 +204,19 @@ impl JsonDecoder {
         let mut deserializer = serde_json::Deserializer::from_slice(bytes);
         let serde_val = serde_json::Value::deserialize(&mut deserializer)
             .map_err(|e| BadMessage(e.to_string()))?;
+        self.decode_request(ctx, &serde_val)
+    }
+
+    /// Decodes an already parsed request. Other formats (ex MessagePack) send the same messages, so
+    /// they parse them into a serde_json::Value and then use this.
+    pub fn decode_request(
+        &self,
+        ctx: &dyn DecodeCtx,
+        serde_val: &serde_json::Value,
+    ) -> RequestResult<Request> {
         let datagram = serde_val
             .as_object()
-            .ok_or_else(|| BadMessage("request is not a JSON object".into()))?;
+            .ok_or_else(|| BadMessage("request is not an object".into()))?;
         let mtype = datagram
             .get("mtype")
             .ok_or_else(|| BadMessage("request does not have an mtype field".into()))?
@@ -134,13 +229,7 @@ impl JsonDecoder {
                 self.decode_value(
                     ctx,
                     datagram.get("value").ok_or_else(|| {
-                        BadMessage(
-                            format!(
-                                "fire request does not have a value: {}",
-                                String::from_utf8_lossy(bytes)
-                            )
-                            .into(),
-                        )
+                        BadMessage(format!("fire request does not have a value: {}", serde_val))
                     })?,
                 )?,
             ),
@@ -150,13 +239,7 @@ impl JsonDecoder {
                 self.decode_value(
                     ctx,
                     datagram.get("value").ok_or_else(|| {
-                        BadMessage(
-                            format!(
-                                "set request does not have a value: {}",
-                                String::from_utf8_lossy(bytes)
-                            )
-                            .into(),
-                        )
+                        BadMessage(format!("set request does not have a value: {}", serde_val))
                     })?,
                 )?,
             ),
@@ -164,30 +247,39 @@ impl JsonDecoder {
                 Self::decode_obj(ctx, &datagram)?,
                 Self::decode_name(&datagram)?,
             ),
-            "subscribe" => Request::subscribe(
-                Self::decode_obj(ctx, &datagram)?,
-                Self::decode_name(&datagram)?,
+            "get_once" => Request::get_once(
+                Self::decode_obj(ctx, datagram)?,
+                Self::decode_name(datagram)?,
+            ),
+            "subscribe" => Request::subscribe_with_options(
+                Self::decode_obj(ctx, datagram)?,
+                Self::decode_name(datagram)?,
+                self.decode_subscribe_options(ctx, datagram)?,
             ),
             "unsubscribe" => Request::unsubscribe(
                 Self::decode_obj(ctx, &datagram)?,
                 Self::decode_name(&datagram)?,
             ),
+            "root" => Request::Root,
             _ => return Err(BadMessage(format!("invalid mtype {:?}", mtype))),
         })
     }
 }
 
 impl Decoder for JsonDecoder {
-    fn decode(&mut self, ctx: &dyn DecodeCtx, bytes: Vec<u8>) -> RequestResult<Vec<Request>> {
-        let mut requests = Vec::new();
-        let datagrams = self
-            .splitter
+    fn set_limits(&mut self, limits: DecodeLimits) {
+        self.limits = limits;
+    }
+
+    fn decode(&mut self, ctx: &dyn DecodeCtx, bytes: Vec<u8>) -> Vec<RequestResult<Request>> {
+        self.splitter
             .data(bytes)
-            .map_err(|e| BadMessage(e.to_string()))?;
-        for datagram in datagrams {
-            requests.push(self.decode_datagram(ctx, &datagram)?);
-        }
-        Ok(requests)
+            .into_iter()
+            .map(|datagram| {
+                let datagram = datagram.map_err(|e| BadMessage(e.to_string()))?;
+                self.decode_datagram(ctx, &datagram)
+            })
+            .collect()
     }
 }
 
@@ -276,6 +368,22 @@ mod decode_tests {
         assert_decodes_to("784.25", Scalar(784.25));
     }
 
+    #[test]
+    fn integers_out_of_range_are_rejected() {
+        assert_results_in_error("99999999999999999999", "out of range");
+        assert_results_in_error("-99999999999999999999", "out of range");
+        assert_results_in_error("18446744073709551615", "too large");
+        assert_decodes_to("9223372036854775807", Integer(i64::MAX));
+        assert_decodes_to("1.5", Scalar(1.5));
+    }
+
+    #[test]
+    fn large_floats_are_not_mistaken_for_integers() {
+        assert_decodes_to("2.0e+27", Scalar(2.0e27));
+        assert_decodes_to("1e19", Scalar(1e19));
+        assert_decodes_to("-99999999999999999999.0", Scalar(-99999999999999999999.0));
+    }
+
     #[test]
     fn scalar_even_when_decimal_is_zero() {
         assert_decodes_to("784.0", Scalar(784.0));
@@ -286,6 +394,23 @@ mod decode_tests {
         assert_decodes_to("\"hello\\n\"", Text("hello\n".to_string()));
     }
 
+    #[test]
+    fn bool() {
+        assert_decodes_to("true", Bool(true));
+        assert_decodes_to("false", Bool(false));
+    }
+
+    #[test]
+    fn bytes() {
+        assert_decodes_to("[\"AAEC/w==\"]", Bytes(vec![0, 1, 2, 255]));
+        assert_decodes_to("[\"\"]", Bytes(vec![]));
+    }
+
+    #[test]
+    fn invalid_base64_bytes_are_rejected() {
+        assert_results_in_error("[\"not base64!\"]", "invalid base64");
+    }
+
     #[test]
     fn null() {
         assert_decodes_to("null", Null);
@@ -324,6 +449,31 @@ mod decode_tests {
         );
     }
 
+    #[test]
+    fn map() {
+        let mut expected = HashMap::new();
+        expected.insert("a".to_string(), Integer(1));
+        expected.insert("b".to_string(), Text("x".to_string()));
+        assert_decodes_to("{\"a\": 1, \"b\": \"x\"}", Map(expected));
+    }
+
+    #[test]
+    fn map_with_entity() {
+        let ctx = MockDecodeCtx::new(12);
+        let mut expected = HashMap::new();
+        expected.insert("target".to_string(), Entity(ctx[5]));
+        assert_decodes_to_with_ctx(&ctx, "{\"target\": [5]}", Map(expected));
+    }
+
+    #[test]
+    fn map_with_unknown_object_is_error() {
+        assert_results_in_error_with_ctx(
+            &MockDecodeCtx::new(12),
+            "{\"target\": [88]}",
+            "object #88",
+        );
+    }
+
     #[test]
     fn array_size_two_is_error() {
         assert_results_in_error("[1, 2]", "length 2");
@@ -359,18 +509,28 @@ mod decode_tests {
 mod message_tests {
     use super::*;
 
+    /// Decodes the JSON, returning the first error if any message failed
+    fn decode(
+        decoder: &mut JsonDecoder,
+        ctx: &dyn DecodeCtx,
+        json: &str,
+    ) -> RequestResult<Vec<Request>> {
+        decoder
+            .decode(ctx, json.as_bytes().to_owned())
+            .into_iter()
+            .collect()
+    }
+
     fn assert_results_in_request(ctx: &dyn DecodeCtx, json: &str, request: Request) {
         let mut decoder = JsonDecoder::new();
-        let result = decoder
-            .decode(ctx, json.as_bytes().to_owned())
-            .expect("failed to decode");
+        let result = decode(&mut decoder, ctx, json).expect("failed to decode");
         assert_eq!(result, vec![request]);
     }
 
     fn assert_results_in_error(json: &str, msg: &str) {
         let mut decoder = JsonDecoder::new();
         let ctx = MockDecodeCtx::new(12);
-        match decoder.decode(&ctx, json.as_bytes().to_owned()) {
+        match decode(&mut decoder, &ctx, json) {
             Ok(output) => panic!("should have errored, instead gave: {:?}", output),
             Err(e) if !format!("{}", e).contains(msg) => {
                 panic!("{:?} does not contain {:?}", e, msg)
@@ -393,6 +553,26 @@ mod message_tests {
         );
     }
 
+    #[test]
+    fn get_once_request() {
+        let e = MockDecodeCtx::new(12);
+        assert_results_in_request(
+            &e,
+            "{ \
+                \"mtype\": \"get_once\", \
+                \"object\": 6, \
+                \"property\": \"foobar\" \
+            }\n",
+            Request::get_once(e[6], "foobar".to_owned()),
+        );
+    }
+
+    #[test]
+    fn root_request() {
+        let e = MockDecodeCtx::new(12);
+        assert_results_in_request(&e, "{\"mtype\": \"root\"}\n", Request::Root);
+    }
+
     #[test]
     fn basic_set_request() {
         let e = MockDecodeCtx::new(12);
@@ -423,6 +603,74 @@ mod message_tests {
         );
     }
 
+    fn fire_request_with_value(value: &str) -> String {
+        format!(
+            "{{\"mtype\": \"fire\", \"object\": 9, \"property\": \"xyz\", \"value\": {}}}\n",
+            value
+        )
+    }
+
+    /// An integer inside the given number of array-wrapped arrays
+    fn nested_value(levels: usize) -> String {
+        format!("{}1{}", "[[".repeat(levels), "]]".repeat(levels))
+    }
+
+    #[test]
+    fn value_nested_to_limit_is_decoded() {
+        let mut decoder = JsonDecoder::new();
+        let ctx = MockDecodeCtx::new(12);
+        let json = fire_request_with_value(&nested_value(DecodeLimits::default().max_depth));
+        assert!(decode(&mut decoder, &ctx, &json).is_ok());
+    }
+
+    #[test]
+    fn value_nested_beyond_limit_is_error() {
+        let json = fire_request_with_value(&nested_value(DecodeLimits::default().max_depth + 1));
+        assert_results_in_error(&json, "nested more than");
+    }
+
+    #[test]
+    fn extremely_nested_value_is_error() {
+        // Must be rejected without overflowing the stack
+        let json = fire_request_with_value(&nested_value(100_000));
+        assert_results_in_error(&json, "recursion limit");
+    }
+
+    #[test]
+    fn value_with_too_many_elements_is_error() {
+        let mut decoder = JsonDecoder::new();
+        decoder.set_limits(DecodeLimits {
+            max_depth: 4,
+            max_elements: 10,
+        });
+        let ctx = MockDecodeCtx::new(12);
+        let small = fire_request_with_value("[[1, 2, 3, 4, 5, 6, 7, 8, 9]]");
+        assert!(decode(&mut decoder, &ctx, &small).is_ok());
+        let big = fire_request_with_value("[[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]]");
+        match decode(&mut decoder, &ctx, &big) {
+            Err(e) => assert!(e.to_string().contains("more than 10 values")),
+            Ok(output) => panic!("should have errored, instead gave: {:?}", output),
+        }
+    }
+
+    #[test]
+    fn element_limit_counts_values_across_nesting() {
+        let mut decoder = JsonDecoder::new();
+        decoder.set_limits(DecodeLimits {
+            max_depth: 4,
+            max_elements: 10,
+        });
+        let ctx = MockDecodeCtx::new(12);
+        // The map, both arrays and their contents are 9 values, none of them big on their own
+        let small = fire_request_with_value(r#"{"a": [[1, 2, 3]], "b": [[4, 5, 6]]}"#);
+        assert!(decode(&mut decoder, &ctx, &small).is_ok());
+        let big = fire_request_with_value(r#"{"a": [[1, 2, 3]], "b": [[4, 5, 6]], "c": [[7]]}"#);
+        match decode(&mut decoder, &ctx, &big) {
+            Err(e) => assert!(e.to_string().contains("more than 10 values")),
+            Ok(output) => panic!("should have errored, instead gave: {:?}", output),
+        }
+    }
+
     #[test]
     fn basic_subscribe_request() {
         let e = MockDecodeCtx::new(12);
@@ -437,6 +685,65 @@ mod message_tests {
         );
     }
 
+    #[test]
+    fn filtered_subscribe_request() {
+        let e = MockDecodeCtx::new(12);
+        let mut properties = HashMap::new();
+        properties.insert("body_class".to_string(), Value::Text("ship".to_string()));
+        assert_results_in_request(
+            &e,
+            "{ \
+                \"mtype\": \"subscribe\", \
+                \"object\": 2, \
+                \"property\": \"bodies\", \
+                \"filter\": {\"body_class\": \"ship\"} \
+            }\n",
+            Request::subscribe_with_options(
+                e[2],
+                "bodies".to_owned(),
+                SubscribeOptions {
+                    filter: Some(EntityFilter::new(properties)),
+                    ..SubscribeOptions::default()
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn subscribe_request_with_frame() {
+        let e = MockDecodeCtx::new(12);
+        assert_results_in_request(
+            &e,
+            "{ \
+                \"mtype\": \"subscribe\", \
+                \"object\": 2, \
+                \"property\": \"position\", \
+                \"frame\": 5 \
+            }\n",
+            Request::subscribe_with_options(
+                e[2],
+                "position".to_owned(),
+                SubscribeOptions {
+                    frame: Some(e[5]),
+                    ..SubscribeOptions::default()
+                },
+            ),
+        );
+    }
+
+    #[test]
+    fn subscribe_filter_must_be_an_object() {
+        assert_results_in_error(
+            "{ \
+                \"mtype\": \"subscribe\", \
+                \"object\": 2, \
+                \"property\": \"bodies\", \
+                \"filter\": [\"ship\"] \
+            }\n",
+            "filter is not an object",
+        );
+    }
+
     #[test]
     fn basic_unsubscribe_request() {
         let e = MockDecodeCtx::new(12);
@@ -475,11 +782,7 @@ mod message_tests {
         let mut result = Vec::new();
         let e = MockDecodeCtx::new(12);
         for json in json {
-            result.extend(
-                decoder
-                    .decode(&e, json.as_bytes().to_owned())
-                    .expect("failed to decode"),
-            );
+            result.extend(decode(&mut decoder, &e, json).expect("failed to decode"));
         }
         assert_eq!(
             result,
@@ -511,9 +814,7 @@ mod message_tests {
             }\n";
         let mut decoder = JsonDecoder::new();
         let e = MockDecodeCtx::new(12);
-        let result = decoder
-            .decode(&e, json.as_bytes().to_owned())
-            .expect("failed to decode");
+        let result = decode(&mut decoder, &e, json).expect("failed to decode");
         assert_eq!(
             result,
             vec![
@@ -549,11 +850,7 @@ mod message_tests {
         let mut result = Vec::new();
         let e = MockDecodeCtx::new(12);
         for json in json {
-            result.extend(
-                decoder
-                    .decode(&e, json.as_bytes().to_owned())
-                    .expect("failed to decode"),
-            );
+            result.extend(decode(&mut decoder, &e, json).expect("failed to decode"));
         }
         assert_eq!(
             result,
@@ -565,6 +862,28 @@ mod message_tests {
         );
     }
 
+    #[test]
+    fn can_process_multiple_requests_with_nul_delimiter() {
+        let json = vec![
+            "{\"mtype\": \"get\", \"object\": 9, \"prop",
+            "erty\": \"foobar\"}\0{\"mtype\": \"set\", \"object\": 2, \"property\": \"abc\",\n",
+            "\"value\": 12}\0",
+        ];
+        let mut decoder = JsonDecoder::with_delimiter(b'\0');
+        let mut result = Vec::new();
+        let e = MockDecodeCtx::new(12);
+        for json in json {
+            result.extend(decode(&mut decoder, &e, json).expect("failed to decode"));
+        }
+        assert_eq!(
+            result,
+            vec![
+                Request::get(e[9], "foobar".to_owned()),
+                Request::set(e[2], "abc".to_owned(), Value::Integer(12)),
+            ]
+        );
+    }
+
     #[test]
     fn errors_without_mtype() {
         assert_results_in_error(
@@ -629,10 +948,41 @@ mod message_tests {
                 \"mtype\": \"set\", \
                 \"object\": 5, \
                 \"property\": \"foobar\", \
-                \"value\": {} \
+                \"value\": [1, 2] \
             }\n",
-            "map not implemented",
+            "non-wrapped array with length 2",
+        );
+    }
+
+    #[test]
+    fn bad_message_is_skipped() {
+        let json = "{ \
+                \"mtype\": \"get\", \
+                \"object\": 3, \
+                \"property\": \"foobar\" \
+            }\n \
+            { \
+                \"mtype\": \"get\", \
+            \n \
+            { \
+                \"mtype\": \"subscribe\", \
+                \"object\": 7, \
+                \"property\": \"xyz\" \
+            }\n";
+        let mut decoder = JsonDecoder::new();
+        let e = MockDecodeCtx::new(12);
+        let result = decoder.decode(&e, json.as_bytes().to_owned());
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0], Ok(Request::get(e[3], "foobar".to_owned())));
+        assert!(matches!(result[1], Err(BadMessage(_))));
+        assert_eq!(result[2], Ok(Request::subscribe(e[7], "xyz".to_owned())));
+        let result = decoder.decode(
+            &e,
+            "{\"mtype\": \"get\", \"object\": 1, \"property\": \"a\"}\n"
+                .as_bytes()
+                .to_owned(),
         );
+        assert_eq!(result, vec![Ok(Request::get(e[1], "a".to_owned()))]);
     }
 
     #[test]
@@ -13,20 +13,38 @@ impl<'a, T> Contextualized<'a, T> {
     }
 }
 
+/// JSON can't represent NaN or infinity, so they are sent as null
+fn finite(value: f64) -> Option<f64> {
+    if value.is_finite() {
+        Some(value)
+    } else {
+        warn!("encoding non-finite number {} as null", value);
+        None
+    }
+}
+
 impl<'a> Serialize for Contextualized<'a, Value> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         match self.value {
             Value::Vector(vector) => {
                 use serde::ser::SerializeTuple;
                 let mut tuple = serializer.serialize_tuple(3)?;
-                tuple.serialize_element(&vector.x)?;
-                tuple.serialize_element(&vector.y)?;
-                tuple.serialize_element(&vector.z)?;
+                tuple.serialize_element(&finite(vector.x))?;
+                tuple.serialize_element(&finite(vector.y))?;
+                tuple.serialize_element(&finite(vector.z))?;
                 tuple.end()
             }
-            Value::Scalar(value) => serializer.serialize_f64(*value),
+            Value::Scalar(value) => finite(*value).serialize(serializer),
             Value::Integer(value) => serializer.serialize_i64(*value),
             Value::Text(value) => serializer.serialize_str(value),
+            Value::Bool(value) => serializer.serialize_bool(*value),
+            Value::Bytes(bytes) => {
+                // Array-wrapped to keep it unambiguous with text
+                use serde::ser::SerializeTuple;
+                let mut outer = serializer.serialize_tuple(1)?;
+                outer.serialize_element(&base64::encode(bytes))?;
+                outer.end()
+            }
             Value::Entity(entity) => {
                 use serde::ser::SerializeTuple;
                 let mut outer = serializer.serialize_tuple(1)?;
@@ -39,6 +57,14 @@ impl<'a> Serialize for Contextualized<'a, Value> {
                 outer.serialize_element(&Contextualized::new(list, self.ctx))?;
                 outer.end()
             }
+            Value::Map(map) => {
+                use serde::ser::SerializeMap;
+                let mut outer = serializer.serialize_map(Some(map.len()))?;
+                for (key, value) in map {
+                    outer.serialize_entry(key, &Contextualized::new(value, self.ctx))?;
+                }
+                outer.end()
+            }
             Value::Null => serializer.serialize_none(),
         }
     }
@@ -56,41 +82,60 @@ impl<'a> Serialize for Contextualized<'a, Vec<Value>> {
     }
 }
 
-pub struct JsonEncoder {}
+pub struct JsonEncoder {
+    /// Appended to each event, if any
+    delimiter: Option<u8>,
+}
 
 impl JsonEncoder {
     pub fn new() -> Self {
-        Self {}
+        Self { delimiter: None }
     }
-}
 
-impl Encoder for JsonEncoder {
-    fn encode_event(&self, ctx: &dyn EncodeCtx, event: &Event) -> Result<Vec<u8>, Box<dyn Error>> {
-        // TODO: why aren't we reusing buffers?
+    /// Ends each encoded event with the given byte, so they can be split on it
+    pub fn with_delimiter(delimiter: u8) -> Self {
+        Self {
+            delimiter: Some(delimiter),
+        }
+    }
+
+    /// Encodes a request the same way a client would send it. Close and malformed requests can not
+    /// be encoded.
+    pub fn encode_request(
+        &self,
+        ctx: &dyn EncodeCtx,
+        request: &Request,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let (entity, property, method) = match request {
+            Request::Method(entity, property, method) => (entity, property, method),
+            _ => return Err(format!("{:?} can not be encoded", request).into()),
+        };
         let buffer = Vec::with_capacity(128);
         let mut serializer = serde_json::Serializer::new(buffer);
         let mut message = serializer.serialize_map(None)?;
-        match event {
-            Event::Method(entity, member, method, value) => {
+        let (mtype, value) = match method {
+            RequestMethod::Action(value) => ("fire", Some(value)),
+            RequestMethod::Set(value) => ("set", Some(value)),
+            RequestMethod::Get => ("get", None),
+            RequestMethod::GetOnce => ("get_once", None),
+            RequestMethod::Subscribe(_) => ("subscribe", None),
+            RequestMethod::Unsubscribe => ("unsubscribe", None),
+        };
+        message.serialize_field("mtype", mtype)?;
+        message.serialize_field("object", &ctx.object_for(*entity))?;
+        message.serialize_field("property", property)?;
+        if let Some(value) = value {
+            message.serialize_field("value", &Contextualized::new(value, ctx))?;
+        }
+        if let RequestMethod::Subscribe(options) = method {
+            if let Some(filter) = &options.filter {
                 message.serialize_field(
-                    "mtype",
-                    match method {
-                        EventMethod::Value => "value",
-                        EventMethod::Update => "update",
-                        EventMethod::Signal => "event",
-                    },
+                    "filter",
+                    &Contextualized::new(&Value::Map(filter.properties().clone()), ctx),
                 )?;
-                message.serialize_field("object", &ctx.object_for(*entity))?;
-                message.serialize_field("property", member)?;
-                message.serialize_field("value", &Contextualized::new(value, ctx))?;
-            }
-            Event::Destroyed(entity) => {
-                message.serialize_field("mtype", "destroyed")?;
-                message.serialize_field("object", &ctx.object_for(*entity))?;
             }
-            Event::FatalError(text) => {
-                message.serialize_field("mtype", "error")?;
-                message.serialize_field("text", text)?;
+            if let Some(frame) = options.frame {
+                message.serialize_field("frame", &ctx.object_for(frame))?;
             }
         }
         message.end()?;
@@ -98,6 +143,96 @@ impl Encoder for JsonEncoder {
     }
 }
 
+impl Encoder for JsonEncoder {
+    fn encode_event(&self, ctx: &dyn EncodeCtx, event: &Event) -> Result<Vec<u8>, Box<dyn Error>> {
+        // TODO: why aren't we reusing buffers?
+        let buffer = Vec::with_capacity(128);
+        let mut serializer = serde_json::Serializer::new(buffer);
+        serialize_event(&mut serializer, ctx, event)?;
+        let mut buffer = serializer.into_inner();
+        if let Some(delimiter) = self.delimiter {
+            buffer.push(delimiter);
+        }
+        Ok(buffer)
+    }
+}
+
+/// Serializes an event as a map with an mtype field. Other formats (ex MessagePack) send the same
+/// messages, so they use this with their own serializer.
+pub fn serialize_event<S: Serializer>(
+    serializer: S,
+    ctx: &dyn EncodeCtx,
+    event: &Event,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let mut message = serializer.serialize_map(None)?;
+    let (event, time) = match event {
+        Event::Timestamped(time, event) => (event.as_ref(), Some(*time)),
+        event => (event, None),
+    };
+    match event {
+        Event::Method(entity, member, method, value) => {
+            message.serialize_entry(
+                "mtype",
+                match method {
+                    EventMethod::Value => "value",
+                    EventMethod::Update => "update",
+                    EventMethod::Signal => "event",
+                },
+            )?;
+            message.serialize_entry("object", &ctx.object_for(*entity))?;
+            message.serialize_entry("property", member)?;
+            message.serialize_entry("value", &Contextualized::new(value, ctx))?;
+        }
+        Event::Delta(entity, member, added, removed) => {
+            message.serialize_entry("mtype", "delta")?;
+            message.serialize_entry("object", &ctx.object_for(*entity))?;
+            message.serialize_entry("property", member)?;
+            message.serialize_entry("added", &Contextualized::new(added, ctx))?;
+            message.serialize_entry("removed", &Contextualized::new(removed, ctx))?;
+        }
+        Event::Destroyed(entity) => {
+            message.serialize_entry("mtype", "destroyed")?;
+            message.serialize_entry("object", &ctx.object_for(*entity))?;
+        }
+        Event::FatalError(text, retry_after) => {
+            message.serialize_entry("mtype", "error")?;
+            message.serialize_entry("text", text)?;
+            if let Some(retry_after) = retry_after {
+                message.serialize_entry("retry_after", retry_after)?;
+            }
+        }
+        Event::Error(text) => {
+            message.serialize_entry("mtype", "request_error")?;
+            message.serialize_entry("text", text)?;
+        }
+        Event::Info(info) => {
+            message.serialize_entry("mtype", "info")?;
+            message.serialize_entry("server_name", &info.server_name)?;
+            message.serialize_entry("server_version", &info.server_version)?;
+            message.serialize_entry("protocol_version", &info.protocol_version)?;
+            message.serialize_entry("tick_rate", &info.tick_rate)?;
+            message.serialize_entry("root", &ctx.object_for(info.root_entity))?;
+        }
+        Event::Root(entity) => {
+            message.serialize_entry("mtype", "root")?;
+            message.serialize_entry("object", &ctx.object_for(*entity))?;
+        }
+        Event::Shutdown => {
+            message.serialize_entry("mtype", "shutdown")?;
+        }
+        Event::Timestamped(..) => {
+            return Err(serde::ser::Error::custom(
+                "timestamped events can not be nested",
+            ))
+        }
+    }
+    if let Some(time) = time {
+        message.serialize_entry("time", &finite(time))?;
+    }
+    message.end()
+}
+
 #[cfg(test)]
 mod encodable_tests {
     use super::*;
@@ -138,6 +273,15 @@ mod encodable_tests {
         assert_json_eq(4.9.into(), "4.9");
     }
 
+    #[test]
+    fn non_finite_floats_are_null() {
+        assert_json_eq(std::f64::NAN.into(), "null");
+        assert_json_eq(
+            Vector3::new(1.0, std::f64::INFINITY, std::f64::NEG_INFINITY).into(),
+            "[1.0, null, null]",
+        );
+    }
+
     #[test]
     fn int() {
         let i: i64 = -243;
@@ -155,11 +299,32 @@ mod encodable_tests {
         assert_json_eq(vec![1, 2, 3, 69, 42].into(), "[[1, 2, 3, 69, 42]]");
     }
 
+    #[test]
+    fn bool() {
+        assert_json_eq(true.into(), "true");
+        assert_json_eq(false.into(), "false");
+    }
+
+    #[test]
+    fn bytes() {
+        assert_json_eq(Value::Bytes(vec![0, 1, 2, 255]), "[\"AAEC/w==\"]");
+        assert_json_eq(Value::Bytes(vec![]), "[\"\"]");
+    }
+
     #[test]
     fn null() {
         assert_json_eq(().into(), "null");
     }
 
+    #[test]
+    fn map() {
+        let e: Vec<EntityKey> = mock_keys(1);
+        let mut map = HashMap::new();
+        map.insert("target".to_string(), Value::Entity(e[0]));
+        map.insert("speed".to_string(), Value::Scalar(2.5));
+        assert_json_eq(Value::Map(map), "{\"target\": [42], \"speed\": 2.5}");
+    }
+
     #[test]
     fn entity() {
         let e: Vec<EntityKey> = mock_keys(1);
@@ -194,6 +359,22 @@ mod message_tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn update_with_nan_scalar_is_valid() {
+        let p = JsonEncoder::new();
+        let e = mock_keys(1);
+        let ev = Event::update(e[0], "period".to_string(), std::f64::NAN.into());
+        assert_json_eq(
+            &p.encode_event(&MockEncoderCtx, &ev).unwrap(),
+            "{
+                \"mtype\": \"update\",
+                \"object\": 42,
+                \"property\": \"period\",
+                \"value\": null
+            }",
+        );
+    }
+
     #[test]
     fn basic_property_update() {
         let p = JsonEncoder::new();
@@ -266,6 +447,27 @@ mod message_tests {
         )
     }
 
+    #[test]
+    fn list_delta() {
+        let p = JsonEncoder::new();
+        let e = mock_keys(2);
+        let prop = "bodies".to_string();
+        assert_json_eq(
+            &p.encode_event(
+                &MockEncoderCtx,
+                &Event::delta(e[0], prop, vec![e[1].into()], vec![]),
+            )
+            .unwrap(),
+            "{
+                \"mtype\": \"delta\",
+                \"object\": 42,
+                \"property\": \"bodies\",
+                \"added\": [[42]],
+                \"removed\": []
+            }",
+        )
+    }
+
     #[test]
     fn entity_destroyed() {
         let p = JsonEncoder::new();
@@ -280,12 +482,25 @@ mod message_tests {
         )
     }
 
+    #[test]
+    fn root() {
+        let p = JsonEncoder::new();
+        let e = mock_keys(1);
+        assert_json_eq(
+            &p.encode_event(&MockEncoderCtx, &Event::Root(e[0])).unwrap(),
+            "{
+                \"mtype\": \"root\",
+                \"object\": 42
+            }",
+        )
+    }
+
     #[test]
     fn fatal_error() {
         let p = JsonEncoder::new();
         let message = "Error Message".to_string();
         assert_json_eq(
-            &p.encode_event(&MockEncoderCtx, &Event::FatalError(message))
+            &p.encode_event(&MockEncoderCtx, &Event::FatalError(message, None))
                 .unwrap(),
             "{
                 \"mtype\": \"error\",
@@ -293,4 +508,144 @@ mod message_tests {
             }",
         )
     }
+
+    #[test]
+    fn fatal_error_with_retry_hint() {
+        let p = JsonEncoder::new();
+        let message = "Error Message".to_string();
+        assert_json_eq(
+            &p.encode_event(&MockEncoderCtx, &Event::FatalError(message, Some(10)))
+                .unwrap(),
+            "{
+                \"mtype\": \"error\",
+                \"text\": \"Error Message\",
+                \"retry_after\": 10
+            }",
+        )
+    }
+
+    #[test]
+    fn timestamped_event_has_time() {
+        let p = JsonEncoder::new();
+        let e = mock_keys(1);
+        let ev = Event::update(e[0], "mass".to_string(), 7.0.into());
+        assert_json_eq(
+            &p.encode_event(&MockEncoderCtx, &Event::Timestamped(12.5, Box::new(ev)))
+                .unwrap(),
+            "{
+                \"mtype\": \"update\",
+                \"object\": 42,
+                \"property\": \"mass\",
+                \"value\": 7.0,
+                \"time\": 12.5
+            }",
+        )
+    }
+
+    #[test]
+    fn non_fatal_error() {
+        let p = JsonEncoder::new();
+        let message = "Error Message".to_string();
+        assert_json_eq(
+            &p.encode_event(&MockEncoderCtx, &Event::Error(message))
+                .unwrap(),
+            "{
+                \"mtype\": \"request_error\",
+                \"text\": \"Error Message\"
+            }",
+        )
+    }
+
+    #[test]
+    fn server_info() {
+        let p = JsonEncoder::new();
+        let e = mock_keys(1);
+        let info = ServerInfo {
+            server_name: "staging".to_string(),
+            server_version: "0.1.0".to_string(),
+            protocol_version: 3,
+            tick_rate: 30.0,
+            root_entity: e[0],
+        };
+        assert_json_eq(
+            &p.encode_event(&MockEncoderCtx, &Event::Info(info)).unwrap(),
+            "{
+                \"mtype\": \"info\",
+                \"server_name\": \"staging\",
+                \"server_version\": \"0.1.0\",
+                \"protocol_version\": 3,
+                \"tick_rate\": 30.0,
+                \"root\": 42
+            }",
+        )
+    }
+
+    #[test]
+    fn fire_request() {
+        let p = JsonEncoder::new();
+        let e = mock_keys(1);
+        let request = Request::action(e[0], "thrust".to_string(), Value::Scalar(2.0));
+        assert_json_eq(
+            &p.encode_request(&MockEncoderCtx, &request).unwrap(),
+            "{
+                \"mtype\": \"fire\",
+                \"object\": 42,
+                \"property\": \"thrust\",
+                \"value\": 2.0
+            }",
+        )
+    }
+
+    #[test]
+    fn subscribe_request() {
+        let p = JsonEncoder::new();
+        let e = mock_keys(1);
+        let request = Request::subscribe(e[0], "position".to_string());
+        assert_json_eq(
+            &p.encode_request(&MockEncoderCtx, &request).unwrap(),
+            "{
+                \"mtype\": \"subscribe\",
+                \"object\": 42,
+                \"property\": \"position\"
+            }",
+        )
+    }
+
+    #[test]
+    fn shutdown() {
+        let p = JsonEncoder::new();
+        assert_json_eq(
+            &p.encode_event(&MockEncoderCtx, &Event::Shutdown).unwrap(),
+            "{
+                \"mtype\": \"shutdown\"
+            }",
+        )
+    }
+
+    #[test]
+    fn events_end_with_delimiter() {
+        let p = JsonEncoder::with_delimiter(b'\0');
+        let e = mock_keys(1);
+        let mut stream = p
+            .encode_event(&MockEncoderCtx, &Event::Destroyed(e[0]))
+            .unwrap();
+        stream.extend(p.encode_event(&MockEncoderCtx, &Event::Shutdown).unwrap());
+        assert_eq!(stream.last(), Some(&b'\0'));
+        let mut splitter = DatagramSplitter::new(b'\0', usize::MAX);
+        let datagrams = splitter.data(stream);
+        assert_eq!(datagrams.len(), 2);
+        assert_json_eq(
+            datagrams[0].as_ref().unwrap(),
+            "{
+                \"mtype\": \"destroyed\",
+                \"object\": 42
+            }",
+        );
+        assert_json_eq(
+            datagrams[1].as_ref().unwrap(),
+            "{
+                \"mtype\": \"shutdown\"
+            }",
+        );
+    }
 }
@@ -4,8 +4,15 @@ mod json_decoder;
 mod json_encoder;
 
 pub use json_decoder::JsonDecoder;
-pub use json_encoder::JsonEncoder;
+pub use json_encoder::{serialize_event, JsonEncoder};
 
-pub fn json_protocol_impls() -> (Box<dyn Encoder>, Box<dyn Decoder>) {
-    (Box::new(JsonEncoder::new()), Box::new(JsonDecoder::new()))
+/// Without a delimiter requests are newline-delimited and events are left for the session to frame
+pub fn json_protocol_impls(delimiter: Option<u8>) -> (Box<dyn Encoder>, Box<dyn Decoder>) {
+    match delimiter {
+        Some(delimiter) => (
+            Box::new(JsonEncoder::with_delimiter(delimiter)),
+            Box::new(JsonDecoder::with_delimiter(delimiter)),
+        ),
+        None => (Box::new(JsonEncoder::new()), Box::new(JsonDecoder::new())),
+    }
 }
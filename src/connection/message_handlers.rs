@@ -31,8 +31,41 @@ pub trait RequestHandler {
         entity: EntityKey,
         name: &str,
     ) -> RequestResult<Box<dyn Any>>;
-    /// Takes a subscription that was previously returned from subscribe()
+    /// Like get_property(), but the value is what a subscription with the options would get
+    fn get_property_with_options(
+        &self,
+        connection: ConnectionKey,
+        entity: EntityKey,
+        name: &str,
+        options: &SubscribeOptions,
+    ) -> RequestResult<Value>;
+    /// Like subscribe(), but with options such as a filter for entity lists. Only properties take
+    /// options.
+    fn subscribe_with_options(
+        &mut self,
+        connection: ConnectionKey,
+        entity: EntityKey,
+        name: &str,
+        options: &SubscribeOptions,
+    ) -> RequestResult<Box<dyn Any>>;
+    /// Takes a subscription that was previously returned from subscribe() or
+    /// subscribe_with_options()
     fn unsubscribe(&mut self, subscription: Box<dyn Any>) -> RequestResult<()>;
+    /// Returns the connections the game has asked to be closed since the last call
+    fn take_disconnect_requests(&mut self) -> Vec<ConnectionKey>;
+    /// Returns if the game has asked for object maps to be reconciled since the last call
+    fn take_reconcile_request(&mut self) -> bool;
+    /// Returns the connections that have asked for a list of connections since the last call
+    fn take_connection_list_requests(&mut self) -> Vec<ConnectionKey>;
+    /// Called once a connection has been closed and removed
+    fn connection_closed(&mut self, connection: ConnectionKey);
+    /// Used to find object map entries for entities that have been destroyed
+    fn entity_exists(&self, entity: EntityKey) -> bool;
+    /// When a connection can't send everything at once, objects with a higher priority are sent
+    /// first
+    fn send_priority(&self, connection: ConnectionKey, entity: EntityKey) -> f64;
+    /// The current game time, used to timestamp events
+    fn time(&self) -> f64;
 }
 
 /// Allows sending property updates and other messages to clients. Implemented by
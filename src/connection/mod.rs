@@ -7,23 +7,36 @@ mod bundle_handler;
 #[allow(clippy::module_inception)]
 mod connection;
 mod connection_collection;
+mod connection_config;
 mod event;
+mod flush_strategy;
 mod format;
 mod json;
 mod message_handlers;
+mod msgpack;
+mod object_id_store;
 mod object_map;
 mod request;
 mod request_error;
+mod request_recording;
 
-pub use connection::{Connection, ConnectionImpl, ConnectionKey};
+pub use connection::{Bandwidth, Connection, ConnectionImpl, ConnectionKey};
 pub use connection_collection::ConnectionCollection;
-pub use event::{Event, EventMethod};
+pub use connection_config::ConnectionConfig;
+pub use event::{Event, EventKind, EventMethod, ServerInfo};
+pub use flush_strategy::{FlushStrategy, FlushTiming};
 pub use message_handlers::{EventHandler, RequestHandler};
+pub use object_id_store::{FileObjectIdStore, ObjectIdStore};
 pub use object_map::{ObjectId, ObjectMap};
 pub use request::{Request, RequestMethod};
 pub use request_error::{RequestError, RequestError::*, RequestResult};
+pub use request_recording::{RequestRecorder, RequestReplay};
 
 use bundle_handler::BundleHandler;
-use format::{DecodeCtx, Decoder, EncodeCtx, Encoder};
-use json::json_protocol_impls;
+pub use format::{select_format, DecodeLimits};
+
+use format::{protocol_impls_for, DecodeCtx, Decoder, EncodeCtx, Encoder};
+use json::{json_protocol_impls, serialize_event, JsonDecoder};
+use msgpack::msgpack_protocol_impls;
+use object_id_store::validate_identity;
 use object_map::ObjectMapImpl;
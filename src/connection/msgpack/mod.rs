@@ -0,0 +1,15 @@
+use super::*;
+
+mod msgpack_decoder;
+mod msgpack_encoder;
+
+pub use msgpack_decoder::MsgpackDecoder;
+pub use msgpack_encoder::MsgpackEncoder;
+
+/// MessagePack values are self-delimiting, so the delimiter is ignored
+pub fn msgpack_protocol_impls(_delimiter: Option<u8>) -> (Box<dyn Encoder>, Box<dyn Decoder>) {
+    (
+        Box::new(MsgpackEncoder::new()),
+        Box::new(MsgpackDecoder::new()),
+    )
+}
@@ -0,0 +1,214 @@
+use super::*;
+use serde::de::Deserialize;
+
+// Cap incomplete messages at 10MB, same as JSON datagrams
+const MAX_MESSAGE_LEN: usize = 10_000_000;
+
+/// Decodes MessagePack requests. They have the same structure as JSON requests, so each one is
+/// parsed into a serde_json::Value and then turned into a request by the JSON decoder.
+pub struct MsgpackDecoder {
+    requests: JsonDecoder,
+    /// Bytes of a message that hasn't been fully received yet
+    pending: Vec<u8>,
+}
+
+impl MsgpackDecoder {
+    pub fn new() -> Self {
+        Self {
+            requests: JsonDecoder::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// If the error is because the message has not been fully received yet
+fn is_incomplete(error: &rmp_serde::decode::Error) -> bool {
+    use rmp_serde::decode::Error::*;
+    match error {
+        InvalidMarkerRead(e) | InvalidDataRead(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+        _ => false,
+    }
+}
+
+impl Decoder for MsgpackDecoder {
+    fn set_limits(&mut self, limits: DecodeLimits) {
+        self.requests.set_limits(limits);
+    }
+
+    fn decode(&mut self, ctx: &dyn DecodeCtx, bytes: Vec<u8>) -> Vec<RequestResult<Request>> {
+        self.pending.extend(bytes);
+        let mut results = Vec::new();
+        let mut consumed = 0;
+        while consumed < self.pending.len() {
+            let mut remaining = &self.pending[consumed..];
+            let mut deserializer = rmp_serde::Deserializer::new(&mut remaining);
+            let parsed = serde_json::Value::deserialize(&mut deserializer);
+            match parsed {
+                Ok(value) => {
+                    consumed = self.pending.len() - remaining.len();
+                    results.push(self.requests.decode_request(ctx, &value));
+                }
+                Err(e) if is_incomplete(&e) => break,
+                Err(e) => {
+                    // There's no way to find the start of the next message, so drop everything
+                    consumed = self.pending.len();
+                    results.push(Err(BadMessage(e.to_string())));
+                }
+            }
+        }
+        self.pending.drain(..consumed);
+        if self.pending.len() > MAX_MESSAGE_LEN {
+            self.pending.clear();
+            results.push(Err(BadMessage(format!(
+                "message longer than {} bytes",
+                MAX_MESSAGE_LEN
+            ))));
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serializes JSON values as a client would. serde_json's own Serialize impl writes numbers
+    /// as strings when arbitrary_precision is enabled.
+    struct AsClient<'a>(&'a serde_json::Value);
+
+    impl<'a> serde::Serialize for AsClient<'a> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::{SerializeMap, SerializeSeq};
+            match self.0 {
+                serde_json::Value::Number(n) => match n.as_u64() {
+                    Some(n) => serializer.serialize_u64(n),
+                    None => serializer.serialize_f64(n.as_f64().unwrap()),
+                },
+                serde_json::Value::Array(array) => {
+                    let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                    for value in array {
+                        seq.serialize_element(&AsClient(value))?;
+                    }
+                    seq.end()
+                }
+                serde_json::Value::Object(object) => {
+                    let mut map = serializer.serialize_map(Some(object.len()))?;
+                    for (key, value) in object {
+                        map.serialize_entry(key, &AsClient(value))?;
+                    }
+                    map.end()
+                }
+                value => value.serialize(serializer),
+            }
+        }
+    }
+
+    fn request(json: serde_json::Value) -> Vec<u8> {
+        rmp_serde::to_vec(&AsClient(&json)).unwrap()
+    }
+
+    fn get_request(object: ObjectId) -> Vec<u8> {
+        request(serde_json::json!({"mtype": "get", "object": object, "property": "mass"}))
+    }
+
+    fn setup() -> (MsgpackDecoder, RwLock<ObjectMapImpl>, EntityKey, ObjectId) {
+        let map = ObjectMapImpl::new();
+        let entity = mock_keys(1)[0];
+        let object = map.get_or_create_object(entity);
+        (MsgpackDecoder::new(), map, entity, object)
+    }
+
+    #[test]
+    fn decodes_request() {
+        let (mut decoder, map, entity, object) = setup();
+        assert_eq!(
+            decoder.decode(&map, get_request(object)),
+            vec![Ok(Request::get(entity, "mass".to_string()))]
+        );
+    }
+
+    #[test]
+    fn decodes_request_with_value() {
+        let (mut decoder, map, entity, object) = setup();
+        let bytes = request(serde_json::json!({
+            "mtype": "set",
+            "object": object,
+            "property": "accel",
+            "value": [1.0, 0.5, 0],
+        }));
+        assert_eq!(
+            decoder.decode(&map, bytes),
+            vec![Ok(Request::set(
+                entity,
+                "accel".to_string(),
+                Vector3::new(1.0, 0.5, 0.0).into()
+            ))]
+        );
+    }
+
+    #[test]
+    fn decodes_multiple_requests_at_once() {
+        let (mut decoder, map, entity, object) = setup();
+        let mut bytes = get_request(object);
+        bytes.extend(request(serde_json::json!({"mtype": "root"})));
+        assert_eq!(
+            decoder.decode(&map, bytes),
+            vec![
+                Ok(Request::get(entity, "mass".to_string())),
+                Ok(Request::Root)
+            ]
+        );
+    }
+
+    #[test]
+    fn decodes_request_split_across_bundles() {
+        let (mut decoder, map, entity, object) = setup();
+        let bytes = get_request(object);
+        let (start, end) = bytes.split_at(bytes.len() / 2);
+        assert_eq!(decoder.decode(&map, start.to_vec()), vec![]);
+        assert_eq!(
+            decoder.decode(&map, end.to_vec()),
+            vec![Ok(Request::get(entity, "mass".to_string()))]
+        );
+    }
+
+    #[test]
+    fn invalid_request_is_error() {
+        let (mut decoder, map, _, object) = setup();
+        let bytes = request(serde_json::json!({"mtype": "foo", "object": object}));
+        let results = decoder.decode(&map, bytes);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn bad_bundle_is_reported_and_skipped() {
+        let (mut decoder, map, entity, object) = setup();
+        // 0xc1 is never used in MessagePack
+        let results = decoder.decode(&map, vec![0xc1, 0x00, 0x01]);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+        assert_eq!(
+            decoder.decode(&map, get_request(object)),
+            vec![Ok(Request::get(entity, "mass".to_string()))]
+        );
+    }
+
+    #[test]
+    fn decode_limits_apply() {
+        let (mut decoder, map, _, object) = setup();
+        decoder.set_limits(DecodeLimits {
+            max_depth: 32,
+            max_elements: 2,
+        });
+        let bytes = request(serde_json::json!({
+            "mtype": "fire",
+            "object": object,
+            "property": "foo",
+            "value": {"a": 1, "b": 2, "c": 3},
+        }));
+        let results = decoder.decode(&map, bytes);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use super::*;
+
+/// Encodes the same messages as the JSON encoder, but as MessagePack. Values are structured the
+/// same way (ex bytes are array-wrapped base64) so clients can share the logic for both.
+pub struct MsgpackEncoder;
+
+impl MsgpackEncoder {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Encoder for MsgpackEncoder {
+    fn encode_event(&self, ctx: &dyn EncodeCtx, event: &Event) -> Result<Vec<u8>, Box<dyn Error>> {
+        let buffer = Vec::with_capacity(128);
+        let mut serializer = rmp_serde::Serializer::new(buffer);
+        serialize_event(&mut serializer, ctx, event)?;
+        Ok(serializer.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockEncodeCtx;
+
+    impl EncodeCtx for MockEncodeCtx {
+        fn object_for(&self, _entity: EntityKey) -> ObjectId {
+            42
+        }
+    }
+
+    fn assert_encodes_to(event: Event, expected: serde_json::Value) {
+        let bytes = MsgpackEncoder::new()
+            .encode_event(&MockEncodeCtx, &event)
+            .expect("failed to encode");
+        let actual: serde_json::Value = rmp_serde::from_slice(&bytes).expect("invalid MessagePack");
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn update() {
+        let e = mock_keys(1);
+        assert_encodes_to(
+            Event::update(
+                e[0],
+                "position".to_string(),
+                Vector3::new(1.0, 2.0, 3.0).into(),
+            ),
+            serde_json::json!({
+                "mtype": "update",
+                "object": 42,
+                "property": "position",
+                "value": [1.0, 2.0, 3.0]
+            }),
+        );
+    }
+
+    #[test]
+    fn entity_value() {
+        let e = mock_keys(1);
+        assert_encodes_to(
+            Event::value(e[0], "parent".to_string(), Value::Entity(e[0])),
+            serde_json::json!({
+                "mtype": "value",
+                "object": 42,
+                "property": "parent",
+                "value": [42]
+            }),
+        );
+    }
+
+    #[test]
+    fn fatal_error() {
+        assert_encodes_to(
+            Event::FatalError("server full".to_string(), Some(10)),
+            serde_json::json!({
+                "mtype": "error",
+                "text": "server full",
+                "retry_after": 10
+            }),
+        );
+    }
+
+    #[test]
+    fn events_are_self_delimiting() {
+        let e = mock_keys(1);
+        let encoder = MsgpackEncoder::new();
+        let mut bytes = encoder
+            .encode_event(&MockEncodeCtx, &Event::Root(e[0]))
+            .unwrap();
+        bytes.extend(
+            encoder
+                .encode_event(&MockEncodeCtx, &Event::Shutdown)
+                .unwrap(),
+        );
+        let mut deserializer = rmp_serde::Deserializer::new(&bytes[..]);
+        use serde::de::Deserialize;
+        let first = serde_json::Value::deserialize(&mut deserializer).unwrap();
+        let second = serde_json::Value::deserialize(&mut deserializer).unwrap();
+        assert_eq!(first, serde_json::json!({"mtype": "root", "object": 42}));
+        assert_eq!(second, serde_json::json!({"mtype": "shutdown"}));
+    }
+}
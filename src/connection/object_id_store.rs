@@ -0,0 +1,289 @@
+use super::*;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Longest identity a client may persist its object IDs under
+const MAX_IDENTITY_LEN: usize = 64;
+
+/// Persists the object IDs an ObjectMap hands out, keyed by a stable identity the client provides.
+/// This lets a client that reconnects see the same object IDs for the same entities.
+pub trait ObjectIdStore: Send + Sync {
+    /// Returns every entity/object ID pair saved for the identity. Fails if the identity is new
+    /// and the store can't take any more.
+    fn load(&self, identity: &str) -> RequestResult<Vec<(EntityKey, ObjectId)>>;
+    /// Saves a pair, which replaces any earlier pair for the same entity
+    fn save(&self, identity: &str, entity: EntityKey, object: ObjectId);
+}
+
+/// Identities are used as file names, so they are restricted to letters, numbers, - and _
+pub fn validate_identity(identity: &str) -> RequestResult<()> {
+    if identity.is_empty()
+        || identity.len() > MAX_IDENTITY_LEN
+        || !identity
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Err(BadRequest(format!(
+            "{:?} is an invalid identity (must be 1-{} letters, numbers, - or _)",
+            identity, MAX_IDENTITY_LEN
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Stores each identity's object IDs in a file in the given directory. Each line is an entity key
+/// and an object ID. Clients pick their own identities, so the number of identities and the number
+/// of lines in each file are limited to keep them from filling the disk.
+pub struct FileObjectIdStore {
+    dir: PathBuf,
+    /// New identities are rejected once there are this many
+    max_identities: usize,
+    /// Saves for an identity are dropped once its file has this many lines after compacting
+    max_objects: usize,
+    /// Line count of each identity's file. Also held while writing so lines from different
+    /// connections don't interleave.
+    files: Mutex<HashMap<String, usize>>,
+}
+
+impl FileObjectIdStore {
+    /// Creates the directory if it doesn't exist
+    pub fn new(
+        dir: &str,
+        max_identities: usize,
+        max_objects: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        std::fs::create_dir_all(dir)?;
+        let mut files = HashMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if let Some(identity) = entry.file_name().to_str() {
+                if validate_identity(identity).is_ok() {
+                    let lines = std::fs::read_to_string(entry.path())?.lines().count();
+                    files.insert(identity.to_string(), lines);
+                }
+            }
+        }
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            max_identities,
+            max_objects,
+            files: Mutex::new(files),
+        })
+    }
+
+    fn path(&self, identity: &str) -> PathBuf {
+        self.dir.join(identity)
+    }
+
+    /// Reads the latest object for each entity saved under the identity
+    fn read(&self, identity: &str) -> std::io::Result<HashMap<EntityKey, ObjectId>> {
+        let contents = match std::fs::read_to_string(self.path(identity)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e),
+        };
+        let mut objects = HashMap::new();
+        for line in contents.lines() {
+            let mut fields = line.split(' ').map(str::parse::<u64>);
+            match (fields.next(), fields.next()) {
+                (Some(Ok(entity)), Some(Ok(object))) => {
+                    objects.insert(slotmap::KeyData::from_ffi(entity).into(), object);
+                }
+                _ => warn!("invalid line in object IDs for {:?}: {:?}", identity, line),
+            }
+        }
+        Ok(objects)
+    }
+
+    /// Rewrites the identity's file with only the given objects, dropping lines that have been
+    /// replaced. Written to a temporary file first so a crash can't lose the old one.
+    fn compact(
+        &self,
+        identity: &str,
+        objects: &HashMap<EntityKey, ObjectId>,
+    ) -> std::io::Result<()> {
+        let temp = self.dir.join(format!("{}.compacting", identity));
+        let mut file = std::fs::File::create(&temp)?;
+        for (entity, object) in objects {
+            writeln!(
+                file,
+                "{} {}",
+                slotmap::KeyData::from(*entity).as_ffi(),
+                object
+            )?;
+        }
+        file.sync_all()?;
+        std::fs::rename(temp, self.path(identity))
+    }
+
+    /// Compacts the file if it has lines that have been replaced, returns the new line count
+    fn compact_if_needed(
+        &self,
+        identity: &str,
+        lines: usize,
+        objects: &HashMap<EntityKey, ObjectId>,
+    ) -> usize {
+        if lines > objects.len() {
+            match self.compact(identity, objects) {
+                Ok(()) => return objects.len(),
+                Err(e) => warn!("failed to compact object IDs for {:?}: {}", identity, e),
+            }
+        }
+        lines
+    }
+}
+
+impl ObjectIdStore for FileObjectIdStore {
+    fn load(&self, identity: &str) -> RequestResult<Vec<(EntityKey, ObjectId)>> {
+        let mut files = self.files.lock().expect("failed to lock object ID store");
+        let lines = match files.get(identity) {
+            Some(lines) => *lines,
+            None if files.len() >= self.max_identities => {
+                warn!(
+                    "not persisting object IDs for {:?}, maximum of {} identities reached",
+                    identity, self.max_identities
+                );
+                return Err(BadRequest("object ID store is full".into()));
+            }
+            None => {
+                files.insert(identity.to_string(), 0);
+                return Ok(Vec::new());
+            }
+        };
+        let objects = match self.read(identity) {
+            Ok(objects) => objects,
+            Err(e) => {
+                warn!("failed to load object IDs for {:?}: {}", identity, e);
+                return Ok(Vec::new());
+            }
+        };
+        let lines = self.compact_if_needed(identity, lines, &objects);
+        files.insert(identity.to_string(), lines);
+        Ok(objects.into_iter().collect())
+    }
+
+    fn save(&self, identity: &str, entity: EntityKey, object: ObjectId) {
+        let mut files = self.files.lock().expect("failed to lock object ID store");
+        let mut lines = match files.get(identity) {
+            Some(lines) => *lines,
+            None => {
+                error!("object ID saved for {:?} before it was loaded", identity);
+                return;
+            }
+        };
+        if lines >= self.max_objects {
+            lines = match self.read(identity) {
+                Ok(objects) => self.compact_if_needed(identity, lines, &objects),
+                Err(e) => {
+                    warn!("failed to load object IDs for {:?}: {}", identity, e);
+                    lines
+                }
+            };
+            files.insert(identity.to_string(), lines);
+            if lines >= self.max_objects {
+                warn!(
+                    "not saving object ID for {:?}, maximum of {} objects reached",
+                    identity, self.max_objects
+                );
+                return;
+            }
+        }
+        let saved = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(identity))
+            .and_then(|mut file| {
+                writeln!(
+                    file,
+                    "{} {}",
+                    slotmap::KeyData::from(entity).as_ffi(),
+                    object
+                )
+            });
+        match saved {
+            Ok(()) => {
+                files.insert(identity.to_string(), lines + 1);
+            }
+            Err(e) => error!("failed to save object ID: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("starscape-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn identities_must_be_simple_names() {
+        assert!(validate_identity("phone-1_A").is_ok());
+        assert!(validate_identity("").is_err());
+        assert!(validate_identity("../secrets").is_err());
+        assert!(validate_identity(&"a".repeat(MAX_IDENTITY_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn file_store_loads_latest_saved_objects() {
+        let dir = temp_dir("object-ids");
+        let store = FileObjectIdStore::new(&dir, 10, 10).unwrap();
+        let e = mock_keys(2);
+        assert_eq!(store.load("a"), Ok(vec![]));
+        assert_eq!(store.load("b"), Ok(vec![]));
+        store.save("a", e[0], 2);
+        store.save("a", e[1], 3);
+        store.save("a", e[0], 4);
+        store.save("b", e[1], 7);
+        let mut loaded = FileObjectIdStore::new(&dir, 10, 10)
+            .unwrap()
+            .load("a")
+            .unwrap();
+        loaded.sort_by_key(|(_, object)| *object);
+        assert_eq!(loaded, vec![(e[1], 3), (e[0], 4)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_store_compacts_replaced_lines_on_load() {
+        let dir = temp_dir("object-ids-compact");
+        let store = FileObjectIdStore::new(&dir, 10, 10).unwrap();
+        let e = mock_keys(1);
+        store.load("a").unwrap();
+        for object in 1..=5 {
+            store.save("a", e[0], object);
+        }
+        let store = FileObjectIdStore::new(&dir, 10, 10).unwrap();
+        assert_eq!(store.load("a"), Ok(vec![(e[0], 5)]));
+        let contents = std::fs::read_to_string(std::path::Path::new(&dir).join("a")).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_store_limits_identities_and_objects() {
+        let dir = temp_dir("object-ids-limits");
+        let store = FileObjectIdStore::new(&dir, 2, 2).unwrap();
+        let e = mock_keys(3);
+        store.load("a").unwrap();
+        store.load("b").unwrap();
+        assert!(store.load("c").is_err());
+        // Replaced lines are compacted away to make room before anything is dropped
+        store.save("a", e[0], 1);
+        store.save("a", e[0], 2);
+        store.save("a", e[1], 3);
+        store.save("a", e[2], 4);
+        let mut loaded = store.load("a").unwrap();
+        loaded.sort_by_key(|(_, object)| *object);
+        assert_eq!(loaded, vec![(e[0], 2), (e[1], 3)]);
+        // Identities with files still count after a restart
+        let store = FileObjectIdStore::new(&dir, 1, 2).unwrap();
+        assert!(store.load("a").is_ok());
+        assert!(store.load("b").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
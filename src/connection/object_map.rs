@@ -9,6 +9,7 @@ pub type ObjectId = u64;
 /// access.
 pub trait ObjectMap: Send + Sync {
     /// Returns the corresponding object ID if the entity is known
+    #[cfg(test)]
     fn get_object(&self, entity: EntityKey) -> Option<ObjectId>;
     /// Returns the corrosponding object ID, or creates a new object ID associated with entity
     fn get_or_create_object(&self, entity: EntityKey) -> ObjectId;
@@ -18,10 +19,15 @@ pub trait ObjectMap: Send + Sync {
     /// returns None, and future calls to get_or_create_object() creates a new ID. IDs are not
     /// recycled.
     fn remove_entity(&self, entity: EntityKey) -> Option<ObjectId>;
+    /// Loads the object IDs saved under identity, and saves all objects from now on. Saved IDs
+    /// that conflict with ones already handed out are ignored. Can only be done once.
+    fn persist(&self, store: Arc<dyn ObjectIdStore>, identity: String) -> RequestResult<()>;
+    /// Removes every entity keep() returns false for, returns how many were removed
+    fn retain_entities(&self, keep: &dyn Fn(EntityKey) -> bool) -> usize;
+    /// Every object ID currently mapped and its entity, sorted by object ID
+    fn objects(&self) -> Vec<(ObjectId, EntityKey)>;
     /// Just needs to return self, only required because Rust is stupid
     fn as_encode_ctx(&self) -> &dyn EncodeCtx;
-    /// Just needs to return self, only required because Rust is stupid
-    fn as_decode_ctx(&self) -> &dyn DecodeCtx;
 }
 
 impl<T: ObjectMap> EncodeCtx for T {
@@ -36,22 +42,34 @@ impl<T: ObjectMap> DecodeCtx for T {
     }
 }
 
+/// The slotmap index of an entity, which is shared by entities that reuse the same slot
+fn slot_index(entity: EntityKey) -> u32 {
+    slotmap::KeyData::from(entity).as_ffi() as u32
+}
+
 /// A RwLock of this type is the normal ObjectMap implementation
 pub struct ObjectMapImpl {
     map: BiHashMap<EntityKey, ObjectId>,
+    /// The most recent entity seen in each slot, used to detect slot reuse
+    slots: HashMap<u32, EntityKey>,
     next_id: ObjectId,
+    /// If set, new objects are saved under the identity
+    store: Option<(Arc<dyn ObjectIdStore>, String)>,
 }
 
 impl ObjectMapImpl {
     pub fn new() -> RwLock<Self> {
         RwLock::new(ObjectMapImpl {
             map: BiHashMap::new(),
+            slots: HashMap::new(),
             next_id: 1,
+            store: None,
         })
     }
 }
 
 impl ObjectMap for RwLock<ObjectMapImpl> {
+    #[cfg(test)]
     fn get_object(&self, entity: EntityKey) -> Option<ObjectId> {
         self.read()
             .expect("failed to lock object map")
@@ -74,18 +92,35 @@ impl ObjectMap for RwLock<ObjectMapImpl> {
                 let mut write = self.write().expect("failed to lock object map");
                 // Because unlocking a reader and locking a writer isn't atomic, we need to check
                 // that the object hasn't been created in the gap
-                match write.map.get_by_left(&entity) {
-                    Some(obj) => *obj,
+                let (id, store) = match write.map.get_by_left(&entity) {
+                    Some(obj) => (*obj, None),
                     None => {
+                        // If an older entity in the same slot is still mapped it must have been
+                        // destroyed without us hearing about it. Retire its object rather than
+                        // letting the client confuse it with the new entity.
+                        if let Some(stale) = write.slots.insert(slot_index(entity), entity) {
+                            if let Some((_, obj)) = write.map.remove_by_left(&stale) {
+                                warn!(
+                                    "slot of {:?} reused by {:?}, retiring object {}",
+                                    stale, entity, obj
+                                );
+                            }
+                        }
                         let id = write.next_id;
                         write.next_id += 1;
                         let overwitten = write.map.insert(entity, id);
                         if overwitten != bimap::Overwritten::Neither {
                             panic!("logic error: overwrite bimap value: {:?}", overwitten)
                         }
-                        id
+                        (id, write.store.clone())
                     }
+                };
+                drop(write);
+                // Saved after unlocking so other threads aren't held up by file IO
+                if let Some((store, identity)) = store {
+                    store.save(&identity, entity, id);
                 }
+                id
             }
         }
     }
@@ -99,18 +134,78 @@ impl ObjectMap for RwLock<ObjectMapImpl> {
     }
 
     fn remove_entity(&self, entity: EntityKey) -> Option<ObjectId> {
-        self.write()
+        let mut write = self.write().expect("failed to lock object map");
+        if write.slots.get(&slot_index(entity)) == Some(&entity) {
+            write.slots.remove(&slot_index(entity));
+        }
+        write.map.remove_by_left(&entity).map(|(_, o)| o)
+    }
+
+    fn persist(&self, store: Arc<dyn ObjectIdStore>, identity: String) -> RequestResult<()> {
+        let already_persisted = || BadRequest("object IDs are already persisted".into());
+        if self
+            .read()
             .expect("failed to lock object map")
+            .store
+            .is_some()
+        {
+            return Err(already_persisted());
+        }
+        // File IO is done without the lock held, so other threads aren't held up by it
+        let saved = store.load(&identity)?;
+        let unsaved: Vec<(EntityKey, ObjectId)> = {
+            let mut write = self.write().expect("failed to lock object map");
+            if write.store.is_some() {
+                return Err(already_persisted());
+            }
+            for &(entity, object) in &saved {
+                if !write.map.contains_left(&entity) && !write.map.contains_right(&object) {
+                    write.map.insert(entity, object);
+                    write.slots.entry(slot_index(entity)).or_insert(entity);
+                    write.next_id = write.next_id.max(object + 1);
+                }
+            }
+            write.store = Some((store.clone(), identity.clone()));
+            write
+                .map
+                .iter()
+                .map(|(entity, object)| (*entity, *object))
+                .filter(|pair| !saved.contains(pair))
+                .collect()
+        };
+        // Objects handed out before now are saved too, so the next session agrees with this one
+        for (entity, object) in unsaved {
+            store.save(&identity, entity, object);
+        }
+        Ok(())
+    }
+
+    fn retain_entities(&self, keep: &dyn Fn(EntityKey) -> bool) -> usize {
+        let mut write = self.write().expect("failed to lock object map");
+        let removed: Vec<EntityKey> = write
             .map
-            .remove_by_left(&entity)
-            .map(|(_, o)| o)
+            .left_values()
+            .filter(|entity| !keep(**entity))
+            .cloned()
+            .collect();
+        for entity in &removed {
+            write.map.remove_by_left(entity);
+            if write.slots.get(&slot_index(*entity)) == Some(entity) {
+                write.slots.remove(&slot_index(*entity));
+            }
+        }
+        removed.len()
     }
 
-    fn as_encode_ctx(&self) -> &dyn EncodeCtx {
-        self
+    fn objects(&self) -> Vec<(ObjectId, EntityKey)> {
+        let read = self.read().expect("failed to lock object map");
+        let mut objects: Vec<(ObjectId, EntityKey)> =
+            read.map.iter().map(|(e, o)| (*o, *e)).collect();
+        objects.sort_unstable_by_key(|(o, _)| *o);
+        objects
     }
 
-    fn as_decode_ctx(&self) -> &dyn DecodeCtx {
+    fn as_encode_ctx(&self) -> &dyn EncodeCtx {
         self
     }
 }
@@ -119,6 +214,26 @@ impl ObjectMap for RwLock<ObjectMapImpl> {
 mod objects_tests {
     use super::*;
 
+    #[derive(Default)]
+    struct MockObjectIdStore(Mutex<Vec<(String, EntityKey, ObjectId)>>);
+
+    impl ObjectIdStore for MockObjectIdStore {
+        fn load(&self, identity: &str) -> RequestResult<Vec<(EntityKey, ObjectId)>> {
+            let saved = self.0.lock().unwrap();
+            Ok(saved
+                .iter()
+                .filter(|(i, _, _)| i == identity)
+                .map(|(_, entity, object)| (*entity, *object))
+                .collect())
+        }
+
+        fn save(&self, identity: &str, entity: EntityKey, object: ObjectId) {
+            let mut saved = self.0.lock().unwrap();
+            saved.retain(|(i, e, _)| !(i == identity && *e == entity));
+            saved.push((identity.to_string(), entity, object));
+        }
+    }
+
     #[test]
     fn objects_can_be_created_and_looked_up() {
         let map = ObjectMapImpl::new();
@@ -210,4 +325,85 @@ mod objects_tests {
         assert_eq!(map.remove_entity(e[0]), Some(o));
         assert_ne!(map.get_or_create_object(e[0]), o);
     }
+
+    #[test]
+    fn entity_reusing_slot_gets_new_object() {
+        let map = ObjectMapImpl::new();
+        let mut entities = DenseSlotMap::<EntityKey, ()>::with_key();
+        let old = entities.insert(());
+        let old_obj = map.get_or_create_object(old);
+        entities.remove(old);
+        let new = entities.insert(());
+        assert_eq!(slot_index(old), slot_index(new));
+        let new_obj = map.get_or_create_object(new);
+        assert_ne!(new_obj, old_obj);
+        assert_eq!(map.get_entity(new_obj), Some(new));
+        assert_eq!(map.get_entity(old_obj), None);
+        assert_eq!(map.get_object(old), None);
+    }
+
+    #[test]
+    fn removed_entity_slot_reuse_gets_new_object() {
+        let map = ObjectMapImpl::new();
+        let mut entities = DenseSlotMap::<EntityKey, ()>::with_key();
+        let old = entities.insert(());
+        let old_obj = map.get_or_create_object(old);
+        assert_eq!(map.remove_entity(old), Some(old_obj));
+        entities.remove(old);
+        let new = entities.insert(());
+        let new_obj = map.get_or_create_object(new);
+        assert_ne!(new_obj, old_obj);
+        assert_eq!(map.get_entity(old_obj), None);
+    }
+
+    #[test]
+    fn objects_lists_exactly_the_live_mappings() {
+        let map = ObjectMapImpl::new();
+        let e = mock_keys(4);
+        let o: Vec<ObjectId> = e
+            .iter()
+            .map(|entity| map.get_or_create_object(*entity))
+            .collect();
+        map.remove_entity(e[1]);
+        map.retain_entities(&|entity| entity != e[3]);
+        let o4 = map.get_or_create_object(e[1]);
+        assert_eq!(map.objects(), vec![(o[0], e[0]), (o[2], e[2]), (o4, e[1])]);
+    }
+
+    #[test]
+    fn sessions_with_same_identity_get_same_object_ids() {
+        let store: Arc<dyn ObjectIdStore> = Arc::new(MockObjectIdStore::default());
+        let e = mock_keys(4);
+        let first = ObjectMapImpl::new();
+        first.get_or_create_object(e[0]);
+        first.persist(store.clone(), "phone".to_string()).unwrap();
+        let o: Vec<ObjectId> = e[1..]
+            .iter()
+            .map(|entity| first.get_or_create_object(*entity))
+            .collect();
+        let second = ObjectMapImpl::new();
+        second.get_or_create_object(e[0]);
+        second.persist(store.clone(), "phone".to_string()).unwrap();
+        // Looked up in a different order than the first session created them
+        assert_eq!(second.get_or_create_object(e[3]), o[2]);
+        assert_eq!(second.get_entity(o[0]), Some(e[1]));
+        assert_eq!(second.get_object(e[2]), Some(o[1]));
+        let other = ObjectMapImpl::new();
+        other.get_or_create_object(e[0]);
+        other.persist(store, "tablet".to_string()).unwrap();
+        assert_eq!(other.get_object(e[3]), None);
+    }
+
+    #[test]
+    fn new_objects_do_not_reuse_saved_ids() {
+        let store: Arc<dyn ObjectIdStore> = Arc::new(MockObjectIdStore::default());
+        let e = mock_keys(3);
+        let first = ObjectMapImpl::new();
+        first.persist(store.clone(), "phone".to_string()).unwrap();
+        let o = first.get_or_create_object(e[0]);
+        let second = ObjectMapImpl::new();
+        second.persist(store.clone(), "phone".to_string()).unwrap();
+        assert_ne!(second.get_or_create_object(e[1]), o);
+        assert!(second.persist(store, "phone".to_string()).is_err());
+    }
 }
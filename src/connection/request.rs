@@ -6,7 +6,9 @@ pub enum RequestMethod {
     Action(Value),
     Set(Value),
     Get,
-    Subscribe,
+    /// Like Get, but responded to right away and errors (such as getting a signal) are reported
+    GetOnce,
+    Subscribe(SubscribeOptions),
     Unsubscribe,
 }
 
@@ -16,8 +18,14 @@ pub enum Request {
     /// A method on an object member (property/action/signal). The member is represented by it's
     /// entity and name).
     Method(EntityKey, String, RequestMethod),
+    /// Asks for the root object, answered with a Root event. Lets clients find it without
+    /// assuming its object ID.
+    Root,
     /// Indicates the session should close.
     Close,
+    /// The client sent something that could not be decoded. It is reported back to the client and
+    /// otherwise ignored.
+    Malformed(RequestError),
 }
 
 impl Request {
@@ -33,8 +41,25 @@ impl Request {
         Self::Method(entity, name, RequestMethod::Get)
     }
 
+    pub fn get_once(entity: EntityKey, name: String) -> Self {
+        Self::Method(entity, name, RequestMethod::GetOnce)
+    }
+
+    #[cfg(test)]
     pub fn subscribe(entity: EntityKey, name: String) -> Self {
-        Self::Method(entity, name, RequestMethod::Subscribe)
+        Self::Method(
+            entity,
+            name,
+            RequestMethod::Subscribe(SubscribeOptions::default()),
+        )
+    }
+
+    pub fn subscribe_with_options(
+        entity: EntityKey,
+        name: String,
+        options: SubscribeOptions,
+    ) -> Self {
+        Self::Method(entity, name, RequestMethod::Subscribe(options))
     }
 
     pub fn unsubscribe(entity: EntityKey, name: String) -> Self {
@@ -14,6 +14,10 @@ pub enum RequestError {
     /// When the request is invalid for some other reason, such as an out-of-range value, a value
     /// of the wrong type, a method that's not allowed the member, etc
     BadRequest(String),
+    /// The connection is not allowed to access this member
+    PermissionDenied(EntityKey, String),
+    /// The member has no current value to get, such as a signal
+    NotAvailable(EntityKey, String),
     /// Returned when there is an internal server error. The connection logs this as an error as
     /// well as sending it to the client.
     InternalError(String),
@@ -29,6 +33,8 @@ impl std::fmt::Display for RequestError {
             Self::BadEntity(e) => write!(f, "{:?} is invalid or destroyed", e),
             Self::BadName(e, n) => write!(f, "{:?} has no member {:?}", e, n),
             Self::BadRequest(msg) => write!(f, "{}", msg),
+            Self::PermissionDenied(e, n) => write!(f, "not allowed to access {:?} on {:?}", n, e),
+            Self::NotAvailable(e, n) => write!(f, "{:?} on {:?} has no current value", n, e),
             Self::InternalError(e) => write!(f, "{}", e),
         }
     }
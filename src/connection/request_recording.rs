@@ -0,0 +1,201 @@
+use super::json::{JsonDecoder, JsonEncoder};
+use super::*;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+/// Uses the raw entity key as the object ID. A recording can be replayed into a state that was
+/// set up the same way as the recorded one, since that creates the same entities in the same order.
+struct RawKeyCtx;
+
+impl EncodeCtx for RawKeyCtx {
+    fn object_for(&self, entity: EntityKey) -> ObjectId {
+        slotmap::KeyData::from(entity).as_ffi()
+    }
+}
+
+impl DecodeCtx for RawKeyCtx {
+    fn entity_for(&self, object: ObjectId) -> RequestResult<EntityKey> {
+        Ok(slotmap::KeyData::from_ffi(object).into())
+    }
+}
+
+/// Records the requests clients send so they can be replayed later by RequestReplay, useful for
+/// reproducing bugs. Each line is the engine tick, the connection ID and the request encoded as
+/// JSON.
+pub struct RequestRecorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+    encoder: JsonEncoder,
+    tick: AtomicU64,
+}
+
+impl RequestRecorder {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            encoder: JsonEncoder::new(),
+            tick: AtomicU64::new(0),
+        }
+    }
+
+    /// Records to a new file at the given path, overwriting any existing file
+    pub fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = std::fs::File::create(path)?;
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+
+    /// Should be called at the end of each engine tick
+    pub fn next_tick(&self) {
+        self.tick.fetch_add(1, SeqCst);
+    }
+
+    pub fn record(&self, connection: ConnectionKey, request: &Request) {
+        let json = match self.encoder.encode_request(&RawKeyCtx, request) {
+            Ok(json) => json,
+            Err(e) => {
+                error!("failed to record request: {}", e);
+                return;
+            }
+        };
+        let mut writer = self.writer.lock().expect("failed to lock request recorder");
+        // Flushed every time so the recording is complete even if the server crashes
+        write!(writer, "{} {} ", self.tick.load(SeqCst), connection.to_id())
+            .and_then(|()| writer.write_all(&json))
+            .and_then(|()| writer.write_all(b"\n"))
+            .and_then(|()| writer.flush())
+            .or_log_error("failed to record request");
+    }
+}
+
+/// Requests loaded from a recording made by RequestRecorder, which are applied to the game as if
+/// the original clients had sent them
+pub struct RequestReplay {
+    /// Tick, connection and request, in the order they were recorded
+    requests: VecDeque<(u64, ConnectionKey, Request)>,
+    tick: u64,
+}
+
+impl RequestReplay {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = std::fs::File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, Box<dyn Error>> {
+        let mut decoder = JsonDecoder::new();
+        let mut requests = VecDeque::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let mut parts = line.splitn(3, ' ');
+            let mut next_part = || {
+                parts
+                    .next()
+                    .ok_or_else(|| format!("line {} of recording is incomplete", i + 1))
+            };
+            let tick: u64 = next_part()?.parse()?;
+            let connection = ConnectionKey::from_id(next_part()?.parse()?);
+            let json = format!("{}\n", next_part()?);
+            for request in decoder.decode(&RawKeyCtx, json.into_bytes()) {
+                requests.push_back((tick, connection, request?));
+            }
+        }
+        Ok(Self { requests, tick: 0 })
+    }
+
+    /// If every request has been replayed
+    pub fn is_done(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Applies the requests recorded during the current tick and moves on to the next one. Should
+    /// be called once per engine tick, before client requests are processed. Only actions and
+    /// sets are replayed, since gets and subscriptions don't change the game.
+    pub fn replay_tick(&mut self, handler: &mut dyn RequestHandler) {
+        while let Some((tick, _, _)) = self.requests.front() {
+            if *tick > self.tick {
+                break;
+            }
+            let (_, connection, request) = self.requests.pop_front().unwrap();
+            if let Request::Method(entity, name, method) = request {
+                match method {
+                    RequestMethod::Action(value) => handler
+                        .fire_action(connection, entity, &name, value)
+                        .or_log_error("replaying action"),
+                    RequestMethod::Set(value) => handler
+                        .set_property(connection, entity, &name, value)
+                        .or_log_error("replaying set"),
+                    _ => (),
+                }
+            }
+        }
+        self.tick += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_requests_are_loaded_with_ticks() {
+        let buffer = SharedBuffer::default();
+        let recorder = RequestRecorder::new(Box::new(buffer.clone()));
+        let e = mock_keys(2);
+        let c = ConnectionKey::from_id(3);
+        let action = Request::action(e[0], "act".to_string(), vec![e[1]].into());
+        let set = Request::set(e[1], "prop".to_string(), Value::Scalar(1.5));
+        let get = Request::get(e[1], "prop".to_string());
+        recorder.record(c, &action);
+        recorder.next_tick();
+        recorder.next_tick();
+        recorder.record(c, &set);
+        recorder.record(c, &get);
+        let replay = RequestReplay::from_reader(&buffer.contents()[..]).unwrap();
+        assert_eq!(
+            replay.requests,
+            vec![(0, c, action), (2, c, set), (2, c, get)]
+        );
+    }
+
+    #[test]
+    fn bytes_round_trip_exactly() {
+        let buffer = SharedBuffer::default();
+        let recorder = RequestRecorder::new(Box::new(buffer.clone()));
+        let e = mock_keys(1);
+        let c = ConnectionKey::from_id(3);
+        let set = Request::set(e[0], "blob".to_string(), Value::Bytes((0..=255).collect()));
+        recorder.record(c, &set);
+        let replay = RequestReplay::from_reader(&buffer.contents()[..]).unwrap();
+        assert_eq!(replay.requests, vec![(0, c, set)]);
+    }
+
+    #[test]
+    fn incomplete_line_is_error() {
+        assert!(RequestReplay::from_reader(&b"7 1\n"[..]).is_err());
+    }
+
+    #[test]
+    fn requests_are_replayed_on_their_tick() {
+        let e = mock_keys(1);
+        let c = ConnectionKey::from_id(1);
+        let mut replay = RequestReplay {
+            requests: vec![
+                (0, c, Request::action(e[0], "a".to_string(), Value::Null)),
+                (2, c, Request::set(e[0], "b".to_string(), Value::Integer(1))),
+                (2, c, Request::get(e[0], "c".to_string())),
+            ]
+            .into(),
+            tick: 0,
+        };
+        let mut handler = MockRequestHandler::new(Ok(()));
+        replay.replay_tick(&mut handler);
+        handler.assert_requests_eq(vec![Request::action(e[0], "a".to_string(), Value::Null)]);
+        replay.replay_tick(&mut handler);
+        assert!(!replay.is_done());
+        replay.replay_tick(&mut handler);
+        handler.assert_requests_eq(vec![
+            Request::action(e[0], "a".to_string(), Value::Null),
+            Request::set(e[0], "b".to_string(), Value::Integer(1)),
+        ]);
+        assert!(replay.is_done());
+    }
+}
@@ -0,0 +1,115 @@
+use super::*;
+
+/// Only lets connections the predicate allows use the conduit, see Conduit::with_access()
+pub struct AccessConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(ConnectionKey, &State) -> bool,
+{
+    conduit: C,
+    predicate: F,
+    pd: PhantomData<(O, I)>,
+}
+
+impl<C, F, O, I> AccessConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(ConnectionKey, &State) -> bool,
+{
+    pub fn new(conduit: C, predicate: F) -> Self {
+        Self {
+            conduit,
+            predicate,
+            pd: PhantomData,
+        }
+    }
+}
+
+impl<C, F, O, I> Conduit<O, I> for AccessConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(ConnectionKey, &State) -> bool + Send + Sync,
+    O: Send + Sync,
+    I: Send + Sync,
+{
+    fn output(&self, state: &State) -> RequestResult<O> {
+        self.conduit.output(state)
+    }
+
+    fn input(&self, state: &mut State, value: I) -> RequestResult<()> {
+        self.conduit.input(state, value)
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.conduit.metadata()
+    }
+
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        (self.predicate)(connection, state) && self.conduit.allows(connection, state)
+    }
+}
+
+impl<C, F, O, I> Subscribable for AccessConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(ConnectionKey, &State) -> bool + Send + Sync,
+    O: Send + Sync,
+    I: Send + Sync,
+{
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        self.conduit.subscribe(state, subscriber)
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        self.conduit.unsubscribe(state, subscriber)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(allowed: ConnectionKey) -> (State, EntityKey) {
+        let mut state = State::new();
+        let entity = state.create_entity().unwrap();
+        install_dial(&mut state, entity, 0)
+            .with_access(move |connection, _| connection == allowed)
+            .install_property(&mut state, entity, "open");
+        (state, entity)
+    }
+
+    #[test]
+    fn denied_connection_can_not_set() {
+        let allowed = ConnectionKey::from_id(1);
+        let denied = ConnectionKey::from_id(2);
+        let (mut state, entity) = setup(allowed);
+        assert_eq!(
+            state.set_property(denied, entity, "open", Value::Integer(1)),
+            Err(PermissionDenied(entity, "open".to_string()))
+        );
+        assert_eq!(*state.component::<Dial>(entity).unwrap().setting, 0);
+    }
+
+    #[test]
+    fn allowed_connection_can_set() {
+        let allowed = ConnectionKey::from_id(1);
+        let (mut state, entity) = setup(allowed);
+        state
+            .set_property(allowed, entity, "open", Value::Integer(1))
+            .unwrap();
+        assert_eq!(*state.component::<Dial>(entity).unwrap().setting, 1);
+    }
+
+    #[test]
+    fn denied_connection_can_not_get_or_subscribe() {
+        let allowed = ConnectionKey::from_id(1);
+        let denied = ConnectionKey::from_id(2);
+        let (mut state, entity) = setup(allowed);
+        assert!(state.get_property(denied, entity, "open").is_err());
+        assert!(state.subscribe(denied, entity, "open").is_err());
+        assert_eq!(
+            state.get_property(allowed, entity, "open"),
+            Ok(Value::Integer(0))
+        );
+    }
+}
@@ -20,6 +20,25 @@ where
             phantom_t: PhantomData,
         }
     }
+
+    /// Describes the action's parameters so client tooling can show what it expects
+    #[must_use]
+    pub fn with_params(
+        self,
+        params: ParamSchema,
+    ) -> MetadataConduit<Self, ActionsDontProduceOutputSilly, T>
+    where
+        T: Send + Sync,
+        IFn: Send + Sync,
+    {
+        MetadataConduit::new(
+            self,
+            Metadata {
+                params: Some(params),
+                ..Metadata::default()
+            },
+        )
+    }
 }
 
 pub enum ActionsDontProduceOutputSilly {}
@@ -1,6 +1,25 @@
 use super::*;
 
-/// The default property implementation
+/// Equality that allows for small differences, so values that barely change aren't resent
+pub trait ApproxEq {
+    fn approx_eq(&self, other: &Self, epsilon: f64) -> bool;
+}
+
+impl ApproxEq for Value {
+    fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        match (self, other) {
+            (Value::Vector(a), Value::Vector(b)) => (*a - *b).magnitude() <= epsilon,
+            (Value::Scalar(a), Value::Scalar(b)) => (a - b).abs() <= epsilon,
+            (Value::Array(a), Value::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.approx_eq(b, epsilon))
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+/// The default property implementation. Subscribers are only notified if the value has changed by
+/// more than the state's update epsilon since they were last notified.
 pub struct CachingConduit<C, T>
 where
     C: Conduit<T, T>,
@@ -32,7 +51,7 @@ where
 impl<C, T> Subscriber for CachingConduit<C, T>
 where
     C: Conduit<T, T>,
-    T: PartialEq + Send + Sync,
+    T: PartialEq + ApproxEq + Send + Sync,
 {
     fn notify(&self, state: &State, sink: &dyn EventHandler) {
         let value = match self.conduit.output(state) {
@@ -46,7 +65,11 @@ where
             .cached_value
             .lock()
             .expect("failed to lock cached value mutex");
-        if cached.as_ref() != Some(&value) {
+        let unchanged = match cached.as_ref() {
+            Some(cached) => cached.approx_eq(&value, state.update_epsilon()),
+            None => false,
+        };
+        if !unchanged {
             *cached = Some(value);
             self.subscribers.send_notifications(state, sink);
         }
@@ -56,7 +79,7 @@ where
 impl<C, T> Conduit<T, T> for Arc<CachingConduit<C, T>>
 where
     C: Conduit<T, T> + 'static,
-    T: PartialEq + Send + Sync,
+    T: PartialEq + ApproxEq + Send + Sync,
 {
     fn output(&self, state: &State) -> RequestResult<T> {
         // TODO: use cache if it is up to date
@@ -67,12 +90,16 @@ where
         // TODO: don't set if same as cache
         self.conduit.input(state, value)
     }
+
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        self.conduit.allows(connection, state)
+    }
 }
 
 impl<C, T> Subscribable for Arc<CachingConduit<C, T>>
 where
     C: Conduit<T, T> + 'static,
-    T: PartialEq + Send + Sync + 'static,
+    T: PartialEq + ApproxEq + Send + Sync + 'static,
 {
     fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
         if self.subscribers.add(subscriber)?.was_empty {
@@ -103,6 +130,12 @@ where
 mod tests {
     use super::*;
 
+    impl ApproxEq for i32 {
+        fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+            f64::from((self - other).abs()) <= epsilon
+        }
+    }
+
     struct MockConduit {
         value_to_get: RequestResult<i32>,
         subscribed: Option<Weak<dyn Subscriber>>,
@@ -310,6 +343,36 @@ mod tests {
         assert_eq!(mock_subscribers[0].notify_count(), 1);
     }
 
+    #[test]
+    fn only_notifies_when_change_is_more_than_epsilon() {
+        let (mut state, caching, inner, subscribers, mock_subscribers) = setup();
+        state.set_update_epsilon(5.0);
+        let event_handler = MockEventHandler::new();
+        caching
+            .subscribe(&state, &subscribers[0])
+            .expect("failed to subscribe");
+        inner.lock().unwrap().value_to_get = Ok(42);
+        caching.notify(&state, &event_handler);
+        assert_eq!(mock_subscribers[0].notify_count(), 1);
+        inner.lock().unwrap().value_to_get = Ok(45);
+        caching.notify(&state, &event_handler);
+        assert_eq!(mock_subscribers[0].notify_count(), 1);
+        // Compared to the last value sent rather than the last value seen
+        inner.lock().unwrap().value_to_get = Ok(48);
+        caching.notify(&state, &event_handler);
+        assert_eq!(mock_subscribers[0].notify_count(), 2);
+    }
+
+    #[test]
+    fn vectors_are_approximately_equal_within_epsilon() {
+        let a = Value::Vector(Vector3::new(1.0, 2.0, 3.0));
+        let b = Value::Vector(Vector3::new(1.0, 2.0, 3.5));
+        assert!(a.approx_eq(&b, 1.0));
+        assert!(!a.approx_eq(&b, 0.1));
+        assert!(Value::Array(vec![a.clone()]).approx_eq(&Value::Array(vec![b.clone()]), 1.0));
+        assert!(!a.approx_eq(&Value::Scalar(1.0), 100.0));
+    }
+
     /*
     #[test]
     fn does_not_attempt_to_get_value_multiple_times_on_one_tick() {
@@ -18,8 +18,9 @@ impl<T: 'static> ComponentListConduit<T> {
 
 impl<T: 'static> Conduit<Value, ReadOnlyPropSetType> for ComponentListConduit<T> {
     fn output(&self, state: &State) -> RequestResult<Value> {
+        // Ordered so the list only changes where entities were added or removed
         let entities: Vec<Value> = state
-            .components_iter::<T>()
+            .components_iter_ordered::<T>()
             .map(|(entity, _)| entity.into())
             .collect();
         Ok(entities.into())
@@ -0,0 +1,61 @@
+use super::*;
+
+/// A read-only conduit for a value that is calculated from the state rather than stored in an
+/// element. inputs_fn returns everything the value depends on, and subscribers are notified when
+/// any of them change.
+pub struct ComputedConduit<OFn, IFn> {
+    output_fn: OFn,
+    inputs_fn: IFn,
+}
+
+impl<T, OFn, IFn> ComputedConduit<OFn, IFn>
+where
+    OFn: Fn(&State) -> RequestResult<T>,
+    for<'a> IFn: Fn(&'a State) -> RequestResult<Vec<&'a dyn Subscribable>>,
+    OFn: 'static,
+    IFn: 'static,
+{
+    #[must_use]
+    pub fn new(output_fn: OFn, inputs_fn: IFn) -> Self {
+        Self {
+            output_fn,
+            inputs_fn,
+        }
+    }
+}
+
+impl<T, OFn, IFn> Conduit<T, ReadOnlyPropSetType> for ComputedConduit<OFn, IFn>
+where
+    OFn: Fn(&State) -> RequestResult<T>,
+    for<'a> IFn: Fn(&'a State) -> RequestResult<Vec<&'a dyn Subscribable>>,
+    OFn: Send + Sync + 'static,
+    IFn: Send + Sync + 'static,
+{
+    fn output(&self, state: &State) -> RequestResult<T> {
+        (self.output_fn)(state)
+    }
+
+    fn input(&self, _state: &mut State, _value: ReadOnlyPropSetType) -> RequestResult<()> {
+        // ReadOnlyPropSetType can't be instantiated, so this can't be called
+        std::unreachable!()
+    }
+}
+
+impl<OFn, IFn> Subscribable for ComputedConduit<OFn, IFn>
+where
+    for<'a> IFn: Fn(&'a State) -> RequestResult<Vec<&'a dyn Subscribable>>,
+{
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        for input in (self.inputs_fn)(state)? {
+            input.subscribe(state, subscriber)?;
+        }
+        Ok(())
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        for input in (self.inputs_fn)(state)? {
+            input.unsubscribe(state, subscriber)?;
+        }
+        Ok(())
+    }
+}
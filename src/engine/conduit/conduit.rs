@@ -6,6 +6,62 @@ pub trait Conduit<O, I>: Subscribable + Send + Sync {
     fn output(&self, state: &State) -> RequestResult<O>;
     fn input(&self, state: &mut State, value: I) -> RequestResult<()>;
 
+    /// Descriptive information about the value for clients
+    fn metadata(&self) -> Metadata {
+        Metadata::default()
+    }
+
+    /// If the given connection may get, set or subscribe to this
+    fn allows(&self, _connection: ConnectionKey, _state: &State) -> bool {
+        true
+    }
+
+    /// Only connections the predicate returns true for will be able to use the property
+    #[must_use]
+    fn with_access<F>(self, predicate: F) -> AccessConduit<Self, F, O, I>
+    where
+        Self: Sized,
+        F: Fn(ConnectionKey, &State) -> bool + Send + Sync,
+    {
+        AccessConduit::new(self, predicate)
+    }
+
+    /// Input the validator returns an error for is rejected without being set
+    #[must_use]
+    fn validate<F>(self, validator: F) -> ValidateConduit<Self, F, O, I>
+    where
+        Self: Sized,
+        F: Fn(&I) -> RequestResult<()> + Send + Sync,
+    {
+        ValidateConduit::new(self, validator)
+    }
+
+    /// Should come after any mapping, so the unit describes the value clients see
+    #[must_use]
+    fn with_unit(self, unit: &'static str) -> MetadataConduit<Self, O, I>
+    where
+        Self: Sized,
+    {
+        let metadata = Metadata {
+            unit: Some(unit),
+            ..self.metadata()
+        };
+        MetadataConduit::new(self, metadata)
+    }
+
+    #[must_use]
+    #[allow(dead_code)]
+    fn with_kind(self, kind: ValueKind) -> MetadataConduit<Self, O, I>
+    where
+        Self: Sized,
+    {
+        let metadata = Metadata {
+            kind,
+            ..self.metadata()
+        };
+        MetadataConduit::new(self, metadata)
+    }
+
     #[must_use]
     fn map_output<F, OuterO>(self, f: F) -> MapOutputConduit<Self, O, I, F>
     where
@@ -42,6 +98,43 @@ pub trait Conduit<O, I>: Subscribable + Send + Sync {
         state.install_property(entity, name, self.map_into::<Value, Value>());
     }
 
+    /// Like install_property() but clients can subscribe from the frame of another entity, in which
+    /// case the conduit framed() builds for that entity is used instead. Framed values are read only.
+    fn install_framed_property<F, C, FO>(
+        self,
+        state: &mut State,
+        entity: EntityKey,
+        name: &'static str,
+        framed: F,
+    ) where
+        Self: Sized + 'static,
+        O: Into<Value> + Send + Sync + 'static,
+        I: Send + Sync + 'static,
+        Value: Into<RequestResult<I>>,
+        F: Fn(EntityKey) -> C + 'static,
+        C: Conduit<FO, ReadOnlyPropSetType> + 'static,
+        FO: Into<Value> + Send + Sync + 'static,
+    {
+        state.install_framed_property(
+            entity,
+            name,
+            self.map_into::<Value, Value>(),
+            move |frame| framed(frame).map_into::<Value, Value>(),
+        );
+    }
+
+    /// Like install_property() but for lists of entities, subscribed clients are only sent what
+    /// was added and removed
+    fn install_delta_property(self, state: &mut State, entity: EntityKey, name: &'static str)
+    where
+        Self: Sized + 'static,
+        O: Into<Value> + Send + Sync + 'static,
+        I: Send + Sync + 'static,
+        Value: Into<RequestResult<I>>,
+    {
+        state.install_delta_property(entity, name, self.map_into::<Value, Value>());
+    }
+
     fn install_signal<T>(self, state: &mut State, entity: EntityKey, name: &'static str)
     where
         Self: Sized + 'static,
@@ -88,6 +181,10 @@ impl<O, I> Conduit<O, I> for Arc<dyn Conduit<O, I>> {
     fn input(&self, state: &mut State, value: I) -> RequestResult<()> {
         (**self).input(state, value)
     }
+
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        (**self).allows(connection, state)
+    }
 }
 
 impl<O, I> Subscribable for Arc<dyn Conduit<O, I>> {
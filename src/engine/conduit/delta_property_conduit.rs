@@ -0,0 +1,203 @@
+use super::*;
+
+/// Like PropertyConduit, but for properties that are a list of entities. Instead of resending the
+/// whole list on every change, clients subscribed to it are sent just the entities that were added
+/// and removed. Get requests still return the whole list.
+pub struct DeltaPropertyConduit<C> {
+    connection: ConnectionKey,
+    entity: EntityKey,
+    name: &'static str,
+    inner: C,
+    /// What the client was last told the list contains
+    last: Mutex<Vec<EntityKey>>,
+}
+
+impl<C> DeltaPropertyConduit<C>
+where
+    C: Conduit<Value, Value> + 'static,
+{
+    pub fn new(
+        connection: ConnectionKey,
+        entity: EntityKey,
+        name: &'static str,
+        inner: C,
+    ) -> Box<dyn Conduit<Value, Value>> {
+        Box::new(Arc::new(Self {
+            connection,
+            entity,
+            name,
+            inner,
+            last: Mutex::new(Vec::new()),
+        }))
+    }
+
+    fn check_access(&self, state: &State) -> RequestResult<()> {
+        if self.inner.allows(self.connection, state) {
+            Ok(())
+        } else {
+            Err(PermissionDenied(self.entity, self.name.to_string()))
+        }
+    }
+
+    fn entities(&self, state: &State) -> RequestResult<Vec<EntityKey>> {
+        RequestResult::<Vec<EntityKey>>::from(self.inner.output(state)?)
+    }
+}
+
+impl<C> Subscriber for DeltaPropertyConduit<C>
+where
+    C: Conduit<Value, Value> + 'static,
+{
+    fn notify(&self, state: &State, handler: &dyn EventHandler) {
+        let current = match self.entities(state) {
+            Ok(current) => current,
+            Err(e) => {
+                error!("handling delta property update: {}", e);
+                return;
+            }
+        };
+        let mut last = self.last.lock().expect("failed to lock last list");
+        let previous: HashSet<EntityKey> = last.iter().cloned().collect();
+        let now: HashSet<EntityKey> = current.iter().cloned().collect();
+        let added: Vec<Value> = current
+            .iter()
+            .filter(|e| !previous.contains(e))
+            .map(|e| (*e).into())
+            .collect();
+        let removed: Vec<Value> = last
+            .iter()
+            .filter(|e| !now.contains(e))
+            .map(|e| (*e).into())
+            .collect();
+        *last = current;
+        if !added.is_empty() || !removed.is_empty() {
+            handler.event(
+                self.connection,
+                Event::delta(self.entity, self.name.to_string(), added, removed),
+            );
+        }
+    }
+}
+
+impl<C> Conduit<Value, Value> for Arc<DeltaPropertyConduit<C>>
+where
+    C: Conduit<Value, Value> + 'static,
+{
+    fn output(&self, state: &State) -> RequestResult<Value> {
+        self.check_access(state)?;
+        self.inner.output(state)
+    }
+
+    fn input(&self, state: &mut State, value: Value) -> RequestResult<()> {
+        self.check_access(state)?;
+        self.inner.input(state, value)
+    }
+}
+
+impl<C> Subscribable for Arc<DeltaPropertyConduit<C>>
+where
+    C: Conduit<Value, Value> + 'static,
+{
+    /// Remembers the current list so later updates can be sent relative to it. Ignores the given
+    /// subscriber, see PropertyConduit.
+    fn subscribe(&self, state: &State, _: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        self.check_access(state)?;
+        *self.last.lock().expect("failed to lock last list") = self.entities(state)?;
+        self.inner
+            .subscribe(state, &(self.clone() as Arc<dyn Subscriber>))
+    }
+
+    fn unsubscribe(&self, state: &State, _: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        self.inner
+            .unsubscribe(state, &(Arc::downgrade(self) as Weak<dyn Subscriber>))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Thing;
+
+    fn setup() -> (State, EntityKey) {
+        let mut state = State::new();
+        let owner = state.create_entity().unwrap();
+        ComponentListConduit::<Thing>::new().install_delta_property(&mut state, owner, "things");
+        (state, owner)
+    }
+
+    fn add_thing(state: &mut State) -> EntityKey {
+        let entity = state.create_entity().unwrap();
+        state.install_component(entity, Thing);
+        entity
+    }
+
+    fn dispatch_notifications(state: &State) -> Vec<Event> {
+        let handler = MockEventHandler::new();
+        let mut notifs = Vec::new();
+        state.notif_queue.swap_buffer(&mut notifs);
+        for notif in notifs {
+            if let Some(notif) = notif.upgrade() {
+                notif.notify(state, &handler);
+            }
+        }
+        let events = handler.0.borrow().iter().map(|(_, e)| e.clone()).collect();
+        events
+    }
+
+    #[test]
+    fn get_returns_full_list() {
+        let (mut state, owner) = setup();
+        let a = add_thing(&mut state);
+        let b = add_thing(&mut state);
+        let sub = state
+            .subscribe(ConnectionKey::null(), owner, "things")
+            .unwrap();
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), owner, "things"),
+            Ok(Value::Array(vec![a.into(), b.into()]))
+        );
+        state.unsubscribe(sub).unwrap();
+    }
+
+    #[test]
+    fn added_entity_is_sent_alone() {
+        let (mut state, owner) = setup();
+        let _a = add_thing(&mut state);
+        let sub = state
+            .subscribe(ConnectionKey::null(), owner, "things")
+            .unwrap();
+        let b = add_thing(&mut state);
+        assert_eq!(
+            dispatch_notifications(&state),
+            vec![Event::delta(
+                owner,
+                "things".to_string(),
+                vec![b.into()],
+                vec![]
+            )]
+        );
+        state.unsubscribe(sub).unwrap();
+    }
+
+    #[test]
+    fn removed_entity_is_sent_alone() {
+        let (mut state, owner) = setup();
+        let a = add_thing(&mut state);
+        let _b = add_thing(&mut state);
+        let sub = state
+            .subscribe(ConnectionKey::null(), owner, "things")
+            .unwrap();
+        state.destroy_entity(a).unwrap();
+        assert_eq!(
+            dispatch_notifications(&state),
+            vec![Event::delta(
+                owner,
+                "things".to_string(),
+                vec![],
+                vec![a.into()]
+            )]
+        );
+        state.unsubscribe(sub).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+
+/// Connects an element holding a list of entities (ex the ships in a fleet) to the conduit system.
+/// Entities that have been destroyed are left out, so clients are never sent dead objects.
+/// Subscribers are notified when the list changes.
+pub struct EntityListConduit<OFn> {
+    output_fn: OFn,
+}
+
+impl<OFn> EntityListConduit<OFn>
+where
+    for<'a> OFn: Fn(&'a State) -> RequestResult<&'a Element<Vec<EntityKey>>>,
+    OFn: 'static,
+{
+    #[must_use]
+    pub fn new(output_fn: OFn) -> Self {
+        Self { output_fn }
+    }
+}
+
+impl<OFn> Conduit<Vec<EntityKey>, ReadOnlyPropSetType> for EntityListConduit<OFn>
+where
+    for<'a> OFn: Fn(&'a State) -> RequestResult<&'a Element<Vec<EntityKey>>>,
+    OFn: Send + Sync + 'static,
+{
+    fn output(&self, state: &State) -> RequestResult<Vec<EntityKey>> {
+        Ok((self.output_fn)(state)?
+            .iter()
+            .filter(|entity| state.entity_exists(**entity))
+            .copied()
+            .collect())
+    }
+
+    fn input(&self, _state: &mut State, _value: ReadOnlyPropSetType) -> RequestResult<()> {
+        // ReadOnlyPropSetType can't be instantiated, so this can't be called
+        std::unreachable!()
+    }
+}
+
+impl<OFn> Subscribable for EntityListConduit<OFn>
+where
+    for<'a> OFn: Fn(&'a State) -> RequestResult<&'a Element<Vec<EntityKey>>>,
+    OFn: Send + Sync + 'static,
+{
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        (self.output_fn)(state)?.subscribe(state, subscriber)
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        (self.output_fn)(state)?.unsubscribe(state, subscriber)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Group {
+        members: Element<Vec<EntityKey>>,
+    }
+
+    fn setup() -> (State, EntityKey, Vec<EntityKey>) {
+        let mut state = State::new();
+        let members: Vec<EntityKey> = (0..3).map(|_| state.create_entity().unwrap()).collect();
+        let group = state.create_entity().unwrap();
+        state.install_component(
+            group,
+            Group {
+                members: Element::new(members.clone()),
+            },
+        );
+        (state, group, members)
+    }
+
+    fn conduit(group: EntityKey) -> impl Conduit<Value, Value> {
+        EntityListConduit::new(move |state| Ok(&state.component::<Group>(group)?.members))
+            .map_into::<Value, Value>()
+    }
+
+    #[test]
+    fn outputs_array_of_entities() {
+        let (state, group, members) = setup();
+        assert_eq!(
+            conduit(group).output(&state),
+            Ok(Value::Array(
+                members.iter().map(|e| Value::Entity(*e)).collect()
+            ))
+        );
+    }
+
+    #[test]
+    fn leaves_out_destroyed_entities() {
+        let (mut state, group, members) = setup();
+        state.destroy_entity(members[1]).unwrap();
+        assert_eq!(
+            conduit(group).output(&state),
+            Ok(Value::Array(vec![
+                Value::Entity(members[0]),
+                Value::Entity(members[2])
+            ]))
+        );
+    }
+
+    #[test]
+    fn notifies_when_membership_changes() {
+        let (mut state, group, members) = setup();
+        let subscriber = MockSubscriber::new();
+        conduit(group).subscribe(&state, &subscriber.get()).unwrap();
+        state
+            .component_mut::<Group>(group)
+            .unwrap()
+            .members
+            .set(vec![members[2]]);
+        assert_eq!(state.notif_queue.len(), 1);
+        assert_eq!(
+            conduit(group).output(&state),
+            Ok(Value::Array(vec![Value::Entity(members[2])]))
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+
+/// Applies a connection's entity filter to the output of a property, see EntityFilter. Without a
+/// filter the output is passed through unchanged.
+pub struct FilterConduit<C> {
+    inner: C,
+    connection: ConnectionKey,
+    filter: Option<EntityFilter>,
+}
+
+impl<C> FilterConduit<C>
+where
+    C: Conduit<Value, Value>,
+{
+    pub fn new(inner: C, connection: ConnectionKey, filter: Option<EntityFilter>) -> Self {
+        Self {
+            inner,
+            connection,
+            filter,
+        }
+    }
+}
+
+impl<C> Conduit<Value, Value> for FilterConduit<C>
+where
+    C: Conduit<Value, Value>,
+{
+    fn output(&self, state: &State) -> RequestResult<Value> {
+        let value = self.inner.output(state)?;
+        Ok(match &self.filter {
+            Some(filter) => filter.apply(state, self.connection, value),
+            None => value,
+        })
+    }
+
+    fn input(&self, state: &mut State, value: Value) -> RequestResult<()> {
+        self.inner.input(state, value)
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.inner.metadata()
+    }
+
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        self.inner.allows(connection, state)
+    }
+}
+
+impl<C> Subscribable for FilterConduit<C>
+where
+    C: Conduit<Value, Value>,
+{
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        self.inner.subscribe(state, subscriber)
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        self.inner.unsubscribe(state, subscriber)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Thing(&'static str);
+
+    fn setup() -> (State, EntityKey) {
+        let mut state = State::new();
+        let owner = state.create_entity().unwrap();
+        ComponentListConduit::<Thing>::new().install_delta_property(&mut state, owner, "things");
+        (state, owner)
+    }
+
+    fn add_thing(state: &mut State, kind: &'static str) -> EntityKey {
+        let entity = state.create_entity().unwrap();
+        state.install_component(entity, Thing(kind));
+        ComputedConduit::new(
+            move |state| Ok(state.component::<Thing>(entity)?.0.to_string()),
+            |_| Ok(vec![]),
+        )
+        .install_property(state, entity, "kind");
+        entity
+    }
+
+    fn kind_filter(kind: &str) -> SubscribeOptions {
+        let mut properties = HashMap::new();
+        properties.insert("kind".to_string(), Value::Text(kind.to_string()));
+        SubscribeOptions {
+            filter: Some(EntityFilter::new(properties)),
+            ..SubscribeOptions::default()
+        }
+    }
+
+    #[test]
+    fn connections_with_different_filters_get_only_their_matches() {
+        let (mut state, owner) = setup();
+        let ship_a = add_thing(&mut state, "ship");
+        let planet_a = add_thing(&mut state, "planet");
+        let (ships, planets) = (ConnectionKey::from_id(1), ConnectionKey::from_id(2));
+        let subs = vec![
+            state
+                .subscribe_with_options(ships, owner, "things", &kind_filter("ship"))
+                .unwrap(),
+            state
+                .subscribe_with_options(planets, owner, "things", &kind_filter("planet"))
+                .unwrap(),
+        ];
+        assert_eq!(
+            state.get_property_with_options(ships, owner, "things", &kind_filter("ship")),
+            Ok(Value::Array(vec![ship_a.into()]))
+        );
+        assert_eq!(
+            state.get_property_with_options(planets, owner, "things", &kind_filter("planet")),
+            Ok(Value::Array(vec![planet_a.into()]))
+        );
+
+        let ship_b = add_thing(&mut state, "ship");
+        let planet_b = add_thing(&mut state, "planet");
+        let handler = MockEventHandler::new();
+        let mut notifs = Vec::new();
+        state.notif_queue.swap_buffer(&mut notifs);
+        for notif in notifs {
+            if let Some(notif) = notif.upgrade() {
+                notif.notify(&state, &handler);
+            }
+        }
+        let mut events = handler.0.into_inner();
+        events.sort_by_key(|(connection, _)| connection.to_id());
+        assert_eq!(
+            events,
+            vec![
+                (
+                    ships,
+                    Event::delta(owner, "things".to_string(), vec![ship_b.into()], vec![])
+                ),
+                (
+                    planets,
+                    Event::delta(owner, "things".to_string(), vec![planet_b.into()], vec![])
+                ),
+            ]
+        );
+        for sub in subs {
+            state.unsubscribe(sub).unwrap();
+        }
+    }
+}
@@ -44,6 +44,13 @@ where
     fn input(&self, state: &mut State, value: SetOuter) -> RequestResult<()> {
         self.conduit.input(state, (self.f)(value)?)
     }
+
+    fn metadata(&self) -> Metadata {
+        self.conduit.metadata()
+    }
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        self.conduit.allows(connection, state)
+    }
 }
 
 impl<C, F, Get, SetInner, SetOuter> Subscribable for MapInputConduit<C, Get, SetInner, SetOuter, F>
@@ -36,6 +36,13 @@ where
     fn input(&self, state: &mut State, value: I) -> RequestResult<()> {
         self.conduit.input(state, value)
     }
+
+    fn metadata(&self) -> Metadata {
+        self.conduit.metadata()
+    }
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        self.conduit.allows(connection, state)
+    }
 }
 impl<C, F, InnerO, OuterO, I> Subscribable for MapOutputConduit<C, InnerO, I, F>
 where
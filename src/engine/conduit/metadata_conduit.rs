@@ -0,0 +1,61 @@
+use super::*;
+
+/// Attaches metadata to a conduit, see Conduit::with_unit() and Conduit::with_kind()
+pub struct MetadataConduit<C, O, I>
+where
+    C: Conduit<O, I>,
+{
+    conduit: C,
+    metadata: Metadata,
+    pd: PhantomData<(O, I)>,
+}
+
+impl<C, O, I> MetadataConduit<C, O, I>
+where
+    C: Conduit<O, I>,
+{
+    pub fn new(conduit: C, metadata: Metadata) -> Self {
+        Self {
+            conduit,
+            metadata,
+            pd: PhantomData,
+        }
+    }
+}
+
+impl<C, O, I> Conduit<O, I> for MetadataConduit<C, O, I>
+where
+    C: Conduit<O, I>,
+    O: Send + Sync,
+    I: Send + Sync,
+{
+    fn output(&self, state: &State) -> RequestResult<O> {
+        self.conduit.output(state)
+    }
+
+    fn input(&self, state: &mut State, value: I) -> RequestResult<()> {
+        self.conduit.input(state, value)
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.metadata
+    }
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        self.conduit.allows(connection, state)
+    }
+}
+
+impl<C, O, I> Subscribable for MetadataConduit<C, O, I>
+where
+    C: Conduit<O, I>,
+    O: Send + Sync,
+    I: Send + Sync,
+{
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        self.conduit.subscribe(state, subscriber)
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        self.conduit.unsubscribe(state, subscriber)
+    }
+}
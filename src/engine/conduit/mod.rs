@@ -1,28 +1,42 @@
 use super::*;
 
+mod access_conduit;
 mod action_conduit;
 mod caching_conduit;
 mod component_list_conduit;
+mod computed_conduit;
 #[allow(clippy::module_inception)]
 mod conduit;
+mod delta_property_conduit;
+mod entity_list_conduit;
+mod filter_conduit;
 mod map_input_conduit;
 mod map_output_conduit;
+mod metadata_conduit;
 mod property_conduit;
 mod ro_conduit;
 mod rw_conduit;
 mod signal_conduit;
 mod try_into_conduit;
+mod validate_conduit;
 
 pub use action_conduit::{ActionConduit, ActionsDontProduceOutputSilly};
 pub use caching_conduit::CachingConduit;
 pub use component_list_conduit::ComponentListConduit;
+pub use computed_conduit::ComputedConduit;
 pub use conduit::Conduit;
 pub use conduit::ReadOnlyPropSetType;
+pub use delta_property_conduit::DeltaPropertyConduit;
+pub use entity_list_conduit::EntityListConduit;
+pub use filter_conduit::FilterConduit;
 pub use property_conduit::PropertyConduit;
 pub use ro_conduit::ROConduit;
 pub use rw_conduit::RWConduit;
 pub use signal_conduit::SignalConduit;
 
+use access_conduit::AccessConduit;
 use map_input_conduit::MapInputConduit;
 use map_output_conduit::MapOutputConduit;
+use metadata_conduit::MetadataConduit;
 use try_into_conduit::TryIntoConduit;
+use validate_conduit::ValidateConduit;
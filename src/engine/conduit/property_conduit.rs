@@ -3,7 +3,8 @@ use super::*;
 /// The top-most conduit of a property, automatically created by State. Note that this conduit
 /// does not use the given subscriber (it handles dispatching updates itself). It uses .subscribe()
 /// and .unsubscribe() to know when to start and stop dispatching updates, but it does not care
-/// what subscriber is sent.
+/// what subscriber is sent. Updates that only echo a value the connection set this tick are
+/// skipped if the state suppresses echoes.
 pub struct PropertyConduit<C> {
     connection: ConnectionKey,
     entity: EntityKey,
@@ -28,6 +29,14 @@ where
             inner,
         }))
     }
+
+    fn check_access(&self, state: &State) -> RequestResult<()> {
+        if self.inner.allows(self.connection, state) {
+            Ok(())
+        } else {
+            Err(PermissionDenied(self.entity, self.name.to_string()))
+        }
+    }
 }
 
 impl<C> Subscriber for PropertyConduit<C>
@@ -42,6 +51,9 @@ where
                 return;
             }
         };
+        if state.is_echo(self.connection, self.entity, self.name, &value) {
+            return;
+        }
         handler.event(
             self.connection,
             Event::update(self.entity, self.name.to_string(), value),
@@ -54,10 +66,12 @@ where
     C: Conduit<Value, Value> + 'static,
 {
     fn output(&self, state: &State) -> RequestResult<Value> {
+        self.check_access(state)?;
         self.inner.output(state)
     }
 
     fn input(&self, state: &mut State, value: Value) -> RequestResult<()> {
+        self.check_access(state)?;
         self.inner.input(state, value)
     }
 }
@@ -68,6 +82,7 @@ where
 {
     /// Uses this as a signal to subscribe, but ignores the given subscriber.
     fn subscribe(&self, state: &State, _: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        self.check_access(state)?;
         self.inner
             .subscribe(state, &(self.clone() as Arc<dyn Subscriber>))
     }
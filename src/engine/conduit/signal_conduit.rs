@@ -56,7 +56,7 @@ where
     C: Conduit<Vec<Value>, SignalsDontTakeInputSilly> + 'static,
 {
     fn output(&self, _: &State) -> RequestResult<Value> {
-        Err(BadRequest("can not get value from signal".into()))
+        Err(NotAvailable(self.entity, self.name.to_string()))
     }
 
     fn input(&self, _: &mut State, _: Value) -> RequestResult<()> {
@@ -31,6 +31,13 @@ where
             ))),
         }
     }
+
+    fn metadata(&self) -> Metadata {
+        self.0.metadata()
+    }
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        self.0.allows(connection, state)
+    }
 }
 impl<C, InnerO, InnerI> Subscribable for TryIntoConduit<C, InnerO, InnerI>
 where
@@ -0,0 +1,106 @@
+use super::*;
+
+/// Rejects input the validator returns an error for before it reaches the wrapped conduit, see
+/// Conduit::validate()
+pub struct ValidateConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(&I) -> RequestResult<()>,
+{
+    conduit: C,
+    validator: F,
+    pd: PhantomData<(O, I)>,
+}
+
+impl<C, F, O, I> ValidateConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(&I) -> RequestResult<()>,
+{
+    pub fn new(conduit: C, validator: F) -> Self {
+        Self {
+            conduit,
+            validator,
+            pd: PhantomData,
+        }
+    }
+}
+
+impl<C, F, O, I> Conduit<O, I> for ValidateConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(&I) -> RequestResult<()> + Send + Sync,
+    O: Send + Sync,
+    I: Send + Sync,
+{
+    fn output(&self, state: &State) -> RequestResult<O> {
+        self.conduit.output(state)
+    }
+
+    fn input(&self, state: &mut State, value: I) -> RequestResult<()> {
+        (self.validator)(&value)?;
+        self.conduit.input(state, value)
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.conduit.metadata()
+    }
+
+    fn allows(&self, connection: ConnectionKey, state: &State) -> bool {
+        self.conduit.allows(connection, state)
+    }
+}
+
+impl<C, F, O, I> Subscribable for ValidateConduit<C, F, O, I>
+where
+    C: Conduit<O, I>,
+    F: Fn(&I) -> RequestResult<()> + Send + Sync,
+    O: Send + Sync,
+    I: Send + Sync,
+{
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        self.conduit.subscribe(state, subscriber)
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        self.conduit.unsubscribe(state, subscriber)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (State, EntityKey) {
+        let mut state = State::new();
+        let entity = state.create_entity().unwrap();
+        install_dial(&mut state, entity, 5)
+            .validate(|value: &i64| {
+                if (0..=10).contains(value) {
+                    Ok(())
+                } else {
+                    Err(BadRequest(format!("{} is out of range", value)))
+                }
+            })
+            .install_property(&mut state, entity, "setting");
+        (state, entity)
+    }
+
+    #[test]
+    fn invalid_value_is_rejected_and_not_stored() {
+        let (mut state, entity) = setup();
+        let result =
+            state.set_property(ConnectionKey::null(), entity, "setting", Value::Integer(11));
+        assert!(matches!(result, Err(BadRequest(_))));
+        assert_eq!(*state.component::<Dial>(entity).unwrap().setting, 5);
+    }
+
+    #[test]
+    fn valid_value_is_stored() {
+        let (mut state, entity) = setup();
+        state
+            .set_property(ConnectionKey::null(), entity, "setting", Value::Integer(7))
+            .unwrap();
+        assert_eq!(*state.component::<Dial>(entity).unwrap().setting, 7);
+    }
+}
@@ -31,12 +31,7 @@ impl<T> Element<T> {
             let lock = self.subscribers.lock().expect("failed to lock subscribers");
             if !lock.list.0.is_empty() {
                 match lock.notif_queue.get() {
-                    Ok(notif_queue) => notif_queue.extend(
-                        lock.list
-                            .0
-                            .iter()
-                            .map(|(_ptr, subscriber)| subscriber.clone()),
-                    ),
+                    Ok(notif_queue) => notif_queue.extend(lock.list.0.iter().cloned()),
                     Err(e) => error!("failed to queue notifications: {}", e),
                 }
             }
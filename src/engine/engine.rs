@@ -1,21 +1,43 @@
 use super::*;
 
+type TickHook = Box<dyn FnMut(&State)>;
+type IdleWhen = Box<dyn Fn(&State) -> bool>;
+
 pub struct Engine {
     should_quit: bool,
     quit_after: f64,
     /// In-game delta-time for each physics step
     physics_tick_delta: f64,
+    /// Multiplied by physics_tick_delta to speed up or slow down the game without changing how
+    /// often ticks happen
+    time_scale: f64,
+    /// Ticks left before the game has warmed up
+    warmup_ticks: u64,
+    /// If the game clock (and so physics) stands still until warmup is over
+    hold_clock_during_warmup: bool,
     pub state: State,
     back_notif_buffer: Vec<Notification>,
     connections: ConnectionCollection,
     physics_tick: Box<dyn Fn(&mut State, f64)>,
+    /// Told when each tick ends, if the connection config records requests
+    recorder: Option<Arc<RequestRecorder>>,
+    /// Applied at the start of each tick until it runs out
+    replay: Option<RequestReplay>,
+    /// Given the state after each physics tick, see set_tick_hook()
+    tick_hook: Option<TickHook>,
+    /// Physics is skipped while there are no connections and this returns true
+    idle_when: Option<IdleWhen>,
 }
 
 impl Engine {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<InitFn, TickFn>(
         new_session_rx: Receiver<Box<dyn SessionBuilder>>,
         physics_tick_delta: f64,
         quit_after: f64,
+        max_entities: usize,
+        server_info_name: Option<String>,
+        connection_config: ConnectionConfig,
         init: InitFn,
         physics_tick: TickFn,
     ) -> Self
@@ -24,25 +46,109 @@ impl Engine {
         TickFn: Fn(&mut State, f64) + 'static,
     {
         let mut state = State::new();
-        let connections = ConnectionCollection::new(new_session_rx, state.root_entity(), 10);
+        state.set_max_entities(max_entities);
+        let recorder = connection_config.recorder.clone();
+        let mut connections =
+            ConnectionCollection::new(new_session_rx, state.root_entity(), 10, connection_config);
+        if let Some(server_name) = server_info_name {
+            connections.set_server_info(ServerInfo::new(
+                server_name,
+                1.0 / physics_tick_delta,
+                state.root_entity(),
+            ));
+        }
         init(&mut state);
         Self {
             should_quit: false,
             quit_after,
             physics_tick_delta,
+            time_scale: 1.0,
+            warmup_ticks: 0,
+            hold_clock_during_warmup: false,
             state,
             back_notif_buffer: Vec::new(),
             connections,
             physics_tick: Box::new(physics_tick),
+            recorder,
+            replay: None,
+            tick_hook: None,
+            idle_when: None,
         }
     }
 
+    /// Runs the game faster (> 1) or slower (< 1) than real time. Must not be negative, which the
+    /// config checks when it's loaded.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        assert!(time_scale >= 0.0);
+        self.time_scale = time_scale;
+    }
+
+    /// The first ticks may be slow while things settle. If hold_clock is true physics runs with a
+    /// delta of 0 for that many ticks, so the game clock doesn't start until they're over.
+    pub fn set_warmup(&mut self, ticks: u64, hold_clock: bool) {
+        self.warmup_ticks = ticks;
+        self.hold_clock_during_warmup = hold_clock;
+    }
+
+    /// Bounds the time spent flushing connections each tick, so one slow client can't hold up
+    /// the rest. Connections are flushed in a rotating order, skipped ones going first next tick.
+    pub fn set_flush_budget(&mut self, budget: Duration) {
+        self.connections.set_flush_budget(budget);
+    }
+
+    /// Connections that haven't finished closing, which happens when the engine is dropped. Lets
+    /// a ShutdownWatchdog report what it was waiting on.
+    pub fn unfinished_connections(&self) -> Arc<Mutex<Vec<String>>> {
+        self.connections.unfinished_connections()
+    }
+
+    /// Applies previously recorded requests as if clients were sending them. The game should be
+    /// set up the same way as when they were recorded.
+    pub fn set_replay(&mut self, replay: RequestReplay) {
+        self.replay = Some(replay);
+    }
+
+    /// Calls the hook with the state after every physics tick, for debugging
+    pub fn set_tick_hook(&mut self, hook: impl FnMut(&State) + 'static) {
+        self.tick_hook = Some(Box::new(hook));
+    }
+
+    /// Saves CPU when nobody is watching: while no clients are connected and idle_when() returns
+    /// true physics is skipped and the game clock stands still. It resumes on the tick a client
+    /// connects.
+    pub fn set_idle_when(&mut self, idle_when: impl Fn(&State) -> bool + 'static) {
+        self.idle_when = Some(Box::new(idle_when));
+    }
+
     /// Runs a single iteration of the game loop
     /// Returns if to continue the game
     pub fn tick(&mut self) -> bool {
+        if let Some(replay) = &mut self.replay {
+            replay.replay_tick(&mut self.state);
+            if replay.is_done() {
+                info!("finished replaying recorded requests");
+                self.replay = None;
+            }
+        }
         self.connections.process_inbound_messages(&mut self.state);
 
-        (self.physics_tick)(&mut self.state, self.physics_tick_delta);
+        let idle = self.connections.is_empty()
+            && self
+                .idle_when
+                .as_ref()
+                .is_some_and(|idle_when| idle_when(&self.state));
+        let delta = if idle || (self.warmup_ticks > 0 && self.hold_clock_during_warmup) {
+            0.0
+        } else {
+            self.physics_tick_delta * self.time_scale
+        };
+        self.warmup_ticks = self.warmup_ticks.saturating_sub(1);
+        if !idle {
+            (self.physics_tick)(&mut self.state, delta);
+            if let Some(hook) = &mut self.tick_hook {
+                hook(&self.state);
+            }
+        }
 
         self.state
             .notif_queue
@@ -54,10 +160,14 @@ impl Engine {
         }
         // this does not deallocate, so we don't need to reallocate every cycle
         self.back_notif_buffer.clear();
+        self.state.clear_client_sets();
 
         self.connections.flush_outbound_messages(&mut self.state);
+        if let Some(recorder) = &self.recorder {
+            recorder.next_tick();
+        }
 
-        self.state.increment_physics(self.physics_tick_delta);
+        self.state.increment_physics(delta);
         if self.state.time() > self.quit_after {
             self.should_quit = true;
             info!(
@@ -74,3 +184,236 @@ impl Drop for Engine {
         self.connections.finalize(&mut self.state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    const EPSILON: f64 = 0.000_001;
+
+    struct Mover {
+        position: f64,
+    }
+
+    /// Returns how far a body moving at 1 unit per second goes in one tick
+    fn distance_per_tick(time_scale: f64) -> f64 {
+        let (_, new_session_rx) = channel();
+        let mut engine = Engine::new(
+            new_session_rx,
+            0.1,
+            f64::INFINITY,
+            usize::MAX,
+            None,
+            ConnectionConfig::default(),
+            |state| {
+                let root = state.root_entity();
+                state.install_component(root, Mover { position: 0.0 });
+            },
+            |state, delta| {
+                let root = state.root_entity();
+                state.component_mut::<Mover>(root).unwrap().position += delta;
+            },
+        );
+        engine.set_time_scale(time_scale);
+        engine.tick();
+        let root = engine.state.root_entity();
+        engine.state.component::<Mover>(root).unwrap().position
+    }
+
+    #[test]
+    fn time_scale_scales_distance_per_tick() {
+        let normal = distance_per_tick(1.0);
+        assert!((normal - 0.1).abs() < EPSILON);
+        assert!((distance_per_tick(2.0) - normal * 2.0).abs() < EPSILON);
+        assert!((distance_per_tick(0.5) - normal * 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn time_scale_scales_game_time() {
+        let (_, new_session_rx) = channel();
+        let mut engine = Engine::new(
+            new_session_rx,
+            0.1,
+            f64::INFINITY,
+            usize::MAX,
+            None,
+            ConnectionConfig::default(),
+            |_| (),
+            |_, _| (),
+        );
+        engine.set_time_scale(3.0);
+        engine.tick();
+        assert!((engine.state.time() - 0.3).abs() < EPSILON);
+    }
+
+    #[test]
+    fn clock_starts_after_warmup_when_held() {
+        let (_, new_session_rx) = channel();
+        let mut engine = Engine::new(
+            new_session_rx,
+            0.1,
+            f64::INFINITY,
+            usize::MAX,
+            None,
+            ConnectionConfig::default(),
+            |_| (),
+            |_, _| (),
+        );
+        engine.set_warmup(2, true);
+        engine.tick();
+        engine.tick();
+        assert!(engine.state.time().abs() < EPSILON);
+        engine.tick();
+        assert!((engine.state.time() - 0.1).abs() < EPSILON);
+    }
+
+    #[test]
+    fn tick_hook_sees_state_after_each_physics_tick() {
+        let (_, new_session_rx) = channel();
+        let mut engine = Engine::new(
+            new_session_rx,
+            0.1,
+            f64::INFINITY,
+            usize::MAX,
+            None,
+            ConnectionConfig::default(),
+            |state| {
+                let root = state.root_entity();
+                state.install_component(root, Mover { position: 0.0 });
+            },
+            |state, delta| {
+                let root = state.root_entity();
+                state.component_mut::<Mover>(root).unwrap().position += delta;
+            },
+        );
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let hook_seen = seen.clone();
+        engine.set_tick_hook(move |state| {
+            let root = state.root_entity();
+            let position = state.component::<Mover>(root).unwrap().position;
+            hook_seen.borrow_mut().push(position);
+        });
+        for _ in 0..3 {
+            engine.tick();
+        }
+        let seen = seen.borrow();
+        assert_eq!(seen.len(), 3);
+        for (i, position) in seen.iter().enumerate() {
+            assert!((position - 0.1 * (i + 1) as f64).abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn physics_is_skipped_while_idle_until_a_client_connects() {
+        let (new_session_tx, new_session_rx) = channel();
+        let mut engine = Engine::new(
+            new_session_rx,
+            0.1,
+            f64::INFINITY,
+            usize::MAX,
+            None,
+            ConnectionConfig::default(),
+            |state| {
+                let root = state.root_entity();
+                state.install_component(root, Mover { position: 0.0 });
+            },
+            |state, delta| {
+                let root = state.root_entity();
+                state.component_mut::<Mover>(root).unwrap().position += delta;
+            },
+        );
+        engine.set_idle_when(|_| true);
+        let position = |engine: &Engine| {
+            let root = engine.state.root_entity();
+            engine.state.component::<Mover>(root).unwrap().position
+        };
+        engine.tick();
+        engine.tick();
+        assert!(position(&engine).abs() < EPSILON);
+        assert!(engine.state.time().abs() < EPSILON);
+        let _client = TestClient::connect(&new_session_tx);
+        engine.tick();
+        assert!((position(&engine) - 0.1).abs() < EPSILON);
+        assert!((engine.state.time() - 0.1).abs() < EPSILON);
+    }
+
+    struct Dials {
+        a: Element<i64>,
+        b: Element<i64>,
+    }
+
+    fn updated_properties(client: &TestClient) -> Vec<String> {
+        let mut properties: Vec<String> = client
+            .take_events()
+            .iter()
+            .map(|event| {
+                assert_eq!(event["mtype"], "update");
+                event["property"].as_str().unwrap().to_string()
+            })
+            .collect();
+        properties.sort();
+        properties
+    }
+
+    #[test]
+    fn only_changed_subscriptions_are_notified_once_each() {
+        let (new_session_tx, new_session_rx) = channel();
+        let mut engine = Engine::new(
+            new_session_rx,
+            0.1,
+            f64::INFINITY,
+            usize::MAX,
+            None,
+            ConnectionConfig::default(),
+            |state| {
+                let root = state.root_entity();
+                state.install_component(
+                    root,
+                    Dials {
+                        a: Element::new(0),
+                        b: Element::new(0),
+                    },
+                );
+                ROConduit::new(move |state| Ok(&state.component::<Dials>(root)?.a))
+                    .install_property(state, root, "a");
+                ROConduit::new(move |state| Ok(&state.component::<Dials>(root)?.b))
+                    .install_property(state, root, "b");
+                ComputedConduit::new(
+                    move |state| {
+                        let dials = state.component::<Dials>(root)?;
+                        Ok(*dials.a + *dials.b)
+                    },
+                    move |state| {
+                        let dials = state.component::<Dials>(root)?;
+                        Ok(vec![&dials.a as &dyn Subscribable, &dials.b])
+                    },
+                )
+                .install_property(state, root, "sum");
+            },
+            |state, _| {
+                // a changes every tick, b every other tick
+                let dials = state.component_mut::<Dials>(state.root_entity()).unwrap();
+                let a = *dials.a + 1;
+                dials.a.set(a);
+                if a % 2 == 0 {
+                    dials.b.set(*dials.b + 1);
+                }
+            },
+        );
+        let client = TestClient::connect(&new_session_tx);
+        engine.tick();
+        for property in &["a", "b", "sum"] {
+            client.send(&format!(
+                r#"{{"mtype": "subscribe", "object": 1, "property": "{}"}}"#,
+                property
+            ));
+        }
+        engine.tick();
+        client.take_events();
+        engine.tick();
+        assert_eq!(updated_properties(&client), vec!["a", "sum"]);
+        engine.tick();
+        assert_eq!(updated_properties(&client), vec!["a", "b", "sum"]);
+    }
+}
@@ -1,13 +1,17 @@
 use super::*;
 
-type ConduitBuilder = Box<dyn Fn(ConnectionKey) -> RequestResult<Box<dyn Conduit<Value, Value>>>>;
+type ConduitBuilder =
+    Box<dyn Fn(ConnectionKey, &SubscribeOptions) -> RequestResult<Box<dyn Conduit<Value, Value>>>>;
 
 /// Conceptual owner of the various components in the state that make up a single "thing"
 pub struct Entity {
     self_key: EntityKey,
     components: AnyMap,
+    /// Short type names of the components in the same order they were registered, for debugging
+    component_type_names: Vec<String>,
     component_cleanup: Vec<Box<dyn FnOnce(&mut State)>>,
     conduit_builders: HashMap<&'static str, ConduitBuilder>,
+    member_metadata: HashMap<&'static str, Metadata>,
 }
 
 impl Entity {
@@ -15,8 +19,10 @@ impl Entity {
         Self {
             self_key,
             components: AnyMap::new(),
+            component_type_names: Vec::new(),
             component_cleanup: Vec::new(),
             conduit_builders: HashMap::new(),
+            member_metadata: HashMap::new(),
         }
     }
 
@@ -34,23 +40,32 @@ impl Entity {
                 self.self_key
             )
         }
+        self.component_type_names.push(short_type_name::<T>());
         self.component_cleanup.push(Box::new(cleanup));
     }
 
+    /// Short type names of all attached components, only intended for debugging
+    pub fn component_type_names(&self) -> &[String] {
+        &self.component_type_names
+    }
+
     pub fn component_key<T: 'static>(&self) -> Option<&ComponentKey<T>> {
         self.components.get::<ComponentKey<T>>()
     }
 
     /// Registers a conduit as a property/signal/action, shows error and does nothing else if there
-    /// is already a registered conduit with the same name
-    pub fn register_conduit<F>(&mut self, name: &'static str, f: F)
+    /// is already a registered conduit with the same name. The builder is given the connection and
+    /// the options it subscribed with.
+    pub fn register_conduit<F>(&mut self, name: &'static str, metadata: Metadata, f: F)
     where
-        F: Fn(ConnectionKey) -> RequestResult<Box<dyn Conduit<Value, Value>>> + 'static,
+        F: Fn(ConnectionKey, &SubscribeOptions) -> RequestResult<Box<dyn Conduit<Value, Value>>>
+            + 'static,
     {
         use std::collections::hash_map::Entry;
         match self.conduit_builders.entry(name) {
             Entry::Vacant(entry) => {
                 entry.insert(Box::new(f));
+                self.member_metadata.insert(name, metadata);
             }
             Entry::Occupied(_) => {
                 error!(
@@ -61,15 +76,27 @@ impl Entity {
         }
     }
 
+    /// Names and metadata of all properties, signals and actions, sorted by name
+    pub fn members(&self) -> Vec<(&'static str, Metadata)> {
+        let mut members: Vec<(&'static str, Metadata)> = self
+            .member_metadata
+            .iter()
+            .map(|(name, metadata)| (*name, *metadata))
+            .collect();
+        members.sort_unstable_by_key(|(name, _)| *name);
+        members
+    }
+
     /// Get the property of the given name
     pub fn conduit(
         &self,
         connection: ConnectionKey,
         name: &str,
+        options: &SubscribeOptions,
     ) -> Option<RequestResult<Box<dyn Conduit<Value, Value>>>> {
         self.conduit_builders
             .get(name)
-            .map(|builder| builder(connection))
+            .map(|builder| builder(connection, options))
     }
 
     /// Remove all components of this entity from the state
@@ -78,6 +105,7 @@ impl Entity {
             cleanup(state);
         }
         self.components.clear();
+        self.component_type_names.clear();
         // TODO: register disconnected from connections
     }
 }
@@ -0,0 +1,39 @@
+use super::*;
+
+/// Matches entities whose properties have the given values, such as {"body_class": "ship"}. A
+/// client can attach one to a subscription so lists of entities only include matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityFilter(HashMap<String, Value>);
+
+impl EntityFilter {
+    pub fn new(properties: HashMap<String, Value>) -> Self {
+        Self(properties)
+    }
+
+    pub fn properties(&self) -> &HashMap<String, Value> {
+        &self.0
+    }
+
+    /// Properties the connection can't get (including ones the entity doesn't have) don't match
+    pub fn matches(&self, state: &State, connection: ConnectionKey, entity: EntityKey) -> bool {
+        self.0
+            .iter()
+            .all(|(name, value)| state.get_property(connection, entity, name).as_ref() == Ok(value))
+    }
+
+    /// Removes entities that don't match from a list. Anything else is left alone.
+    pub fn apply(&self, state: &State, connection: ConnectionKey, value: Value) -> Value {
+        match value {
+            Value::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .filter(|item| match item {
+                        Value::Entity(entity) => self.matches(state, connection, *entity),
+                        _ => true,
+                    })
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+}
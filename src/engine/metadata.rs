@@ -0,0 +1,56 @@
+use super::*;
+
+/// What sort of number a property holds, beyond its type
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ValueKind {
+    #[default]
+    Plain,
+    /// An angle in radians that wraps around
+    #[allow(dead_code)]
+    Angle,
+}
+
+impl PropertyEnum for ValueKind {
+    const VARIANTS: &'static [(Self, &'static str)] =
+        &[(ValueKind::Plain, "plain"), (ValueKind::Angle, "angle")];
+}
+
+/// The type of an action parameter, as a client would send it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamType {
+    Scalar,
+    Integer,
+    Vector,
+    Text,
+    Bool,
+    Object,
+    /// Any value is accepted
+    Any,
+}
+
+impl PropertyEnum for ParamType {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (ParamType::Scalar, "scalar"),
+        (ParamType::Integer, "integer"),
+        (ParamType::Vector, "vector"),
+        (ParamType::Text, "text"),
+        (ParamType::Bool, "bool"),
+        (ParamType::Object, "object"),
+        (ParamType::Any, "any"),
+    ];
+}
+
+/// Names and types of an action's parameters. An action with one parameter takes it directly,
+/// one with more takes an array of them in order and one with none takes null.
+pub type ParamSchema = &'static [(&'static str, ParamType)];
+
+/// Descriptive information about a member that clients can get through introspection. Attached to
+/// a conduit with with_unit(), with_kind() or ActionConduit::with_params() before it is installed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Metadata {
+    /// Such as "km" or "km/s", None if the value has no unit
+    pub unit: Option<&'static str>,
+    pub kind: ValueKind,
+    /// Only set on actions, None if the action doesn't describe its parameters
+    pub params: Option<ParamSchema>,
+}
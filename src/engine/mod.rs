@@ -9,10 +9,14 @@ mod element;
 #[allow(clippy::module_inception)]
 mod engine;
 mod entity;
+mod entity_filter;
+mod metadata;
 mod notif_queue;
+mod property_enum;
 mod signal;
 mod state;
 mod subscribable;
+mod subscribe_options;
 mod subscriber;
 mod subscriber_list;
 mod subscription;
@@ -20,14 +24,19 @@ mod sync_subscriber_list;
 mod value;
 
 pub use conduit::{
-    ActionConduit, ComponentListConduit, Conduit, ROConduit, RWConduit, ReadOnlyPropSetType,
+    ActionConduit, ActionsDontProduceOutputSilly, ComponentListConduit, ComputedConduit, Conduit,
+    EntityListConduit, ROConduit, RWConduit, ReadOnlyPropSetType,
 };
 pub use element::Element;
 pub use engine::Engine;
+pub use entity_filter::EntityFilter;
+pub use metadata::{Metadata, ParamSchema, ParamType, ValueKind};
 pub use notif_queue::{NotifQueue, Notification};
+pub use property_enum::PropertyEnum;
 pub use signal::Signal;
 pub use state::{EntityKey, State};
 pub use subscribable::Subscribable;
+pub use subscribe_options::SubscribeOptions;
 pub use subscriber::Subscriber;
 pub use subscriber_list::SubscriberList;
 pub use sync_subscriber_list::SyncSubscriberList;
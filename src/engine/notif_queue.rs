@@ -2,36 +2,57 @@ use super::*;
 
 pub type Notification = Weak<dyn Subscriber>;
 
-/// A queue of pending notifications, there is currently one per state
+/// Subscribers waiting to be notified, in the order they were first queued
+struct DirtySet {
+    notifs: Vec<Notification>,
+    /// Pointers of the queued subscribers (see SubscriberList for why they're usizes)
+    queued: HashSet<usize>,
+}
+
+/// A queue of pending notifications, there is currently one per state. Acts as a dirty set: a
+/// subscriber queued several times (ex because several things it depends on changed) is only
+/// notified once when the queue is drained.
 #[derive(Clone)]
-pub struct NotifQueue(Arc<Mutex<Vec<Notification>>>);
+pub struct NotifQueue(Arc<Mutex<DirtySet>>);
 
 impl NotifQueue {
     pub fn new() -> Self {
-        Self(Arc::new(Mutex::new(Vec::new())))
+        Self(Arc::new(Mutex::new(DirtySet {
+            notifs: Vec::new(),
+            queued: HashSet::new(),
+        })))
     }
 
-    pub fn extend<T: IntoIterator<Item = Notification>>(&self, iter: T) {
-        self.0
-            .lock()
-            .expect("failed to lock NotifQueue")
-            .extend(iter);
+    /// Queues each subscriber that isn't already queued. Takes (pointer, subscriber) pairs the
+    /// same as SubscriberList stores them.
+    pub fn extend<T: IntoIterator<Item = (usize, Notification)>>(&self, iter: T) {
+        let mut lock = self.0.lock().expect("failed to lock NotifQueue");
+        let DirtySet { notifs, queued } = &mut *lock;
+        for (ptr, notif) in iter {
+            if queued.insert(ptr) {
+                notifs.push(notif);
+            }
+        }
     }
 
-    /// Swaps the internal buffer with another. This is useful because two buffers can be swapped
-    /// and forth without deallocating either.
+    /// Drains the queue by swapping the internal buffer with another, after which subscribers can
+    /// be queued again. This is useful because two buffers can be swapped back and forth without
+    /// deallocating either.
     pub fn swap_buffer(&self, other: &mut Vec<Notification>) {
         // This doesn't deallocate the memory
         other.clear();
-        std::mem::swap(
-            &mut *self.0.lock().expect("failed to lock NotifQueue"),
-            other,
-        );
+        let mut lock = self.0.lock().expect("failed to lock NotifQueue");
+        std::mem::swap(&mut lock.notifs, other);
+        lock.queued.clear();
     }
 
     #[cfg(test)]
     pub fn len(&self) -> usize {
-        self.0.lock().expect("failed to lock NotifQueue").len()
+        self.0
+            .lock()
+            .expect("failed to lock NotifQueue")
+            .notifs
+            .len()
     }
 }
 
@@ -49,22 +70,57 @@ mod tests {
         Arc::downgrade(&MockSubscriber::new().get())
     }
 
+    /// The subscribers need to outlive the queue, or they could share an address
+    fn queued(subscribers: &[MockSubscriber]) -> Vec<(usize, Notification)> {
+        subscribers
+            .iter()
+            .map(|s| (s.get().thin_ptr() as usize, Arc::downgrade(&s.get())))
+            .collect()
+    }
+
+    fn subscribers(count: usize) -> Vec<MockSubscriber> {
+        (0..count).map(|_| MockSubscriber::new()).collect()
+    }
+
     #[test]
     fn can_extend() {
         let notif_queue = NotifQueue::new();
-        notif_queue.extend(vec![notif(), notif(), notif()]);
+        let subscribers = subscribers(3);
+        notif_queue.extend(queued(&subscribers));
         assert_eq!(notif_queue.len(), 3);
     }
 
     #[test]
     fn can_swap_buffers() {
         let notif_queue = NotifQueue::new();
+        let subscribers = subscribers(3);
         let mut buf = vec![];
-        notif_queue.extend(vec![notif(), notif(), notif()]);
+        notif_queue.extend(queued(&subscribers));
         notif_queue.swap_buffer(&mut buf);
         assert_eq!(buf.len(), 3);
     }
 
+    #[test]
+    fn subscriber_is_only_queued_once() {
+        let notif_queue = NotifQueue::new();
+        let subscribers = subscribers(2);
+        notif_queue.extend(queued(&subscribers));
+        notif_queue.extend(queued(&subscribers[..1]));
+        notif_queue.extend(queued(&subscribers));
+        assert_eq!(notif_queue.len(), 2);
+    }
+
+    #[test]
+    fn subscriber_can_be_queued_again_after_swap() {
+        let notif_queue = NotifQueue::new();
+        let subscribers = subscribers(1);
+        let mut buf = vec![];
+        notif_queue.extend(queued(&subscribers));
+        notif_queue.swap_buffer(&mut buf);
+        notif_queue.extend(queued(&subscribers));
+        assert_eq!(notif_queue.len(), 1);
+    }
+
     #[test]
     fn clears_on_buffer_swap() {
         let notif_queue = NotifQueue::new();
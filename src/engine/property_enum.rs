@@ -0,0 +1,77 @@
+use super::*;
+
+/// An enum that clients see as one of a fixed set of strings. Use to_property() and
+/// from_property() with map_output() and map_input() to expose it as a property.
+pub trait PropertyEnum: Copy + PartialEq + Send + Sync + 'static {
+    /// Every variant along with the string that represents it
+    const VARIANTS: &'static [(Self, &'static str)];
+
+    fn to_property(self) -> RequestResult<String> {
+        Self::VARIANTS
+            .iter()
+            .find(|(variant, _)| *variant == self)
+            .map(|(_, name)| name.to_string())
+            .ok_or_else(|| InternalError("enum variant has no property string".to_string()))
+    }
+
+    fn from_property(value: String) -> RequestResult<Self> {
+        Self::VARIANTS
+            .iter()
+            .find(|(_, name)| *name == value)
+            .map(|(variant, _)| *variant)
+            .ok_or_else(|| {
+                let names: Vec<String> = Self::VARIANTS
+                    .iter()
+                    .map(|(_, name)| format!("{:?}", name))
+                    .collect();
+                BadRequest(format!(
+                    "{:?} is invalid, must be one of {}",
+                    value,
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    enum Fruit {
+        Apple,
+        Banana,
+        Durian,
+    }
+
+    impl PropertyEnum for Fruit {
+        const VARIANTS: &'static [(Self, &'static str)] =
+            &[(Fruit::Apple, "apple"), (Fruit::Banana, "banana")];
+    }
+
+    #[test]
+    fn converts_to_string() {
+        assert_eq!(Fruit::Banana.to_property(), Ok("banana".to_string()));
+    }
+
+    #[test]
+    fn accepts_valid_string() {
+        assert_eq!(Fruit::from_property("apple".to_string()), Ok(Fruit::Apple));
+    }
+
+    #[test]
+    fn rejects_unknown_string_with_valid_values() {
+        match Fruit::from_property("cherry".to_string()) {
+            Err(BadRequest(msg)) => {
+                assert!(msg.contains("\"cherry\""));
+                assert!(msg.contains("\"apple\", \"banana\""));
+            }
+            other => panic!("expected BadRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn variant_without_string_is_internal_error() {
+        assert!(matches!(Fruit::Durian.to_property(), Err(InternalError(_))));
+    }
+}
@@ -112,10 +112,10 @@ impl<T: Clone + Send + Sync + 'static> Signal<T> {
             // Only add the dispatcher to the notification queue for the first signal fired
             if pending.signal_events.is_empty() {
                 match pending.state_notif_queue.get() {
-                    Ok(notif_queue) => notif_queue
-                        .extend(std::iter::once(
-                            Arc::downgrade(&dispatcher) as Weak<dyn Subscriber>
-                        )),
+                    Ok(notif_queue) => notif_queue.extend(std::iter::once((
+                        dispatcher.thin_ptr() as usize,
+                        Arc::downgrade(&dispatcher) as Weak<dyn Subscriber>,
+                    ))),
                     Err(e) => error!("failed to fire signal: {}", e),
                 }
             }
@@ -6,8 +6,10 @@ new_key_type! {
     pub struct EntityKey;
 }
 
-type ComponentMap<T> = DenseSlotMap<ComponentKey<T>, (EntityKey, T)>;
+/// The u64 is the order the component was installed in, see State::components_iter_ordered()
+type ComponentMap<T> = DenseSlotMap<ComponentKey<T>, (EntityKey, T, u64)>;
 type ComponentElement<T> = (PhantomData<T>, Element<()>);
+type SendPriorityFn = Box<dyn Fn(&State, ConnectionKey, EntityKey) -> f64 + Send + Sync>;
 
 /// Every game has one state. It owns all entities and components. Most code that uses the state
 /// will be passed a reference to it. Entities and components inherit the state's mutability (if a
@@ -21,6 +23,33 @@ pub struct State {
     entities: DenseSlotMap<EntityKey, Entity>,
     components: AnyMap,
     component_list_elements: Mutex<AnyMap>, // TODO: change to subscription trackers
+    /// Connections the game wants closed, taken by the connection collection each tick
+    disconnect_requests: Vec<ConnectionKey>,
+    /// If set, connections purge destroyed entities from their object maps at the start of the
+    /// next tick
+    reconcile_requested: bool,
+    /// Connections to send a "connections" signal to, taken by the connection collection each tick
+    connection_list_requests: Vec<ConnectionKey>,
+    /// Connections that have closed since the game last took them
+    closed_connections: Vec<ConnectionKey>,
+    /// Connections that have been granted admin access, forgotten once they close
+    admins: HashSet<ConnectionKey>,
+    /// create_entity() fails once there are this many entities
+    max_entities: usize,
+    /// Properties are only resent to clients when they change by more than this
+    update_epsilon: f64,
+    /// Goes up with each installed component
+    next_component_order: u64,
+    /// How many client subscriptions each member has, for finding the ones driving bandwidth
+    subscriber_counts: HashMap<(EntityKey, String), u64>,
+    /// If set, clients aren't sent updates that only echo a value they set
+    suppress_echoes: bool,
+    /// Values clients set this tick by connection and member, only recorded if suppress_echoes is
+    /// set. Cleared once notifications have been sent.
+    client_sets: HashMap<(ConnectionKey, EntityKey, String), Value>,
+    /// Set by the game to decide which objects are sent to a connection first, see
+    /// set_send_priority()
+    send_priority: Option<SendPriorityFn>,
     pub notif_queue: NotifQueue,
 }
 
@@ -33,9 +62,21 @@ impl Default for State {
             entities: DenseSlotMap::with_key(),
             components: AnyMap::new(),
             component_list_elements: Mutex::new(AnyMap::new()),
+            disconnect_requests: Vec::new(),
+            reconcile_requested: false,
+            connection_list_requests: Vec::new(),
+            closed_connections: Vec::new(),
+            admins: HashSet::new(),
+            max_entities: usize::MAX,
+            update_epsilon: 0.0,
+            next_component_order: 0,
+            subscriber_counts: HashMap::new(),
+            suppress_echoes: false,
+            client_sets: HashMap::new(),
+            send_priority: None,
             notif_queue: NotifQueue::new(),
         };
-        state.root = state.create_entity();
+        state.root = state.create_entity().expect("failed to create root entity");
         state
     }
 }
@@ -45,9 +86,22 @@ impl State {
         Self::default()
     }
 
-    /// Returns the key for the newly created entity
-    pub fn create_entity(&mut self) -> EntityKey {
-        self.entities.insert_with_key(Entity::new)
+    /// Returns the key for the newly created entity. Fails without changing anything if the max
+    /// entity count has been reached.
+    pub fn create_entity(&mut self) -> RequestResult<EntityKey> {
+        if self.entities.len() >= self.max_entities {
+            Err(BadRequest(format!(
+                "can not create entity, maximum of {} reached",
+                self.max_entities
+            )))
+        } else {
+            Ok(self.entities.insert_with_key(Entity::new))
+        }
+    }
+
+    /// Sets the maximum number of entities (including the root) for create_entity()
+    pub fn set_max_entities(&mut self, max_entities: usize) {
+        self.max_entities = max_entities;
     }
 
     /// Returns the root entity, which is automatically created on construction. This will be the
@@ -56,6 +110,78 @@ impl State {
         self.root
     }
 
+    /// Closes the given client connection at the end of the tick. Does nothing if the connection
+    /// does not exist.
+    pub fn disconnect(&mut self, connection: ConnectionKey) {
+        self.disconnect_requests.push(connection);
+    }
+
+    /// Sends the connection a "connections" signal on the root entity describing every connection,
+    /// at the start of the next tick. Only the connection collection knows them.
+    pub fn request_connection_list(&mut self, connection: ConnectionKey) {
+        self.connection_list_requests.push(connection);
+    }
+
+    /// Lets the connection use admin members until it closes
+    pub fn grant_admin(&mut self, connection: ConnectionKey) {
+        self.admins.insert(connection);
+    }
+
+    /// If the connection has been granted admin access. The server itself (the null connection)
+    /// always has it.
+    pub fn is_admin(&self, connection: ConnectionKey) -> bool {
+        connection.is_null() || self.admins.contains(&connection)
+    }
+
+    /// Connections that have closed since the last call
+    pub fn take_closed_connections(&mut self) -> Vec<ConnectionKey> {
+        std::mem::take(&mut self.closed_connections)
+    }
+
+    /// Makes every connection forget entities that no longer exist at the start of the next tick.
+    /// Only needed if a destroyed entity was somehow missed.
+    pub fn reconcile_object_maps(&mut self) {
+        self.reconcile_requested = true;
+    }
+
+    /// How much a property has to change by before clients are updated
+    pub fn update_epsilon(&self) -> f64 {
+        self.update_epsilon
+    }
+
+    /// Must not be negative, which the config checks when it's loaded
+    pub fn set_update_epsilon(&mut self, epsilon: f64) {
+        assert!(epsilon >= 0.0);
+        self.update_epsilon = epsilon;
+    }
+
+    /// Stop sending a client updates for properties it set when the value is what it set them to.
+    /// Other subscribers are still updated.
+    pub fn set_suppress_echoes(&mut self, suppress_echoes: bool) {
+        self.suppress_echoes = suppress_echoes;
+    }
+
+    /// If the connection set the member to exactly this value this tick (only tracked if echoes
+    /// are suppressed)
+    pub fn is_echo(
+        &self,
+        connection: ConnectionKey,
+        entity: EntityKey,
+        name: &str,
+        value: &Value,
+    ) -> bool {
+        !self.client_sets.is_empty()
+            && self
+                .client_sets
+                .get(&(connection, entity, name.to_string()))
+                == Some(value)
+    }
+
+    /// Forgets which values clients set, called after each tick's notifications are sent
+    pub fn clear_client_sets(&mut self) {
+        self.client_sets.clear();
+    }
+
     /// Current time in seconds since the start of the game
     pub fn time(&self) -> f64 {
         self.time
@@ -92,6 +218,61 @@ impl State {
         Ok(())
     }
 
+    /// How many client subscriptions the member currently has
+    pub fn subscriber_count(&self, entity: EntityKey, name: &str) -> u64 {
+        self.subscriber_counts
+            .get(&(entity, name.to_string()))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// Subscribes to the conduit and counts the subscription against the member
+    fn track_subscription(
+        &mut self,
+        conduit: Box<dyn Conduit<Value, Value>>,
+        entity: EntityKey,
+        name: &str,
+    ) -> RequestResult<Box<dyn Any>> {
+        let member = (entity, name.to_string());
+        let subscription = Subscription::new(self, conduit, member.clone())?;
+        *self.subscriber_counts.entry(member).or_insert(0) += 1;
+        Ok(Box::new(subscription))
+    }
+
+    /// If the entity has been created and not destroyed
+    pub fn entity_exists(&self, entity: EntityKey) -> bool {
+        self.entities.contains_key(entity)
+    }
+
+    /// When a connection can't send all the values it has been asked for at once, the ones for
+    /// entities with a higher priority are sent first. Without this every entity has a priority of
+    /// 0 (the root is always sent first).
+    pub fn set_send_priority<F>(&mut self, priority: F)
+    where
+        F: Fn(&State, ConnectionKey, EntityKey) -> f64 + Send + Sync + 'static,
+    {
+        self.send_priority = Some(Box::new(priority));
+    }
+
+    /// Names and metadata of everything installed on the entity, for introspection
+    pub fn members(&self, entity: EntityKey) -> RequestResult<Vec<(&'static str, Metadata)>> {
+        Ok(self
+            .entities
+            .get(entity)
+            .ok_or(BadEntity(entity))?
+            .members())
+    }
+
+    /// Short type names of all components attached to the given entity, only intended for
+    /// debugging
+    pub fn component_type_names(&self, entity: EntityKey) -> RequestResult<&[String]> {
+        Ok(self
+            .entities
+            .get(entity)
+            .ok_or(BadEntity(entity))?
+            .component_type_names())
+    }
+
     /// Attaches the new component to the given entity
     /// Panics if the entity already has a component of the given type
     pub fn install_component<T: 'static>(&mut self, entity: EntityKey, component: T) {
@@ -99,11 +280,13 @@ impl State {
             .entities
             .get_mut(entity)
             .expect("can not add component to invalid entity");
+        let order = self.next_component_order;
+        self.next_component_order += 1;
         let map: &mut ComponentMap<T> = self
             .components
             .entry()
             .or_insert_with(DenseSlotMap::with_key);
-        let key = map.insert((entity, component));
+        let key = map.insert((entity, component, order));
         e.register_component(key, move |state| state.remove_component(key));
         self.trigger_component_list_element_update::<T>();
         // TODO: test that an update is sent to the component list element
@@ -164,7 +347,7 @@ impl State {
         &'a self,
     ) -> Box<dyn std::iter::Iterator<Item = (EntityKey, &T)> + 'a> {
         if let Some(map) = self.components.get::<ComponentMap<T>>() {
-            Box::new(map.values().map(|(entity, value)| (*entity, value)))
+            Box::new(map.values().map(|(entity, value, _)| (*entity, value)))
         } else {
             Box::new(std::iter::empty())
         }
@@ -175,11 +358,30 @@ impl State {
         &'a mut self,
     ) -> Box<dyn std::iter::Iterator<Item = (EntityKey, &mut T)> + 'a> {
         match self.components.get_mut::<ComponentMap<T>>() {
-            Some(map) => Box::new(map.values_mut().map(|(entity, value)| (*entity, value))),
+            Some(map) => Box::new(map.values_mut().map(|(entity, value, _)| (*entity, value))),
             None => Box::new(std::iter::empty()),
         }
     }
 
+    /// Like components_iter(), but always in the order the components were installed. That order
+    /// isn't effected by other components being removed, which can reorder components_iter().
+    /// Slower, so only use when order matters.
+    pub fn components_iter_ordered<'a, T: 'static>(
+        &'a self,
+    ) -> Box<dyn std::iter::Iterator<Item = (EntityKey, &'a T)> + 'a> {
+        if let Some(map) = self.components.get::<ComponentMap<T>>() {
+            let mut components: Vec<&(EntityKey, T, u64)> = map.values().collect();
+            components.sort_unstable_by_key(|(_, _, order)| *order);
+            Box::new(
+                components
+                    .into_iter()
+                    .map(|(entity, value, _)| (*entity, value)),
+            )
+        } else {
+            Box::new(std::iter::empty())
+        }
+    }
+
     /// Subscribe to be notified when a component of type T is created or destroyed
     pub fn subscribe_to_component_list<T: 'static>(
         &self,
@@ -219,13 +421,90 @@ impl State {
         C: Conduit<Value, Value> + 'static,
     {
         if let Some(entity) = self.entities.get_mut(entity_key) {
+            let metadata = conduit.metadata();
             let conduit = CachingConduit::new(conduit);
-            entity.register_conduit(name, move |connection| {
+            entity.register_conduit(name, metadata, move |connection, options| {
+                if options.frame.is_some() {
+                    return Err(BadRequest(format!("{} can not be seen from a frame", name)));
+                }
                 Ok(PropertyConduit::new(
                     connection,
                     entity_key,
                     name,
-                    conduit.clone(),
+                    FilterConduit::new(conduit.clone(), connection, options.filter.clone()),
+                ))
+            });
+        } else {
+            panic!(
+                "failed to register property on invalid entity {:?}",
+                entity_key
+            );
+        }
+    }
+
+    /// Like install_property(), but a client can subscribe from the frame of another entity. The
+    /// conduit for that is built by framed() each time, and isn't cached.
+    pub fn install_framed_property<C, F, FC>(
+        &mut self,
+        entity_key: EntityKey,
+        name: &'static str,
+        conduit: C,
+        framed: F,
+    ) where
+        C: Conduit<Value, Value> + 'static,
+        F: Fn(EntityKey) -> FC + 'static,
+        FC: Conduit<Value, Value> + 'static,
+    {
+        if let Some(entity) = self.entities.get_mut(entity_key) {
+            let metadata = conduit.metadata();
+            let conduit = CachingConduit::new(conduit);
+            entity.register_conduit(name, metadata, move |connection, options| {
+                let filter = options.filter.clone();
+                Ok(match options.frame {
+                    Some(frame) => PropertyConduit::new(
+                        connection,
+                        entity_key,
+                        name,
+                        FilterConduit::new(framed(frame), connection, filter),
+                    ),
+                    None => PropertyConduit::new(
+                        connection,
+                        entity_key,
+                        name,
+                        FilterConduit::new(conduit.clone(), connection, filter),
+                    ),
+                })
+            });
+        } else {
+            panic!(
+                "failed to register property on invalid entity {:?}",
+                entity_key
+            );
+        }
+    }
+
+    /// Create a property that is a list of entities, which subscribers receive as deltas. Panics if
+    /// entity doesn't exist or already has something with this name.
+    pub fn install_delta_property<C>(
+        &mut self,
+        entity_key: EntityKey,
+        name: &'static str,
+        conduit: C,
+    ) where
+        C: Conduit<Value, Value> + 'static,
+    {
+        if let Some(entity) = self.entities.get_mut(entity_key) {
+            let metadata = conduit.metadata();
+            let conduit = CachingConduit::new(conduit);
+            entity.register_conduit(name, metadata, move |connection, options| {
+                if options.frame.is_some() {
+                    return Err(BadRequest(format!("{} can not be seen from a frame", name)));
+                }
+                Ok(DeltaPropertyConduit::new(
+                    connection,
+                    entity_key,
+                    name,
+                    FilterConduit::new(conduit.clone(), connection, options.filter.clone()),
                 ))
             });
         } else {
@@ -245,7 +524,13 @@ impl State {
         if let Some(entity) = self.entities.get_mut(entity_key) {
             let conduit =
                 Arc::new(conduit) as Arc<dyn Conduit<Vec<Value>, SignalsDontTakeInputSilly>>;
-            entity.register_conduit(name, move |connection| {
+            entity.register_conduit(name, Metadata::default(), move |connection, options| {
+                if !options.is_default() {
+                    return Err(BadRequest(format!(
+                        "{} does not take subscribe options",
+                        name
+                    )));
+                }
                 Ok(SignalConduit::new(
                     connection,
                     entity_key,
@@ -269,9 +554,16 @@ impl State {
         C: Conduit<ActionsDontProduceOutputSilly, Value> + 'static,
     {
         if let Some(entity) = self.entities.get_mut(entity_key) {
+            let metadata = conduit.metadata();
             let conduit =
                 Arc::new(conduit.map_output(|_| unreachable!())) as Arc<dyn Conduit<Value, Value>>;
-            entity.register_conduit(name, move |connection| {
+            entity.register_conduit(name, metadata, move |connection, options| {
+                if !options.is_default() {
+                    return Err(BadRequest(format!(
+                        "{} does not take subscribe options",
+                        name
+                    )));
+                }
                 Ok(PropertyConduit::new(
                     connection,
                     entity_key,
@@ -287,6 +579,38 @@ impl State {
         }
     }
 
+    /// Like install_action(), but the conduit is built by for_connection() each time it's used so
+    /// the action knows which connection fired it
+    pub fn install_connection_action<F, C>(
+        &mut self,
+        entity_key: EntityKey,
+        name: &'static str,
+        for_connection: F,
+    ) where
+        F: Fn(ConnectionKey) -> C + 'static,
+        C: Conduit<ActionsDontProduceOutputSilly, Value> + 'static,
+    {
+        if let Some(entity) = self.entities.get_mut(entity_key) {
+            let metadata = for_connection(ConnectionKey::null()).metadata();
+            entity.register_conduit(name, metadata, move |connection, options| {
+                if !options.is_default() {
+                    return Err(BadRequest(format!(
+                        "{} does not take subscribe options",
+                        name
+                    )));
+                }
+                let conduit = Arc::new(for_connection(connection).map_output(|_| unreachable!()))
+                    as Arc<dyn Conduit<Value, Value>>;
+                Ok(PropertyConduit::new(connection, entity_key, name, conduit))
+            });
+        } else {
+            panic!(
+                "failed to register property on invalid entity {:?}",
+                entity_key
+            );
+        }
+    }
+
     #[cfg(test)]
     pub fn is_empty(&self) -> bool {
         // pending_updates intentionally not checked
@@ -295,16 +619,18 @@ impl State {
             && self.entities.get(self.root).is_some()
     }
 
-    /// Returns the conduit for the property, signal or action with the given name.
+    /// Returns the conduit for the property, signal or action with the given name. Only
+    /// properties can have subscribe options.
     fn conduit(
         &self,
         connection: ConnectionKey,
         entity_key: EntityKey,
         name: &str,
+        options: &SubscribeOptions,
     ) -> RequestResult<Box<dyn Conduit<Value, Value>>> {
         let entity = self.entities.get(entity_key).ok_or(BadEntity(entity_key))?;
         let conduit = entity
-            .conduit(connection, name)
+            .conduit(connection, name, options)
             .ok_or_else(|| BadName(entity_key, name.into()))??;
         Ok(conduit)
     }
@@ -356,7 +682,7 @@ impl RequestHandler for State {
         name: &str,
         value: Value,
     ) -> RequestResult<()> {
-        let conduit = self.conduit(connection, entity, name)?;
+        let conduit = self.conduit(connection, entity, name, &SubscribeOptions::default())?;
         // TODO: check if this is actually an action (currently "fireing" a property sets it)
         conduit.input(self, value)
     }
@@ -368,9 +694,15 @@ impl RequestHandler for State {
         name: &str,
         value: Value,
     ) -> RequestResult<()> {
-        let conduit = self.conduit(connection, entity, name)?;
-        // TODO: check if this is actually a property (currently "setting" an action fires it)
-        conduit.input(self, value)
+        let conduit = self.conduit(connection, entity, name, &SubscribeOptions::default())?;
+        if !self.suppress_echoes || connection.is_null() {
+            // TODO: check if this is actually a property (currently "setting" an action fires it)
+            return conduit.input(self, value);
+        }
+        conduit.input(self, value.clone())?;
+        self.client_sets
+            .insert((connection, entity, name.to_string()), value);
+        Ok(())
     }
 
     fn get_property(
@@ -379,7 +711,7 @@ impl RequestHandler for State {
         entity: EntityKey,
         name: &str,
     ) -> RequestResult<Value> {
-        let conduit = self.conduit(connection, entity, name)?;
+        let conduit = self.conduit(connection, entity, name, &SubscribeOptions::default())?;
         conduit.output(self)
     }
 
@@ -389,17 +721,76 @@ impl RequestHandler for State {
         entity: EntityKey,
         name: &str,
     ) -> RequestResult<Box<dyn Any>> {
-        let conduit = self.conduit(connection, entity, name)?;
-        let subscription = Subscription::new(self, conduit)?;
-        Ok(Box::new(subscription))
+        let conduit = self.conduit(connection, entity, name, &SubscribeOptions::default())?;
+        self.track_subscription(conduit, entity, name)
+    }
+
+    fn get_property_with_options(
+        &self,
+        connection: ConnectionKey,
+        entity: EntityKey,
+        name: &str,
+        options: &SubscribeOptions,
+    ) -> RequestResult<Value> {
+        let conduit = self.conduit(connection, entity, name, options)?;
+        conduit.output(self)
+    }
+
+    fn subscribe_with_options(
+        &mut self,
+        connection: ConnectionKey,
+        entity: EntityKey,
+        name: &str,
+        options: &SubscribeOptions,
+    ) -> RequestResult<Box<dyn Any>> {
+        let conduit = self.conduit(connection, entity, name, options)?;
+        self.track_subscription(conduit, entity, name)
     }
 
     fn unsubscribe(&mut self, subscription: Box<dyn Any>) -> RequestResult<()> {
         let subscription: Box<Subscription> = subscription
             .downcast()
             .map_err(|_| InternalError("downcast to Subscription failed".into()))?;
+        if let Some(count) = self.subscriber_counts.get_mut(subscription.member()) {
+            *count -= 1;
+            if *count == 0 {
+                self.subscriber_counts.remove(subscription.member());
+            }
+        }
         subscription.unsubscribe(self)
     }
+
+    fn take_disconnect_requests(&mut self) -> Vec<ConnectionKey> {
+        std::mem::take(&mut self.disconnect_requests)
+    }
+
+    fn take_reconcile_request(&mut self) -> bool {
+        std::mem::take(&mut self.reconcile_requested)
+    }
+
+    fn take_connection_list_requests(&mut self) -> Vec<ConnectionKey> {
+        std::mem::take(&mut self.connection_list_requests)
+    }
+
+    fn connection_closed(&mut self, connection: ConnectionKey) {
+        self.admins.remove(&connection);
+        self.closed_connections.push(connection);
+    }
+
+    fn entity_exists(&self, entity: EntityKey) -> bool {
+        State::entity_exists(self, entity)
+    }
+
+    fn send_priority(&self, connection: ConnectionKey, entity: EntityKey) -> f64 {
+        match &self.send_priority {
+            Some(priority) => priority(self, connection, entity),
+            None => 0.0,
+        }
+    }
+
+    fn time(&self) -> f64 {
+        State::time(self)
+    }
 }
 
 #[cfg(test)]
@@ -425,6 +816,19 @@ mod tests {
         assert_eq!(state.time(), 3.5);
     }
 
+    #[test]
+    fn create_entity_fails_past_max() {
+        let mut state = State::new();
+        state.set_max_entities(3);
+        let a = state.create_entity().unwrap();
+        state.install_component(a, MockComponent(7));
+        let b = state.create_entity().unwrap();
+        assert!(matches!(state.create_entity(), Err(BadRequest(_))));
+        assert_eq!(state.entities.len(), 3);
+        assert_eq!(state.component::<MockComponent>(a), Ok(&MockComponent(7)));
+        assert!(state.entities.get(b).is_some());
+    }
+
     #[test]
     fn is_empty_by_default() {
         let state = State::new();
@@ -434,14 +838,14 @@ mod tests {
     #[test]
     fn not_empty_after_entity_created() {
         let mut state = State::new();
-        let _ = state.create_entity();
+        let _ = state.create_entity().unwrap();
         assert!(!state.is_empty());
     }
 
     #[test]
     fn is_empty_after_entity_created_and_destroyed() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         state.destroy_entity(e).unwrap();
         assert!(state.is_empty());
     }
@@ -449,7 +853,7 @@ mod tests {
     #[test]
     fn is_empty_after_entity_and_component_created_and_destroyed() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         state.install_component(e, MockComponent(3));
         state.destroy_entity(e).unwrap();
         assert!(state.is_empty());
@@ -459,7 +863,7 @@ mod tests {
     #[should_panic(expected = "invalid entity")]
     fn panics_when_component_added_to_destroyed_entity() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         state.destroy_entity(e).unwrap();
         state.install_component(e, MockComponent(3));
     }
@@ -468,7 +872,7 @@ mod tests {
     #[should_panic(expected = "multiple")]
     fn panics_when_2nd_component_of_same_type_added_to_entity() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         state.install_component(e, MockComponent(3));
         state.install_component(e, MockComponent(4));
     }
@@ -476,15 +880,53 @@ mod tests {
     #[test]
     fn components_of_different_types_can_be_added_to_entity() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         state.install_component(e, MockComponent(3));
         state.install_component(e, OtherMockComponent(true));
     }
 
+    #[test]
+    fn getting_signal_is_not_available() {
+        let mut state = State::new();
+        let e = state.create_entity().unwrap();
+        Signal::<Value>::new()
+            .conduit(&state.notif_queue)
+            .install_signal(&mut state, e, "sig");
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), e, "sig"),
+            Err(NotAvailable(e, "sig".to_string()))
+        );
+    }
+
+    #[test]
+    fn ordered_iteration_is_in_install_order_after_removal() {
+        let mut state = State::new();
+        let unrelated = state.create_entity().unwrap();
+        state.install_component(unrelated, MockComponent(0));
+        let abc: Vec<EntityKey> = (1..=3)
+            .map(|i| {
+                let e = state.create_entity().unwrap();
+                state.install_component(e, MockComponent(i));
+                e
+            })
+            .collect();
+        state.destroy_entity(unrelated).unwrap();
+        let iterated: Vec<(EntityKey, &MockComponent)> =
+            state.components_iter_ordered::<MockComponent>().collect();
+        assert_eq!(
+            iterated,
+            vec![
+                (abc[0], &MockComponent(1)),
+                (abc[1], &MockComponent(2)),
+                (abc[2], &MockComponent(3))
+            ]
+        );
+    }
+
     #[test]
     fn can_get_component() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         state.install_component(e, MockComponent(3));
         assert_eq!(state.component::<MockComponent>(e), Ok(&MockComponent(3)));
     }
@@ -492,8 +934,8 @@ mod tests {
     #[test]
     fn multiple_entities_can_be_created_and_destroyed() {
         let mut state = State::new();
-        let e0 = state.create_entity();
-        let e1 = state.create_entity();
+        let e0 = state.create_entity().unwrap();
+        let e1 = state.create_entity().unwrap();
         state.destroy_entity(e1).unwrap();
         state.destroy_entity(e0).unwrap();
         assert!(state.is_empty());
@@ -502,8 +944,8 @@ mod tests {
     #[test]
     fn can_get_components_from_multiple_entities() {
         let mut state = State::new();
-        let e0 = state.create_entity();
-        let e1 = state.create_entity();
+        let e0 = state.create_entity().unwrap();
+        let e1 = state.create_entity().unwrap();
         state.install_component(e0, MockComponent(0));
         state.install_component(e1, MockComponent(1));
         assert_eq!(state.component::<MockComponent>(e0), Ok(&MockComponent(0)));
@@ -520,14 +962,14 @@ mod tests {
     #[test]
     fn getting_invalid_component_is_err() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         assert!(state.component::<MockComponent>(e).is_err());
     }
 
     #[test]
     fn can_mutate_component() {
         let mut state = State::new();
-        let e = state.create_entity();
+        let e = state.create_entity().unwrap();
         state.install_component(e, MockComponent(3));
         let mut c = state
             .component_mut::<MockComponent>(e)
@@ -0,0 +1,17 @@
+use super::*;
+
+/// Options a client can send with a subscribe request, which change what it receives
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubscribeOptions {
+    /// If set, entity lists only include entities that match
+    pub filter: Option<EntityFilter>,
+    /// If set, the property is seen from the co-rotating frame of this entity. Only properties
+    /// installed with install_framed_property() support this.
+    pub frame: Option<EntityKey>,
+}
+
+impl SubscribeOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
@@ -8,6 +8,8 @@ impl Subscriber for NullSubscriber {
 
 pub struct Subscription {
     conduit: Box<dyn Conduit<Value, Value>>,
+    /// Entity and name of the subscribed member
+    member: (EntityKey, String),
     is_unsubscribed: bool,
 }
 
@@ -16,15 +18,24 @@ pub struct Subscription {
 /// This works ? for some reason? It's very jank and I don't completely understand it, but
 /// refactoring takes significant thought.
 impl Subscription {
-    pub fn new(state: &State, conduit: Box<dyn Conduit<Value, Value>>) -> RequestResult<Self> {
+    pub fn new(
+        state: &State,
+        conduit: Box<dyn Conduit<Value, Value>>,
+        member: (EntityKey, String),
+    ) -> RequestResult<Self> {
         let subscriber: Arc<dyn Subscriber> = Arc::new(NullSubscriber);
         conduit.subscribe(state, &subscriber)?;
         Ok(Self {
             conduit,
+            member,
             is_unsubscribed: false,
         })
     }
 
+    pub fn member(&self) -> &(EntityKey, String) {
+        &self.member
+    }
+
     pub fn unsubscribe(mut self, state: &State) -> RequestResult<()> {
         self.is_unsubscribed = true;
         let subscriber: Weak<dyn Subscriber> = Weak::<NullSubscriber>::new();
@@ -9,12 +9,13 @@ pub enum Value {
     Scalar(f64),
     Integer(i64),
     Text(String),
+    Bool(bool),
+    /// Opaque binary data, such as a blob a client stores on an entity
+    Bytes(Vec<u8>),
     Entity(EntityKey),
     Array(Vec<Value>),
+    Map(HashMap<String, Value>),
     Null,
-    // TODO: add boolean
-    // TODO: add map
-    // (for each JSON encoding, JSON decoding and Value getting needs to be tested)
 }
 
 impl AssertIsSync for Value {}
@@ -39,6 +40,12 @@ impl From<Vector3<f64>> for Value {
     }
 }
 
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
 impl From<f64> for Value {
     fn from(value: f64) -> Self {
         Value::Scalar(value)
@@ -100,6 +107,15 @@ where
     }
 }
 
+impl<T> From<HashMap<String, T>> for Value
+where
+    T: Into<Value>,
+{
+    fn from(map: HashMap<String, T>) -> Self {
+        Value::Map(map.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
 impl From<()> for Value {
     fn from(_: ()) -> Self {
         Value::Null
@@ -190,6 +206,30 @@ impl Value {
     pub fn is_null(&self) -> bool {
         matches!(self, Value::Null)
     }
+
+    /// The named field, if this is a map that has it
+    pub fn get_field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Map(map) => map.get(name),
+            _ => None,
+        }
+    }
+
+    /// Decodes the named field of a map, or returns default if the field is missing or null. Fails
+    /// if this is not a map or the field is the wrong type. The type has to be given explicitly, as
+    /// the compiler gives up inferring it from the default.
+    pub fn field_or<T>(&self, name: &str, default: T) -> DecodeResult<T>
+    where
+        Value: Into<DecodeResult<T>>,
+    {
+        if !matches!(self, Value::Map(_)) {
+            return Err(BadRequest(format!("{:?} is not a map", self)));
+        }
+        match self.get_field(name) {
+            None | Some(Value::Null) => Ok(default),
+            Some(value) => value.clone().into(),
+        }
+    }
 }
 
 impl From<Value> for DecodeResult<Value> {
@@ -254,6 +294,15 @@ impl From<Value> for DecodeResult<String> {
     }
 }
 
+impl From<Value> for DecodeResult<bool> {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Bool(value) => Ok(value),
+            _ => Err(BadRequest(format!("{:?} is not a boolean", value))),
+        }
+    }
+}
+
 impl From<Value> for DecodeResult<EntityKey> {
     fn from(value: Value) -> Self {
         match value {
@@ -267,10 +316,21 @@ impl From<Value> for DecodeResult<EntityKey> {
 impl From<Value> for DecodeResult<ColorRGB> {
     fn from(value: Value) -> Self {
         let s: String = Into::<DecodeResult<String>>::into(value)?;
-        if !s.starts_with("0x") {
-            return Err(BadRequest("color does not start with 0x".to_string()));
-        }
-        let u = u32::from_str_radix(&s[2..], 16)
+        // 0x may be followed by fewer digits, # (as used on the web) and bare hex must have 6
+        let digits = match s.strip_prefix("0x") {
+            Some(digits) => digits,
+            None => {
+                let digits = s.strip_prefix('#').unwrap_or(&s);
+                if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(BadRequest(format!(
+                        "{:?} is not a color (expected 0x, # or 6 hex digits)",
+                        s
+                    )));
+                }
+                digits
+            }
+        };
+        let u = u32::from_str_radix(digits, 16)
             .map_err(|e| BadRequest(format!("could not parse color: {}", e)))?;
         if u >> 24 != 0 {
             return Err(BadRequest("color has too many digits".to_string()));
@@ -291,6 +351,21 @@ where
     }
 }
 
+impl<T> From<Value> for DecodeResult<HashMap<String, T>>
+where
+    Value: Into<DecodeResult<T>>,
+{
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Map(map) => map
+                .into_iter()
+                .map(|(k, v)| Into::<DecodeResult<T>>::into(v).map(|v| (k, v)))
+                .collect(),
+            _ => Err(BadRequest(format!("{:?} is not a map", value))),
+        }
+    }
+}
+
 impl From<Value> for DecodeResult<()> {
     fn from(value: Value) -> Self {
         if value.is_null() {
@@ -525,6 +600,48 @@ mod decode_tests {
         assert!(matches!(DecodeResult::<T>::from(decodable), Err(_)));
     }
 
+    fn params() -> Value {
+        let mut map = HashMap::new();
+        map.insert("altitude".to_string(), Scalar(12.5));
+        map.insert("label".to_string(), Text("moon".to_string()));
+        map.insert("target".to_string(), Null);
+        Map(map)
+    }
+
+    #[test]
+    fn get_field_finds_present_fields_of_maps_only() {
+        assert_eq!(params().get_field("altitude"), Some(&Scalar(12.5)));
+        assert_eq!(params().get_field("speed"), None);
+        assert_eq!(Scalar(1.0).get_field("altitude"), None);
+    }
+
+    #[test]
+    fn field_or_decodes_present_field() {
+        assert_eq!(params().field_or::<f64>("altitude", 0.0), Ok(12.5));
+        assert_eq!(
+            params().field_or::<String>("label", String::new()),
+            Ok("moon".to_string())
+        );
+    }
+
+    #[test]
+    fn field_or_uses_default_for_absent_or_null_field() {
+        assert_eq!(params().field_or::<f64>("speed", 3.0), Ok(3.0));
+        assert_eq!(params().field_or::<i64>("target", 7), Ok(7));
+    }
+
+    #[test]
+    fn field_or_rejects_wrong_type_and_non_maps() {
+        assert!(matches!(
+            params().field_or::<f64>("label", 0.0),
+            Err(BadRequest(_))
+        ));
+        assert!(matches!(
+            Scalar(1.0).field_or::<f64>("altitude", 0.0),
+            Err(BadRequest(_))
+        ));
+    }
+
     #[test]
     fn can_get_decoded() {
         assert_decodes_to::<Value>(Integer(7), Integer(7));
@@ -586,6 +703,17 @@ mod decode_tests {
         assert_decodes_to::<String>(Text("hello".to_string()), "hello".to_string());
     }
 
+    #[test]
+    fn can_get_bool() {
+        assert_decodes_to::<bool>(Bool(true), true);
+        assert_decodes_to::<bool>(Bool(false), false);
+    }
+
+    #[test]
+    fn can_not_get_bool_from_int() {
+        assert_doesnt_decode_to::<bool>(Integer(1));
+    }
+
     #[test]
     fn can_get_null() {
         assert_decodes_to::<()>(Null, ());
@@ -631,6 +759,34 @@ mod decode_tests {
         assert_decodes_to::<ColorRGB>(Text("0xF801a2".to_string()), color);
     }
 
+    #[test]
+    fn can_get_color_with_hash() {
+        let color = ColorRGB::from_u32(0xF801A2);
+        assert_decodes_to::<ColorRGB>(Text("#F801A2".to_string()), color);
+    }
+
+    #[test]
+    fn can_get_color_without_prefix() {
+        let color = ColorRGB::from_u32(0xF801A2);
+        assert_decodes_to::<ColorRGB>(Text("F801A2".to_string()), color);
+    }
+
+    #[test]
+    fn malformed_colors_are_rejected() {
+        for s in &[
+            "#F801A",
+            "#F801A2F",
+            "F801AG",
+            "#+801A2",
+            "0xF801A2F",
+            "0x",
+            "blue",
+            "",
+        ] {
+            assert_doesnt_decode_to::<ColorRGB>(Text(s.to_string()));
+        }
+    }
+
     #[test]
     fn can_get_array_of_ints() {
         let values = vec![7, 8, 9];
@@ -1,5 +1,11 @@
 use super::*;
 
+/// Default for how strongly altitude error is turned into a goal vertical velocity. 1.0 means the
+/// autopilot tries to correct the error in ~1s.
+pub const DEFAULT_AUTOPILOT_PROPORTIONAL_GAIN: f64 = 1.0;
+/// Default for how strongly velocity error is turned into acceleration
+pub const DEFAULT_AUTOPILOT_DERIVATIVE_GAIN: f64 = 1.0;
+
 /// Parameters to calculate acceleration required to achieve a specific orbit. The algorithm that
 /// uses this assumes we're currently orbiting around the gravity body, and no other gravity wells
 /// have a significant effect.
@@ -22,6 +28,12 @@ struct OrbitParams {
     goal_axis: Option<Vector3<f64>>,
     /// The direction from the grav body we want to be at (normalized)
     goal_virtical_direction: Option<Vector3<f64>>,
+    /// How strongly altitude error is turned into a goal vertical velocity
+    proportional_gain: f64,
+    /// How strongly velocity error is turned into acceleration
+    derivative_gain: f64,
+    /// Commanded accelerations with a magnitude smaller than this are dropped
+    tolerance: f64,
 }
 
 fn orbit_params(state: &State, ship_key: EntityKey) -> Result<OrbitParams, Box<dyn Error>> {
@@ -38,6 +50,9 @@ fn orbit_params(state: &State, ship_key: EntityKey) -> Result<OrbitParams, Box<d
     let position = *body.position;
     let velocity = *body.velocity;
     let max_acceleration = *ship.max_acceleration;
+    let proportional_gain = *ship.autopilot.proportional_gain;
+    let derivative_gain = *ship.autopilot.derivative_gain;
+    let tolerance = *ship.autopilot.tolerance;
     let grav_body = state
         .component::<Body>(grav_body_key)
         .map_err(|e| format!("getting gravity body: {}", e))?;
@@ -84,6 +99,9 @@ fn orbit_params(state: &State, ship_key: EntityKey) -> Result<OrbitParams, Box<d
         goal_altitude,
         goal_axis,
         goal_virtical_direction,
+        proportional_gain,
+        derivative_gain,
+        tolerance,
     })
 }
 
@@ -122,7 +140,7 @@ fn accel_for_orbit(params: &OrbitParams) -> Vector3<f64> {
                 params.goal_altitude * scale
             }
         };
-        let goal_vertical_velocity = goal_altitude - altitude; // achieve goal in ~1s
+        let goal_vertical_velocity = (goal_altitude - altitude) * params.proportional_gain;
         vertical_velocity_error = goal_vertical_velocity - vertical_velocity;
     } else {
         forward_velocity_error = params.max_acceleration;
@@ -132,42 +150,327 @@ fn accel_for_orbit(params: &OrbitParams) -> Vector3<f64> {
         Some(axis) => vertical_direction.cross(-axis).normalize() - lateral_direction,
         None => Vector3::zero(),
     };
-    let ideal_accel = lateral_direction * forward_velocity_error
-        + vertical_direction * vertical_velocity_error
+    let ideal_accel = (lateral_direction * forward_velocity_error
+        + vertical_direction * vertical_velocity_error)
+        * params.derivative_gain
         + pitch_error * 10.0;
-    if ideal_accel.magnitude() <= params.max_acceleration {
+    if ideal_accel.magnitude() < params.tolerance {
+        Vector3::zero()
+    } else if ideal_accel.magnitude() <= params.max_acceleration {
         ideal_accel
     } else {
         ideal_accel.normalize() * params.max_acceleration
     }
 }
 
-fn orbit(state: &mut State, ship_key: EntityKey) -> Result<(), Box<dyn Error>> {
+fn orbit(state: &mut State, ship_key: EntityKey) -> Result<AutopilotStatus, Box<dyn Error>> {
     let params = orbit_params(state, ship_key)?;
     let acceleration = accel_for_orbit(&params);
     state
         .component_mut::<Ship>(ship_key)?
         .acceleration
         .set(acceleration);
-    Ok(())
+    // accel_for_orbit() only returns zero once all errors are within tolerance
+    if acceleration == Vector3::zero() {
+        Ok(AutopilotStatus::Matched)
+    } else {
+        Ok(AutopilotStatus::Approaching)
+    }
+}
+
+fn flee(
+    state: &mut State,
+    ship_key: EntityKey,
+    epsilon: f64,
+) -> Result<AutopilotStatus, Box<dyn Error>> {
+    let ship = state.component::<Ship>(ship_key)?;
+    let target_key = *ship.autopilot.target;
+    if target_key.is_null() {
+        return Err("no target to flee from".into());
+    }
+    let safe_distance = (*ship.autopilot.distance).unwrap_or(f64::INFINITY);
+    let max_acceleration = *ship.max_acceleration;
+    let position = *state.component::<Body>(ship_key)?.position;
+    let target_position = *state
+        .component::<Body>(target_key)
+        .map_err(|e| format!("getting flee target: {}", e))?
+        .position;
+    let away = position - target_position;
+    let (acceleration, status) = if away.magnitude() >= safe_distance {
+        (Vector3::zero(), AutopilotStatus::Safe)
+    } else if away.magnitude() > epsilon {
+        (
+            away.normalize_to(max_acceleration),
+            AutopilotStatus::Fleeing,
+        )
+    } else {
+        // We're right on top of the target, so any direction is away
+        (
+            Vector3::unit_x() * max_acceleration,
+            AutopilotStatus::Fleeing,
+        )
+    };
+    state
+        .component_mut::<Ship>(ship_key)?
+        .acceleration
+        .set(acceleration);
+    Ok(status)
 }
 
-pub fn run_autopilot(state: &mut State, _: f64) {
+/// Switches the ship back to manual control with no thrust
+fn disable_autopilot(state: &mut State, ship_key: EntityKey) {
+    if let Ok(ship) = state.component_mut::<Ship>(ship_key) {
+        ship.acceleration.set(Vector3::zero());
+        ship.autopilot.scheme.set(AutopilotScheme::Off);
+        ship.autopilot.status.set(AutopilotStatus::Off);
+    }
+}
+
+pub fn run_autopilot(state: &mut State, _: f64, epsilon: f64) {
     // TODO: improve the ECS so we don't need to collect a vec here
     let ships: Vec<EntityKey> = state.components_iter::<Ship>().map(|(e, _)| e).collect();
     for ship_key in ships {
         if let Ok(ship) = state.component::<Ship>(ship_key) {
             let scheme = *ship.autopilot.scheme;
-            if let Err(err) = match scheme {
-                AutopilotScheme::Off => Ok(()),
-                AutopilotScheme::Orbit => orbit(state, ship_key),
-            } {
+            let target = *ship.autopilot.target;
+            if scheme != AutopilotScheme::Off && !target.is_null() && !state.entity_exists(target) {
+                warn!(
+                    "autopilot target {:?} of {:?} was destroyed, switching to manual",
+                    target, ship_key
+                );
+                disable_autopilot(state, ship_key);
                 if let Ok(ship) = state.component_mut::<Ship>(ship_key) {
-                    ship.acceleration.set(Vector3::zero());
-                    ship.autopilot.scheme.set(AutopilotScheme::Off);
+                    ship.autopilot.target.set(EntityKey::null());
+                }
+                continue;
+            }
+            let status = match scheme {
+                AutopilotScheme::Off => Ok(AutopilotStatus::Off),
+                AutopilotScheme::Orbit => orbit(state, ship_key),
+                AutopilotScheme::Flee => flee(state, ship_key, epsilon),
+            };
+            match status {
+                Ok(status) => {
+                    if let Ok(ship) = state.component_mut::<Ship>(ship_key) {
+                        ship.autopilot.status.set(status);
+                    }
+                }
+                Err(err) => {
+                    disable_autopilot(state, ship_key);
+                    error!("{:?} failed for {:?}: {}", scheme, ship_key, err);
                 }
-                error!("{:?} failed for {:?}: {}", scheme, ship_key, err);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Just over the gravity body threshold, so gravity is negligible compared to thrust
+    const PLANET_MASS: f64 = 200_000.0;
+    const START_ALTITUDE: f64 = 10.0;
+    const GOAL_ALTITUDE: f64 = 10.5;
+
+    /// Puts a ship in a circular orbit around a tiny planet and turns on the orbit autopilot with a
+    /// slightly higher goal altitude
+    fn orbit_setup(proportional_gain: f64) -> (State, EntityKey) {
+        let mut state = State::new();
+        God::default().install(&mut state, &GameConfig::default());
+        let planet = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(PLANET_MASS)
+            .install(&mut state, planet);
+        let orbit_speed = (GRAVITATIONAL_CONSTANT * PLANET_MASS / START_ALTITUDE).sqrt();
+        let ship = create_ship(
+            &mut state,
+            Point3::new(START_ALTITUDE, 0.0, 0.0),
+            Vector3::new(0.0, orbit_speed, 0.0),
+            EPSILON,
+        )
+        .unwrap();
+        let autopilot = &mut state.component_mut::<Ship>(ship).unwrap().autopilot;
+        autopilot.scheme.set(AutopilotScheme::Orbit);
+        autopilot.distance.set(Some(GOAL_ALTITUDE));
+        autopilot.proportional_gain.set(proportional_gain);
+        (state, ship)
+    }
+
+    /// Runs orbit_setup() and returns the ship's altitude after each tick
+    fn orbit_approach(proportional_gain: f64, ticks: u32) -> Vec<f64> {
+        let (mut state, ship) = orbit_setup(proportional_gain);
+        (0..ticks)
+            .map(|_| {
+                physics_tick(&mut state, TICK_TIME, &GameConfig::default());
+                state
+                    .component::<Body>(ship)
+                    .unwrap()
+                    .position
+                    .to_vec()
+                    .magnitude()
+            })
+            .collect()
+    }
+
+    fn max(altitudes: &[f64]) -> f64 {
+        altitudes.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    fn flee_setup(ship_position: Point3<f64>, safe_distance: f64) -> (State, EntityKey) {
+        let mut state = State::new();
+        let target = state.create_entity().unwrap();
+        Body::new().install(&mut state, target);
+        let ship = create_ship(&mut state, ship_position, Vector3::zero(), EPSILON).unwrap();
+        let autopilot = &mut state.component_mut::<Ship>(ship).unwrap().autopilot;
+        autopilot.scheme.set(AutopilotScheme::Flee);
+        autopilot.target.set(target);
+        autopilot.distance.set(Some(safe_distance));
+        (state, ship)
+    }
+
+    #[test]
+    fn flee_accelerates_directly_away_from_target() {
+        let (mut state, ship) = flee_setup(Point3::new(3.0, 4.0, 0.0), 20.0);
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        let ship = state.component::<Ship>(ship).unwrap();
+        let expected = Vector3::new(0.6, 0.8, 0.0) * *ship.max_acceleration;
+        assert!((*ship.acceleration - expected).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn flee_treats_target_within_epsilon_as_on_top() {
+        let (mut state, ship) = flee_setup(Point3::new(0.0, 0.5, 0.0), 20.0);
+        run_autopilot(&mut state, TICK_TIME, 1.0);
+        let ship = state.component::<Ship>(ship).unwrap();
+        let expected = Vector3::unit_x() * *ship.max_acceleration;
+        assert!((*ship.acceleration - expected).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn flee_cuts_thrust_past_safe_distance() {
+        let (mut state, ship) = flee_setup(Point3::new(3.0, 4.0, 0.0), 4.0);
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .acceleration
+            .set(Vector3::new(1.0, 0.0, 0.0));
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        let ship = state.component::<Ship>(ship).unwrap();
+        assert_eq!(*ship.acceleration, Vector3::zero());
+        assert_eq!(*ship.autopilot.scheme, AutopilotScheme::Flee);
+    }
+
+    #[test]
+    fn default_gain_overshoots_goal() {
+        let altitudes = orbit_approach(DEFAULT_AUTOPILOT_PROPORTIONAL_GAIN, 300);
+        assert!(max(&altitudes) > GOAL_ALTITUDE + 0.01);
+    }
+
+    #[test]
+    fn lower_gain_approaches_slower_without_overshooting() {
+        let default = orbit_approach(DEFAULT_AUTOPILOT_PROPORTIONAL_GAIN, 300);
+        let lower = orbit_approach(0.1, 300);
+        assert!(max(&lower) < GOAL_ALTITUDE);
+        assert!(lower[TICKS_PER_SEC as usize] > START_ALTITUDE);
+        assert!(lower[TICKS_PER_SEC as usize] < default[TICKS_PER_SEC as usize]);
+    }
+
+    fn status(state: &State, ship: EntityKey) -> AutopilotStatus {
+        *state.component::<Ship>(ship).unwrap().autopilot.status
+    }
+
+    #[test]
+    fn status_follows_scheme_changes() {
+        let (mut state, ship) = flee_setup(Point3::new(3.0, 4.0, 0.0), 20.0);
+        assert_eq!(status(&state, ship), AutopilotStatus::Off);
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        assert_eq!(status(&state, ship), AutopilotStatus::Fleeing);
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .autopilot
+            .distance
+            .set(Some(4.0));
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        assert_eq!(status(&state, ship), AutopilotStatus::Safe);
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .autopilot
+            .scheme
+            .set(AutopilotScheme::Off);
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        assert_eq!(status(&state, ship), AutopilotStatus::Off);
+    }
+
+    #[test]
+    fn status_is_off_after_failure() {
+        let (mut state, ship) = flee_setup(Point3::new(3.0, 4.0, 0.0), 20.0);
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .autopilot
+            .target
+            .set(EntityKey::null());
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        assert_eq!(status(&state, ship), AutopilotStatus::Off);
+    }
+
+    #[test]
+    fn destroying_orbit_target_switches_to_manual() {
+        let (mut state, ship) = orbit_setup(DEFAULT_AUTOPILOT_PROPORTIONAL_GAIN);
+        let target = state.create_entity().unwrap();
+        Body::new()
+            .with_position(Point3::new(-20.0, 0.0, 0.0))
+            .install(&mut state, target);
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .autopilot
+            .target
+            .set(target);
+        physics_tick(&mut state, TICK_TIME, &GameConfig::default());
+        assert_ne!(
+            *state.component::<Ship>(ship).unwrap().acceleration,
+            Vector3::zero()
+        );
+        state.destroy_entity(target).unwrap();
+        physics_tick(&mut state, TICK_TIME, &GameConfig::default());
+        let ship = state.component::<Ship>(ship).unwrap();
+        assert_eq!(*ship.acceleration, Vector3::zero());
+        assert_eq!(*ship.autopilot.scheme, AutopilotScheme::Off);
+        assert_eq!(*ship.autopilot.status, AutopilotStatus::Off);
+        assert!(ship.autopilot.target.is_null());
+    }
+
+    #[test]
+    fn destroying_flee_target_switches_to_manual() {
+        let (mut state, ship) = flee_setup(Point3::new(3.0, 4.0, 0.0), 20.0);
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        let target = *state.component::<Ship>(ship).unwrap().autopilot.target;
+        state.destroy_entity(target).unwrap();
+        run_autopilot(&mut state, TICK_TIME, EPSILON);
+        let ship = state.component::<Ship>(ship).unwrap();
+        assert_eq!(*ship.acceleration, Vector3::zero());
+        assert_eq!(*ship.autopilot.scheme, AutopilotScheme::Off);
+    }
+
+    #[test]
+    fn status_is_matched_once_orbit_converges() {
+        let (mut state, ship) = orbit_setup(DEFAULT_AUTOPILOT_PROPORTIONAL_GAIN);
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .autopilot
+            .tolerance
+            .set(0.001);
+        physics_tick(&mut state, TICK_TIME, &GameConfig::default());
+        assert_eq!(status(&state, ship), AutopilotStatus::Approaching);
+        for _ in 0..(TICKS_PER_SEC * 40) {
+            physics_tick(&mut state, TICK_TIME, &GameConfig::default());
+        }
+        assert_eq!(status(&state, ship), AutopilotStatus::Matched);
+    }
+}
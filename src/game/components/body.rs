@@ -12,6 +12,13 @@ pub enum BodyClass {
     Ship,
 }
 
+impl PropertyEnum for BodyClass {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (BodyClass::Celestial, "celestial"),
+        (BodyClass::Ship, "ship"),
+    ];
+}
+
 /// Collision shape
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Shape {
@@ -20,6 +27,21 @@ pub enum Shape {
 }
 
 impl Shape {
+    /// A radius of 0 is a point, a positive radius is a sphere. Negative, NaN and infinite radii
+    /// are rejected.
+    pub fn from_radius(radius: f64) -> RequestResult<Self> {
+        if radius == 0.0 {
+            Ok(Shape::Point)
+        } else if radius > 0.0 && radius.is_finite() {
+            Ok(Shape::Sphere { radius })
+        } else {
+            Err(BadRequest(format!(
+                "{} is an invalid radius (must be finite and >= 0)",
+                radius
+            )))
+        }
+    }
+
     pub fn radius(&self) -> f64 {
         match self {
             Shape::Point => 0.0,
@@ -58,6 +80,10 @@ pub struct Body {
     pub gravity_parent: Element<EntityKey>,
     /// The interface the physics system uses to talk to the controller of this object
     pub collision_handler: Box<dyn CollisionHandler>,
+    /// Fired with the other body when the two pass close to each other without colliding
+    pub proximity: Signal<EntityKey>,
+    /// If the body is skipped by gravity, acceleration and motion (it still pulls on others)
+    pub frozen: Element<bool>,
 }
 
 impl Default for Body {
@@ -72,6 +98,8 @@ impl Default for Body {
             name: Element::new(None),
             gravity_parent: Element::new(EntityKey::null()),
             collision_handler: Box::new(()),
+            proximity: Signal::new(),
+            frozen: Element::new(false),
         }
     }
 }
@@ -122,42 +150,98 @@ impl Body {
         self
     }
 
+    /// Moves the body instantly, keeping its velocity unless a new one is given. Both are set
+    /// before returning, so they're never seen out of sync by physics or clients.
+    pub fn teleport(&mut self, position: Point3<f64>, velocity: Option<Vector3<f64>>) {
+        self.position.set(position);
+        if let Some(velocity) = velocity {
+            self.velocity.set(velocity);
+        }
+    }
+
     /// Attaches the body to the given entty, and adds a gravity body if the mass is at least
     /// GRAVITY_BODY_THRESH
-    pub fn install(self, state: &mut State, entity: EntityKey) {
+    pub fn install(mut self, state: &mut State, entity: EntityKey) {
         if *self.mass >= GRAVITY_BODY_THRESH {
             state.install_component(entity, GravityBody);
         }
+        self.proximity
+            .conduit(&state.notif_queue)
+            .install_signal(state, entity, "proximity");
         state.install_component(entity, self);
 
-        ROConduit::new(move |state| Ok(&state.component::<Body>(entity)?.class))
-            .map_output(|class| {
-                Ok(match class {
-                    BodyClass::Celestial => "celestial".to_string(),
-                    BodyClass::Ship => "ship".to_string(),
-                })
-            })
-            .install_property(state, entity, "class");
+        class_conduit(entity).install_property(state, entity, "class");
+        // Same as class, for clients that expect the more explicit name
+        class_conduit(entity).install_property(state, entity, "body_class");
 
         RWConduit::new(
             move |state| Ok(&state.component::<Body>(entity)?.position),
             move |state, value| Ok(state.component_mut::<Body>(entity)?.position.set(value)),
         )
-        .install_property(state, entity, "position");
+        .with_unit("km")
+        .install_framed_property(state, entity, "position", move |frame| {
+            CoRotatingConduit::new(entity, frame, CoRotatingFrame::position)
+        });
 
         RWConduit::new(
             move |state| Ok(&state.component::<Body>(entity)?.velocity),
             move |state, value| Ok(state.component_mut::<Body>(entity)?.velocity.set(value)),
         )
-        .install_property(state, entity, "velocity");
+        .with_unit("km/s")
+        .install_framed_property(state, entity, "velocity", move |frame| {
+            CoRotatingConduit::new(entity, frame, CoRotatingFrame::velocity)
+        });
+
+        ActionConduit::new(move |state, (position, velocity)| {
+            state
+                .component_mut::<Body>(entity)?
+                .teleport(position, velocity);
+            Ok(())
+        })
+        .with_params(&[
+            ("position", ParamType::Vector),
+            ("velocity", ParamType::Vector),
+        ])
+        .with_access(move |connection, state| can_teleport(state, connection, entity))
+        .install_action(state, entity, "teleport");
+
+        ComputedConduit::new(
+            move |state| Ok(state.component::<Body>(entity)?.velocity.magnitude()),
+            move |state| {
+                Ok(vec![
+                    &state.component::<Body>(entity)?.velocity as &dyn Subscribable,
+                ])
+            },
+        )
+        .with_unit("km/s")
+        .install_property(state, entity, "speed");
 
         RWConduit::new(
             move |state| Ok(&state.component::<Body>(entity)?.mass),
             move |state, value| Ok(state.component_mut::<Body>(entity)?.mass.set(value)),
         )
+        .with_unit("mt")
         .install_property(state, entity, "mass");
 
         OrbitConduit::new(entity).install_property(state, entity, "orbit");
+        // Same as orbit, but a map with named fields and the apsis distances
+        OrbitConduit::new(entity)
+            .map_output(|orbit| Ok(orbit.to_map()))
+            .install_property(state, entity, "orbit_map");
+
+        RelativeConduit::new(entity, |body, parent| *body.position - *parent.position)
+            .with_unit("km")
+            .install_property(state, entity, "relative_position");
+
+        RelativeConduit::new(entity, |body, parent| *body.velocity - *parent.velocity)
+            .with_unit("km/s")
+            .install_property(state, entity, "relative_velocity");
+
+        RWConduit::new(
+            move |state| Ok(&state.component::<Body>(entity)?.frozen),
+            move |state, value| Ok(state.component_mut::<Body>(entity)?.frozen.set(value)),
+        )
+        .install_property(state, entity, "frozen");
 
         RWConduit::new(
             move |state| Ok(&state.component::<Body>(entity)?.color),
@@ -174,24 +258,63 @@ impl Body {
         ROConduit::new(move |state| Ok(&state.component::<Body>(entity)?.gravity_parent))
             .install_property(state, entity, "grav_parent");
 
-        RWConduit::new(
-            move |state| Ok(&state.component::<Body>(entity)?.shape),
-            move |state, value| Ok(state.component_mut::<Body>(entity)?.shape.set(value)),
+        // Parent, grandparent and so on up to the root well, so clients can build the system tree
+        // without walking grav_parent themselves
+        ComputedConduit::new(
+            move |state| gravity_ancestors(state, entity),
+            move |state| {
+                let mut inputs =
+                    vec![&state.component::<Body>(entity)?.gravity_parent as &dyn Subscribable];
+                for ancestor in gravity_ancestors(state, entity)? {
+                    inputs.push(&state.component::<Body>(ancestor)?.gravity_parent);
+                }
+                Ok(inputs)
+            },
         )
-        .map_output(|shape| Ok(shape.radius()))
-        .map_input(|radius| {
-            if radius == 0.0 {
-                Ok(Shape::Point)
-            } else if radius > 0.0 {
-                Ok(Shape::Sphere { radius })
-            } else {
-                Err(BadRequest("size must be >= 0".into()))
-            }
-        })
-        .install_property(state, entity, "size");
+        .install_property(state, entity, "grav_hierarchy");
+
+        radius_conduit(entity)
+            .with_unit("km")
+            .install_property(state, entity, "radius");
+        // Old name for radius, kept for existing clients
+        radius_conduit(entity)
+            .with_unit("km")
+            .install_property(state, entity, "size");
     }
 }
 
+/// Only admins and the connection controlling a ship can move a body directly
+fn can_teleport(state: &State, connection: ConnectionKey, entity: EntityKey) -> bool {
+    state.is_admin(connection)
+        || matches!(state.component::<Ship>(entity), Ok(ship) if *ship.controller == connection)
+}
+
+/// The chain of gravity parents from the body's parent to the root well. Stops early if the chain
+/// loops, which can happen for a tick while parents are being updated.
+fn gravity_ancestors(state: &State, entity: EntityKey) -> RequestResult<Vec<EntityKey>> {
+    let mut ancestors = Vec::new();
+    let mut parent = *state.component::<Body>(entity)?.gravity_parent;
+    while !parent.is_null() && parent != entity && !ancestors.contains(&parent) {
+        ancestors.push(parent);
+        parent = *state.component::<Body>(parent)?.gravity_parent;
+    }
+    Ok(ancestors)
+}
+
+fn class_conduit(entity: EntityKey) -> impl Conduit<String, ReadOnlyPropSetType> {
+    ROConduit::new(move |state| Ok(&state.component::<Body>(entity)?.class))
+        .map_output(BodyClass::to_property)
+}
+
+fn radius_conduit(entity: EntityKey) -> impl Conduit<f64, f64> {
+    RWConduit::new(
+        move |state| Ok(&state.component::<Body>(entity)?.shape),
+        move |state, value| Ok(state.component_mut::<Body>(entity)?.shape.set(value)),
+    )
+    .map_output(|shape| Ok(shape.radius()))
+    .map_input(Shape::from_radius)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Collision {
     /// The time from now until the collision will occur
@@ -213,3 +336,256 @@ pub trait CollisionHandler {
 impl CollisionHandler for () {
     fn collision(&self, _state: &State, _collision: &Collision) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CollisionFlag(Arc<AtomicBool>);
+
+    impl CollisionHandler for CollisionFlag {
+        fn collision(&self, _state: &State, _collision: &Collision) {
+            self.0.store(true, SeqCst);
+        }
+    }
+
+    /// Sets up a point moving 1km towards a body 3km away, returns the state, the stationary body
+    /// and a flag that is set when the point collides
+    fn approach_setup() -> (State, EntityKey, Arc<AtomicBool>) {
+        let mut state = State::new();
+        let collided = Arc::new(AtomicBool::new(false));
+        let point = state.create_entity().unwrap();
+        Body::new()
+            .with_velocity(Vector3::new(1.0, 0.0, 0.0))
+            .with_collision_handler(Box::new(CollisionFlag(collided.clone())))
+            .install(&mut state, point);
+        let target = state.create_entity().unwrap();
+        Body::new()
+            .with_position(Point3::new(3.0, 0.0, 0.0))
+            .with_sphere_shape(0.5)
+            .install(&mut state, target);
+        (state, target, collided)
+    }
+
+    fn set_radius(state: &mut State, entity: EntityKey, radius: f64) -> RequestResult<()> {
+        state.set_property(ConnectionKey::null(), entity, "radius", radius.into())
+    }
+
+    #[test]
+    fn body_class_distinguishes_ships_from_celestials() {
+        let mut state = State::new();
+        let ship = create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).unwrap();
+        let planet = state.create_entity().unwrap();
+        Body::new()
+            .with_class(BodyClass::Celestial)
+            .with_mass(6e+21)
+            .install(&mut state, planet);
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), ship, "body_class"),
+            Ok(Value::Text("ship".to_string()))
+        );
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), planet, "body_class"),
+            Ok(Value::Text("celestial".to_string()))
+        );
+    }
+
+    #[test]
+    fn relative_properties_are_against_gravity_parent() {
+        let mut state = State::new();
+        let planet = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(6e+21)
+            .with_position(Point3::new(1.0e+5, 0.0, 0.0))
+            .with_velocity(Vector3::new(0.0, 2.0, 0.0))
+            .install(&mut state, planet);
+        let ship = create_ship(
+            &mut state,
+            Point3::new(1.1e+5, 500.0, 0.0),
+            Vector3::new(0.5, 9.0, 0.0),
+            EPSILON,
+        )
+        .unwrap();
+        apply_gravity(&mut state, 1.0, EPSILON);
+        let (ship_body, planet_body) = (
+            state.component::<Body>(ship).unwrap(),
+            state.component::<Body>(planet).unwrap(),
+        );
+        assert_eq!(*ship_body.gravity_parent, planet);
+        let position = *ship_body.position - *planet_body.position;
+        let velocity = *ship_body.velocity - *planet_body.velocity;
+        let get = |entity, name| state.get_property(ConnectionKey::null(), entity, name);
+        assert_eq!(get(ship, "relative_position"), Ok(Value::Vector(position)));
+        assert_eq!(get(ship, "relative_velocity"), Ok(Value::Vector(velocity)));
+        assert_eq!(get(planet, "relative_position"), Ok(Value::Null));
+        assert_eq!(get(planet, "relative_velocity"), Ok(Value::Null));
+    }
+
+    #[test]
+    fn moon_hierarchy_lists_planet_then_star() {
+        let mut state = State::new();
+        let star = state.create_entity().unwrap();
+        Body::new().with_mass(6e+23).install(&mut state, star);
+        let planet = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(6e+21)
+            .with_position(Point3::new(-2.0e+6, 27.5, 154.0))
+            .with_velocity(Vector3::new(0.0, 1.0, 0.0))
+            .install(&mut state, planet);
+        let moon = state.create_entity().unwrap();
+        Body::new()
+            .with_position(Point3::new(-2.0e+6 + 100.0, 27.5, 154.0))
+            .install(&mut state, moon);
+        apply_gravity(&mut state, 1.0, EPSILON);
+        let get = |entity| state.get_property(ConnectionKey::null(), entity, "grav_hierarchy");
+        assert_eq!(get(moon), Ok(vec![planet, star].into()));
+        assert_eq!(get(planet), Ok(vec![star].into()));
+        assert_eq!(get(star), Ok(Value::Array(Vec::new())));
+    }
+
+    #[test]
+    fn radius_property_reads_shape() {
+        let (state, target, _) = approach_setup();
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), target, "radius"),
+            Ok(Value::Scalar(0.5))
+        );
+    }
+
+    #[test]
+    fn setting_radius_changes_collisions() {
+        let (mut state, target, collided) = approach_setup();
+        apply_collisions(&state, 1.0, 1, EPSILON, CollisionOrder::Discovery);
+        assert!(!collided.load(SeqCst));
+        set_radius(&mut state, target, 2.5).unwrap();
+        assert_eq!(
+            *state.component::<Body>(target).unwrap().shape,
+            Shape::Sphere { radius: 2.5 }
+        );
+        apply_collisions(&state, 1.0, 1, EPSILON, CollisionOrder::Discovery);
+        assert!(collided.load(SeqCst));
+    }
+
+    #[test]
+    fn zero_radius_is_point() {
+        let (mut state, target, _) = approach_setup();
+        set_radius(&mut state, target, 0.0).unwrap();
+        assert_eq!(
+            *state.component::<Body>(target).unwrap().shape,
+            Shape::Point
+        );
+    }
+
+    #[test]
+    fn speed_is_computed_from_velocity() {
+        let (state, target, _) = approach_setup();
+        let point = state
+            .components_iter::<Body>()
+            .map(|(e, _)| e)
+            .find(|e| *e != target)
+            .unwrap();
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), point, "speed"),
+            Ok(Value::Scalar(1.0))
+        );
+    }
+
+    #[test]
+    fn speed_subscribers_notified_only_when_velocity_changes() {
+        let (mut state, target, _) = approach_setup();
+        let subscription = state
+            .subscribe(ConnectionKey::null(), target, "speed")
+            .unwrap();
+        state
+            .component_mut::<Body>(target)
+            .unwrap()
+            .position
+            .set(Point3::new(4.0, 0.0, 0.0));
+        assert_eq!(state.notif_queue.len(), 0);
+        state
+            .component_mut::<Body>(target)
+            .unwrap()
+            .velocity
+            .set(Vector3::new(0.0, 3.0, 4.0));
+        assert_eq!(state.notif_queue.len(), 1);
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), target, "speed"),
+            Ok(Value::Scalar(5.0))
+        );
+        state.unsubscribe(subscription).unwrap();
+    }
+
+    fn teleport(state: &mut State, entity: EntityKey, value: Value) -> RequestResult<()> {
+        state.fire_action(ConnectionKey::null(), entity, "teleport", value)
+    }
+
+    #[test]
+    fn teleport_sets_position_and_velocity() {
+        let (mut state, target, _) = approach_setup();
+        let position = Point3::new(10.0, 0.0, 0.0);
+        let velocity = Vector3::new(0.0, 2.0, 0.0);
+        teleport(&mut state, target, (position, Some(velocity)).into()).unwrap();
+        let body = state.component::<Body>(target).unwrap();
+        assert_eq!(*body.position, position);
+        assert_eq!(*body.velocity, velocity);
+        apply_motion(&mut state, 1.0);
+        assert_eq!(
+            *state.component::<Body>(target).unwrap().position,
+            Point3::new(10.0, 2.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn teleport_without_velocity_keeps_velocity() {
+        let (mut state, target, _) = approach_setup();
+        state
+            .component_mut::<Body>(target)
+            .unwrap()
+            .velocity
+            .set(Vector3::new(1.0, 0.0, 0.0));
+        let position = Point3::new(0.0, 5.0, 0.0);
+        teleport(&mut state, target, (position, None::<Vector3<f64>>).into()).unwrap();
+        apply_motion(&mut state, 1.0);
+        let body = state.component::<Body>(target).unwrap();
+        assert_eq!(*body.position, Point3::new(1.0, 5.0, 0.0));
+        assert_eq!(*body.velocity, Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn only_admins_and_controller_can_teleport() {
+        let mut state = State::new();
+        let ship = create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).unwrap();
+        let controller = ConnectionKey::from_id(1);
+        let other = ConnectionKey::from_id(2);
+        let value: Value = (Point3::new(1.0, 0.0, 0.0), None::<Vector3<f64>>).into();
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .controller
+            .set(controller);
+        assert_eq!(
+            state.fire_action(other, ship, "teleport", value.clone()),
+            Err(PermissionDenied(ship, "teleport".to_string()))
+        );
+        state
+            .fire_action(controller, ship, "teleport", value.clone())
+            .unwrap();
+        state.grant_admin(other);
+        state.fire_action(other, ship, "teleport", value).unwrap();
+    }
+
+    #[test]
+    fn invalid_radius_is_rejected() {
+        let (mut state, target, _) = approach_setup();
+        for radius in &[-1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                set_radius(&mut state, target, *radius),
+                Err(BadRequest(_))
+            ));
+        }
+        assert_eq!(
+            *state.component::<Body>(target).unwrap().shape,
+            Shape::Sphere { radius: 0.5 }
+        );
+    }
+}
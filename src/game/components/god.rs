@@ -1,41 +1,141 @@
 use super::*;
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name, unit (or null), kind and parameter names and types (or null) of a member
+type MemberInfo = (
+    String,
+    Option<String>,
+    String,
+    Option<Vec<(String, String)>>,
+);
+
 pub struct God {
     pub time: Element<f64>,
+    /// Sum of the momentum of all bodies, updated each tick
+    pub total_momentum: Element<Vector3<f64>>,
+    /// Sum of the kinetic energy of all bodies, updated each tick
+    pub total_kinetic_energy: Element<f64>,
+    /// Sum of the gravitational potential energy between each pair of gravity wells, updated each
+    /// tick
+    pub total_potential_energy: Element<f64>,
     ship_created: Signal<EntityKey>,
+    /// Ships that can be claimed, only used if the lobby is enabled
+    lobby: Element<Vec<EntityKey>>,
+    /// Client supplied token, game time and wall clock time (seconds since the unix epoch)
+    time_sync: Signal<(Value, f64, f64)>,
+    /// Only used if debug actions are enabled
+    component_types: Signal<(EntityKey, Vec<String>)>,
+    /// Only used if debug actions are enabled
+    members: Signal<(EntityKey, Vec<MemberInfo>)>,
+    /// Entity, member name and how many client subscriptions it has. Only used if debug actions
+    /// are enabled.
+    subscriber_count: Signal<(EntityKey, String, u64)>,
     max_connections: Element<u64>,
     current_connections: Element<u64>,
+    /// Only used if debug actions are enabled. Entities removed from object maps by the most
+    /// recent reconcile_object_maps action.
+    purged_object_count: Element<u64>,
+    /// Keys and values of the public server config options, only admins can see it
+    config: Element<Vec<(String, String)>>,
+    /// Server version, scenario and which optional features are enabled, set on install
+    server_info: Element<HashMap<String, Value>>,
 }
 
 impl Default for God {
     fn default() -> Self {
         Self {
             time: Element::new(0.0),
+            total_momentum: Element::new(Vector3::zero()),
+            total_kinetic_energy: Element::new(0.0),
+            total_potential_energy: Element::new(0.0),
             ship_created: Signal::new(),
+            lobby: Element::new(Vec::new()),
+            time_sync: Signal::new(),
+            component_types: Signal::new(),
+            members: Signal::new(),
+            subscriber_count: Signal::new(),
             max_connections: Element::new(0),
             current_connections: Element::new(0),
+            purged_object_count: Element::new(0),
+            config: Element::new(Vec::new()),
+            server_info: Element::new(HashMap::new()),
         }
     }
 }
 
 impl God {
     /// Installs the god as the root entity, must only be called once per state
-    pub fn install(mut self, state: &mut State) {
+    pub fn install(mut self, state: &mut State, config: &GameConfig) {
         let entity = state.root_entity();
 
+        self.config.set(config.public_config.clone());
+        ROConduit::new(move |state| Ok(&state.component::<God>(entity)?.config))
+            .with_access(admin_access(config.debug_actions))
+            .install_property(state, entity, "config");
+
+        self.server_info.set(server_info(config));
+        ROConduit::new(move |state| Ok(&state.component::<God>(entity)?.server_info))
+            .install_property(state, entity, "server_info");
+
+        if config.debug_actions {
+            self.install_debug_actions(state, config);
+        }
+
         self.ship_created
             .conduit(&state.notif_queue)
             .install_signal(state, entity, "ship_created");
-        ActionConduit::new(move |state, (position, velocity)| {
-            let ship = create_ship(state, position, velocity);
-            state.component_mut::<God>(entity)?.ship_created.fire(ship);
+        let spawn_policy = config.spawn_policy;
+        let lobby = config.lobby;
+        let epsilon = config.epsilon;
+        ActionConduit::new(move |state, args| {
+            let (position, velocity) = create_ship_args(args)?;
+            let position = spawn_position(state, position, SHIP_RADIUS, spawn_policy, epsilon)?;
+            let ship = create_ship(state, position, velocity, epsilon)?;
+            let god = state.component_mut::<God>(entity)?;
+            god.ship_created.fire(ship);
+            if lobby {
+                god.lobby.get_mut().push(ship);
+            }
             Ok(())
         })
+        .with_params(&[
+            ("position", ParamType::Vector),
+            ("velocity", ParamType::Vector),
+        ])
         .install_action(state, entity, "create_ship");
 
         ROConduit::new(move |state| Ok(&state.component::<God>(entity)?.time))
+            .with_unit("s")
             .install_property(state, entity, "time");
 
+        self.time_sync
+            .conduit(&state.notif_queue)
+            .install_signal(state, entity, "time_sync");
+        ActionConduit::new(move |state, token| {
+            let game_time = state.time();
+            let wall_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| InternalError(format!("system clock is before epoch: {}", e)))?
+                .as_secs_f64();
+            state
+                .component_mut::<God>(entity)?
+                .time_sync
+                .fire((token, game_time, wall_time));
+            Ok(())
+        })
+        .with_params(&[("token", ParamType::Any)])
+        .install_action(state, entity, "sync_time");
+
+        ROConduit::new(move |state| Ok(&state.component::<God>(entity)?.total_momentum))
+            .install_property(state, entity, "total_momentum");
+
+        ROConduit::new(move |state| Ok(&state.component::<God>(entity)?.total_kinetic_energy))
+            .install_property(state, entity, "total_kinetic_energy");
+
+        ROConduit::new(move |state| Ok(&state.component::<God>(entity)?.total_potential_energy))
+            .install_property(state, entity, "total_potential_energy");
+
         RWConduit::new(
             move |state| Ok(&state.component::<God>(entity)?.max_connections),
             move |state, value| {
@@ -58,8 +158,669 @@ impl God {
         )
         .install_property(state, entity, "conn_count");
 
-        ComponentListConduit::<Body>::new().install_property(state, entity, "bodies");
+        if config.debug_actions || !config.admin_token.is_empty() {
+            Self::install_admin_actions(state, config);
+        }
+
+        ComponentListConduit::<Body>::new().install_delta_property(state, entity, "bodies");
+
+        if config.lobby {
+            EntityListConduit::new(move |state| Ok(&state.component::<God>(entity)?.lobby))
+                .install_delta_property(state, entity, "lobby");
+            state.install_connection_action(entity, "claim", move |connection| {
+                ActionConduit::new(move |state, ship| claim_ship(state, connection, ship))
+                    .with_params(&[("ship", ParamType::Object)])
+                    .map_into::<ActionsDontProduceOutputSilly, Value>()
+            });
+        }
 
         state.install_component(entity, self);
     }
+
+    /// Installs actions for managing connections. While debug actions are enabled every client can
+    /// use them, otherwise only clients that have become admins can.
+    fn install_admin_actions(state: &mut State, config: &GameConfig) {
+        let entity = state.root_entity();
+        let admin_access = admin_access(config.debug_actions);
+
+        state.install_connection_action(entity, "list_connections", move |connection| {
+            ActionConduit::new(move |state, ()| {
+                state.request_connection_list(connection);
+                Ok(())
+            })
+            .with_params(&[])
+            .with_access(admin_access)
+            .map_into::<ActionsDontProduceOutputSilly, Value>()
+        });
+
+        ActionConduit::new(|state, id| {
+            state.disconnect(ConnectionKey::from_id(id));
+            Ok(())
+        })
+        .with_params(&[("id", ParamType::Integer)])
+        .with_access(admin_access)
+        .install_action(state, entity, "disconnect_connection");
+
+        if !config.admin_token.is_empty() {
+            let admin_token = config.admin_token.clone();
+            state.install_connection_action(entity, "become_admin", move |connection| {
+                let admin_token = admin_token.clone();
+                ActionConduit::new(move |state, token: String| {
+                    if !same_secret(&token, &admin_token) {
+                        warn!("{:?} sent the wrong admin token", connection);
+                        return Err(BadRequest("wrong admin token".into()));
+                    }
+                    state.grant_admin(connection);
+                    Ok(())
+                })
+                .with_params(&[("token", ParamType::Text)])
+                .map_into::<ActionsDontProduceOutputSilly, Value>()
+            });
+        }
+    }
+
+    fn install_debug_actions(&mut self, state: &mut State, config: &GameConfig) {
+        let entity = state.root_entity();
+
+        let protect_primary_body = config.protect_primary_body;
+        ActionConduit::new(move |state, target| {
+            state.component::<Body>(target)?;
+            if protect_primary_body && primary_body(state) == Some(target) {
+                return Err(BadRequest("can not destroy the primary body".into()));
+            }
+            state
+                .destroy_entity(target)
+                .map_err(|e| InternalError(e.to_string()))
+        })
+        .with_params(&[("body", ParamType::Object)])
+        .install_action(state, entity, "destroy_body");
+
+        self.component_types
+            .conduit(&state.notif_queue)
+            .install_signal(state, entity, "component_types");
+        ActionConduit::new(move |state, target| {
+            let names = state.component_type_names(target)?.to_vec();
+            state
+                .component_mut::<God>(entity)?
+                .component_types
+                .fire((target, names));
+            Ok(())
+        })
+        .with_params(&[("target", ParamType::Object)])
+        .install_action(state, entity, "get_component_types");
+
+        self.members
+            .conduit(&state.notif_queue)
+            .install_signal(state, entity, "members");
+        ActionConduit::new(move |state, target| {
+            let members = state
+                .members(target)?
+                .into_iter()
+                .map(|(name, metadata)| {
+                    let params = metadata
+                        .params
+                        .map(|params| {
+                            params
+                                .iter()
+                                .map(|(name, param_type)| {
+                                    Ok((name.to_string(), param_type.to_property()?))
+                                })
+                                .collect::<RequestResult<Vec<_>>>()
+                        })
+                        .transpose()?;
+                    Ok((
+                        name.to_string(),
+                        metadata.unit.map(str::to_string),
+                        metadata.kind.to_property()?,
+                        params,
+                    ))
+                })
+                .collect::<RequestResult<Vec<_>>>()?;
+            state
+                .component_mut::<God>(entity)?
+                .members
+                .fire((target, members));
+            Ok(())
+        })
+        .with_params(&[("target", ParamType::Object)])
+        .install_action(state, entity, "get_members");
+
+        self.subscriber_count
+            .conduit(&state.notif_queue)
+            .install_signal(state, entity, "subscriber_count");
+        ActionConduit::new(move |state, (target, name): (EntityKey, String)| {
+            let count = state.subscriber_count(target, &name);
+            state
+                .component_mut::<God>(entity)?
+                .subscriber_count
+                .fire((target, name, count));
+            Ok(())
+        })
+        .with_params(&[("target", ParamType::Object), ("property", ParamType::Text)])
+        .install_action(state, entity, "get_subscriber_count");
+
+        RWConduit::new(
+            move |state| Ok(&state.component::<God>(entity)?.purged_object_count),
+            move |state, value| {
+                Ok(state
+                    .component_mut::<God>(entity)?
+                    .purged_object_count
+                    .set(value))
+            },
+        )
+        .install_property(state, entity, "purged_object_count");
+        ActionConduit::new(|state, ()| {
+            state.reconcile_object_maps();
+            Ok(())
+        })
+        .with_params(&[])
+        .install_action(state, entity, "reconcile_object_maps");
+    }
+}
+
+fn server_info(config: &GameConfig) -> HashMap<String, Value> {
+    let mut info = HashMap::new();
+    info.insert(
+        "version".to_string(),
+        env!("CARGO_PKG_VERSION").to_string().into(),
+    );
+    info.insert(
+        "scenario".to_string(),
+        config.scenario.to_property().unwrap_or_default().into(),
+    );
+    info.insert("debug_actions".to_string(), config.debug_actions.into());
+    info.insert("lobby".to_string(), config.lobby.into());
+    info
+}
+
+/// Access predicate for members only admins can use. While debug actions are enabled every client
+/// counts as an admin.
+fn admin_access(
+    debug_actions: bool,
+) -> impl Fn(ConnectionKey, &State) -> bool + Send + Sync + Copy {
+    move |connection, state| debug_actions || state.is_admin(connection)
+}
+
+/// Compares without returning early, so the time taken doesn't leak how much of a secret matched
+fn same_secret(given: &str, secret: &str) -> bool {
+    given.len() == secret.len()
+        && given
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// create_ship takes a [position, velocity] array, or a map with a position and optional velocity
+fn create_ship_args(args: Value) -> RequestResult<(Point3<f64>, Vector3<f64>)> {
+    if !matches!(args, Value::Map(_)) {
+        return args.into();
+    }
+    let position: RequestResult<Point3<f64>> = args
+        .get_field("position")
+        .cloned()
+        .ok_or_else(|| BadRequest("create_ship requires a position".into()))?
+        .into();
+    Ok((
+        position?,
+        args.field_or::<Vector3<f64>>("velocity", Vector3::zero())?,
+    ))
+}
+
+/// Takes the ship out of the lobby and gives control of it to the connection
+fn claim_ship(state: &mut State, connection: ConnectionKey, ship: EntityKey) -> RequestResult<()> {
+    if connection.is_null() {
+        return Err(BadRequest("only clients can claim ships".into()));
+    }
+    let root = state.root_entity();
+    if !state.component::<Ship>(ship)?.controller.is_null() {
+        return Err(BadRequest(format!("{:?} has already been claimed", ship)));
+    }
+    let lobby = &mut state.component_mut::<God>(root)?.lobby;
+    let index = lobby
+        .iter()
+        .position(|available| *available == ship)
+        .ok_or_else(|| BadRequest(format!("{:?} is not in the lobby", ship)))?;
+    lobby.get_mut().remove(index);
+    state
+        .component_mut::<Ship>(ship)?
+        .controller
+        .set(connection);
+    Ok(())
+}
+
+/// Drops destroyed ships from the lobby, and puts ships claimed by connections that have since
+/// closed back in it
+pub fn update_lobby(state: &mut State) {
+    let closed = state.take_closed_connections();
+    let released: Vec<EntityKey> = if closed.is_empty() {
+        Vec::new()
+    } else {
+        state
+            .components_iter::<Ship>()
+            .filter(|(_, ship)| closed.contains(&ship.controller))
+            .map(|(entity, _)| entity)
+            .collect()
+    };
+    for &ship in &released {
+        if let Ok(ship) = state.component_mut::<Ship>(ship) {
+            ship.controller.set(ConnectionKey::null());
+        }
+    }
+    let root = state.root_entity();
+    let god = match state.component::<God>(root) {
+        Ok(god) => god,
+        Err(_) => return,
+    };
+    if released.is_empty() && god.lobby.iter().all(|&ship| state.entity_exists(ship)) {
+        return;
+    }
+    let lobby: Vec<EntityKey> = god
+        .lobby
+        .iter()
+        .copied()
+        .filter(|&ship| state.entity_exists(ship))
+        .chain(released)
+        .collect();
+    if let Ok(god) = state.component_mut::<God>(root) {
+        god.lobby.set(lobby);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_sync_reports_current_game_time() {
+        let mut state = State::new();
+        God::default().install(&mut state, &GameConfig::default());
+        let root = state.root_entity();
+        let notif_queue = state.notif_queue.clone();
+        let conduit = state
+            .component_mut::<God>(root)
+            .unwrap()
+            .time_sync
+            .conduit(&notif_queue);
+        state.increment_physics(0.25);
+        state.increment_physics(0.25);
+        state
+            .fire_action(ConnectionKey::null(), root, "sync_time", Value::Integer(7))
+            .unwrap();
+        let events = conduit.output(&state).unwrap();
+        assert_eq!(events.len(), 1);
+        let (token, game_time, wall_time) = events[0].clone();
+        assert_eq!(token, Value::Integer(7));
+        assert_eq!(game_time, state.time());
+        assert!(wall_time > 0.0);
+    }
+
+    #[test]
+    fn introspection_reports_property_units() {
+        let mut state = State::new();
+        let config = GameConfig {
+            debug_actions: true,
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let root = state.root_entity();
+        let target = state.create_entity().unwrap();
+        RWConduit::new(
+            move |state| Ok(&state.component::<Body>(target)?.position),
+            move |state, value| Ok(state.component_mut::<Body>(target)?.position.set(value)),
+        )
+        .with_unit("km")
+        .install_property(&mut state, target, "distance");
+        let notif_queue = state.notif_queue.clone();
+        let conduit = state
+            .component_mut::<God>(root)
+            .unwrap()
+            .members
+            .conduit(&notif_queue);
+        state
+            .fire_action(ConnectionKey::null(), root, "get_members", target.into())
+            .unwrap();
+        let events = conduit.output(&state).unwrap();
+        assert_eq!(events.len(), 1);
+        let (entity, members) = events[0].clone();
+        assert_eq!(entity, target);
+        assert_eq!(
+            members,
+            vec![(
+                "distance".to_string(),
+                Some("km".to_string()),
+                "plain".to_string(),
+                None
+            )]
+        );
+    }
+
+    #[test]
+    fn introspection_reports_action_params_that_decode() {
+        let mut state = State::new();
+        let config = GameConfig {
+            debug_actions: true,
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let root = state.root_entity();
+        let notif_queue = state.notif_queue.clone();
+        let conduit = state
+            .component_mut::<God>(root)
+            .unwrap()
+            .members
+            .conduit(&notif_queue);
+        state
+            .fire_action(ConnectionKey::null(), root, "get_members", root.into())
+            .unwrap();
+        let (_, members) = conduit.output(&state).unwrap()[0].clone();
+        let params = |action: &str| {
+            members
+                .iter()
+                .find(|(name, _, _, _)| name == action)
+                .unwrap()
+                .3
+                .clone()
+                .unwrap()
+        };
+        let create_ship_params = params("create_ship");
+        assert_eq!(
+            create_ship_params,
+            vec![
+                ("position".to_string(), "vector".to_string()),
+                ("velocity".to_string(), "vector".to_string())
+            ]
+        );
+        // A value built from the schema is what the action decodes
+        let args: Vec<Value> = create_ship_params
+            .iter()
+            .map(|(_, param_type)| {
+                assert_eq!(param_type, "vector");
+                Vector3::new(1.0, 2.0, 3.0).into()
+            })
+            .collect();
+        assert_eq!(
+            state.fire_action(ConnectionKey::null(), root, "create_ship", args.into()),
+            Ok(())
+        );
+        assert!(state
+            .fire_action(
+                ConnectionKey::null(),
+                root,
+                "create_ship",
+                vec![Value::Scalar(1.0), Value::Scalar(2.0)].into()
+            )
+            .is_err());
+        assert_eq!(
+            params("get_members"),
+            vec![("target".to_string(), "object".to_string())]
+        );
+        assert!(params("reconcile_object_maps").is_empty());
+    }
+
+    #[test]
+    fn subscriber_count_follows_subscriptions() {
+        let mut state = State::new();
+        let config = GameConfig {
+            debug_actions: true,
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let root = state.root_entity();
+        let notif_queue = state.notif_queue.clone();
+        let conduit = state
+            .component_mut::<God>(root)
+            .unwrap()
+            .subscriber_count
+            .conduit(&notif_queue);
+        let count = |state: &mut State| {
+            let args: Vec<Value> = vec![root.into(), "time".to_string().into()];
+            state
+                .fire_action(
+                    ConnectionKey::null(),
+                    root,
+                    "get_subscriber_count",
+                    args.into(),
+                )
+                .unwrap();
+            let (entity, name, count) = conduit.output(state).unwrap().last().unwrap().clone();
+            assert_eq!((entity, name.as_str()), (root, "time"));
+            count
+        };
+        let connections: Vec<ConnectionKey> = mock_keys(2);
+        let subscriptions: Vec<Box<dyn Any>> = connections
+            .iter()
+            .map(|connection| state.subscribe(*connection, root, "time").unwrap())
+            .collect();
+        assert_eq!(count(&mut state), 2);
+        let mut subscriptions = subscriptions.into_iter();
+        state.unsubscribe(subscriptions.next().unwrap()).unwrap();
+        assert_eq!(count(&mut state), 1);
+        state.unsubscribe(subscriptions.next().unwrap()).unwrap();
+        assert_eq!(count(&mut state), 0);
+    }
+
+    #[test]
+    fn only_admins_can_manage_connections() {
+        let mut state = State::new();
+        let config = GameConfig {
+            admin_token: "hunter2".to_string(),
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let root = state.root_entity();
+        let client = ConnectionKey::from_id(1);
+        let victim = ConnectionKey::from_id(2);
+        assert_eq!(
+            state.fire_action(client, root, "disconnect_connection", victim.to_id().into()),
+            Err(PermissionDenied(root, "disconnect_connection".to_string()))
+        );
+        assert_eq!(
+            state.fire_action(client, root, "list_connections", Value::Null),
+            Err(PermissionDenied(root, "list_connections".to_string()))
+        );
+        assert!(state
+            .fire_action(client, root, "become_admin", Value::Text("hunter3".into()))
+            .is_err());
+        assert!(!state.is_admin(client));
+        state
+            .fire_action(client, root, "become_admin", Value::Text("hunter2".into()))
+            .unwrap();
+        state
+            .fire_action(client, root, "disconnect_connection", victim.to_id().into())
+            .unwrap();
+        assert_eq!(state.take_disconnect_requests(), vec![victim]);
+        state
+            .fire_action(client, root, "list_connections", Value::Null)
+            .unwrap();
+        assert_eq!(state.take_connection_list_requests(), vec![client]);
+        state.connection_closed(client);
+        assert!(!state.is_admin(client));
+    }
+
+    #[test]
+    fn only_admins_can_read_config() {
+        let mut state = State::new();
+        let config = GameConfig {
+            public_config: vec![("time_scale".to_string(), "1".to_string())],
+            admin_token: "hunter2".to_string(),
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let root = state.root_entity();
+        let client = ConnectionKey::from_id(1);
+        assert_eq!(
+            state.get_property(client, root, "config"),
+            Err(PermissionDenied(root, "config".to_string()))
+        );
+        state.grant_admin(client);
+        assert!(state.get_property(client, root, "config").is_ok());
+    }
+
+    #[test]
+    fn connection_actions_are_not_installed_without_admins_or_debug_actions() {
+        let mut state = State::new();
+        God::default().install(&mut state, &GameConfig::default());
+        let root = state.root_entity();
+        let client = ConnectionKey::from_id(1);
+        assert!(state
+            .fire_action(client, root, "list_connections", Value::Null)
+            .is_err());
+        assert!(state
+            .fire_action(client, root, "disconnect_connection", Value::Integer(2))
+            .is_err());
+    }
+
+    #[test]
+    fn protected_primary_body_can_not_be_destroyed() {
+        let mut state = State::new();
+        let config = GameConfig {
+            debug_actions: true,
+            protect_primary_body: true,
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let root = state.root_entity();
+        let sun = state.create_entity().unwrap();
+        Body::new().with_mass(1.0e+12).install(&mut state, sun);
+        let moon = state.create_entity().unwrap();
+        Body::new().with_mass(1.0e+9).install(&mut state, moon);
+        assert!(state
+            .fire_action(ConnectionKey::null(), root, "destroy_body", sun.into())
+            .is_err());
+        assert!(state.entity_exists(sun));
+        state
+            .fire_action(ConnectionKey::null(), root, "destroy_body", moon.into())
+            .unwrap();
+        assert!(!state.entity_exists(moon));
+    }
+
+    /// State with the lobby enabled and one ship in it
+    fn lobby_setup() -> (State, EntityKey) {
+        let mut state = State::new();
+        let config = GameConfig {
+            lobby: true,
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let root = state.root_entity();
+        let args = vec![
+            Value::Vector(Vector3::zero()),
+            Value::Vector(Vector3::zero()),
+        ];
+        state
+            .fire_action(ConnectionKey::null(), root, "create_ship", args.into())
+            .unwrap();
+        let ship = state.component::<God>(root).unwrap().lobby[0];
+        (state, ship)
+    }
+
+    #[test]
+    fn create_ship_takes_a_map_without_velocity() {
+        let (mut state, _) = lobby_setup();
+        let root = state.root_entity();
+        let mut args = HashMap::new();
+        args.insert(
+            "position".to_string(),
+            Value::Vector(Vector3::new(1.0, 2.0, 3.0)),
+        );
+        state
+            .fire_action(ConnectionKey::null(), root, "create_ship", Value::Map(args))
+            .unwrap();
+        let ship = state.component::<God>(root).unwrap().lobby[1];
+        let body = state.component::<Body>(ship).unwrap();
+        assert_eq!(*body.position, Point3::new(1.0, 2.0, 3.0));
+        assert_eq!(*body.velocity, Vector3::zero());
+        assert!(state
+            .fire_action(
+                ConnectionKey::null(),
+                root,
+                "create_ship",
+                Value::Map(HashMap::new())
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn claimed_ship_leaves_lobby_and_belongs_to_claimer() {
+        let (mut state, ship) = lobby_setup();
+        let root = state.root_entity();
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), root, "lobby"),
+            Ok(vec![ship].into())
+        );
+        let claimer = ConnectionKey::from_id(1);
+        state
+            .fire_action(claimer, root, "claim", ship.into())
+            .unwrap();
+        assert_eq!(
+            state.get_property(ConnectionKey::null(), root, "lobby"),
+            Ok(Value::Array(Vec::new()))
+        );
+        assert_eq!(*state.component::<Ship>(ship).unwrap().controller, claimer);
+        assert!(state
+            .fire_action(ConnectionKey::from_id(2), root, "claim", ship.into())
+            .is_err());
+        assert_eq!(*state.component::<Ship>(ship).unwrap().controller, claimer);
+    }
+
+    #[test]
+    fn ships_can_not_be_claimed_without_a_connection() {
+        let (mut state, ship) = lobby_setup();
+        let root = state.root_entity();
+        assert!(state
+            .fire_action(ConnectionKey::null(), root, "claim", ship.into())
+            .is_err());
+        assert_eq!(*state.component::<God>(root).unwrap().lobby, vec![ship]);
+        assert!(state.component::<Ship>(ship).unwrap().controller.is_null());
+    }
+
+    #[test]
+    fn ship_returns_to_lobby_when_claimer_disconnects() {
+        let (mut state, ship) = lobby_setup();
+        let root = state.root_entity();
+        let claimer = ConnectionKey::from_id(1);
+        state
+            .fire_action(claimer, root, "claim", ship.into())
+            .unwrap();
+        update_lobby(&mut state);
+        assert!(state.component::<God>(root).unwrap().lobby.is_empty());
+        state.connection_closed(claimer);
+        update_lobby(&mut state);
+        assert_eq!(*state.component::<God>(root).unwrap().lobby, vec![ship]);
+        assert!(state.component::<Ship>(ship).unwrap().controller.is_null());
+    }
+
+    #[test]
+    fn destroyed_ship_leaves_lobby() {
+        let (mut state, ship) = lobby_setup();
+        let root = state.root_entity();
+        state.destroy_entity(ship).unwrap();
+        update_lobby(&mut state);
+        assert!(state.component::<God>(root).unwrap().lobby.is_empty());
+    }
+
+    #[test]
+    fn server_info_reflects_config() {
+        let mut state = State::new();
+        let config = GameConfig {
+            scenario: Scenario::Empty,
+            lobby: true,
+            ..GameConfig::default()
+        };
+        God::default().install(&mut state, &config);
+        let info = state
+            .get_property(ConnectionKey::null(), state.root_entity(), "server_info")
+            .unwrap();
+        assert_eq!(info.get_field("lobby"), Some(&Value::Bool(true)));
+        assert_eq!(
+            info.get_field("scenario"),
+            Some(&Value::Text("empty".to_string()))
+        );
+        assert_eq!(
+            info.get_field("version"),
+            Some(&Value::Text(env!("CARGO_PKG_VERSION").to_string()))
+        );
+    }
 }
@@ -10,6 +10,43 @@ pub enum AutopilotScheme {
     ///   given body
     /// - distance: distance from the target to orbit
     Orbit,
+    /// Thrust directly away from a body at max acceleration
+    /// - target: the body to flee from, must not be null
+    /// - distance: once this far from the target thrust is cut, if null never stops thrusting
+    Flee,
+}
+
+impl PropertyEnum for AutopilotScheme {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (AutopilotScheme::Off, "off"),
+        (AutopilotScheme::Orbit, "orbit"),
+        (AutopilotScheme::Flee, "flee"),
+    ];
+}
+
+/// What the autopilot is currently doing, as reported to clients
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AutopilotStatus {
+    /// The autopilot is not controlling the ship
+    Off,
+    /// Orbit scheme is maneuvering towards the goal orbit
+    Approaching,
+    /// Orbit scheme has reached the goal orbit and is holding it
+    Matched,
+    /// Flee scheme is thrusting away from the target
+    Fleeing,
+    /// Flee scheme is beyond the safe distance and has cut thrust
+    Safe,
+}
+
+impl PropertyEnum for AutopilotStatus {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (AutopilotStatus::Off, "off"),
+        (AutopilotStatus::Approaching, "approaching"),
+        (AutopilotStatus::Matched, "matched"),
+        (AutopilotStatus::Fleeing, "fleeing"),
+        (AutopilotStatus::Safe, "safe"),
+    ];
 }
 
 /// The data required for server-side control of a ship
@@ -18,31 +55,50 @@ pub struct AutopilotData {
     pub scheme: Element<AutopilotScheme>,
     pub target: Element<EntityKey>,
     pub distance: Element<Option<f64>>,
+    /// How strongly position error is corrected, lower values approach more slowly
+    pub proportional_gain: Element<f64>,
+    /// How strongly velocity error is corrected, higher values damp oscillation more
+    pub derivative_gain: Element<f64>,
+    /// Commanded accelerations below this magnitude are ignored, starts at the physics epsilon
+    pub tolerance: Element<f64>,
+    /// Set by the autopilot each tick, not settable by clients
+    pub status: Element<AutopilotStatus>,
 }
 
 /// A vehicle that can maneuver under its own thrust
 pub struct Ship {
     pub max_acceleration: Element<f64>,
     pub acceleration: Element<Vector3<f64>>,
+    /// Fastest the ship can go relative to its gravity parent, None for no limit
+    pub max_speed: Element<Option<f64>>,
     pub autopilot: AutopilotData,
+    /// The connection that claimed the ship from the lobby, null if it hasn't been claimed
+    pub controller: Element<ConnectionKey>,
 }
 
 impl Ship {
-    fn new(max_acceleration: f64) -> Self {
+    fn new(max_acceleration: f64, epsilon: f64) -> Self {
         Self {
             max_acceleration: Element::new(max_acceleration),
             acceleration: Element::new(Vector3::zero()),
+            max_speed: Element::new(None),
             autopilot: AutopilotData {
                 scheme: Element::new(AutopilotScheme::Off),
                 target: Element::new(EntityKey::null()),
                 distance: Element::new(None),
+                proportional_gain: Element::new(DEFAULT_AUTOPILOT_PROPORTIONAL_GAIN),
+                derivative_gain: Element::new(DEFAULT_AUTOPILOT_DERIVATIVE_GAIN),
+                tolerance: Element::new(epsilon),
+                status: Element::new(AutopilotStatus::Off),
             },
+            controller: Element::new(ConnectionKey::null()),
         }
     }
 
-    fn set_thrust(&mut self, thrust: Vector3<f64>) -> RequestResult<()> {
+    /// Thrust over the max acceleration (plus epsilon) is cut down to it and reported as an error
+    fn set_thrust(&mut self, thrust: Vector3<f64>, epsilon: f64) -> RequestResult<()> {
         let magnitude = thrust.magnitude();
-        if magnitude > *self.max_acceleration + EPSILON {
+        if magnitude > *self.max_acceleration + epsilon {
             let fixed = thrust.normalize() * *self.max_acceleration;
             self.acceleration.set(fixed);
             Err(BadRequest(format!(
@@ -70,18 +126,27 @@ impl CollisionHandler for ShipBodyController {
     }
 }
 
-pub fn create_ship(state: &mut State, position: Point3<f64>, velocity: Vector3<f64>) -> EntityKey {
-    let entity = state.create_entity();
+pub const SHIP_RADIUS: f64 = 1.0;
+
+/// Fails if the state's max entity count has been reached. epsilon is the physics epsilon from
+/// the game config.
+pub fn create_ship(
+    state: &mut State,
+    position: Point3<f64>,
+    velocity: Vector3<f64>,
+    epsilon: f64,
+) -> RequestResult<EntityKey> {
+    let entity = state.create_entity()?;
 
     Body::new()
         .with_class(BodyClass::Ship)
         .with_position(position)
         .with_velocity(velocity)
-        .with_sphere_shape(1.0)
+        .with_sphere_shape(SHIP_RADIUS)
         .with_collision_handler(Box::new(ShipBodyController { ship: entity }))
         .install(state, entity);
 
-    state.install_component(entity, Ship::new(1.0)); // 100G (too much)
+    state.install_component(entity, Ship::new(1.0, epsilon)); // 100G (too much)
 
     RWConduit::new(
         move |state| Ok(&state.component::<Ship>(entity)?.max_acceleration),
@@ -96,10 +161,28 @@ pub fn create_ship(state: &mut State, position: Point3<f64>, velocity: Vector3<f
 
     RWConduit::new(
         move |state| Ok(&state.component::<Ship>(entity)?.acceleration),
-        move |state, value| state.component_mut::<Ship>(entity)?.set_thrust(value),
+        move |state, value| {
+            state
+                .component_mut::<Ship>(entity)?
+                .set_thrust(value, epsilon)
+        },
     )
     .install_property(state, entity, "accel");
 
+    RWConduit::new(
+        move |state| Ok(&state.component::<Ship>(entity)?.max_speed),
+        move |state, value| Ok(state.component_mut::<Ship>(entity)?.max_speed.set(value)),
+    )
+    .validate(|value: &Option<f64>| match value {
+        Some(speed) if !speed.is_finite() || *speed < 0.0 => Err(BadRequest(format!(
+            "{} is an invalid max speed (must be finite and >= 0)",
+            speed
+        ))),
+        _ => Ok(()),
+    })
+    .with_unit("km/s")
+    .install_property(state, entity, "max_speed");
+
     RWConduit::new(
         move |state| Ok(&state.component::<Ship>(entity)?.autopilot.scheme),
         move |state, value| {
@@ -110,20 +193,8 @@ pub fn create_ship(state: &mut State, position: Point3<f64>, velocity: Vector3<f
                 .set(value))
         },
     )
-    .map_output(|scheme| {
-        Ok(match scheme {
-            AutopilotScheme::Off => "off".to_string(),
-            AutopilotScheme::Orbit => "orbit".to_string(),
-        })
-    })
-    .map_input(|scheme: String| match &scheme[..] {
-        "off" => Ok(AutopilotScheme::Off),
-        "orbit" => Ok(AutopilotScheme::Orbit),
-        _ => Err(BadRequest(format!(
-            "{:?} is an invalid autopilot scheme",
-            scheme
-        ))),
-    })
+    .map_output(AutopilotScheme::to_property)
+    .map_input(AutopilotScheme::from_property)
     .install_property(state, entity, "ap_scheme");
 
     RWConduit::new(
@@ -150,7 +221,61 @@ pub fn create_ship(state: &mut State, position: Point3<f64>, velocity: Vector3<f
     )
     .install_property(state, entity, "ap_distance");
 
-    entity
+    RWConduit::new(
+        move |state| Ok(&state.component::<Ship>(entity)?.autopilot.proportional_gain),
+        move |state, value| {
+            Ok(state
+                .component_mut::<Ship>(entity)?
+                .autopilot
+                .proportional_gain
+                .set(value))
+        },
+    )
+    .validate(non_negative_autopilot_param)
+    .install_property(state, entity, "ap_p_gain");
+
+    RWConduit::new(
+        move |state| Ok(&state.component::<Ship>(entity)?.autopilot.derivative_gain),
+        move |state, value| {
+            Ok(state
+                .component_mut::<Ship>(entity)?
+                .autopilot
+                .derivative_gain
+                .set(value))
+        },
+    )
+    .validate(non_negative_autopilot_param)
+    .install_property(state, entity, "ap_d_gain");
+
+    RWConduit::new(
+        move |state| Ok(&state.component::<Ship>(entity)?.autopilot.tolerance),
+        move |state, value| {
+            Ok(state
+                .component_mut::<Ship>(entity)?
+                .autopilot
+                .tolerance
+                .set(value))
+        },
+    )
+    .validate(non_negative_autopilot_param)
+    .install_property(state, entity, "ap_tolerance");
+
+    ROConduit::new(move |state| Ok(&state.component::<Ship>(entity)?.autopilot.status))
+        .map_output(AutopilotStatus::to_property)
+        .install_property(state, entity, "autopilot_status");
+
+    Ok(entity)
+}
+
+fn non_negative_autopilot_param(value: &f64) -> RequestResult<()> {
+    if value.is_finite() && *value >= 0.0 {
+        Ok(())
+    } else {
+        Err(BadRequest(format!(
+            "{} is an invalid autopilot parameter (must be finite and >= 0)",
+            value
+        )))
+    }
 }
 
 #[cfg(test)]
@@ -161,17 +286,122 @@ mod tests {
     fn body_has_correct_position() {
         let pos = Point3::new(1.0, 2.0, 3.0);
         let mut state = State::new();
-        let ship = create_ship(&mut state, pos, Vector3::zero());
+        let ship = create_ship(&mut state, pos, Vector3::zero(), EPSILON).unwrap();
         assert_eq!(*state.component::<Body>(ship).unwrap().position, pos);
     }
 
     #[test]
     fn body_has_sphere_shape() {
         let mut state = State::new();
-        let ship = create_ship(&mut state, Point3::new(1.0, 2.0, 3.0), Vector3::zero());
+        let ship = create_ship(
+            &mut state,
+            Point3::new(1.0, 2.0, 3.0),
+            Vector3::zero(),
+            EPSILON,
+        )
+        .unwrap();
         assert_eq!(
             *state.component::<Body>(ship).unwrap().shape,
             body::Shape::Sphere { radius: 1.0 }
         );
     }
+
+    #[test]
+    fn creating_ship_past_entity_cap_fails() {
+        let mut state = State::new();
+        let first = create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).unwrap();
+        state.set_max_entities(2);
+        assert!(create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).is_err());
+        assert!(state.component::<Ship>(first).is_ok());
+        assert_eq!(state.components_iter::<Ship>().count(), 1);
+    }
+
+    #[test]
+    fn thrusting_ship_plateaus_at_max_speed_relative_to_parent() {
+        let mut state = State::new();
+        let planet = state.create_entity().unwrap();
+        let planet_velocity = Vector3::new(0.0, 5.0, 0.0);
+        Body::new()
+            .with_mass(1.0e+20)
+            .with_velocity(planet_velocity)
+            .install(&mut state, planet);
+        let ship = create_ship(
+            &mut state,
+            Point3::new(1.0e+6, 0.0, 0.0),
+            planet_velocity,
+            EPSILON,
+        )
+        .unwrap();
+        state
+            .set_property(ConnectionKey::null(), ship, "max_speed", 3.0.into())
+            .unwrap();
+        state
+            .set_property(
+                ConnectionKey::null(),
+                ship,
+                "accel",
+                Vector3::unit_x().into(),
+            )
+            .unwrap();
+        let relative_speed = |state: &State| {
+            let ship = *state.component::<Body>(ship).unwrap().velocity;
+            let planet = *state.component::<Body>(planet).unwrap().velocity;
+            (ship - planet).magnitude()
+        };
+        for _ in 0..10 {
+            apply_acceleration(&mut state, 1.0);
+            apply_gravity(&mut state, 1.0, EPSILON);
+            limit_ship_speeds(&mut state);
+            apply_motion(&mut state, 1.0);
+            assert!(relative_speed(&state) <= 3.0 + EPSILON);
+        }
+        assert_eq!(
+            *state.component::<Body>(ship).unwrap().gravity_parent,
+            planet
+        );
+        assert!((relative_speed(&state) - 3.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn thrust_limit_uses_given_epsilon() {
+        let mut state = State::new();
+        let loose = create_ship(&mut state, Point3::origin(), Vector3::zero(), 0.1).unwrap();
+        let strict = create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).unwrap();
+        let thrust = Vector3::new(1.05, 0.0, 0.0);
+        assert!(state
+            .set_property(ConnectionKey::null(), loose, "accel", thrust.into())
+            .is_ok());
+        assert!(state
+            .set_property(ConnectionKey::null(), strict, "accel", thrust.into())
+            .is_err());
+    }
+
+    #[test]
+    fn autopilot_tolerance_starts_at_given_epsilon() {
+        let mut state = State::new();
+        let ship = create_ship(&mut state, Point3::origin(), Vector3::zero(), 0.1).unwrap();
+        let ship = state.component::<Ship>(ship).unwrap();
+        assert_eq!(*ship.autopilot.tolerance, 0.1);
+    }
+
+    #[test]
+    fn negative_max_speed_is_rejected() {
+        let mut state = State::new();
+        let ship = create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).unwrap();
+        assert!(state
+            .set_property(ConnectionKey::null(), ship, "max_speed", (-1.0).into())
+            .is_err());
+        assert!(state
+            .set_property(ConnectionKey::null(), ship, "max_speed", Value::Null)
+            .is_ok());
+    }
+
+    #[test]
+    fn reports_body_and_ship_components() {
+        let mut state = State::new();
+        let ship = create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).unwrap();
+        let names = state.component_type_names(ship).unwrap();
+        assert!(names.contains(&"Body".to_string()));
+        assert!(names.contains(&"Ship".to_string()));
+    }
 }
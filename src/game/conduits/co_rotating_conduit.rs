@@ -0,0 +1,182 @@
+use super::*;
+
+/// The frame of a body orbiting its gravity parent. The origin is the parent, the x axis points
+/// at the body and the z axis is along the orbit's angular momentum, so the frame rotates with the
+/// body.
+pub struct CoRotatingFrame {
+    origin: Point3<f64>,
+    origin_velocity: Vector3<f64>,
+    axes: [Vector3<f64>; 3],
+    angular_velocity: Vector3<f64>,
+}
+
+impl CoRotatingFrame {
+    /// None if the body is not moving around its parent, since then there is no rotation
+    pub fn new(body: &Body, parent: &Body) -> Option<Self> {
+        let position = *body.position - *parent.position;
+        let velocity = *body.velocity - *parent.velocity;
+        let angular_momentum = position.cross(velocity);
+        if position.magnitude2() == 0.0 || angular_momentum.magnitude2() == 0.0 {
+            return None;
+        }
+        let x = position.normalize();
+        let z = angular_momentum.normalize();
+        Some(Self {
+            origin: *parent.position,
+            origin_velocity: *parent.velocity,
+            axes: [x, z.cross(x), z],
+            angular_velocity: angular_momentum / position.magnitude2(),
+        })
+    }
+
+    fn to_frame(&self, vector: Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(
+            vector.dot(self.axes[0]),
+            vector.dot(self.axes[1]),
+            vector.dot(self.axes[2]),
+        )
+    }
+
+    pub fn position(&self, body: &Body) -> Vector3<f64> {
+        self.to_frame(*body.position - self.origin)
+    }
+
+    /// Velocity relative to the rotating frame, so it is zero for anything moving with it
+    pub fn velocity(&self, body: &Body) -> Vector3<f64> {
+        let position = *body.position - self.origin;
+        self.to_frame(*body.velocity - self.origin_velocity - self.angular_velocity.cross(position))
+    }
+}
+
+/// A read-only vector of a body seen from the co-rotating frame of a reference body, such as its
+/// position in that frame. Null when the reference has no parent or isn't orbiting it.
+pub struct CoRotatingConduit<F> {
+    tracker: ParentTracker,
+    body: EntityKey,
+    quantity: F,
+}
+
+impl<F> CoRotatingConduit<F>
+where
+    F: Fn(&CoRotatingFrame, &Body) -> Vector3<f64> + Send + Sync,
+{
+    /// quantity is given the frame and then the body
+    pub fn new(body: EntityKey, reference: EntityKey, quantity: F) -> Self {
+        Self {
+            tracker: ParentTracker::new(reference),
+            body,
+            quantity,
+        }
+    }
+}
+
+impl<F> Conduit<Option<Vector3<f64>>, ReadOnlyPropSetType> for CoRotatingConduit<F>
+where
+    F: Fn(&CoRotatingFrame, &Body) -> Vector3<f64> + Send + Sync,
+{
+    fn output(&self, state: &State) -> RequestResult<Option<Vector3<f64>>> {
+        let reference = state.component::<Body>(self.tracker.body())?;
+        let parent = self.tracker.update_parent(state);
+        if parent.is_null() {
+            return Ok(None);
+        }
+        let parent = state.component::<Body>(parent)?;
+        let body = state.component::<Body>(self.body)?;
+        Ok(CoRotatingFrame::new(reference, parent).map(|frame| (self.quantity)(&frame, body)))
+    }
+
+    fn input(&self, _: &mut State, _: ReadOnlyPropSetType) -> RequestResult<()> {
+        unreachable!()
+    }
+}
+
+impl<F> Subscribable for CoRotatingConduit<F> {
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        // The tracker expects its body to exist, and the reference is chosen by the client
+        state.component::<Body>(self.tracker.body())?;
+        let body = state.component::<Body>(self.body)?;
+        self.tracker.subscribe(state, subscriber)?;
+        if self.body != self.tracker.body() {
+            body.position.subscribe(state, subscriber)?;
+            body.velocity.subscribe(state, subscriber)?;
+        }
+        Ok(())
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        self.tracker.unsubscribe(state, subscriber)?;
+        if self.body != self.tracker.body() {
+            let body = state.component::<Body>(self.body)?;
+            body.position.unsubscribe(state, subscriber)?;
+            body.velocity.unsubscribe(state, subscriber)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLANET_MASS: f64 = 6e+21;
+    const ORBIT_RADIUS: f64 = 1e+4;
+
+    fn circular_orbit(angle: f64) -> (Point3<f64>, Vector3<f64>) {
+        let speed = (GRAVITATIONAL_CONSTANT * PLANET_MASS / ORBIT_RADIUS).sqrt();
+        (
+            Point3::new(angle.cos(), angle.sin(), 0.0) * ORBIT_RADIUS,
+            Vector3::new(-angle.sin(), angle.cos(), 0.0) * speed,
+        )
+    }
+
+    fn in_frame(state: &State, body: EntityKey, name: &str, frame: EntityKey) -> Vector3<f64> {
+        let options = SubscribeOptions {
+            frame: Some(frame),
+            ..SubscribeOptions::default()
+        };
+        match state.get_property_with_options(ConnectionKey::null(), body, name, &options) {
+            Ok(Value::Vector(vector)) => vector,
+            other => panic!("{} in frame is {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn co_rotating_position_is_constant_in_circular_orbit() {
+        let mut state = State::new();
+        let planet = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(PLANET_MASS)
+            .install(&mut state, planet);
+        let (position, velocity) = circular_orbit(0.0);
+        let leader = create_ship(&mut state, position, velocity, EPSILON).unwrap();
+        let (position, velocity) = circular_orbit(-0.3);
+        let follower = create_ship(&mut state, position, velocity, EPSILON).unwrap();
+        apply_gravity(&mut state, 0.0, EPSILON);
+        let start = in_frame(&state, follower, "position", leader);
+        for _ in 0..200 {
+            apply_gravity(&mut state, 10.0, EPSILON);
+            apply_motion(&mut state, 10.0);
+        }
+        let end = in_frame(&state, follower, "position", leader);
+        assert!((end - start).magnitude() < ORBIT_RADIUS * 0.01);
+        assert!(end.y < 0.0);
+        assert!(end.z.abs() < EPSILON);
+        let leader_position = in_frame(&state, leader, "position", leader);
+        assert!(leader_position.y.abs() < EPSILON);
+        assert!(in_frame(&state, follower, "velocity", leader).magnitude() < 0.1);
+    }
+
+    #[test]
+    fn frame_must_be_a_body() {
+        let mut state = State::new();
+        let ship = create_ship(&mut state, Point3::origin(), Vector3::zero(), EPSILON).unwrap();
+        let not_a_body = state.create_entity().unwrap();
+        let options = SubscribeOptions {
+            frame: Some(not_a_body),
+            ..SubscribeOptions::default()
+        };
+        assert!(state
+            .subscribe_with_options(ConnectionKey::null(), ship, "position", &options)
+            .is_err());
+    }
+}
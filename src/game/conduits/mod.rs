@@ -1,5 +1,12 @@
 use super::*;
 
+mod co_rotating_conduit;
 mod orbit_conduit;
+mod parent_tracker;
+mod relative_conduit;
 
+pub use co_rotating_conduit::*;
 pub use orbit_conduit::*;
+pub use relative_conduit::*;
+
+use parent_tracker::ParentTracker;
@@ -1,7 +1,8 @@
 use super::*;
 
 /// [Orbital Elements on Wikipedia](https://en.wikipedia.org/wiki/Orbital_elements) may be helpful
-/// in understanding this struct
+/// in understanding this struct. Times are in game seconds, the same as State::time(), so the body
+/// is at the periapsis at start_time plus any whole number of period_times.
 pub struct OrbitData {
     /// Size of the semi-major axis (longest radius) (commonly a)
     semi_major: f64,
@@ -15,10 +16,10 @@ pub struct OrbitData {
     /// Angle (in radiuans, in the orbit space) of the periapsis (body's closest point to the
     /// parent) relative to the ascending node (commonly ω)
     periapsis: f64,
-    /// Some time at which the body was/will be at the periapsis
+    /// Some game time at which the body was/will be at the periapsis
     start_time: f64,
-    /// Time it takes for a full orbit to complete. Calculatable from parent mass and G, but MUST
-    /// be updated atomically with the rest of the orbit.
+    /// Game seconds it takes for a full orbit to complete. Calculatable from parent mass and G,
+    /// but MUST be updated atomically with the rest of the orbit.
     period_time: f64,
     /// The "gravity parent" of the body. Should always be the same as the dedicated property of
     /// that name. Duplicated here because it MUST be updated atomically with the rest of the orbit
@@ -26,14 +27,67 @@ pub struct OrbitData {
     parent: EntityKey,
 }
 
+impl OrbitData {
+    /// 0 for a circle, approaching 1 as the ellipse gets longer (commonly e)
+    pub fn eccentricity(&self) -> f64 {
+        (1.0 - (self.semi_minor * self.semi_minor) / (self.semi_major * self.semi_major))
+            .max(0.0)
+            .sqrt()
+    }
+
+    /// Angle (in radians, normalized to [0, 2π)) the body would have swept through since the
+    /// periapsis at the given game time if it moved at a constant rate (commonly M)
+    pub fn mean_anomaly(&self, time: f64) -> f64 {
+        normalize_angle(TAU * (time - self.start_time) / self.period_time)
+    }
+
+    /// Distance from the parent at the furthest point of the orbit
+    pub fn apoapsis_distance(&self) -> f64 {
+        self.semi_major * (1.0 + self.eccentricity())
+    }
+
+    /// Distance from the parent at the closest point of the orbit
+    pub fn periapsis_distance(&self) -> f64 {
+        self.semi_major * (1.0 - self.eccentricity())
+    }
+
+    /// Named fields, including ones derived from the orbit that the array encoding doesn't have
+    /// room for. Angles are normalized to [0, 2π).
+    pub fn to_map(&self) -> Value {
+        let fields: Vec<(&str, Value)> = vec![
+            ("semi_major", self.semi_major.into()),
+            ("semi_minor", self.semi_minor.into()),
+            ("inclination", normalize_angle(self.inclination).into()),
+            (
+                "ascending_node",
+                normalize_angle(self.ascending_node).into(),
+            ),
+            ("periapsis", normalize_angle(self.periapsis).into()),
+            ("start_time", self.start_time.into()),
+            ("period_time", self.period_time.into()),
+            ("parent", self.parent.into()),
+            ("eccentricity", self.eccentricity().into()),
+            ("apoapsis_distance", self.apoapsis_distance().into()),
+            ("periapsis_distance", self.periapsis_distance().into()),
+        ];
+        Value::Map(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+}
+
+/// Angles are normalized to [0, 2π)
 impl From<OrbitData> for Value {
     fn from(orbit: OrbitData) -> Self {
         let array: Vec<Value> = vec![
             orbit.semi_major.into(),
             orbit.semi_minor.into(),
-            orbit.inclination.into(),
-            orbit.ascending_node.into(),
-            orbit.periapsis.into(),
+            normalize_angle(orbit.inclination).into(),
+            normalize_angle(orbit.ascending_node).into(),
+            normalize_angle(orbit.periapsis).into(),
             orbit.start_time.into(),
             orbit.period_time.into(),
             orbit.parent.into(),
@@ -44,69 +98,20 @@ impl From<OrbitData> for Value {
 
 /// A conduit that implements a body's orbit property
 pub struct OrbitConduit {
-    subscribers: SyncSubscriberList,
-    body: EntityKey,
-    cached_parent: Mutex<EntityKey>,
+    tracker: ParentTracker,
 }
 
 impl OrbitConduit {
     pub fn new(body: EntityKey) -> Self {
         Self {
-            subscribers: SyncSubscriberList::new(),
-            body,
-            cached_parent: Mutex::new(EntityKey::null()),
-        }
-    }
-
-    fn for_each_parent_subscribable<F: Fn(&dyn Subscribable)>(
-        state: &State,
-        parent: EntityKey,
-        f: &F,
-    ) -> RequestResult<()> {
-        let parent_body = state.component::<Body>(parent)?;
-        f(&parent_body.position);
-        f(&parent_body.velocity);
-        f(&parent_body.mass);
-        Ok(())
-    }
-
-    fn for_each_subscribable<F: Fn(&dyn Subscribable)>(
-        &self,
-        state: &State,
-        f: &F,
-    ) -> RequestResult<()> {
-        let body = state.component::<Body>(self.body)?;
-        Self::for_each_parent_subscribable(state, *self.cached_parent.lock().unwrap(), f)?;
-        f(&body.gravity_parent);
-        f(&body.position);
-        f(&body.velocity);
-        f(&body.mass);
-        Ok(())
-    }
-
-    /// Ensures we are subscribed to the properties of the currently correct parent, and returns it
-    fn update_parent(&self, state: &State) -> EntityKey {
-        let parent = *state
-            .component::<Body>(self.body)
-            .expect("OrbitConduit body does not exist")
-            .gravity_parent;
-        let mut cached_parent = self.cached_parent.lock().unwrap();
-        if parent != *cached_parent {
-            let _ = Self::for_each_parent_subscribable(state, *cached_parent, &|s| {
-                self.subscribers.unsubscribe_all(state, s);
-            });
-            *cached_parent = parent;
-            let _ = Self::for_each_parent_subscribable(state, *cached_parent, &|s| {
-                self.subscribers.subscribe_all(state, s);
-            });
+            tracker: ParentTracker::new(body),
         }
-        *cached_parent
     }
 }
 
 impl Conduit<OrbitData, ReadOnlyPropSetType> for OrbitConduit {
     fn output(&self, state: &State) -> RequestResult<OrbitData> {
-        let parent = self.update_parent(state);
+        let parent = self.tracker.update_parent(state);
         Ok(OrbitData {
             semi_major: 100.0,
             semi_minor: 50.0,
@@ -126,26 +131,87 @@ impl Conduit<OrbitData, ReadOnlyPropSetType> for OrbitConduit {
 
 impl Subscribable for OrbitConduit {
     fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
-        // If the parent isn't initialized, we could miss notifications if we don't set it up here
-        self.update_parent(state);
-        self.for_each_subscribable(state, &|s| {
-            s.subscribe(state, subscriber)
-                .or_log_error("subscribing to OrbitConduit");
-        })?;
-        self.subscribers.add(subscriber)?;
-        Ok(())
+        self.tracker.subscribe(state, subscriber)
     }
 
     fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
-        // No need to update parent here, it reflects the currently subscribed to things which is
-        // all that matters.
-        self.for_each_subscribable(state, &|s| {
-            s.unsubscribe(state, subscriber)
-                .or_log_error("unsubscribing from OrbitConduit");
-        })?;
-        self.subscribers.remove(subscriber)?;
-        Ok(())
+        self.tracker.unsubscribe(state, subscriber)
     }
 }
 
-// TODO: test
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orbit(semi_major: f64, semi_minor: f64) -> OrbitData {
+        OrbitData {
+            semi_major,
+            semi_minor,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            periapsis: 0.0,
+            start_time: 0.0,
+            period_time: 10.0,
+            parent: EntityKey::null(),
+        }
+    }
+
+    #[test]
+    fn apsis_distances_of_ellipse() {
+        // Eccentricity is sqrt(1 - 60²/100²) = 0.8
+        let orbit = orbit(100.0, 60.0);
+        assert!((orbit.apoapsis_distance() - 180.0).abs() < EPSILON);
+        assert!((orbit.periapsis_distance() - 20.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn apsis_distances_of_circle_are_radius() {
+        let orbit = orbit(50.0, 50.0);
+        assert!((orbit.apoapsis_distance() - 50.0).abs() < EPSILON);
+        assert!((orbit.periapsis_distance() - 50.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn mean_anomaly_repeats_after_one_period() {
+        let mut orbit = orbit(100.0, 60.0);
+        orbit.start_time = 3.0;
+        assert!(orbit.mean_anomaly(3.0).abs() < EPSILON);
+        assert!((orbit.mean_anomaly(5.5) - TAU / 4.0).abs() < EPSILON);
+        for &time in &[0.0, 4.25, 17.0, 123.4] {
+            let after = orbit.mean_anomaly(time + orbit.period_time);
+            let diff = normalize_angle(after - orbit.mean_anomaly(time) + TAU / 2.0) - TAU / 2.0;
+            assert!(diff.abs() < EPSILON, "{} drifted by {}", time, diff);
+        }
+    }
+
+    #[test]
+    fn map_has_eccentricity() {
+        let eccentricity = |orbit: OrbitData| match orbit.to_map() {
+            Value::Map(map) => match map.get("eccentricity") {
+                Some(Value::Scalar(eccentricity)) => *eccentricity,
+                other => panic!("unexpected eccentricity {:?}", other),
+            },
+            other => panic!("unexpected orbit map {:?}", other),
+        };
+        assert!((eccentricity(orbit(100.0, 60.0)) - 0.8).abs() < EPSILON);
+        assert!(eccentricity(orbit(50.0, 50.0)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn map_has_apsis_distances() {
+        match orbit(100.0, 60.0).to_map() {
+            Value::Map(map) => {
+                assert_eq!(map.len(), 11);
+                match map.get("apoapsis_distance") {
+                    Some(Value::Scalar(distance)) => assert!((distance - 180.0).abs() < EPSILON),
+                    other => panic!("unexpected apoapsis_distance {:?}", other),
+                }
+                match map.get("periapsis_distance") {
+                    Some(Value::Scalar(distance)) => assert!((distance - 20.0).abs() < EPSILON),
+                    other => panic!("unexpected periapsis_distance {:?}", other),
+                }
+            }
+            other => panic!("unexpected orbit map {:?}", other),
+        }
+    }
+}
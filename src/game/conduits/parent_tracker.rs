@@ -0,0 +1,92 @@
+use super::*;
+
+/// Keeps subscribers of a conduit that depends on a body and its gravity parent subscribed to the
+/// current parent, which can change at any time
+pub struct ParentTracker {
+    subscribers: SyncSubscriberList,
+    body: EntityKey,
+    cached_parent: Mutex<EntityKey>,
+}
+
+impl ParentTracker {
+    pub fn new(body: EntityKey) -> Self {
+        Self {
+            subscribers: SyncSubscriberList::new(),
+            body,
+            cached_parent: Mutex::new(EntityKey::null()),
+        }
+    }
+
+    pub fn body(&self) -> EntityKey {
+        self.body
+    }
+
+    fn for_each_parent_subscribable<F: Fn(&dyn Subscribable)>(
+        state: &State,
+        parent: EntityKey,
+        f: &F,
+    ) -> RequestResult<()> {
+        let parent_body = state.component::<Body>(parent)?;
+        f(&parent_body.position);
+        f(&parent_body.velocity);
+        f(&parent_body.mass);
+        Ok(())
+    }
+
+    fn for_each_subscribable<F: Fn(&dyn Subscribable)>(
+        &self,
+        state: &State,
+        f: &F,
+    ) -> RequestResult<()> {
+        let body = state.component::<Body>(self.body)?;
+        Self::for_each_parent_subscribable(state, *self.cached_parent.lock().unwrap(), f)?;
+        f(&body.gravity_parent);
+        f(&body.position);
+        f(&body.velocity);
+        f(&body.mass);
+        Ok(())
+    }
+
+    /// Ensures we are subscribed to the properties of the currently correct parent, and returns it
+    pub fn update_parent(&self, state: &State) -> EntityKey {
+        let parent = *state
+            .component::<Body>(self.body)
+            .expect("ParentTracker body does not exist")
+            .gravity_parent;
+        let mut cached_parent = self.cached_parent.lock().unwrap();
+        if parent != *cached_parent {
+            let _ = Self::for_each_parent_subscribable(state, *cached_parent, &|s| {
+                self.subscribers.unsubscribe_all(state, s);
+            });
+            *cached_parent = parent;
+            let _ = Self::for_each_parent_subscribable(state, *cached_parent, &|s| {
+                self.subscribers.subscribe_all(state, s);
+            });
+        }
+        *cached_parent
+    }
+}
+
+impl Subscribable for ParentTracker {
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        // If the parent isn't initialized, we could miss notifications if we don't set it up here
+        self.update_parent(state);
+        self.for_each_subscribable(state, &|s| {
+            s.subscribe(state, subscriber)
+                .or_log_error("subscribing to ParentTracker");
+        })?;
+        self.subscribers.add(subscriber)?;
+        Ok(())
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        // No need to update parent here, it reflects the currently subscribed to things which is
+        // all that matters.
+        self.for_each_subscribable(state, &|s| {
+            s.unsubscribe(state, subscriber)
+                .or_log_error("unsubscribing from ParentTracker");
+        })?;
+        self.subscribers.remove(subscriber)?;
+        Ok(())
+    }
+}
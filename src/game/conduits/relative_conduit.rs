@@ -0,0 +1,50 @@
+use super::*;
+
+/// A read-only vector computed from a body and its gravity parent, such as the body's position in
+/// its parent's frame. Null when the body has no parent.
+pub struct RelativeConduit<F> {
+    tracker: ParentTracker,
+    relative: F,
+}
+
+impl<F> RelativeConduit<F>
+where
+    F: Fn(&Body, &Body) -> Vector3<f64> + Send + Sync,
+{
+    /// relative is given the body and then its parent
+    pub fn new(body: EntityKey, relative: F) -> Self {
+        Self {
+            tracker: ParentTracker::new(body),
+            relative,
+        }
+    }
+}
+
+impl<F> Conduit<Option<Vector3<f64>>, ReadOnlyPropSetType> for RelativeConduit<F>
+where
+    F: Fn(&Body, &Body) -> Vector3<f64> + Send + Sync,
+{
+    fn output(&self, state: &State) -> RequestResult<Option<Vector3<f64>>> {
+        let parent = self.tracker.update_parent(state);
+        if parent.is_null() {
+            return Ok(None);
+        }
+        let body = state.component::<Body>(self.tracker.body())?;
+        let parent = state.component::<Body>(parent)?;
+        Ok(Some((self.relative)(body, parent)))
+    }
+
+    fn input(&self, _: &mut State, _: ReadOnlyPropSetType) -> RequestResult<()> {
+        unreachable!()
+    }
+}
+
+impl<F> Subscribable for RelativeConduit<F> {
+    fn subscribe(&self, state: &State, subscriber: &Arc<dyn Subscriber>) -> RequestResult<()> {
+        self.tracker.subscribe(state, subscriber)
+    }
+
+    fn unsubscribe(&self, state: &State, subscriber: &Weak<dyn Subscriber>) -> RequestResult<()> {
+        self.tracker.unsubscribe(state, subscriber)
+    }
+}
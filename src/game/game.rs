@@ -9,14 +9,16 @@ struct CelestialInfo<'a> {
     radius: f64,
 }
 
-fn create_celestial(state: &mut State, scale: f64, info: CelestialInfo) -> EntityKey {
-    let e = state.create_entity();
+fn create_celestial(state: &mut State, scale: f64, epsilon: f64, info: CelestialInfo) -> EntityKey {
+    let e = state
+        .create_entity()
+        .expect("max_entities is too low for the scenario");
     let (parent_pos, parent_vel, parent_mass) = state
         .component::<Body>(info.parent)
         .map(|parent| (*parent.position, *parent.velocity, *parent.mass))
         .unwrap_or_else(|_| (Point3::origin(), Vector3::zero(), 0.0));
     let pos = parent_pos + Vector3::new(info.distance, 0.0, 0.0) * scale;
-    let vel = if info.distance > EPSILON && parent_mass > EPSILON {
+    let vel = if info.distance > epsilon && parent_mass > epsilon {
         let unscaled_parent_mass = parent_mass / scale;
         (GRAVITATIONAL_CONSTANT * unscaled_parent_mass / info.distance).sqrt() // for circular orbit
     } else {
@@ -36,7 +38,9 @@ fn create_celestial(state: &mut State, scale: f64, info: CelestialInfo) -> Entit
 
 // TODO: generalize create_celestial() to support non-circular, non-level orbits
 fn create_planet_9(state: &mut State, scale: f64) {
-    let e = state.create_entity();
+    let e = state
+        .create_entity()
+        .expect("max_entities is too low for the scenario");
     Body::new()
         .with_class(BodyClass::Celestial)
         .with_position(Point3::new(3.0e8, 0.0, 6.0e7) * scale)
@@ -48,13 +52,14 @@ fn create_planet_9(state: &mut State, scale: f64) {
         .install(state, e);
 }
 
-fn init_solar_system(state: &mut State, scale: f64) {
+fn init_solar_system(state: &mut State, scale: f64, epsilon: f64) {
     // Note that scale affects mass, size and position but not velocity. This keeps orbits correct.
 
     // All values are intended to be correct for Sol (the Sun)
     let sol = create_celestial(
         state,
         scale,
+        epsilon,
         CelestialInfo {
             name: "Sol",
             color: 0xffe461,
@@ -69,6 +74,7 @@ fn init_solar_system(state: &mut State, scale: f64) {
     let _venus = create_celestial(
         state,
         scale,
+        epsilon,
         CelestialInfo {
             name: "Mercury",
             color: 0xb89984,
@@ -83,6 +89,7 @@ fn init_solar_system(state: &mut State, scale: f64) {
     let _venus = create_celestial(
         state,
         scale,
+        epsilon,
         CelestialInfo {
             name: "Venus",
             color: 0xbaa87d,
@@ -97,6 +104,7 @@ fn init_solar_system(state: &mut State, scale: f64) {
     let earth = create_celestial(
         state,
         scale,
+        epsilon,
         CelestialInfo {
             name: "Earth",
             color: 0x1d55f0,
@@ -111,6 +119,7 @@ fn init_solar_system(state: &mut State, scale: f64) {
     let _luna = create_celestial(
         state,
         scale,
+        epsilon,
         CelestialInfo {
             name: "Luna",
             color: 0xd2d2d2,
@@ -125,6 +134,7 @@ fn init_solar_system(state: &mut State, scale: f64) {
     let _mars = create_celestial(
         state,
         scale,
+        epsilon,
         CelestialInfo {
             name: "Mars",
             color: 0xd65733,
@@ -138,13 +148,267 @@ fn init_solar_system(state: &mut State, scale: f64) {
     create_planet_9(state, scale);
 }
 
-pub fn init(state: &mut State) {
-    God::default().install(state);
+/// Procedurally generates a star orbited by planets in circular orbits. The same seed always
+/// generates the same system.
+fn init_generated_system(state: &mut State, scale: f64, epsilon: f64, seed: u64) {
+    let mut rng = SeededRng::new(seed);
 
-    init_solar_system(state, 0.000001);
+    // Somewhere around the size of Sol
+    let star = create_celestial(
+        state,
+        scale,
+        epsilon,
+        CelestialInfo {
+            name: "Star",
+            color: 0xffe461,
+            parent: EntityKey::null(),
+            distance: 0.0,
+            mass: 1.989e+27 * rng.range(0.5, 2.0),
+            radius: 696340.0 * rng.range(0.7, 1.5),
+        },
+    );
+
+    let planet_count = rng.range_inclusive(3, 9);
+    // Start a bit closer than Mercury and space each planet further out than the last
+    let mut distance = 5.0e+7 * rng.range(0.8, 1.2);
+    for i in 0..planet_count {
+        let mass = 10_f64.powf(rng.range(19.0, 22.5));
+        let name = format!("Planet {}", i + 1);
+        create_celestial(
+            state,
+            scale,
+            epsilon,
+            CelestialInfo {
+                name: &name,
+                color: (rng.next_u64() & 0xffffff) as u32,
+                parent: star,
+                distance,
+                mass,
+                // Assume about the same density as Earth
+                radius: 6371.0 * (mass / 5.972e+21).cbrt(),
+            },
+        );
+        distance *= rng.range(1.4, 2.2);
+    }
+}
+
+/// What the game starts with
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Scenario {
+    /// Our solar system
+    #[default]
+    Default,
+    /// A system generated from GameConfig::system_seed
+    Procedural,
+    /// Nothing but the god object, for tests and sandboxes
+    Empty,
+}
+
+impl PropertyEnum for Scenario {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (Scenario::Default, "default"),
+        (Scenario::Procedural, "procedural"),
+        (Scenario::Empty, "empty"),
+    ];
+}
+
+/// What happens when something is spawned overlapping an existing body
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpawnPolicy {
+    /// Spawn it anyway and let the collision code sort it out
+    #[default]
+    Allow,
+    /// Fail the request
+    Reject,
+    /// Move it just outside the body it overlaps
+    Nudge,
+}
+
+impl PropertyEnum for SpawnPolicy {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (SpawnPolicy::Allow, "allow"),
+        (SpawnPolicy::Reject, "reject"),
+        (SpawnPolicy::Nudge, "nudge"),
+    ];
+}
+
+/// How many times a spawn can be nudged out of one body and into another before giving up
+const MAX_SPAWN_NUDGES: usize = 8;
+/// Nudged spawns are placed this many times the touching distance from the body they overlapped
+const SPAWN_NUDGE_MARGIN: f64 = 1.01;
+
+/// Returns where a new body of the given radius should be spawned, or an error if the policy does
+/// not allow spawning it near the requested position. epsilon is the physics epsilon from the game
+/// config.
+pub fn spawn_position(
+    state: &State,
+    position: Point3<f64>,
+    radius: f64,
+    policy: SpawnPolicy,
+    epsilon: f64,
+) -> RequestResult<Point3<f64>> {
+    if policy == SpawnPolicy::Allow {
+        return Ok(position);
+    }
+    let mut position = position;
+    for _ in 0..MAX_SPAWN_NUDGES {
+        let overlapping = state
+            .components_iter::<Body>()
+            .find(|(_, body)| body.position.distance(position) < body.shape.radius() + radius);
+        match (overlapping, policy) {
+            (None, _) => return Ok(position),
+            (Some((_, body)), SpawnPolicy::Nudge) => {
+                let away = position - *body.position;
+                let direction = if away.magnitude() > epsilon {
+                    away
+                } else {
+                    Vector3::unit_x()
+                };
+                position = *body.position
+                    + direction.normalize_to((body.shape.radius() + radius) * SPAWN_NUDGE_MARGIN);
+            }
+            (Some((entity, _)), _) => {
+                return Err(BadRequest(format!("can not spawn inside {:?}", entity)))
+            }
+        }
+    }
+    Err(BadRequest(
+        "could not find a clear position to spawn".to_string(),
+    ))
+}
+
+/// Settings that effect how the game is set up, generally loaded from the config
+#[derive(Clone)]
+pub struct GameConfig {
+    /// Expose actions that are only useful for debugging the server
+    pub debug_actions: bool,
+    pub scenario: Scenario,
+    /// Used by the procedural scenario, the same seed always generates the same system
+    pub system_seed: u64,
+    /// Fast bodies are checked for collisions in up to this many substeps per tick, 0 or 1 disables
+    pub max_collision_substeps: u32,
+    /// The order collisions in the same tick are handled in
+    pub collision_order: CollisionOrder,
+    /// What happens when a ship is created overlapping an existing body
+    pub spawn_policy: SpawnPolicy,
+    /// Ships created by clients are listed in a lobby on the root object until a connection
+    /// claims one
+    pub lobby: bool,
+    /// Connections that send this to the become_admin action are granted admin access. Empty
+    /// disables becoming an admin.
+    pub admin_token: String,
+    /// Config keys and values clients are allowed to see
+    pub public_config: Vec<(String, String)>,
+    /// Distances and masses at or below this are treated as zero by physics. Should be scaled along
+    /// with the size of the universe.
+    pub epsilon: f64,
+    /// Bodies that come within this many times their combined radius without colliding get a
+    /// proximity signal, 1 or less disables
+    pub proximity_alert_scale: f64,
+    /// Stops the destroy_body debug action from destroying the primary body (generally the sun)
+    pub protect_primary_body: bool,
+    /// Bodies moving faster than this are slowed down to it, 0 or less disables
+    pub max_speed: f64,
+    /// Every body attracts every other, not just gravity wells. Expensive with many ships.
+    pub mutual_gravity: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            debug_actions: false,
+            scenario: Scenario::default(),
+            system_seed: 0,
+            max_collision_substeps: 0,
+            collision_order: CollisionOrder::default(),
+            spawn_policy: SpawnPolicy::default(),
+            lobby: false,
+            admin_token: String::new(),
+            public_config: Vec::new(),
+            epsilon: EPSILON,
+            proximity_alert_scale: 0.0,
+            protect_primary_body: false,
+            max_speed: 0.0,
+            mutual_gravity: false,
+        }
+    }
+}
+
+/// Ships the connection controls are sent first, then other bodies from nearest to farthest from
+/// them (or from the origin if it doesn't control any). Objects without a body (ex the god object)
+/// are sent before bodies.
+fn send_priority(state: &State, connection: ConnectionKey, entity: EntityKey) -> f64 {
+    if matches!(state.component::<Ship>(entity), Ok(ship) if *ship.controller == connection) {
+        return f64::INFINITY;
+    }
+    let position = match state.component::<Body>(entity) {
+        Ok(body) => *body.position,
+        Err(_) => return 0.0,
+    };
+    let distances = state
+        .components_iter::<Ship>()
+        .filter(|(_, ship)| *ship.controller == connection)
+        .filter_map(|(ship, _)| state.component::<Body>(ship).ok())
+        .map(|body| body.position.distance(position));
+    let nearest = distances.fold(None, |nearest: Option<f64>, distance| {
+        Some(nearest.map_or(distance, |nearest| nearest.min(distance)))
+    });
+    -nearest.unwrap_or_else(|| position.distance(Point3::origin()))
 }
 
-pub fn physics_tick(state: &mut State, delta: f64) {
+pub fn init(state: &mut State, config: &GameConfig) {
+    God::default().install(state, config);
+    state.set_send_priority(send_priority);
+
+    match config.scenario {
+        Scenario::Default => init_solar_system(state, 0.000001, config.epsilon),
+        Scenario::Procedural => {
+            init_generated_system(state, 0.000001, config.epsilon, config.system_seed)
+        }
+        Scenario::Empty => (),
+    }
+}
+
+/// If the total kinetic energy of the system is below the threshold, so physics can be paused
+/// without anyone noticing much
+pub fn is_settled(state: &State, max_kinetic_energy: f64) -> bool {
+    matches!(
+        state.component::<God>(state.root_entity()),
+        Ok(god) if *god.total_kinetic_energy < max_kinetic_energy
+    )
+}
+
+/// Game time between logs of a watched body
+const WATCH_LOG_INTERVAL: f64 = 1.0;
+
+/// Returns a tick hook that logs the position and velocity of the body with the given name at
+/// debug level (at most once per WATCH_LOG_INTERVAL), for comparing against what clients see when
+/// investigating desyncs
+pub fn watch_body(name: String) -> impl FnMut(&State) {
+    let mut next_log = f64::NEG_INFINITY;
+    move |state| {
+        if state.time() < next_log {
+            return;
+        }
+        next_log = state.time() + WATCH_LOG_INTERVAL;
+        let watched = state
+            .components_iter::<Body>()
+            .find(|(_, body)| body.name.as_deref() == Some(name.as_str()));
+        match watched {
+            Some((entity, body)) => debug!(
+                "watched body {} ({:?}) at {}s: position {:?}, velocity {:?}",
+                name,
+                entity,
+                state.time(),
+                *body.position,
+                *body.velocity
+            ),
+            None => debug!("watched body {} does not exist", name),
+        }
+    }
+}
+
+pub fn physics_tick(state: &mut State, delta: f64, config: &GameConfig) {
     let time = state.time();
     state
         .component_mut::<God>(state.root_entity())
@@ -152,8 +416,221 @@ pub fn physics_tick(state: &mut State, delta: f64) {
         .time
         .set(time);
     apply_acceleration(state, delta);
-    apply_gravity(state, delta);
-    apply_collisions(state, delta);
+    apply_gravity(state, delta, config.epsilon);
+    if config.mutual_gravity {
+        apply_mutual_gravity(state, delta, config.epsilon);
+    }
+    apply_collisions(
+        state,
+        delta,
+        config.max_collision_substeps,
+        config.epsilon,
+        config.collision_order,
+    );
+    apply_proximity_alerts(
+        state,
+        delta,
+        config.proximity_alert_scale,
+        config.max_collision_substeps,
+        config.epsilon,
+    );
+    clamp_velocities(state, config.max_speed);
+    limit_ship_speeds(state);
     apply_motion(state, delta);
-    run_autopilot(state, delta);
+    run_autopilot(state, delta, config.epsilon);
+    update_totals(state, config.epsilon);
+    update_lobby(state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generated_positions(seed: u64) -> Vec<Point3<f64>> {
+        let mut state = State::new();
+        init(
+            &mut state,
+            &GameConfig {
+                scenario: Scenario::Procedural,
+                system_seed: seed,
+                ..GameConfig::default()
+            },
+        );
+        state
+            .components_iter::<Body>()
+            .map(|(_, body)| *body.position)
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_generates_same_system() {
+        let a = generated_positions(12);
+        let b = generated_positions(12);
+        assert!(a.len() >= 4);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn different_seeds_generate_different_systems() {
+        assert_ne!(generated_positions(12), generated_positions(13));
+    }
+
+    #[test]
+    fn over_limit_velocity_is_clamped_on_next_tick() {
+        let config = GameConfig {
+            scenario: Scenario::Empty,
+            max_speed: 10.0,
+            ..GameConfig::default()
+        };
+        let mut state = State::new();
+        init(&mut state, &config);
+        let fast = state.create_entity().unwrap();
+        Body::new()
+            .with_velocity(Vector3::new(30.0, 40.0, 0.0))
+            .install(&mut state, fast);
+        let slow = state.create_entity().unwrap();
+        Body::new()
+            .with_velocity(Vector3::new(0.0, 2.0, 0.0))
+            .install(&mut state, slow);
+        physics_tick(&mut state, 1.0, &config);
+        let fast_body = state.component::<Body>(fast).unwrap();
+        assert!((*fast_body.velocity - Vector3::new(6.0, 8.0, 0.0)).magnitude() < EPSILON);
+        assert!((*fast_body.position - Point3::new(6.0, 8.0, 0.0)).magnitude() < EPSILON);
+        assert_eq!(
+            *state.component::<Body>(slow).unwrap().velocity,
+            Vector3::new(0.0, 2.0, 0.0)
+        );
+    }
+
+    fn body_names(scenario: Scenario) -> Vec<String> {
+        let mut state = State::new();
+        init(
+            &mut state,
+            &GameConfig {
+                scenario,
+                ..GameConfig::default()
+            },
+        );
+        state
+            .components_iter::<Body>()
+            .filter_map(|(_, body)| (*body.name).clone())
+            .collect()
+    }
+
+    #[test]
+    fn empty_scenario_only_has_god() {
+        let mut state = State::new();
+        init(
+            &mut state,
+            &GameConfig {
+                scenario: Scenario::Empty,
+                ..GameConfig::default()
+            },
+        );
+        assert_eq!(state.components_iter::<Body>().count(), 0);
+        assert!(state.component::<God>(state.root_entity()).is_ok());
+    }
+
+    #[test]
+    fn default_scenario_has_the_sun() {
+        let names = body_names(Scenario::Default);
+        assert!(names.contains(&"Sol".to_string()));
+        assert!(names.contains(&"Earth".to_string()));
+    }
+
+    #[test]
+    fn procedural_scenario_has_a_star_and_planets() {
+        let names = body_names(Scenario::Procedural);
+        assert!(names.contains(&"Star".to_string()));
+        assert!(names.contains(&"Planet 1".to_string()));
+        assert!(!names.contains(&"Sol".to_string()));
+    }
+
+    fn state_with_planet() -> State {
+        let mut state = State::new();
+        let planet = state.create_entity().unwrap();
+        Body::new()
+            .with_position(Point3::new(10.0, 0.0, 0.0))
+            .with_sphere_shape(5.0)
+            .install(&mut state, planet);
+        state
+    }
+
+    #[test]
+    fn spawning_inside_body_is_rejected() {
+        let state = state_with_planet();
+        let position = Point3::new(12.0, 0.0, 0.0);
+        assert!(spawn_position(&state, position, 1.0, SpawnPolicy::Reject, EPSILON).is_err());
+    }
+
+    #[test]
+    fn spawning_in_clear_spot_succeeds() {
+        let state = state_with_planet();
+        let position = Point3::new(-10.0, 0.0, 0.0);
+        for policy in &[SpawnPolicy::Reject, SpawnPolicy::Nudge] {
+            assert_eq!(
+                spawn_position(&state, position, 1.0, *policy, EPSILON).unwrap(),
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn spawning_inside_body_is_nudged_out() {
+        let state = state_with_planet();
+        let position = spawn_position(
+            &state,
+            Point3::new(12.0, 0.0, 0.0),
+            1.0,
+            SpawnPolicy::Nudge,
+            EPSILON,
+        )
+        .unwrap();
+        assert!(position.x > 16.0);
+        assert!(position.y.abs() < EPSILON);
+    }
+
+    #[test]
+    fn spawning_inside_body_is_allowed_by_default() {
+        let state = state_with_planet();
+        let position = Point3::new(12.0, 0.0, 0.0);
+        assert_eq!(
+            spawn_position(&state, position, 1.0, SpawnPolicy::default(), EPSILON).unwrap(),
+            position
+        );
+    }
+
+    #[test]
+    fn controlled_ship_and_nearby_bodies_are_sent_first() {
+        let mut state = State::new();
+        state.set_send_priority(send_priority);
+        let connection = ConnectionKey::from_id(1);
+        let ship = create_ship(
+            &mut state,
+            Point3::new(100.0, 0.0, 0.0),
+            Vector3::zero(),
+            EPSILON,
+        )
+        .unwrap();
+        state
+            .component_mut::<Ship>(ship)
+            .unwrap()
+            .controller
+            .set(connection);
+        let body_at = |state: &mut State, x: f64| {
+            let entity = state.create_entity().unwrap();
+            Body::new()
+                .with_position(Point3::new(x, 0.0, 0.0))
+                .install(state, entity);
+            entity
+        };
+        let near = body_at(&mut state, 90.0);
+        let far = body_at(&mut state, 0.0);
+        let other = ConnectionKey::from_id(2);
+        let priority = |entity| state.send_priority(connection, entity);
+        assert!(priority(ship) > priority(near));
+        assert!(priority(near) > priority(far));
+        // Without a ship, bodies nearer the origin come first
+        assert!(state.send_priority(other, far) > state.send_priority(other, near));
+    }
 }
@@ -9,12 +9,16 @@ mod conduits;
 mod game;
 mod physics;
 
-pub use game::{init, physics_tick};
+pub use game::{init, is_settled, physics_tick, watch_body, GameConfig, Scenario, SpawnPolicy};
+pub use physics::CollisionOrder;
+
+use game::spawn_position;
 
 use autopilot::*;
 use components::*;
 use conduits::*;
 use physics::*;
 
-/// A very small value; used for floating-point comparisons
+/// A very small value; the default physics epsilon (see GameConfig::epsilon) and used by tests for
+/// floating-point comparisons
 const EPSILON: f64 = 0.000_001;
@@ -5,8 +5,50 @@ use super::*;
 /// That means that converting to our units (km and mt) we get…
 pub const GRAVITATIONAL_CONSTANT: f64 = 6.67430e-17;
 
-/// Applies the force of gravity to bodies' velocities
-pub fn apply_gravity(state: &mut State, dt: f64) {
+/// Orders gravity wells by descending mass. Equal masses are ordered by entity so the result does
+/// not depend on iteration order.
+fn well_order(a: (f64, EntityKey), b: (f64, EntityKey)) -> std::cmp::Ordering {
+    b.0.partial_cmp(&a.0)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.1.cmp(&b.1))
+}
+
+/// The most massive gravity body, which is given an infinite sphere of influence by
+/// apply_gravity(). If it is destroyed the next most massive takes over on the next tick.
+pub fn primary_body(state: &State) -> Option<EntityKey> {
+    state
+        .components_iter::<GravityBody>()
+        .filter_map(|(entity, _)| {
+            let body = state.component::<Body>(entity).ok()?;
+            Some((*body.mass, entity))
+        })
+        .min_by(|a, b| well_order(*a, *b))
+        .map(|(_, entity)| entity)
+}
+
+/// Change in velocity over dt of a body at position pulled towards an attractor, or None if they
+/// are within epsilon of each other (there is no direction to pull in)
+fn gravity_delta_vel(
+    position: Point3<f64>,
+    attractor: Point3<f64>,
+    mass: f64,
+    dt: f64,
+    epsilon: f64,
+) -> Option<Vector3<f64>> {
+    // Get the distance², which is faster than normal distance and all we need
+    let distance2 = attractor.distance2(position);
+    if distance2 > epsilon * epsilon {
+        // Acceleration due to gravity follows the inverse square law
+        let acceleration = GRAVITATIONAL_CONSTANT * mass / distance2;
+        Some((attractor - position).normalize_to(acceleration * dt))
+    } else {
+        None
+    }
+}
+
+/// Applies the force of gravity to bodies' velocities. Bodies within epsilon of a gravity well are
+/// treated as coincident with it and not pulled (there is no direction to pull them in).
+pub fn apply_gravity(state: &mut State, dt: f64, epsilon: f64) {
     // we can't access the body (and thus the position) of a gravity well while we are mutating the
     // position of bodies, so we collect all the info we need into a local vec (which should be
     // good for performence as well)
@@ -35,13 +77,10 @@ pub fn apply_gravity(state: &mut State, dt: f64) {
         })
         .collect();
     // For the sphere of influence calculation, we need to look at gravity wells in descending order
-    wells.sort_unstable_by(|a, b| {
-        b.mass
-            .partial_cmp(&a.mass)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
+    wells.sort_unstable_by(|a, b| well_order((a.mass, a.entity), (b.mass, b.entity)));
     if !wells.is_empty() {
-        // This will be the most massive object, presumably the sun
+        // This will be the most massive object (the same one primary_body() returns), presumably
+        // the sun
         wells[0].sphere_of_influence2 = f64::INFINITY;
     }
     // Now calculate the sphere of influence of each body. To do this, we need to know the body's parent. But wait!
@@ -77,15 +116,20 @@ pub fn apply_gravity(state: &mut State, dt: f64) {
             (EntityKey::null(), f64::INFINITY),
             |(grav_parent, grav_parent_mass), well| {
                 if well.entity != body_entity {
-                    // Get the distance², which is faster than normal distance and all we need
+                    // Frozen bodies still get a gravity parent, they just aren't pulled
                     let distance2 = well.position.distance2(*body.position);
-                    // Acceleration due to gravity follows the inverse square law
-                    let acceleration = GRAVITATIONAL_CONSTANT * well.mass / distance2;
-                    // Change in velocity is previously calculated acceleration towards the well
-                    let delta_vel =
-                        (well.position - *body.position).normalize_to(acceleration * dt);
-                    // Apply delta-velocity to the body
-                    body.velocity.set(*body.velocity + delta_vel);
+                    if !*body.frozen {
+                        let delta_vel = gravity_delta_vel(
+                            *body.position,
+                            well.position,
+                            well.mass,
+                            dt,
+                            epsilon,
+                        );
+                        if let Some(delta_vel) = delta_vel {
+                            body.velocity.set(*body.velocity + delta_vel);
+                        }
+                    }
                     // Now we check if if the well is a candidate to be this body's gravity parent. To be one it must:
                     // - Be less massive than the current candidate
                     // - Be more massive than the body
@@ -104,8 +148,67 @@ pub fn apply_gravity(state: &mut State, dt: f64) {
     });
 }
 
+/// Applies the gravity of bodies that aren't gravity wells (such as ships) to all other bodies.
+/// Along with apply_gravity() this makes every body attract every other, which is O(n²) in the
+/// number of bodies so it's only done in the mutual gravity mode. Does not change gravity parents.
+pub fn apply_mutual_gravity(state: &mut State, dt: f64, epsilon: f64) {
+    let attractors: Vec<(EntityKey, Point3<f64>, f64)> = state
+        .components_iter::<Body>()
+        .filter(|(entity, body)| {
+            *body.mass > epsilon && state.component::<GravityBody>(*entity).is_err()
+        })
+        .map(|(entity, body)| (entity, *body.position, *body.mass))
+        .collect();
+    if attractors.is_empty() {
+        return;
+    }
+    let iter = state.components_iter_mut::<Body>();
+    iter.for_each(|(body_entity, body)| {
+        if *body.frozen {
+            return;
+        }
+        let delta_vel: Vector3<f64> = attractors
+            .iter()
+            .filter(|(entity, _, _)| *entity != body_entity)
+            .filter_map(|(_, position, mass)| {
+                gravity_delta_vel(*body.position, *position, *mass, dt, epsilon)
+            })
+            .sum();
+        body.velocity.set(*body.velocity + delta_vel);
+    });
+}
+
+/// Returns the time until two spheres that are r apart when touching start touching, if that
+/// happens in the next dt
 #[allow(clippy::many_single_char_names)]
-fn check_if_bodies_collides(body1: &Body, body2: &Body, dt: f64) -> Option<f64> {
+fn time_until_contact(
+    rel_pos: Vector3<f64>,
+    rel_vel: Vector3<f64>,
+    r: f64,
+    dt: f64,
+) -> Option<f64> {
+    let a = rel_vel.magnitude2();
+    let b = 2.0 * (rel_pos.x * rel_vel.x + rel_pos.y * rel_vel.y + rel_pos.z * rel_vel.z);
+    let c = rel_pos.magnitude2() - r * r;
+    // only care about the first solution (when the two spheres start touching)
+    // divide by zero is fine
+    let t = (-b - (b * b - 4.0 * a * c).sqrt()) / (2.0 * a);
+    if t >= 0.0 && t < dt {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// If max_substeps is more than 1 and the bodies move further than their combined radius relative
+/// to each other this tick, dt is split into up to max_substeps pieces that are checked in order
+fn check_if_bodies_collides(
+    body1: &Body,
+    body2: &Body,
+    dt: f64,
+    max_substeps: u32,
+    epsilon: f64,
+) -> Option<f64> {
     // r = r1 + r2
     // x = x1 - x2, y = …, z = …
     // dx = dx1 - dx2, dy = …, dz = …
@@ -118,25 +221,71 @@ fn check_if_bodies_collides(body1: &Body, body2: &Body, dt: f64) -> Option<f64>
     // b = 2(x*dx + y*dy + z*dz)
     // c = x^2 + y^2 + z^2 - r^2
     let r = body1.shape.radius() + body2.shape.radius();
-    if r > EPSILON {
+    if r > epsilon {
         let rel_pos = *body1.position - *body2.position;
         let rel_vel = *body1.velocity - *body2.velocity;
-        let a = rel_vel.magnitude2();
-        let b = 2.0 * (rel_pos.x * rel_vel.x + rel_pos.y * rel_vel.y + rel_pos.z * rel_vel.z);
-        let c = rel_pos.magnitude2() - r * r;
-        // only care about the first solution (when the two spheres start touching)
-        // divide by zero is fine
-        let t = (-b - (b * b - 4.0 * a * c).sqrt()) / (2.0 * a);
-        if t >= 0.0 && t < dt {
-            return Some(t);
+        // When the bodies start far apart compared to their size, b^2 - 4ac loses too much
+        // precision and grazing collisions are missed. Each substep starts closer together.
+        let substeps = if max_substeps > 1 {
+            ((rel_vel.magnitude() * dt / r).ceil() as u32)
+                .max(1)
+                .min(max_substeps)
+        } else {
+            1
+        };
+        let substep_dt = dt / substeps as f64;
+        for i in 0..substeps {
+            let start = substep_dt * i as f64;
+            if let Some(t) = time_until_contact(rel_pos + rel_vel * start, rel_vel, r, substep_dt) {
+                return Some(start + t);
+            }
         }
     }
     None
 }
 
-/// Handles body collisions
-pub fn apply_collisions(state: &State, dt: f64) {
+/// The order collision handlers are called in when there are several collisions in a tick
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CollisionOrder {
+    /// The order collisions are found in, which depends on the order bodies were created
+    #[default]
+    Discovery,
+    /// Biggest impacts first (by descending combined mass), and the heavier body of each pair
+    /// first, so results are reproducible
+    Mass,
+}
+
+impl PropertyEnum for CollisionOrder {
+    const VARIANTS: &'static [(Self, &'static str)] = &[
+        (CollisionOrder::Discovery, "discovery"),
+        (CollisionOrder::Mass, "mass"),
+    ];
+}
+
+/// Two bodies that collide this tick, with the one that's notified first as a
+struct CollidingPair<'a> {
+    a: (EntityKey, &'a Body),
+    b: (EntityKey, &'a Body),
+    time_until: f64,
+}
+
+impl<'a> CollidingPair<'a> {
+    fn combined_mass(&self) -> f64 {
+        *self.a.1.mass + *self.b.1.mass
+    }
+}
+
+/// Handles body collisions, see check_if_bodies_collides() for max_substeps. Bodies whose combined
+/// radius is within epsilon of zero never collide.
+pub fn apply_collisions(
+    state: &State,
+    dt: f64,
+    max_substeps: u32,
+    epsilon: f64,
+    order: CollisionOrder,
+) {
     // TODO: sort bodies and don't compare bodies that can not touch
+    let mut pairs = Vec::new();
     state.components_iter::<Body>().for_each(|(key1, body1)| {
         let _ = state
             .components_iter::<Body>()
@@ -146,18 +295,92 @@ pub fn apply_collisions(state: &State, dt: f64) {
                     // once it catches up to the outer loop
                     Err(())
                 } else {
-                    if let Some(time_until) = check_if_bodies_collides(body1, body2, dt) {
-                        body1
-                            .collision_handler
-                            .collision(state, &Collision::new(time_until, key2));
-                        body2
-                            .collision_handler
-                            .collision(state, &Collision::new(time_until, key1));
+                    if let Some(time_until) =
+                        check_if_bodies_collides(body1, body2, dt, max_substeps, epsilon)
+                    {
+                        pairs.push(CollidingPair {
+                            a: (key1, body1),
+                            b: (key2, body2),
+                            time_until,
+                        });
                     }
                     Ok(())
                 }
             });
     });
+    if order == CollisionOrder::Mass {
+        for pair in &mut pairs {
+            if well_order((*pair.a.1.mass, pair.a.0), (*pair.b.1.mass, pair.b.0))
+                == std::cmp::Ordering::Greater
+            {
+                std::mem::swap(&mut pair.a, &mut pair.b);
+            }
+        }
+        pairs.sort_by(|p, q| {
+            well_order((p.combined_mass(), p.a.0), (q.combined_mass(), q.a.0))
+                .then_with(|| p.b.0.cmp(&q.b.0))
+        });
+    }
+    for pair in pairs {
+        pair.a
+            .1
+            .collision_handler
+            .collision(state, &Collision::new(pair.time_until, pair.b.0));
+        pair.b
+            .1
+            .collision_handler
+            .collision(state, &Collision::new(pair.time_until, pair.a.0));
+    }
+}
+
+/// Fires the proximity signal on both bodies when they come within scale times their combined
+/// radius this tick without colliding. Only entering the alert radius counts, so each pass fires
+/// once. A scale of 1 or less disables alerts.
+pub fn apply_proximity_alerts(
+    state: &mut State,
+    dt: f64,
+    scale: f64,
+    max_substeps: u32,
+    epsilon: f64,
+) {
+    if scale <= 1.0 {
+        return;
+    }
+    // Signals need mutable bodies, so collect the pairs first
+    let mut alerts = Vec::new();
+    state.components_iter::<Body>().for_each(|(key1, body1)| {
+        let _ = state
+            .components_iter::<Body>()
+            .try_for_each(|(key2, body2)| {
+                if key1 == key2 {
+                    Err(())
+                } else {
+                    let r = body1.shape.radius() + body2.shape.radius();
+                    if r > epsilon
+                        && time_until_contact(
+                            *body1.position - *body2.position,
+                            *body1.velocity - *body2.velocity,
+                            r * scale,
+                            dt,
+                        )
+                        .is_some()
+                        && check_if_bodies_collides(body1, body2, dt, max_substeps, epsilon)
+                            .is_none()
+                    {
+                        alerts.push((key1, key2));
+                    }
+                    Ok(())
+                }
+            });
+    });
+    for (key1, key2) in alerts {
+        if let Ok(body) = state.component_mut::<Body>(key1) {
+            body.proximity.fire(key2);
+        }
+        if let Ok(body) = state.component_mut::<Body>(key2) {
+            body.proximity.fire(key1);
+        }
+    }
 }
 
 /// Applies thrust of all ships to their velocity
@@ -167,20 +390,103 @@ pub fn apply_acceleration(state: &mut State, dt: f64) {
     let ships: Vec<EntityKey> = state.components_iter::<Ship>().map(|(e, _)| e).collect();
     for e in ships {
         let thrust = *state.component::<Ship>(e).unwrap().acceleration;
-        let vel = &mut state.component_mut::<Body>(e).unwrap().velocity;
-        vel.set(**vel + thrust * dt);
+        let body = state.component_mut::<Body>(e).unwrap();
+        if !*body.frozen {
+            body.velocity.set(*body.velocity + thrust * dt);
+        }
+    }
+}
+
+/// Scales down the velocity of any body moving faster than max_speed, so a numerical blowup can't
+/// send it tunneling through everything. 0 or less disables.
+pub fn clamp_velocities(state: &mut State, max_speed: f64) {
+    if max_speed <= 0.0 {
+        return;
+    }
+    for (entity, body) in state
+        .components_iter_mut::<Body>()
+        .filter(|(_, body)| !*body.frozen)
+    {
+        let speed = body.velocity.magnitude();
+        if speed > max_speed {
+            warn!(
+                "clamping speed of {:?} from {} to {}",
+                entity, speed, max_speed
+            );
+            body.velocity.set(*body.velocity * (max_speed / speed));
+        }
+    }
+}
+
+/// Slows down ships going faster than their max speed relative to their gravity parent
+pub fn limit_ship_speeds(state: &mut State) {
+    let limits: Vec<(EntityKey, f64)> = state
+        .components_iter::<Ship>()
+        .filter_map(|(e, ship)| Some((e, (*ship.max_speed)?)))
+        .collect();
+    for (e, max_speed) in limits {
+        let parent = *state.component::<Body>(e).unwrap().gravity_parent;
+        let parent_velocity = state
+            .component::<Body>(parent)
+            .map(|parent| *parent.velocity)
+            .unwrap_or_else(|_| Vector3::zero());
+        let body = state.component_mut::<Body>(e).unwrap();
+        let relative = *body.velocity - parent_velocity;
+        if !*body.frozen && relative.magnitude() > max_speed {
+            body.velocity
+                .set(parent_velocity + relative.normalize_to(max_speed));
+        }
     }
 }
 
-/// Applies velocity of all bodies to their position
+/// Applies velocity of all bodies that aren't frozen to their position
 pub fn apply_motion(state: &mut State, dt: f64) {
     let iter = state.components_iter_mut::<Body>();
-    for (_, body) in iter {
+    for (_, body) in iter.filter(|(_, body)| !*body.frozen) {
         body.position.set(*body.position + dt * *body.velocity);
         //info!("position: {:?}", *body.position);
     }
 }
 
+/// Updates the total momentum, kinetic energy and gravitational potential energy of the system,
+/// which are useful for catching physics bugs that don't conserve them. Wells within epsilon of
+/// each other are coincident and don't contribute potential energy (as they don't pull each other).
+pub fn update_totals(state: &mut State, epsilon: f64) {
+    let (momentum, kinetic_energy) = state.components_iter::<Body>().fold(
+        (Vector3::zero(), 0.0),
+        |(momentum, kinetic_energy), (_, body)| {
+            (
+                momentum + *body.mass * *body.velocity,
+                kinetic_energy + 0.5 * *body.mass * body.velocity.magnitude2(),
+            )
+        },
+    );
+    let wells: Vec<(Point3<f64>, f64)> = state
+        .components_iter::<GravityBody>()
+        .map(|(entity, _)| {
+            let body = state
+                .component::<Body>(entity)
+                .expect("GravityBody does not have a body");
+            (*body.position, *body.mass)
+        })
+        .collect();
+    let mut potential_energy = 0.0;
+    for (i, (position_a, mass_a)) in wells.iter().enumerate() {
+        for (position_b, mass_b) in &wells[i + 1..] {
+            let distance = position_a.distance(*position_b);
+            if distance > epsilon {
+                potential_energy -= GRAVITATIONAL_CONSTANT * mass_a * mass_b / distance;
+            }
+        }
+    }
+    let god = state
+        .component_mut::<God>(state.root_entity())
+        .expect("failed to get root");
+    god.total_momentum.set(momentum);
+    god.total_kinetic_energy.set(kinetic_energy);
+    god.total_potential_energy.set(potential_energy);
+}
+
 #[cfg(test)]
 #[allow(clippy::float_cmp)]
 mod gravity_tests {
@@ -190,7 +496,7 @@ mod gravity_tests {
     const EARTH_RADIUS: f64 = 6368.0; // radius of earth
 
     fn create_body_entity(state: &mut State, body: Body, gravity: bool) -> EntityKey {
-        let entity = state.create_entity();
+        let entity = state.create_entity().unwrap();
         state.install_component(entity, body);
         if gravity {
             state.install_component(entity, GravityBody);
@@ -204,7 +510,7 @@ mod gravity_tests {
         let mut state = State::new();
         let body = create_body_entity(&mut state, Body::new().with_mass(EARTH_MASS), true);
         assert_eq!(*state.component::<Body>(body).unwrap().velocity, velocity);
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         assert_eq!(*state.component::<Body>(body).unwrap().velocity, velocity);
     }
 
@@ -219,10 +525,69 @@ mod gravity_tests {
             true,
         );
         assert_eq!(*state.component::<Body>(body).unwrap().velocity, velocity);
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         assert_eq!(*state.component::<Body>(body).unwrap().velocity, velocity);
     }
 
+    #[test]
+    fn close_body_is_coincident_with_larger_epsilon() {
+        let position = Point3::new(0.001, 0.0, 0.0);
+        let mut state = State::new();
+        let _ = create_body_entity(&mut state, Body::new().with_mass(EARTH_MASS), true);
+        let body = create_body_entity(&mut state, Body::new().with_position(position), false);
+        apply_gravity(&mut state, 1.0, EPSILON);
+        assert!(state.component::<Body>(body).unwrap().velocity.x < -EPSILON);
+        state
+            .component_mut::<Body>(body)
+            .unwrap()
+            .velocity
+            .set(Vector3::zero());
+        apply_gravity(&mut state, 1.0, 0.01);
+        assert_eq!(
+            *state.component::<Body>(body).unwrap().velocity,
+            Vector3::zero()
+        );
+    }
+
+    #[test]
+    fn ships_attract_each_other_only_with_mutual_gravity() {
+        let run = |mutual: bool| {
+            let mut state = State::new();
+            let ships = [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0)]
+                .iter()
+                .map(|position| {
+                    create_body_entity(
+                        &mut state,
+                        Body::new().with_mass(50_000.0).with_position(*position),
+                        false,
+                    )
+                })
+                .collect::<Vec<_>>();
+            for _ in 0..10 {
+                apply_gravity(&mut state, 1.0, EPSILON);
+                if mutual {
+                    apply_mutual_gravity(&mut state, 1.0, EPSILON);
+                }
+                apply_motion(&mut state, 1.0);
+            }
+            let body = |i: usize| state.component::<Body>(ships[i]).unwrap();
+            (
+                *body(0).velocity,
+                *body(1).velocity,
+                body(0).position.distance(*body(1).position),
+            )
+        };
+        let (v0, v1, distance) = run(false);
+        assert_eq!(v0, Vector3::zero());
+        assert_eq!(v1, Vector3::zero());
+        assert_eq!(distance, 1.0);
+        let (v0, v1, distance) = run(true);
+        assert!(v0.x > 0.0);
+        assert!(v1.x < 0.0);
+        assert!((v0 + v1).magnitude() < EPSILON);
+        assert!(distance < 1.0);
+    }
+
     #[test]
     fn body_falls_towards_gravity_source() {
         let position = Point3::new(20.0e+3, 0.0, 0.0);
@@ -230,7 +595,7 @@ mod gravity_tests {
         let _ = create_body_entity(&mut state, Body::new().with_mass(EARTH_MASS), true);
         let body = create_body_entity(&mut state, Body::new().with_position(position), false);
         assert_eq!(state.component::<Body>(body).unwrap().velocity.x, 0.0);
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         let v = *state.component::<Body>(body).unwrap().velocity;
         assert!(v.x < -EPSILON);
         assert_eq!(v.y, 0.0);
@@ -244,13 +609,13 @@ mod gravity_tests {
         let mut state_a = State::new();
         let _ = create_body_entity(&mut state_a, Body::new().with_mass(EARTH_MASS), true);
         let body_a = create_body_entity(&mut state_a, Body::new().with_position(position), false);
-        apply_gravity(&mut state_a, 1.0);
+        apply_gravity(&mut state_a, 1.0, EPSILON);
         let v_a = *state_a.component::<Body>(body_a).unwrap().velocity;
 
         let mut state_b = State::new();
         let _ = create_body_entity(&mut state_b, Body::new().with_mass(EARTH_MASS), true);
         let body_b = create_body_entity(&mut state_b, Body::new().with_position(position), false);
-        apply_gravity(&mut state_b, 0.5);
+        apply_gravity(&mut state_b, 0.5, EPSILON);
         let v_b = *state_b.component::<Body>(body_b).unwrap().velocity;
 
         assert!((v_a.x - (v_b.x * 2.0)).abs() < EPSILON);
@@ -262,7 +627,7 @@ mod gravity_tests {
         let mut state = State::new();
         let _ = create_body_entity(&mut state, Body::new().with_mass(EARTH_MASS), true);
         let body = create_body_entity(&mut state, Body::new().with_position(position), false);
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         let v = *state.component::<Body>(body).unwrap().velocity;
         assert!(v.x < -EPSILON);
         assert!(v.y.abs() < EPSILON);
@@ -283,7 +648,7 @@ mod gravity_tests {
             true,
         );
         let body = create_body_entity(&mut state, Body::new().with_position(position), false);
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         let v = *state.component::<Body>(body).unwrap().velocity;
         assert!(v.x.abs() < EPSILON);
         assert!(v.y.abs() < EPSILON);
@@ -301,7 +666,7 @@ mod gravity_tests {
             Body::new().with_position(position).with_velocity(velocity),
             false,
         );
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         assert_eq!(
             *state.component::<Body>(body).unwrap().gravity_parent,
             planet
@@ -312,6 +677,52 @@ mod gravity_tests {
         );
     }
 
+    #[test]
+    fn destroying_primary_makes_next_most_massive_primary() {
+        let mut state = State::new();
+        let sun = create_body_entity(&mut state, Body::new().with_mass(EARTH_MASS * 1000.0), true);
+        let small = create_body_entity(
+            &mut state,
+            Body::new()
+                .with_mass(EARTH_MASS / 2.0)
+                .with_position(Point3::new(-1.0e+6, 0.0, 0.0)),
+            true,
+        );
+        let big = create_body_entity(
+            &mut state,
+            Body::new()
+                .with_mass(EARTH_MASS)
+                .with_position(Point3::new(1.0e+6, 0.0, 0.0)),
+            true,
+        );
+        // Too far from either planet to be in their sphere of influence
+        let probe = create_body_entity(
+            &mut state,
+            Body::new().with_position(Point3::new(0.0, 1.0e+8, 0.0)),
+            false,
+        );
+        let parent = |state: &State, body| *state.component::<Body>(body).unwrap().gravity_parent;
+        apply_gravity(&mut state, 1.0, EPSILON);
+        assert_eq!(primary_body(&state), Some(sun));
+        assert_eq!(parent(&state, probe), sun);
+        state.destroy_entity(sun).unwrap();
+        apply_gravity(&mut state, 1.0, EPSILON);
+        assert_eq!(primary_body(&state), Some(big));
+        assert_eq!(parent(&state, probe), big);
+        assert_eq!(parent(&state, small), big);
+        assert_eq!(parent(&state, big), EntityKey::null());
+        // With equal masses the lower entity key is always chosen
+        state
+            .component_mut::<Body>(small)
+            .unwrap()
+            .mass
+            .set(EARTH_MASS);
+        apply_gravity(&mut state, 1.0, EPSILON);
+        let expected = small.min(big);
+        assert_eq!(primary_body(&state), Some(expected));
+        assert_eq!(parent(&state, probe), expected);
+    }
+
     #[test]
     fn gravity_parent_for_three_body_system() {
         let position_a = Point3::new(-2.0e+6, 27.5, 154.0);
@@ -328,7 +739,7 @@ mod gravity_tests {
             true,
         );
         let body = create_body_entity(&mut state, Body::new().with_position(position_b), false);
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         assert_eq!(
             *state.component::<Body>(sun).unwrap().gravity_parent,
             EntityKey::null()
@@ -349,7 +760,7 @@ mod gravity_tests {
         let mut state = State::new();
         let _ = create_body_entity(&mut state, Body::new().with_mass(EARTH_MASS), true);
         let body = create_body_entity(&mut state, Body::new().with_position(position), false);
-        apply_gravity(&mut state, 1.0);
+        apply_gravity(&mut state, 1.0, EPSILON);
         let v = *state.component::<Body>(body).unwrap().velocity;
         assert!(v.y.abs() < EPSILON);
         assert!(v.z.abs() < EPSILON);
@@ -390,33 +801,40 @@ mod collision_tests {
     fn two_body_test(
         body1: Body,
         body2: Body,
+        max_substeps: u32,
     ) -> (EntityKey, EntityKey, Vec<Collision>, Vec<Collision>) {
         let mut state = State::new();
         let c1 = MockController::new();
         let c2 = MockController::new();
-        let b1 = state.create_entity();
+        let b1 = state.create_entity().unwrap();
         state.install_component(b1, body1.with_collision_handler(Box::new(c1.clone())));
-        let b2 = state.create_entity();
+        let b2 = state.create_entity().unwrap();
         state.install_component(b2, body2.with_collision_handler(Box::new(c2.clone())));
-        apply_collisions(&state, 1.0);
+        apply_collisions(
+            &state,
+            1.0,
+            max_substeps,
+            EPSILON,
+            CollisionOrder::Discovery,
+        );
         let col1 = c1.read().unwrap().collisions.clone();
         let col2 = c2.read().unwrap().collisions.clone();
         (b1, b2, col1, col2)
     }
 
     fn create_body_entity(state: &mut State, body: Body) {
-        let entity = state.create_entity();
+        let entity = state.create_entity().unwrap();
         state.install_component(entity, body);
     }
 
     fn assert_do_not_collide(body1: Body, body2: Body) {
-        let (_, _, col1, col2) = two_body_test(body1, body2);
+        let (_, _, col1, col2) = two_body_test(body1, body2, 1);
         assert_eq!(col1, vec![]);
         assert_eq!(col2, vec![]);
     }
 
     fn assert_collides(body1: Body, body2: Body, time: f64) {
-        let (b1, b2, col1, col2) = two_body_test(body1, body2);
+        let (b1, b2, col1, col2) = two_body_test(body1, body2, 1);
         assert_eq!(col1.len(), 1);
         assert_eq!(col2.len(), 1);
         assert_eq!(col1[0].body, b2);
@@ -433,7 +851,7 @@ mod collision_tests {
             &mut state,
             Body::new().with_collision_handler(Box::new(c1.clone())),
         );
-        apply_collisions(&state, 1.0);
+        apply_collisions(&state, 1.0, 1, EPSILON, CollisionOrder::Discovery);
         assert_eq!(c1.read().unwrap().collisions, vec![]);
     }
 
@@ -447,7 +865,7 @@ mod collision_tests {
                 .with_sphere_shape(1.0)
                 .with_collision_handler(Box::new(c1.clone())),
         );
-        apply_collisions(&state, 1.0);
+        apply_collisions(&state, 1.0, 1, EPSILON, CollisionOrder::Discovery);
         assert_eq!(c1.read().unwrap().collisions, vec![]);
     }
 
@@ -462,7 +880,7 @@ mod collision_tests {
                 .with_sphere_shape(1.0)
                 .with_collision_handler(Box::new(c1.clone())),
         );
-        apply_collisions(&state, 1.0);
+        apply_collisions(&state, 1.0, 1, EPSILON, CollisionOrder::Discovery);
         assert_eq!(c1.read().unwrap().collisions, vec![]);
     }
 
@@ -482,10 +900,58 @@ mod collision_tests {
                 .with_position(Point3::new(2.0, 0.0, 0.0))
                 .with_velocity(Vector3::new(-2.0, 0.0, 0.0)),
         );
-        apply_collisions(&state, 0.25);
+        apply_collisions(&state, 0.25, 1, EPSILON, CollisionOrder::Discovery);
         assert_eq!(c1.read().unwrap().collisions, vec![]);
     }
 
+    /// Records (notified body, other body) for each collision
+    struct OrderLog {
+        body: EntityKey,
+        log: Arc<Mutex<Vec<(EntityKey, EntityKey)>>>,
+    }
+
+    impl CollisionHandler for OrderLog {
+        fn collision(&self, _state: &State, collision: &Collision) {
+            self.log.lock().unwrap().push((self.body, collision.body));
+        }
+    }
+
+    /// Creates three spheres in a row in the given order. The outer ones hit the middle one this
+    /// tick but not each other. Returns the collision order by index when ordered by mass.
+    fn chain_collision_order(masses: &[f64], creation_order: &[usize]) -> Vec<(usize, usize)> {
+        let velocities = [2.0, 0.0, -1.5];
+        let mut state = State::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut entities = vec![EntityKey::null(); masses.len()];
+        for &i in creation_order {
+            let entity = state.create_entity().unwrap();
+            Body::new()
+                .with_position(Point3::new(i as f64 * 3.0, 0.0, 0.0))
+                .with_velocity(Vector3::new(velocities[i], 0.0, 0.0))
+                .with_sphere_shape(1.0)
+                .with_mass(masses[i])
+                .with_collision_handler(Box::new(OrderLog {
+                    body: entity,
+                    log: log.clone(),
+                }))
+                .install(&mut state, entity);
+            entities[i] = entity;
+        }
+        apply_collisions(&state, 1.0, 1, EPSILON, CollisionOrder::Mass);
+        let index = |entity| entities.iter().position(|e| *e == entity).unwrap();
+        let log = log.lock().unwrap();
+        log.iter().map(|(a, b)| (index(*a), index(*b))).collect()
+    }
+
+    #[test]
+    fn mass_ordered_chain_collision_is_independent_of_creation_order() {
+        let masses = [1.0, 10.0, 100.0];
+        let expected = vec![(2, 1), (1, 2), (1, 0), (0, 1)];
+        assert_eq!(chain_collision_order(&masses, &[0, 1, 2]), expected);
+        assert_eq!(chain_collision_order(&masses, &[2, 1, 0]), expected);
+        assert_eq!(chain_collision_order(&masses, &[1, 2, 0]), expected);
+    }
+
     #[test]
     fn stationary_non_touching_spheres_do_not_collide() {
         assert_do_not_collide(
@@ -615,6 +1081,74 @@ mod collision_tests {
             0.304_564,
         );
     }
+
+    /// A small body moving a billion km per tick that grazes another small body half way through
+    fn fast_projectile() -> (Body, Body) {
+        (
+            Body::new().with_sphere_shape(1.0),
+            Body::new()
+                .with_position(Point3::new(-299_999_999.2, -400_000_000.6, 0.0))
+                .with_velocity(Vector3::new(6.0e+8, 8.0e+8, 0.0))
+                .with_sphere_shape(1.0),
+        )
+    }
+
+    #[test]
+    fn fast_projectile_tunnels_without_substeps() {
+        let (target, projectile) = fast_projectile();
+        let (_, _, col1, col2) = two_body_test(target, projectile, 1);
+        assert_eq!(col1, vec![]);
+        assert_eq!(col2, vec![]);
+    }
+
+    #[test]
+    fn fast_projectile_collides_with_substeps() {
+        let (target, projectile) = fast_projectile();
+        let (b1, b2, col1, col2) = two_body_test(target, projectile, 64);
+        assert_eq!(col1.len(), 1);
+        assert_eq!(col2.len(), 1);
+        assert_eq!(col1[0].body, b2);
+        assert_eq!(col2[0].body, b1);
+        assert!((col1[0].time_until - 0.5).abs() < EPSILON);
+    }
+
+    #[test]
+    fn collision_time_is_the_same_with_substeps() {
+        let (_, b2, col1, _) = two_body_test(
+            Body::new().with_sphere_shape(1.0),
+            Body::new()
+                .with_position(Point3::new(5.0, 0.0, 0.0))
+                .with_velocity(Vector3::new(-8.0, 0.0, 0.0))
+                .with_sphere_shape(1.0),
+            64,
+        );
+        assert_eq!(col1.len(), 1);
+        assert_eq!(col1[0].body, b2);
+        assert!((col1[0].time_until - 0.375).abs() < EPSILON);
+    }
+
+    #[test]
+    fn near_miss_fires_proximity_signal_once() {
+        let mut state = State::new();
+        let mut body1 = Body::new()
+            .with_sphere_shape(1.0)
+            .with_position(Point3::new(-20.0, 4.0, 0.0))
+            .with_velocity(Vector3::new(1.0, 0.0, 0.0));
+        let mut body2 = Body::new().with_sphere_shape(1.0);
+        // Nothing flushes the notifications, so the conduits accumulate every signal fired
+        let signal1 = body1.proximity.conduit(&state.notif_queue);
+        let signal2 = body2.proximity.conduit(&state.notif_queue);
+        let b1 = state.create_entity().unwrap();
+        state.install_component(b1, body1);
+        let b2 = state.create_entity().unwrap();
+        state.install_component(b2, body2);
+        for _ in 0..40 {
+            apply_proximity_alerts(&mut state, 1.0, 3.0, 1, EPSILON);
+            apply_motion(&mut state, 1.0);
+        }
+        assert_eq!(signal1.output(&state), Ok(vec![b2]));
+        assert_eq!(signal2.output(&state), Ok(vec![b1]));
+    }
 }
 
 #[cfg(test)]
@@ -623,7 +1157,7 @@ mod motion_tests {
     use super::*;
 
     fn create_body_entity(state: &mut State, body: Body) -> EntityKey {
-        let entity = state.create_entity();
+        let entity = state.create_entity().unwrap();
         state.install_component(entity, body);
         entity
     }
@@ -671,6 +1205,39 @@ mod motion_tests {
         );
     }
 
+    #[test]
+    fn frozen_body_stays_put_and_keeps_velocity() {
+        let mut state = State::new();
+        let velocity = Vector3::new(1.0, 0.0, 0.0);
+        let mut frozen_body = Body::new()
+            .with_position(Point3::new(0.0, 10.0, 0.0))
+            .with_velocity(velocity)
+            .with_mass(1e+15);
+        frozen_body.frozen.set(true);
+        let frozen = create_body_entity(&mut state, frozen_body);
+        state.install_component(frozen, GravityBody);
+        let moving = create_body_entity(
+            &mut state,
+            Body::new()
+                .with_position(Point3::new(0.0, 8.0, 0.0))
+                .with_velocity(velocity)
+                .with_mass(1e+15),
+        );
+        state.install_component(moving, GravityBody);
+        for _ in 0..3 {
+            apply_acceleration(&mut state, 1.0);
+            apply_gravity(&mut state, 1.0, EPSILON);
+            apply_motion(&mut state, 1.0);
+        }
+        let frozen_body = state.component::<Body>(frozen).unwrap();
+        assert_eq!(*frozen_body.position, Point3::new(0.0, 10.0, 0.0));
+        assert_eq!(*frozen_body.velocity, velocity);
+        let moving_body = state.component::<Body>(moving).unwrap();
+        // Pulled towards the frozen body as well as carried along by its velocity
+        assert!(moving_body.velocity.y > 0.0);
+        assert!(moving_body.position.x > 2.9);
+    }
+
     #[test]
     fn respects_dt() {
         let mut state = State::new();
@@ -685,3 +1252,107 @@ mod motion_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod totals_tests {
+    use super::*;
+
+    const EARTH_MASS: f64 = 5.972e+21;
+
+    fn total_momentum(state: &State) -> Vector3<f64> {
+        *state
+            .component::<God>(state.root_entity())
+            .unwrap()
+            .total_momentum
+    }
+
+    #[test]
+    fn reports_momentum_and_kinetic_energy() {
+        let mut state = State::new();
+        God::default().install(&mut state, &GameConfig::default());
+        let a = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(2.0)
+            .with_velocity(Vector3::new(3.0, 0.0, 0.0))
+            .install(&mut state, a);
+        let b = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(1.0)
+            .with_velocity(Vector3::new(0.0, -4.0, 0.0))
+            .install(&mut state, b);
+        update_totals(&mut state, EPSILON);
+        let god = state.component::<God>(state.root_entity()).unwrap();
+        assert_eq!(*god.total_momentum, Vector3::new(6.0, -4.0, 0.0));
+        assert!((*god.total_kinetic_energy - 17.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn momentum_is_conserved_when_bodies_collide() {
+        let mut state = State::new();
+        God::default().install(&mut state, &GameConfig::default());
+        let a = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(EARTH_MASS)
+            .with_sphere_shape(3000.0)
+            .with_position(Point3::new(-10.0e+3, 0.0, 0.0))
+            .with_velocity(Vector3::new(5.0, 0.0, 0.0))
+            .install(&mut state, a);
+        let b = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(EARTH_MASS / 2.0)
+            .with_sphere_shape(3000.0)
+            .with_position(Point3::new(10.0e+3, 1.0e+3, 0.0))
+            .with_velocity(Vector3::new(-4.0, 0.0, 0.0))
+            .install(&mut state, b);
+        update_totals(&mut state, EPSILON);
+        let initial = total_momentum(&state);
+        assert!(initial.magnitude() > EPSILON);
+        for _ in 0..2000 {
+            physics_tick(&mut state, 1.0, &GameConfig::default());
+            let error = (total_momentum(&state) - initial).magnitude();
+            assert!(error / initial.magnitude() < 1.0e-9);
+        }
+    }
+
+    fn total_energy(state: &State) -> f64 {
+        let god = state.component::<God>(state.root_entity()).unwrap();
+        *god.total_kinetic_energy + *god.total_potential_energy
+    }
+
+    #[test]
+    fn energy_is_conserved_in_stable_orbit() {
+        let sun_mass = 2.0e+27;
+        let planet_mass = 6.0e+21;
+        let distance = 1.5e+8;
+        let speed = (GRAVITATIONAL_CONSTANT * sun_mass / distance).sqrt();
+        let mut state = State::new();
+        God::default().install(&mut state, &GameConfig::default());
+        let sun = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(sun_mass)
+            .with_velocity(Vector3::new(0.0, -planet_mass / sun_mass * speed, 0.0))
+            .install(&mut state, sun);
+        let planet = state.create_entity().unwrap();
+        Body::new()
+            .with_mass(planet_mass)
+            .with_position(Point3::new(distance, 0.0, 0.0))
+            .with_velocity(Vector3::new(0.0, speed, 0.0))
+            .install(&mut state, planet);
+        update_totals(&mut state, EPSILON);
+        let potential_energy = *state
+            .component::<God>(state.root_entity())
+            .unwrap()
+            .total_potential_energy;
+        let expected = -GRAVITATIONAL_CONSTANT * sun_mass * planet_mass / distance;
+        assert!(((potential_energy - expected) / expected).abs() < 1.0e-12);
+        let initial = total_energy(&state);
+        let mut previous = initial;
+        for _ in 0..2000 {
+            physics_tick(&mut state, 3600.0, &GameConfig::default());
+            let energy = total_energy(&state);
+            assert!(((energy - previous) / initial).abs() < 1.0e-6);
+            assert!(((energy - initial) / initial).abs() < 1.0e-4);
+            previous = energy;
+        }
+    }
+}
@@ -1,18 +1,273 @@
 extern crate config;
 
-use config::{Config, ConfigError, Environment, File};
+use config::{Config, ConfigError, Environment, File, Value};
+use std::collections::HashMap;
+
+/// Config values that are safe to show to clients. Anything else (secrets, file paths, addresses)
+/// is left out, so new options stay hidden until they're added here.
+const PUBLIC_KEYS: &[&str] = &[
+    "server_name",
+    "tcp",
+    "websockets",
+    "webrtc",
+    "webrtc_jitter_depth",
+    "https",
+    "scenario",
+    "max_game_time",
+    "time_scale",
+    "idle_kinetic_energy",
+    "update_epsilon",
+    "suppress_echoes",
+    "max_collision_substeps",
+    "collision_order",
+    "physics_epsilon",
+    "proximity_alert_scale",
+    "spawn_policy",
+    "lobby",
+    "max_entities",
+    "debug_actions",
+    "protect_primary_body",
+    "max_speed",
+    "mutual_gravity",
+    "timestamp_events",
+    "read_only",
+    "max_values_per_tick",
+    "max_subscriptions",
+    "max_pending_gets",
+    "max_send_failures",
+    "flush_strategy",
+    "immediate_events",
+    "per_tick_events",
+    "max_decode_depth",
+    "max_decode_elements",
+    "delimiter",
+    "send_server_info",
+];
+
+/// Integer options that are counts or limits, and so are read as usizes
+const COUNT_KEYS: &[&str] = &[
+    "worker_threads",
+    "warmup_ticks",
+    "webrtc_jitter_depth",
+    "max_collision_substeps",
+    "max_entities",
+    "max_values_per_tick",
+    "max_subscriptions",
+    "max_pending_gets",
+    "max_send_failures",
+    "max_decode_depth",
+    "max_decode_elements",
+    "max_object_id_identities",
+    "max_object_ids_per_identity",
+];
+
+/// Float options that must be zero or more
+const NON_NEGATIVE_KEYS: &[&str] = &["time_scale", "update_epsilon"];
 
 /// Get the current configuration.
 pub fn get() -> Result<Config, ConfigError> {
+    let mut conf = defaults();
+    conf.merge(File::with_name("starscape"))?
+        .merge(Environment::with_prefix("STARSCAPE"))
+        .unwrap();
+    validate(&conf)?;
+    Ok(conf)
+}
+
+/// Catches values that would otherwise wrap around or trip an assertion once they're used
+fn validate(conf: &Config) -> Result<(), ConfigError> {
+    for key in COUNT_KEYS {
+        let value = conf.get_int(key)?;
+        if value < 0 {
+            return Err(ConfigError::Message(format!(
+                "{} must not be negative (got {})",
+                key, value
+            )));
+        }
+    }
+    for key in NON_NEGATIVE_KEYS {
+        let value = conf.get_float(key)?;
+        if value.is_nan() || value < 0.0 {
+            return Err(ConfigError::Message(format!(
+                "{} must be zero or more (got {})",
+                key, value
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn defaults() -> Config {
     let mut conf = Config::default();
+    conf.set_default("server_name", hostname()).unwrap();
+    conf.set_default("worker_threads", 0_i64).unwrap();
+    conf.set_default("handle_ctrlc", true).unwrap();
+    conf.set_default("warmup_ticks", 0_i64).unwrap();
+    conf.set_default("warmup_holds_clock", false).unwrap();
     conf.set_default("tcp", true).unwrap();
     conf.set_default("websockets", true).unwrap();
     conf.set_default("webrtc", true).unwrap();
+    conf.set_default("webrtc_bind_ip", "").unwrap();
+    conf.set_default("webrtc_public_ip", "").unwrap();
+    conf.set_default("webrtc_jitter_depth", 0_i64).unwrap();
     conf.set_default("https", true).unwrap();
+    conf.set_default("https_cert", "../ssl/cert.pem").unwrap();
+    conf.set_default("https_key", "../ssl/privkey.pem").unwrap();
     conf.set_default("http_content", "../web/dist").unwrap();
+    conf.set_default("open_browser", false).unwrap();
+    conf.set_default("browser_url", "").unwrap();
+    conf.set_default("scenario", "default").unwrap();
+    conf.set_default("system_seed", 0_i64).unwrap();
     conf.set_default("max_game_time", 1200.0).unwrap();
-    conf.merge(File::with_name("starscape"))?
-        .merge(Environment::with_prefix("STARSCAPE"))
+    conf.set_default("time_scale", 1.0).unwrap();
+    conf.set_default("idle_kinetic_energy", 0.0).unwrap();
+    conf.set_default("update_epsilon", 0.0).unwrap();
+    conf.set_default("suppress_echoes", false).unwrap();
+    conf.set_default("max_collision_substeps", 1_i64).unwrap();
+    conf.set_default("collision_order", "discovery").unwrap();
+    conf.set_default("physics_epsilon", 0.000_001).unwrap();
+    conf.set_default("proximity_alert_scale", 0.0).unwrap();
+    conf.set_default("spawn_policy", "allow").unwrap();
+    conf.set_default("lobby", false).unwrap();
+    conf.set_default("max_entities", 100_000_i64).unwrap();
+    conf.set_default("debug_actions", false).unwrap();
+    conf.set_default("admin_token", "").unwrap();
+    conf.set_default("watch_body", "").unwrap();
+    conf.set_default("protect_primary_body", false).unwrap();
+    conf.set_default("max_speed", 0.0).unwrap();
+    conf.set_default("mutual_gravity", false).unwrap();
+    conf.set_default("timestamp_events", false).unwrap();
+    conf.set_default("read_only", false).unwrap();
+    conf.set_default("max_values_per_tick", 0_i64).unwrap();
+    conf.set_default("max_subscriptions", 0_i64).unwrap();
+    conf.set_default("max_pending_gets", 0_i64).unwrap();
+    conf.set_default("max_send_failures", 1_i64).unwrap();
+    conf.set_default("flush_strategy", "immediate").unwrap();
+    conf.set_default("immediate_events", "").unwrap();
+    conf.set_default("per_tick_events", "").unwrap();
+    conf.set_default("flush_budget", 0.0).unwrap();
+    conf.set_default("max_decode_depth", 32_i64).unwrap();
+    conf.set_default("max_decode_elements", 10_000_i64).unwrap();
+    conf.set_default("delimiter", "").unwrap();
+    conf.set_default("record_requests", "").unwrap();
+    conf.set_default("replay_requests", "").unwrap();
+    conf.set_default("object_id_dir", "").unwrap();
+    conf.set_default("max_object_id_identities", 1000_i64)
         .unwrap();
-    Ok(conf)
+    conf.set_default("max_object_ids_per_identity", 10_000_i64)
+        .unwrap();
+    conf.set_default("send_server_info", false).unwrap();
+    conf.set_default("shutdown_timeout", 10.0).unwrap();
+    conf
+}
+
+/// The machine's hostname, or "starscape" if it can't be found
+fn hostname() -> String {
+    std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "starscape".to_string())
+}
+
+/// Resolved values of the public config options, as key/value pairs sorted by key
+pub fn public_values(conf: &Config) -> Result<Vec<(String, String)>, ConfigError> {
+    let table: HashMap<String, Value> = conf.clone().try_into()?;
+    let mut values: Vec<(String, String)> = table
+        .into_iter()
+        .filter(|(key, _)| PUBLIC_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key, value.to_string()))
+        .collect();
+    values.sort();
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(validate(&defaults()).is_ok());
+    }
+
+    #[test]
+    fn negative_counts_are_invalid() {
+        let mut conf = defaults();
+        conf.set("max_entities", -1).unwrap();
+        let error = validate(&conf).unwrap_err().to_string();
+        assert!(error.contains("max_entities"), "{}", error);
+    }
+
+    #[test]
+    fn negative_max_values_per_tick_is_invalid() {
+        let mut conf = defaults();
+        conf.set("max_values_per_tick", -1).unwrap();
+        let error = validate(&conf).unwrap_err().to_string();
+        assert!(error.contains("max_values_per_tick"), "{}", error);
+    }
+
+    #[test]
+    fn negative_max_collision_substeps_is_invalid() {
+        let mut conf = defaults();
+        conf.set("max_collision_substeps", -1).unwrap();
+        let error = validate(&conf).unwrap_err().to_string();
+        assert!(error.contains("max_collision_substeps"), "{}", error);
+    }
+
+    #[test]
+    fn negative_max_send_failures_is_invalid() {
+        let mut conf = defaults();
+        conf.set("max_send_failures", -1).unwrap();
+        let error = validate(&conf).unwrap_err().to_string();
+        assert!(error.contains("max_send_failures"), "{}", error);
+    }
+
+    #[test]
+    fn nan_update_epsilon_is_invalid() {
+        let mut conf = defaults();
+        conf.set("update_epsilon", f64::NAN).unwrap();
+        let error = validate(&conf).unwrap_err().to_string();
+        assert!(error.contains("update_epsilon"), "{}", error);
+    }
+
+    #[test]
+    fn negative_time_scale_is_invalid() {
+        let mut conf = defaults();
+        conf.set("time_scale", -0.5).unwrap();
+        let error = validate(&conf).unwrap_err().to_string();
+        assert!(error.contains("time_scale"), "{}", error);
+    }
+
+    #[test]
+    fn public_values_reflect_changed_options() {
+        let mut conf = defaults();
+        conf.set("time_scale", 2.5).unwrap();
+        let values = public_values(&conf).unwrap();
+        assert!(values.contains(&("time_scale".to_string(), "2.5".to_string())));
+        assert!(values.contains(&("tcp".to_string(), "true".to_string())));
+    }
+
+    #[test]
+    fn public_values_exclude_https_key() {
+        let conf = defaults();
+        assert!(conf.get_str("https_key").is_ok());
+        let values = public_values(&conf).unwrap();
+        assert!(values.iter().all(|(key, _)| key != "https_key"));
+        assert!(values.iter().all(|(_, value)| !value.contains("privkey")));
+    }
+
+    #[test]
+    fn public_values_only_include_allowed_keys() {
+        let mut conf = defaults();
+        conf.set("admin_token", "hunter2").unwrap();
+        conf.set("some_future_secret", "hunter3").unwrap();
+        let values = public_values(&conf).unwrap();
+        assert!(!values.is_empty());
+        assert!(values
+            .iter()
+            .all(|(key, _)| PUBLIC_KEYS.contains(&key.as_str())));
+        assert!(values.iter().all(|(_, value)| !value.contains("hunter")));
+    }
 }
@@ -2,10 +2,14 @@ use std::error::Error;
 
 /// Splits a stream of bytes into datagrams
 /// Assums a specific byte is always a delimiter
+/// If a datagram grows past max_buffer an error is returned in its place, and the rest of it (up
+/// to and including the next delimiter) is thrown away. Datagrams after that are unaffected.
 pub struct DatagramSplitter {
     pending_data: Vec<u8>,
     delimiter: u8,
     max_buffer: usize,
+    /// If we're in the middle of throwing away an oversized datagram
+    discarding: bool,
 }
 
 impl DatagramSplitter {
@@ -14,40 +18,41 @@ impl DatagramSplitter {
             pending_data: Vec::new(),
             delimiter,
             max_buffer,
+            discarding: false,
         }
     }
 
     /// Splits the given data into datagrams
     /// Saves any leftover bytes to be the start of the next datagram
-    pub fn data(&mut self, data: Vec<u8>) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    /// Each oversized datagram results in a single error, the splitter can continue to be used
+    pub fn data(&mut self, data: Vec<u8>) -> Vec<Result<Vec<u8>, Box<dyn Error>>> {
         let delimiter = self.delimiter;
-        let mut datagrams = data.split(|b| *b == delimiter);
-        let mut first = self.pending_data.split_off(0);
-        let first_of_data = datagrams.next().unwrap_or(&[]);
-        if first.len() + first_of_data.len() > self.max_buffer {
-            self.pending_data = vec![];
-            return Err("datagram too long".into());
-        }
-        first.extend(first_of_data);
-        let result: Result<Vec<Vec<u8>>, Box<dyn Error>> = std::iter::once(Ok(first))
-            .chain(datagrams.map(|d| {
-                if d.len() > self.max_buffer {
-                    Err("datagram too long".into())
-                } else {
-                    Ok(d.to_owned())
+        let mut result = Vec::new();
+        let mut chunks = data.split(|b| *b == delimiter).peekable();
+        while let Some(chunk) = chunks.next() {
+            let complete = chunks.peek().is_some();
+            if self.discarding {
+                // Drop everything until the end of the oversized datagram
+                if complete {
+                    self.discarding = false;
                 }
-            }))
-            .collect();
-        match result {
-            Ok(mut datagrams) => {
-                self.pending_data = datagrams.pop().unwrap();
-                Ok(datagrams.into_iter().filter(|d| !d.is_empty()).collect())
+                continue;
             }
-            Err(e) => {
+            if self.pending_data.len() + chunk.len() > self.max_buffer {
                 self.pending_data = vec![];
-                Err(e)
+                self.discarding = !complete;
+                result.push(Err("datagram too long".into()));
+                continue;
+            }
+            self.pending_data.extend(chunk);
+            if complete {
+                let datagram = self.pending_data.split_off(0);
+                if !datagram.is_empty() {
+                    result.push(Ok(datagram));
+                }
             }
         }
+        result
     }
 }
 
@@ -60,7 +65,11 @@ mod decoder_tests {
         let mut splitter = DatagramSplitter::new(b'|', usize::MAX);
         let mut result = Vec::new();
         for packet in &io {
-            result.push(splitter.data(packet.0.as_bytes().to_owned()).unwrap());
+            let datagrams: Result<Vec<Vec<u8>>, Box<dyn Error>> = splitter
+                .data(packet.0.as_bytes().to_owned())
+                .into_iter()
+                .collect();
+            result.push(datagrams.unwrap());
         }
         let result_strs: Vec<Vec<&str>> = result
             .iter()
@@ -132,18 +141,66 @@ mod decoder_tests {
         assert_splits_to(vec![("abc|", vec!["abc"]), ("|xyz|||", vec!["xyz"])]);
     }
 
+    /// Feeds the string to the splitter and returns the results as strings, with errors as "ERR"
+    fn split_lossy(splitter: &mut DatagramSplitter, data: &str) -> Vec<String> {
+        splitter
+            .data(data.as_bytes().to_owned())
+            .into_iter()
+            .map(|d| match d {
+                Ok(d) => String::from_utf8(d).expect("failed to convert to UTF-8 string"),
+                Err(_) => "ERR".to_string(),
+            })
+            .collect()
+    }
+
+    fn is_ok(results: Vec<Result<Vec<u8>, Box<dyn Error>>>) -> bool {
+        results.iter().all(|d| d.is_ok())
+    }
+
     #[test]
     fn does_not_error_if_each_packet_small_enough() {
         let mut splitter = DatagramSplitter::new(b'|', 4);
-        assert!(splitter.data("abc|".as_bytes().to_owned()).is_ok());
-        assert!(splitter.data("abc|xyz|i".as_bytes().to_owned()).is_ok());
-        assert!(splitter.data("|ab".as_bytes().to_owned()).is_ok());
+        assert!(is_ok(splitter.data("abc|".as_bytes().to_owned())));
+        assert!(is_ok(splitter.data("abc|xyz|i".as_bytes().to_owned())));
+        assert!(is_ok(splitter.data("|ab".as_bytes().to_owned())));
     }
 
     #[test]
     fn erros_with_too_much_data() {
         let mut splitter = DatagramSplitter::new(b'|', 4);
-        assert!(splitter.data("ab|ab".as_bytes().to_owned()).is_ok());
-        assert!(splitter.data("xyz".as_bytes().to_owned()).is_err());
+        assert!(is_ok(splitter.data("ab|ab".as_bytes().to_owned())));
+        assert!(!is_ok(splitter.data("xyz".as_bytes().to_owned())));
+    }
+
+    #[test]
+    fn recovers_after_complete_oversized_datagram() {
+        let mut splitter = DatagramSplitter::new(b'|', 4);
+        assert_eq!(
+            split_lossy(&mut splitter, "abcdefg|ab|xyz|"),
+            vec!["ERR", "ab", "xyz"]
+        );
+        assert_eq!(split_lossy(&mut splitter, "abc|"), vec!["abc"]);
+    }
+
+    #[test]
+    fn discards_rest_of_oversized_datagram_across_calls() {
+        let mut splitter = DatagramSplitter::new(b'|', 4);
+        assert_eq!(split_lossy(&mut splitter, "ab|abc"), vec!["ab"]);
+        assert_eq!(split_lossy(&mut splitter, "defg"), vec!["ERR"]);
+        assert_eq!(
+            split_lossy(&mut splitter, "hijklmnop"),
+            Vec::<String>::new()
+        );
+        assert_eq!(split_lossy(&mut splitter, "qr|xyz|a"), vec!["xyz"]);
+        assert_eq!(split_lossy(&mut splitter, "b|"), vec!["ab"]);
+    }
+
+    #[test]
+    fn reports_each_oversized_datagram_once() {
+        let mut splitter = DatagramSplitter::new(b'|', 4);
+        assert_eq!(
+            split_lossy(&mut splitter, "abcdefg|hijklmn|ok|"),
+            vec!["ERR", "ERR", "ok"]
+        );
     }
 }
@@ -0,0 +1,20 @@
+use std::{fs, io, path::Path};
+
+/// The parts of the filesystem the server checks at startup, so they can be mocked in tests
+pub trait Filesystem {
+    /// Returns an error explaining why if the path is not a directory that can be read
+    fn check_readable_dir(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The actual filesystem
+pub struct RealFilesystem;
+
+impl Filesystem for RealFilesystem {
+    fn check_readable_dir(&self, path: &Path) -> io::Result<()> {
+        if !fs::metadata(path)?.is_dir() {
+            return Err(io::Error::other("not a directory"));
+        }
+        fs::read_dir(path)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,63 @@
 use std::{
+    collections::VecDeque,
     thread::sleep,
     time::{Duration, Instant},
 };
 
+/// Number of recent ticks considered when deciding if the game is consistently running slow
+const DRIFT_WINDOW: usize = 20;
+/// If at least this many ticks in the window went over budget, the game is considered to be drifting
+const DRIFT_THRESHOLD: usize = 10;
+
+/// Keeps track of recent tick overruns so a one-off hiccup can be told apart from the game
+/// consistently taking longer than it should.
+struct OverrunTracker {
+    /// How far over budget (in seconds) each recent tick went, 0 for ticks that were on time
+    recent: VecDeque<f64>,
+    /// If the current stretch of drift has already been reported
+    reported: bool,
+    /// Ticks left to ignore while the game warms up
+    warmup: usize,
+}
+
+impl OverrunTracker {
+    fn new() -> Self {
+        Self {
+            recent: VecDeque::with_capacity(DRIFT_WINDOW),
+            reported: false,
+            warmup: 0,
+        }
+    }
+
+    /// Records how far over budget a tick went (0 if it was on time). The first time sustained
+    /// drift is detected returns the average overrun of the recent over budget ticks. Won't return
+    /// anything again until the window has been clear of overruns. Ticks during warmup are ignored.
+    fn record(&mut self, overrun: f64) -> Option<f64> {
+        if self.warmup > 0 {
+            self.warmup -= 1;
+            return None;
+        }
+        if self.recent.len() >= DRIFT_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(overrun);
+        let (count, total) = self
+            .recent
+            .iter()
+            .filter(|overrun| **overrun > 0.0)
+            .fold((0, 0.0), |(count, total), overrun| {
+                (count + 1, total + overrun)
+            });
+        if count == 0 {
+            self.reported = false;
+        } else if count >= DRIFT_THRESHOLD && !self.reported {
+            self.reported = true;
+            return Some(total / count as f64);
+        }
+        None
+    }
+}
+
 /// In charge of sleeping to keep the game timed correctly, regardless of how long processing each
 /// tick takes.
 pub struct Metronome {
@@ -17,6 +72,7 @@ pub struct Metronome {
     /// make sense to slow the game down rather than use up the entire time budget. This is because
     /// clients should be able to mamke a roundtrip each tick.
     min_sleep: f64,
+    overruns: OverrunTracker,
 }
 
 impl Metronome {
@@ -31,9 +87,16 @@ impl Metronome {
             prev_tick_start: Instant::now(),
             target_tick,
             min_sleep,
+            overruns: OverrunTracker::new(),
         }
     }
 
+    /// Slow ticks are expected while the game warms up, so this many ticks won't count towards
+    /// warning about the game running slow
+    pub fn set_warmup_ticks(&mut self, ticks: usize) {
+        self.overruns.warmup = ticks;
+    }
+
     /// Sleeps for the remainder of the tick. That is, sleeps for however long is required so that
     /// the time at return is target_tick greater than the time at the previous return. If the
     /// required sleep time is less than min_sleep then there is no drift. If the rest of the game
@@ -46,12 +109,21 @@ impl Metronome {
             sleep(Duration::from_secs_f64(sleep_time));
             // doing it this way instead of taking current time prevents drift
             self.prev_tick_start += Duration::from_secs_f64(self.target_tick);
+            self.overruns.record(0.0);
         } else {
-            warn!(
+            let overrun = self.min_sleep - sleep_time;
+            // A single slow tick is usually just a hiccup, so only warn if it keeps happening
+            debug!(
                 "tick took {:?} which is {:?} too long",
                 Duration::from_secs_f64(elapsed),
-                Duration::from_secs_f64(self.min_sleep - sleep_time)
+                Duration::from_secs_f64(overrun)
             );
+            if let Some(average) = self.overruns.record(overrun) {
+                warn!(
+                    "ticks are consistently taking too long, on average {:?} over budget",
+                    Duration::from_secs_f64(average)
+                );
+            }
             if self.min_sleep > 0.0 {
                 sleep(Duration::from_secs_f64(self.min_sleep))
             }
@@ -74,6 +146,52 @@ mod tests {
         }
     }
 
+    #[test]
+    fn single_overrun_is_not_reported() {
+        let mut tracker = OverrunTracker::new();
+        for i in 0..DRIFT_WINDOW * 2 {
+            let overrun = if i == 5 { 0.5 } else { 0.0 };
+            assert_eq!(tracker.record(overrun), None);
+        }
+    }
+
+    #[test]
+    fn sustained_overruns_are_reported_once() {
+        let mut tracker = OverrunTracker::new();
+        let reports: Vec<f64> = (0..DRIFT_WINDOW * 3)
+            .filter_map(|i| tracker.record(if i % 2 == 0 { 0.1 } else { 0.3 }))
+            .collect();
+        assert_eq!(reports.len(), 1);
+        assert!((reports[0] - 0.2).abs() < 0.000_001);
+    }
+
+    #[test]
+    fn overruns_during_warmup_are_not_reported() {
+        let mut tracker = OverrunTracker::new();
+        tracker.warmup = DRIFT_WINDOW * 2;
+        for _ in 0..DRIFT_WINDOW * 2 {
+            assert_eq!(tracker.record(0.5), None);
+        }
+        let reports = (0..DRIFT_WINDOW)
+            .filter_map(|_| tracker.record(0.5))
+            .count();
+        assert_eq!(reports, 1);
+    }
+
+    #[test]
+    fn drift_is_reported_again_after_recovering() {
+        let mut tracker = OverrunTracker::new();
+        let mut reports = 0;
+        for overrun in &[0.1, 0.0, 0.1] {
+            for _ in 0..DRIFT_WINDOW {
+                if tracker.record(*overrun).is_some() {
+                    reports += 1;
+                }
+            }
+        }
+        assert_eq!(reports, 2);
+    }
+
     #[test]
     fn sleeps_for_correct_time() {
         let mut m = Metronome::new(SHORT_TIME, 0.0);
@@ -5,18 +5,32 @@ use super::*;
 mod color_rgb;
 pub mod config;
 mod datagram_splitter;
+mod filesystem;
 mod initializable;
 mod metronome;
+mod normalize_angle;
 mod or_log;
+mod quit_signal;
+mod runtime;
+mod seeded_rng;
+mod short_type_name;
+mod shutdown_watchdog;
 #[cfg(test)]
 mod test_helpers;
 mod thin_ptr;
 
 pub use color_rgb::ColorRGB;
 pub use datagram_splitter::DatagramSplitter;
+pub use filesystem::{Filesystem, RealFilesystem};
 pub use initializable::Initializable;
 pub use metronome::Metronome;
+pub use normalize_angle::normalize_angle;
 pub use or_log::OrLog;
+pub use quit_signal::init_quit_signal;
+pub use runtime::build_runtime;
+pub use seeded_rng::SeededRng;
+pub use short_type_name::short_type_name;
+pub use shutdown_watchdog::ShutdownWatchdog;
 #[cfg(test)]
 pub use test_helpers::*;
 pub use thin_ptr::ThinPtr;
@@ -0,0 +1,46 @@
+use std::f64::consts::TAU;
+
+/// Wraps an angle in radians to the range [0, 2π), so clients always get the same value for the
+/// same direction
+pub fn normalize_angle(angle: f64) -> f64 {
+    let wrapped = angle.rem_euclid(TAU);
+    // Tiny negative angles round up to exactly 2π
+    if wrapped >= TAU {
+        0.0
+    } else {
+        wrapped
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 0.000_001;
+
+    #[test]
+    fn angles_in_range_are_unchanged() {
+        assert_eq!(normalize_angle(0.0), 0.0);
+        assert_eq!(normalize_angle(1.5), 1.5);
+    }
+
+    #[test]
+    fn angles_out_of_range_are_wrapped() {
+        assert!((normalize_angle(TAU + 1.0) - 1.0).abs() < EPSILON);
+        assert!((normalize_angle(-1.0) - (TAU - 1.0)).abs() < EPSILON);
+        assert_eq!(normalize_angle(TAU), 0.0);
+    }
+
+    #[test]
+    fn angles_near_full_turn_are_consistent() {
+        let below = normalize_angle(TAU - 1e-9);
+        let negative = normalize_angle(-1e-9);
+        assert!(below < TAU);
+        assert!((below - negative).abs() < EPSILON);
+        let tiny = normalize_angle(1e-9);
+        let above = normalize_angle(TAU + 1e-9);
+        assert!((tiny - above).abs() < EPSILON);
+        assert_eq!(normalize_angle(-1e-20), 0.0);
+    }
+}
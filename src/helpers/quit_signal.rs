@@ -0,0 +1,43 @@
+use super::*;
+
+/// Gets a message when the user quits with Ctrl+C, which gives us graceful shutdown. If
+/// handle_ctrlc is false no handler is installed and the default signal behavior applies, which is
+/// what supervisors such as systemd expect.
+pub fn init_quit_signal(handle_ctrlc: bool) -> Receiver<()> {
+    init_quit_signal_with(handle_ctrlc, |tx| {
+        ctrlc::set_handler(move || {
+            warn!("processing Ctrl+C from user…");
+            tx.send(()).expect("failed to send quit signal");
+        })
+        .expect("error setting Ctrl+C handler");
+    })
+}
+
+fn init_quit_signal_with(handle_ctrlc: bool, install: impl FnOnce(Sender<()>)) -> Receiver<()> {
+    let (tx, rx) = channel();
+    if handle_ctrlc {
+        install(tx);
+    }
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::TryRecvError;
+
+    #[test]
+    fn handler_is_installed_when_enabled() {
+        let mut installed = None;
+        let rx = init_quit_signal_with(true, |tx| installed = Some(tx));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        installed.unwrap().send(()).unwrap();
+        assert_eq!(rx.try_recv(), Ok(()));
+    }
+
+    #[test]
+    fn no_handler_is_installed_when_disabled() {
+        let rx = init_quit_signal_with(false, |_| panic!("handler should not be installed"));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+}
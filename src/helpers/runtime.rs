@@ -0,0 +1,35 @@
+use tokio::runtime::{Builder, Runtime};
+
+/// Builds the multi-threaded async runtime the server runs on. 0 worker threads uses tokio's
+/// default (one per core), which is generally far more than the server needs.
+pub fn build_runtime(worker_threads: usize) -> std::io::Result<Runtime> {
+    let mut builder = Builder::new();
+    builder.threaded_scheduler().enable_all();
+    if worker_threads > 0 {
+        builder.core_threads(worker_threads);
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashSet, thread};
+
+    #[test]
+    fn tasks_run_on_configured_number_of_workers() {
+        let mut rt = build_runtime(1).unwrap();
+        let threads: HashSet<thread::ThreadId> = rt.block_on(async {
+            let handles: Vec<_> = (0..20)
+                .map(|_| tokio::spawn(async { thread::current().id() }))
+                .collect();
+            let mut threads = HashSet::new();
+            for handle in handles {
+                threads.insert(handle.await.unwrap());
+            }
+            threads
+        });
+        assert_eq!(threads.len(), 1);
+        assert!(!threads.contains(&thread::current().id()));
+    }
+}
@@ -0,0 +1,66 @@
+/// A small deterministic random number generator (SplitMix64). Not suitable for anything security
+/// related, but the same seed always produces the same sequence on every platform.
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in the range [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// Returns a value in the range [min, max)
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + self.next_f64() * (max - min)
+    }
+
+    /// Returns a value in the range [min, max] (inclusive)
+    pub fn range_inclusive(&mut self, min: u64, max: u64) -> u64 {
+        min + self.next_u64() % (max - min + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        let mut a = SeededRng::new(42);
+        let mut b = SeededRng::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_sequences() {
+        let mut a = SeededRng::new(1);
+        let mut b = SeededRng::new(2);
+        assert_ne!(a.next_u64(), b.next_u64());
+    }
+
+    #[test]
+    fn range_stays_in_bounds() {
+        let mut rng = SeededRng::new(7);
+        for _ in 0..1000 {
+            let f = rng.range(-2.0, 3.0);
+            assert!(f >= -2.0 && f < 3.0);
+            let i = rng.range_inclusive(3, 5);
+            assert!(i >= 3 && i <= 5);
+        }
+    }
+}
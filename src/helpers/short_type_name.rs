@@ -0,0 +1,47 @@
+use super::*;
+
+/// Like std::any::type_name(), but with module paths removed. For example
+/// `Vec<alloc::string::String>` becomes `Vec<String>`. Only intended for debugging and logging.
+pub fn short_type_name<T: ?Sized>() -> String {
+    let full = type_name::<T>();
+    let mut result = String::with_capacity(full.len());
+    let mut segment_start = 0;
+    let mut chars = full.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            result.truncate(segment_start);
+        } else {
+            result.push(c);
+            if !(c.is_alphanumeric() || c == '_') {
+                segment_start = result.len();
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct LocalType;
+
+    #[test]
+    fn removes_module_path() {
+        assert_eq!(short_type_name::<LocalType>(), "LocalType");
+    }
+
+    #[test]
+    fn leaves_primitives_alone() {
+        assert_eq!(short_type_name::<f64>(), "f64");
+    }
+
+    #[test]
+    fn removes_module_paths_from_generic_arguments() {
+        assert_eq!(
+            short_type_name::<Option<(String, LocalType)>>(),
+            "Option<(String, LocalType)>"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use super::*;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread::JoinHandle;
+
+/// Calls on_timeout (which generally exits the process) unless dropped within the timeout. Started
+/// when shutdown begins, so a wedged session or task can't keep the server from exiting.
+pub struct ShutdownWatchdog {
+    done_tx: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ShutdownWatchdog {
+    pub fn start(timeout: Duration, on_timeout: impl FnOnce() + Send + 'static) -> Self {
+        let (done_tx, done_rx) = channel::<()>();
+        let thread = std::thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = done_rx.recv_timeout(timeout) {
+                on_timeout();
+            }
+        });
+        Self {
+            done_tx: Some(done_tx),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for ShutdownWatchdog {
+    fn drop(&mut self) {
+        // Disconnecting the channel tells the thread shutdown finished
+        self.done_tx.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn does_nothing_if_dropped_in_time() {
+        let fired = Arc::new(AtomicBool::new(false));
+        let watchdog = {
+            let fired = fired.clone();
+            ShutdownWatchdog::start(Duration::from_secs(10), move || fired.store(true, SeqCst))
+        };
+        drop(watchdog);
+        assert!(!fired.load(SeqCst));
+    }
+}
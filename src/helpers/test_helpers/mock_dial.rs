@@ -0,0 +1,21 @@
+use super::*;
+
+/// A component with a single integer setting, for testing properties
+pub struct Dial {
+    pub setting: Element<i64>,
+}
+
+/// Installs a dial with the given setting on the entity and returns a conduit for it, which the
+/// caller can wrap before installing it as a property
+pub fn install_dial(state: &mut State, entity: EntityKey, setting: i64) -> impl Conduit<i64, i64> {
+    state.install_component(
+        entity,
+        Dial {
+            setting: Element::new(setting),
+        },
+    );
+    RWConduit::new(
+        move |state| Ok(&state.component::<Dial>(entity)?.setting),
+        move |state, value| Ok(state.component_mut::<Dial>(entity)?.setting.set(value)),
+    )
+}
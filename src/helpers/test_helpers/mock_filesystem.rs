@@ -0,0 +1,25 @@
+use super::*;
+use std::{io, path::Path};
+
+/// A filesystem where only the given directories exist
+pub struct MockFilesystem {
+    dirs: Vec<String>,
+}
+
+impl MockFilesystem {
+    pub fn new(dirs: &[&str]) -> Self {
+        Self {
+            dirs: dirs.iter().map(|dir| dir.to_string()).collect(),
+        }
+    }
+}
+
+impl Filesystem for MockFilesystem {
+    fn check_readable_dir(&self, path: &Path) -> io::Result<()> {
+        if self.dirs.iter().any(|dir| Path::new(dir) == path) {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, "no such directory"))
+        }
+    }
+}
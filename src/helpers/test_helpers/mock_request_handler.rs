@@ -3,6 +3,11 @@ use super::*;
 struct MockRequestHandlerInner {
     should_return: RequestResult<()>,
     requests: Vec<Request>,
+    disconnect_requests: Vec<ConnectionKey>,
+    connection_list_requests: Vec<ConnectionKey>,
+    closed_connections: Vec<ConnectionKey>,
+    priorities: HashMap<EntityKey, f64>,
+    time: f64,
 }
 
 struct MockSub(EntityKey, String);
@@ -16,12 +21,50 @@ impl MockRequestHandler {
         Self(Arc::new(Mutex::new(MockRequestHandlerInner {
             should_return,
             requests: Vec::new(),
+            disconnect_requests: Vec::new(),
+            connection_list_requests: Vec::new(),
+            closed_connections: Vec::new(),
+            priorities: HashMap::new(),
+            time: 0.0,
         })))
     }
 
+    /// The connection will be returned from the next take_disconnect_requests()
+    pub fn request_disconnect(&self, connection: ConnectionKey) {
+        self.0.lock().unwrap().disconnect_requests.push(connection);
+    }
+
+    /// The connection will be returned from the next take_connection_list_requests()
+    pub fn request_connection_list(&self, connection: ConnectionKey) {
+        self.0
+            .lock()
+            .unwrap()
+            .connection_list_requests
+            .push(connection);
+    }
+
+    /// Returned from send_priority() for the entity, which otherwise returns 0
+    pub fn set_priority(&self, entity: EntityKey, priority: f64) {
+        self.0.lock().unwrap().priorities.insert(entity, priority);
+    }
+
+    /// Returned from time()
+    pub fn set_time(&self, time: f64) {
+        self.0.lock().unwrap().time = time;
+    }
+
     pub fn assert_requests_eq(&self, expected: Vec<Request>) {
         assert_eq!(self.0.lock().unwrap().requests, expected);
     }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.0.lock().unwrap().requests.clone()
+    }
+
+    /// Connections connection_closed() has been called with
+    pub fn closed_connections(&self) -> Vec<ConnectionKey> {
+        self.0.lock().unwrap().closed_connections.clone()
+    }
 }
 
 impl RequestHandler for MockRequestHandler {
@@ -70,6 +113,31 @@ impl RequestHandler for MockRequestHandler {
             .map(|()| Box::new(MockSub(e, n.to_string())) as Box<dyn Any>)
     }
 
+    fn get_property_with_options(
+        &self,
+        c: ConnectionKey,
+        e: EntityKey,
+        n: &str,
+        _: &SubscribeOptions,
+    ) -> RequestResult<Value> {
+        self.get_property(c, e, n)
+    }
+
+    fn subscribe_with_options(
+        &mut self,
+        _: ConnectionKey,
+        e: EntityKey,
+        n: &str,
+        o: &SubscribeOptions,
+    ) -> RequestResult<Box<dyn Any>> {
+        let mut lock = self.0.lock().unwrap();
+        lock.requests
+            .push(Request::subscribe_with_options(e, n.to_string(), o.clone()));
+        lock.should_return
+            .clone()
+            .map(|()| Box::new(MockSub(e, n.to_string())) as Box<dyn Any>)
+    }
+
     fn unsubscribe(&mut self, subscription: Box<dyn Any>) -> RequestResult<()> {
         let mut lock = self.0.lock().unwrap();
         let sub: Box<MockSub> = subscription.downcast().unwrap();
@@ -77,4 +145,38 @@ impl RequestHandler for MockRequestHandler {
             .push(Request::unsubscribe(sub.0, sub.1.to_string()));
         lock.should_return.clone()
     }
+
+    fn take_disconnect_requests(&mut self) -> Vec<ConnectionKey> {
+        std::mem::take(&mut self.0.lock().unwrap().disconnect_requests)
+    }
+
+    fn take_reconcile_request(&mut self) -> bool {
+        false
+    }
+
+    fn take_connection_list_requests(&mut self) -> Vec<ConnectionKey> {
+        std::mem::take(&mut self.0.lock().unwrap().connection_list_requests)
+    }
+
+    fn connection_closed(&mut self, connection: ConnectionKey) {
+        self.0.lock().unwrap().closed_connections.push(connection);
+    }
+
+    fn entity_exists(&self, _: EntityKey) -> bool {
+        true
+    }
+
+    fn send_priority(&self, _: ConnectionKey, entity: EntityKey) -> f64 {
+        self.0
+            .lock()
+            .unwrap()
+            .priorities
+            .get(&entity)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    fn time(&self) -> f64 {
+        self.0.lock().unwrap().time
+    }
 }
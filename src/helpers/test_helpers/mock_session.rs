@@ -5,6 +5,11 @@ struct MockSessionInner {
     pub bundles: Vec<Vec<u8>>,
     pub should_error: bool,
     pub is_closed: bool,
+    /// How many more bundles to fail with WouldBlock
+    pub would_block: usize,
+    /// How many more bundles to fail with a transient error (TimedOut)
+    pub transient_errors: usize,
+    pub flush_count: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -16,6 +21,9 @@ impl MockSession {
             bundles: Vec::new(),
             should_error,
             is_closed: false,
+            would_block: 0,
+            transient_errors: 0,
+            flush_count: 0,
         })))
     }
 
@@ -31,6 +39,30 @@ impl MockSession {
         assert_eq!(actual, expected);
     }
 
+    pub fn bundles(&self) -> Vec<Vec<u8>> {
+        self.0.lock().unwrap().bundles.clone()
+    }
+
+    /// Like bundles(), but the next call only returns bundles sent after this one
+    pub fn take_bundles(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.0.lock().unwrap().bundles)
+    }
+
+    /// The next count bundles fail with WouldBlock and are not recorded
+    pub fn set_would_block(&self, count: usize) {
+        self.0.lock().unwrap().would_block = count;
+    }
+
+    /// The next count bundles fail with TimedOut and are not recorded
+    pub fn set_transient_errors(&self, count: usize) {
+        self.0.lock().unwrap().transient_errors = count;
+    }
+
+    /// How many times the session has been flushed
+    pub fn flush_count(&self) -> usize {
+        self.0.lock().unwrap().flush_count
+    }
+
     pub fn is_closed(&self) -> bool {
         self.0.lock().unwrap().is_closed
     }
@@ -42,6 +74,16 @@ impl Session for MockSession {
         if lock.is_closed {
             panic!("sent bundle after MockSession closed");
         }
+        if lock.would_block > 0 {
+            lock.would_block -= 1;
+            return Err(Box::new(std::io::Error::from(
+                std::io::ErrorKind::WouldBlock,
+            )));
+        }
+        if lock.transient_errors > 0 {
+            lock.transient_errors -= 1;
+            return Err(Box::new(std::io::Error::from(std::io::ErrorKind::TimedOut)));
+        }
         lock.bundles.push(data.to_vec());
         if lock.should_error {
             Err("MockSession error".into())
@@ -54,6 +96,11 @@ impl Session for MockSession {
         usize::MAX
     }
 
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().flush_count += 1;
+        Ok(())
+    }
+
     fn close(&mut self) {
         let mut lock = self.0.lock().unwrap();
         if lock.is_closed {
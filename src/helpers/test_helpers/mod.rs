@@ -8,7 +8,9 @@ use std::{
 };
 
 mod attempt_any_to_string;
+mod mock_dial;
 mod mock_event_handler;
+mod mock_filesystem;
 mod mock_inbound_handler;
 mod mock_keys;
 mod mock_request_handler;
@@ -17,9 +19,13 @@ mod mock_subscriber;
 mod provision_socket;
 mod run_with_timeout;
 mod run_with_tokio;
+mod shared_buffer;
+mod test_client;
 
 pub use attempt_any_to_string::*;
+pub use mock_dial::*;
 pub use mock_event_handler::*;
+pub use mock_filesystem::*;
 pub use mock_inbound_handler::*;
 pub use mock_keys::*;
 pub use mock_request_handler::*;
@@ -28,3 +34,5 @@ pub use mock_subscriber::*;
 pub use provision_socket::*;
 pub use run_with_timeout::*;
 pub use run_with_tokio::*;
+pub use shared_buffer::SharedBuffer;
+pub use test_client::TestClient;
@@ -0,0 +1,169 @@
+use super::*;
+
+type SharedHandler = Arc<Mutex<Option<Box<dyn InboundBundleHandler>>>>;
+
+struct TestClientBuilder {
+    handler: SharedHandler,
+    session: MockSession,
+}
+
+impl Debug for TestClientBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TestClientBuilder")
+    }
+}
+
+impl SessionBuilder for TestClientBuilder {
+    fn build(
+        self: Box<Self>,
+        handler: Box<dyn InboundBundleHandler>,
+    ) -> Result<Box<dyn Session>, Box<dyn Error>> {
+        *self.handler.lock().unwrap() = Some(handler);
+        Ok(Box::new(self.session))
+    }
+}
+
+/// A JSON client on a mock session, for testing protocol-level behavior against a real engine
+pub struct TestClient {
+    handler: SharedHandler,
+    session: MockSession,
+}
+
+impl TestClient {
+    /// Sends the client to the engine the sender belongs to, it's connected on the next tick
+    pub fn connect(new_session_tx: &Sender<Box<dyn SessionBuilder>>) -> Self {
+        let handler = Arc::new(Mutex::new(None));
+        let session = MockSession::new(false);
+        new_session_tx
+            .send(Box::new(TestClientBuilder {
+                handler: handler.clone(),
+                session: session.clone(),
+            }))
+            .expect("failed to send test client");
+        Self { handler, session }
+    }
+
+    /// Sends a single JSON request, which is processed on the next tick
+    pub fn send(&self, request: &str) {
+        self.handler
+            .lock()
+            .unwrap()
+            .as_mut()
+            .expect("test client not connected yet, tick the engine first")
+            .handle(format!("{}\n", request).as_bytes());
+    }
+
+    /// Decoded events the client has received since the last call
+    pub fn take_events(&self) -> Vec<serde_json::Value> {
+        self.session
+            .take_bundles()
+            .iter()
+            .map(|bundle| serde_json::from_slice(bundle).expect("event is not valid JSON"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(
+        new_session_rx: Receiver<Box<dyn SessionBuilder>>,
+        server_name: Option<String>,
+    ) -> Engine {
+        Engine::new(
+            new_session_rx,
+            0.1,
+            f64::INFINITY,
+            usize::MAX,
+            server_name,
+            ConnectionConfig::default(),
+            |state| {
+                let root = state.root_entity();
+                install_dial(state, root, 0).install_property(state, root, "setting");
+            },
+            |_, _| (),
+        )
+    }
+
+    #[test]
+    fn subscribed_client_gets_value_then_update() {
+        let (new_session_tx, new_session_rx) = channel();
+        let mut engine = engine(new_session_rx, None);
+        let client = TestClient::connect(&new_session_tx);
+        engine.tick();
+        client.send(r#"{"mtype": "subscribe", "object": 1, "property": "setting"}"#);
+        engine.tick();
+        assert_eq!(
+            client.take_events(),
+            vec![serde_json::json!({
+                "mtype": "value",
+                "object": 1,
+                "property": "setting",
+                "value": 0
+            })]
+        );
+        client.send(r#"{"mtype": "set", "object": 1, "property": "setting", "value": 5}"#);
+        engine.tick();
+        assert_eq!(
+            client.take_events(),
+            vec![serde_json::json!({
+                "mtype": "update",
+                "object": 1,
+                "property": "setting",
+                "value": 5
+            })]
+        );
+    }
+
+    #[test]
+    fn root_request_gets_root_object_id_from_welcome() {
+        let (new_session_tx, new_session_rx) = channel();
+        let mut engine = engine(new_session_rx, Some("test".to_string()));
+        let client = TestClient::connect(&new_session_tx);
+        engine.tick();
+        let welcome = client.take_events();
+        assert_eq!(welcome[0]["mtype"], "info");
+        let root = welcome[0]["root"].clone();
+        assert_eq!(root, 1);
+        client.send(r#"{"mtype": "root"}"#);
+        engine.tick();
+        assert_eq!(
+            client.take_events(),
+            vec![serde_json::json!({"mtype": "root", "object": root})]
+        );
+    }
+
+    #[test]
+    fn setter_does_not_get_echo_when_echoes_are_suppressed() {
+        let (new_session_tx, new_session_rx) = channel();
+        let mut engine = engine(new_session_rx, None);
+        engine.state.set_suppress_echoes(true);
+        let setter = TestClient::connect(&new_session_tx);
+        let watcher = TestClient::connect(&new_session_tx);
+        engine.tick();
+        for client in &[&setter, &watcher] {
+            client.send(r#"{"mtype": "subscribe", "object": 1, "property": "setting"}"#);
+        }
+        engine.tick();
+        setter.take_events();
+        watcher.take_events();
+        setter.send(r#"{"mtype": "set", "object": 1, "property": "setting", "value": 5}"#);
+        engine.tick();
+        assert_eq!(setter.take_events(), Vec::<serde_json::Value>::new());
+        assert_eq!(
+            watcher.take_events(),
+            vec![serde_json::json!({
+                "mtype": "update",
+                "object": 1,
+                "property": "setting",
+                "value": 5
+            })]
+        );
+        // Changes the setter didn't make still reach it
+        watcher.send(r#"{"mtype": "set", "object": 1, "property": "setting", "value": 7}"#);
+        engine.tick();
+        assert_eq!(setter.take_events().len(), 1);
+        assert_eq!(watcher.take_events(), Vec::<serde_json::Value>::new());
+    }
+}
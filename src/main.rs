@@ -37,7 +37,7 @@ use std::{
     ops::Deref,
     sync::mpsc::{channel, Receiver, Sender},
     sync::{
-        atomic::{AtomicBool, Ordering::SeqCst},
+        atomic::{AtomicBool, AtomicU64, Ordering::SeqCst},
         Arc, Mutex, RwLock, Weak,
     },
     time::Duration,
@@ -57,62 +57,217 @@ const TIME_BUDGET: f64 = 0.01;
 const MIN_SLEEP_TIME: f64 = TICK_TIME - TIME_BUDGET;
 
 /// By default show error, warn and info messages
-fn init_logger() {
+/// Log lines are prefixed with the server name so logs from multiple servers can be told apart
+fn init_logger(server_name: String) {
+    use std::io::Write;
     env_logger::builder()
-        .format_timestamp_millis()
+        .format(move |buf, record| {
+            writeln!(
+                buf,
+                "[{} {} {} {}] {}",
+                buf.timestamp_millis(),
+                server_name,
+                buf.default_styled_level(record.level()),
+                record.target(),
+                record.args()
+            )
+        })
         .filter_level(log::LevelFilter::Info)
         .parse_default_env()
         .init();
 }
 
-/// This gives us graceful shutdown when the user quits with Ctrl+C on the terminal
-fn init_ctrlc_handler() -> Receiver<()> {
-    let (tx, rx) = channel();
-    ctrlc::set_handler(move || {
-        warn!("processing Ctrl+C from user…");
-        tx.send(()).expect("failed to send quit signal");
-    })
-    .expect("error setting Ctrl+C handler");
-    rx
+/// Reads an IP address from the config, where an empty string means none
+fn optional_ip(conf: &::config::Config, key: &str) -> Option<std::net::IpAddr> {
+    Some(conf.get_str(key).unwrap())
+        .filter(|ip| !ip.is_empty())
+        .map(|ip| {
+            ip.parse()
+                .unwrap_or_else(|e| panic!("invalid {}: {}", key, e))
+        })
 }
 
-#[tokio::main]
-async fn main() {
-    init_logger();
+/// Builds the settings every client connection is created with
+fn connection_config(conf: &::config::Config) -> ConnectionConfig {
+    let mut flush_strategy = FlushStrategy::new(
+        FlushTiming::from_property(conf.get_str("flush_strategy").unwrap())
+            .expect("invalid flush_strategy"),
+    );
+    for (key, timing) in &[
+        ("immediate_events", FlushTiming::Immediate),
+        ("per_tick_events", FlushTiming::PerTick),
+    ] {
+        for name in conf.get_str(key).unwrap().split(',').map(str::trim) {
+            if !name.is_empty() {
+                let kind = EventKind::from_property(name.to_string())
+                    .unwrap_or_else(|e| panic!("invalid {}: {}", key, e));
+                flush_strategy.set(kind, *timing);
+            }
+        }
+    }
+    let delimiter = conf.get_str("delimiter").unwrap();
+    let delimiter = match delimiter.as_bytes() {
+        [] => None,
+        [byte] => Some(*byte),
+        _ => panic!("delimiter {:?} is not a single byte", delimiter),
+    };
+    let record_path = conf.get_str("record_requests").unwrap();
+    let recorder = if record_path.is_empty() {
+        None
+    } else {
+        info!("recording requests to {}", record_path);
+        Some(Arc::new(
+            RequestRecorder::create(&record_path).expect("failed to record"),
+        ))
+    };
+    let object_id_dir = conf.get_str("object_id_dir").unwrap();
+    let object_id_store: Option<Arc<dyn ObjectIdStore>> = if object_id_dir.is_empty() {
+        None
+    } else {
+        info!("persisting client object IDs in {}", object_id_dir);
+        Some(Arc::new(
+            FileObjectIdStore::new(
+                &object_id_dir,
+                conf.get_int("max_object_id_identities").unwrap() as usize,
+                conf.get_int("max_object_ids_per_identity").unwrap() as usize,
+            )
+            .expect("failed to open object ID directory"),
+        ))
+    };
+    ConnectionConfig {
+        read_only: conf.get_bool("read_only").unwrap(),
+        debug_actions: conf.get_bool("debug_actions").unwrap(),
+        timestamp_events: conf.get_bool("timestamp_events").unwrap(),
+        max_values_per_flush: conf.get_int("max_values_per_tick").unwrap() as usize,
+        max_subscriptions: conf.get_int("max_subscriptions").unwrap() as usize,
+        max_pending_gets: conf.get_int("max_pending_gets").unwrap() as usize,
+        max_send_failures: conf.get_int("max_send_failures").unwrap() as u64,
+        decode_limits: DecodeLimits {
+            max_depth: conf.get_int("max_decode_depth").unwrap() as usize,
+            max_elements: conf.get_int("max_decode_elements").unwrap() as usize,
+        },
+        delimiter,
+        recorder,
+        object_id_store,
+        flush_strategy,
+    }
+}
+
+fn main() {
     let conf = config::get().expect("config");
-    let ctrlc_rx = init_ctrlc_handler();
+    let mut runtime = build_runtime(conf.get_int("worker_threads").unwrap() as usize)
+        .expect("failed to build async runtime");
+    let watchdog = runtime.block_on(run(conf));
+    // Shutting down the runtime waits for its tasks, which the watchdog also covers
+    drop(runtime);
+    drop(watchdog);
+}
+
+/// Runs the server until the game stops or the user quits. Returns a watchdog that force-exits if
+/// the rest of shutdown doesn't finish in time (if enabled), which should be dropped once it has.
+async fn run(conf: ::config::Config) -> Option<ShutdownWatchdog> {
+    let server_name = conf.get_str("server_name").unwrap();
+    init_logger(server_name.clone());
+    let ctrlc_rx = init_quit_signal(conf.get_bool("handle_ctrlc").unwrap());
 
     info!("initializing game…");
 
     // Create a server, which will spin up everything required to talk to clients. The server object
-    // is not used directly but needs to be kept in scope for as long as the game runs.
+    // needs to be kept in scope for as long as the game runs.
     let (new_session_tx, new_session_rx) = channel();
-    let _server = Server::new(
-        conf.get_bool("tcp").unwrap(),
-        conf.get_bool("websockets").unwrap(),
-        conf.get_bool("webrtc").unwrap(),
-        conf.get_bool("https").unwrap(),
-        Some(&conf.get_str("http_content").unwrap()),
-        new_session_tx,
-    )
-    .unwrap_or_else(|e| {
+    let server_config = ServerConfig {
+        tcp: conf.get_bool("tcp").unwrap(),
+        tcp_port: None,
+        websockets: conf.get_bool("websockets").unwrap(),
+        webrtc: conf.get_bool("webrtc").unwrap(),
+        webrtc_bind_ip: optional_ip(&conf, "webrtc_bind_ip"),
+        webrtc_public_ip: optional_ip(&conf, "webrtc_public_ip"),
+        webrtc_jitter_depth: conf.get_int("webrtc_jitter_depth").unwrap() as usize,
+        https: conf.get_bool("https").unwrap(),
+        https_cert_path: conf.get_str("https_cert").unwrap(),
+        https_key_path: conf.get_str("https_key").unwrap(),
+        static_content_path: Some(conf.get_str("http_content").unwrap()),
+        open_browser: conf.get_bool("open_browser").unwrap(),
+        browser_url: Some(conf.get_str("browser_url").unwrap()).filter(|url| !url.is_empty()),
+    };
+    let server = Server::new(&server_config, new_session_tx).unwrap_or_else(|e| {
         error!("{}", e);
         panic!("failed to create game");
     });
 
+    let game_config = game::GameConfig {
+        debug_actions: conf.get_bool("debug_actions").unwrap(),
+        scenario: game::Scenario::from_property(conf.get_str("scenario").unwrap())
+            .expect("invalid scenario"),
+        system_seed: conf.get_int("system_seed").unwrap() as u64,
+        max_collision_substeps: conf.get_int("max_collision_substeps").unwrap() as u32,
+        collision_order: game::CollisionOrder::from_property(
+            conf.get_str("collision_order").unwrap(),
+        )
+        .expect("invalid collision_order"),
+        public_config: config::public_values(&conf).expect("failed to read config"),
+        spawn_policy: game::SpawnPolicy::from_property(conf.get_str("spawn_policy").unwrap())
+            .expect("invalid spawn_policy"),
+        lobby: conf.get_bool("lobby").unwrap(),
+        admin_token: conf.get_str("admin_token").unwrap(),
+        epsilon: conf.get_float("physics_epsilon").unwrap(),
+        proximity_alert_scale: conf.get_float("proximity_alert_scale").unwrap(),
+        protect_primary_body: conf.get_bool("protect_primary_body").unwrap(),
+        max_speed: conf.get_float("max_speed").unwrap(),
+        mutual_gravity: conf.get_bool("mutual_gravity").unwrap(),
+    };
+    let tick_config = game_config.clone();
+
     // Create the game engine. The `init` and `physics_tick` callbacks are the entiry points into
     // the `game` module
     let mut engine = Engine::new(
         new_session_rx,
         TICK_TIME,
         conf.get_float("max_game_time").unwrap(),
-        game::init,
-        game::physics_tick,
+        conf.get_int("max_entities").unwrap() as usize,
+        if conf.get_bool("send_server_info").unwrap() {
+            Some(server_name)
+        } else {
+            None
+        },
+        connection_config(&conf),
+        move |state| game::init(state, &game_config),
+        move |state, delta| game::physics_tick(state, delta, &tick_config),
     );
 
+    engine.set_time_scale(conf.get_float("time_scale").unwrap());
+    let idle_kinetic_energy = conf.get_float("idle_kinetic_energy").unwrap();
+    if idle_kinetic_energy > 0.0 {
+        engine.set_idle_when(move |state| game::is_settled(state, idle_kinetic_energy));
+    }
+    let warmup_ticks = conf.get_int("warmup_ticks").unwrap() as u64;
+    engine.set_warmup(warmup_ticks, conf.get_bool("warmup_holds_clock").unwrap());
+    let flush_budget = conf.get_float("flush_budget").unwrap();
+    if flush_budget > 0.0 {
+        engine.set_flush_budget(Duration::from_secs_f64(flush_budget));
+    }
+    let watch_body = conf.get_str("watch_body").unwrap();
+    if !watch_body.is_empty() {
+        info!("logging the state of {} at debug level", watch_body);
+        engine.set_tick_hook(game::watch_body(watch_body));
+    }
+    let replay_path = conf.get_str("replay_requests").unwrap();
+    if !replay_path.is_empty() {
+        info!("replaying requests from {}", replay_path);
+        engine.set_replay(RequestReplay::load(&replay_path).expect("failed to load replay"));
+    }
+    engine
+        .state
+        .set_update_epsilon(conf.get_float("update_epsilon").unwrap());
+    engine
+        .state
+        .set_suppress_echoes(conf.get_bool("suppress_echoes").unwrap());
+
     info!("running game…");
 
     let mut metronome = Metronome::new(TICK_TIME, MIN_SLEEP_TIME);
+    metronome.set_warmup_ticks(warmup_ticks as usize);
+    server.set_ready(true);
     while engine.tick() {
         metronome.sleep();
         if ctrlc_rx.try_recv().is_ok() {
@@ -120,6 +275,28 @@ async fn main() {
             break;
         }
     }
+    server.set_ready(false);
+
+    info!("game stopped");
 
-    info!("game stopped")
+    let shutdown_timeout = conf.get_float("shutdown_timeout").unwrap();
+    let watchdog = if shutdown_timeout > 0.0 {
+        let unfinished = engine.unfinished_connections();
+        Some(ShutdownWatchdog::start(
+            Duration::from_secs_f64(shutdown_timeout),
+            move || {
+                error!(
+                    "shutdown took over {}s, forcing exit with unfinished connections: {:?}",
+                    shutdown_timeout,
+                    unfinished.lock().unwrap()
+                );
+                std::process::exit(1);
+            },
+        ))
+    } else {
+        None
+    };
+    drop(engine);
+    drop(server);
+    watchdog
 }
@@ -0,0 +1,117 @@
+use super::*;
+
+/// Opens URLs in a web browser. A trait so tests don't launch a real one.
+pub trait BrowserLauncher {
+    fn open(&self, url: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// Opens URLs in the system's default browser
+pub struct SystemBrowser;
+
+impl BrowserLauncher for SystemBrowser {
+    fn open(&self, url: &str) -> Result<(), Box<dyn Error>> {
+        let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+            ("open", &[])
+        } else if cfg!(target_os = "windows") {
+            ("cmd", &["/C", "start", ""])
+        } else {
+            ("xdg-open", &[])
+        };
+        std::process::Command::new(program)
+            .args(args)
+            .arg(url)
+            .spawn()
+            .map_err(|e| format!("failed to run {}: {}", program, e))?;
+        Ok(())
+    }
+}
+
+/// The URL a browser on this machine can reach a server bound to the given address at. Unspecified
+/// addresses (0.0.0.0 and ::) become localhost, and the scheme's default port is left out.
+pub fn browser_url(https: bool, addr: SocketAddr) -> String {
+    let (scheme, default_port) = if https { ("https", 443) } else { ("http", 80) };
+    let host = match addr.ip() {
+        ip if ip.is_unspecified() => "localhost".to_string(),
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    };
+    if addr.port() == default_port {
+        format!("{}://{}/", scheme, host)
+    } else {
+        format!("{}://{}:{}/", scheme, host, addr.port())
+    }
+}
+
+/// Opens override_url if set, otherwise the URL of the HTTP server (if any). Failing to open a
+/// browser is logged and otherwise ignored.
+pub fn open_browser(
+    launcher: &dyn BrowserLauncher,
+    override_url: Option<&str>,
+    server: Option<(bool, SocketAddr)>,
+) {
+    let url = match (override_url, server) {
+        (Some(url), _) => url.to_string(),
+        (None, Some((https, addr))) => browser_url(https, addr),
+        (None, None) => {
+            warn!("not opening a browser, as there is no HTTP server");
+            return;
+        }
+    };
+    info!("opening {} in a browser", url);
+    if let Err(e) = launcher.open(&url) {
+        warn!("failed to open browser: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct MockLauncher(RefCell<Vec<String>>);
+
+    impl BrowserLauncher for MockLauncher {
+        fn open(&self, url: &str) -> Result<(), Box<dyn Error>> {
+            self.0.borrow_mut().push(url.to_string());
+            Ok(())
+        }
+    }
+
+    fn opened(override_url: Option<&str>, server: Option<(bool, SocketAddr)>) -> Vec<String> {
+        let launcher = MockLauncher(RefCell::new(Vec::new()));
+        open_browser(&launcher, override_url, server);
+        launcher.0.into_inner()
+    }
+
+    #[test]
+    fn opened_url_matches_server_scheme_host_and_port() {
+        assert_eq!(
+            opened(None, Some((false, "127.0.0.1:56560".parse().unwrap()))),
+            vec!["http://127.0.0.1:56560/"]
+        );
+        assert_eq!(
+            opened(None, Some((true, "192.168.1.7:443".parse().unwrap()))),
+            vec!["https://192.168.1.7/"]
+        );
+        assert_eq!(
+            opened(None, Some((true, "[::1]:8443".parse().unwrap()))),
+            vec!["https://[::1]:8443/"]
+        );
+        assert_eq!(
+            opened(None, Some((false, "0.0.0.0:80".parse().unwrap()))),
+            vec!["http://localhost/"]
+        );
+    }
+
+    #[test]
+    fn override_url_is_opened_instead() {
+        assert_eq!(
+            opened(
+                Some("https://example.com/play"),
+                Some((false, "127.0.0.1:56560".parse().unwrap()))
+            ),
+            vec!["https://example.com/play"]
+        );
+        assert_eq!(opened(None, None), Vec::<String>::new());
+    }
+}
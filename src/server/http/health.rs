@@ -0,0 +1,40 @@
+use super::*;
+use warp::http::StatusCode;
+
+/// Serves `/health` for load balancers: 200 while the engine is ticking and 503 otherwise (during
+/// startup and shutdown). Doesn't touch the game, so no connection is needed.
+pub fn health_filter(ready: Arc<AtomicBool>) -> GenericFilter {
+    warp::path("health")
+        .and(warp::path::end())
+        .map(move || {
+            let reply = if ready.load(SeqCst) {
+                warp::reply::with_status("ok", StatusCode::OK)
+            } else {
+                warp::reply::with_status("unavailable", StatusCode::SERVICE_UNAVAILABLE)
+            };
+            Box::new(reply) as Box<dyn warp::Reply>
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health_status(filter: &GenericFilter) -> StatusCode {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(warp::test::request().path("/health").reply(filter))
+            .status()
+    }
+
+    #[test]
+    fn reports_unavailable_until_ready_and_after_drain() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let filter = health_filter(ready.clone());
+        assert_eq!(health_status(&filter), StatusCode::SERVICE_UNAVAILABLE);
+        ready.store(true, SeqCst);
+        assert_eq!(health_status(&filter), StatusCode::OK);
+        ready.store(false, SeqCst);
+        assert_eq!(health_status(&filter), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -1,5 +1,9 @@
 use super::*;
 
+mod browser;
+mod health;
 mod http_server;
 
+pub use browser::{open_browser, SystemBrowser};
+pub use health::health_filter;
 pub use http_server::HttpServer;
@@ -11,7 +11,7 @@ mod tcp;
 mod webrtc;
 mod websocket;
 
-pub use server::Server;
+pub use server::{Server, ServerConfig};
 pub use session::{InboundBundleHandler, Session, SessionBuilder};
 
 use http::*;
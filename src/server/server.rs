@@ -1,4 +1,5 @@
 use super::*;
+use std::path::Path;
 
 const HTTP_PORT: u16 = 80;
 const HTTPS_PORT: u16 = 443;
@@ -14,74 +15,129 @@ pub trait ServerComponent: Debug {}
 /// Creates and owns the various components that allow clients to connect
 pub struct Server {
     _components: Vec<Box<dyn ServerComponent>>,
+    ready: Arc<AtomicBool>,
+}
+
+/// Which network protocols the server should listen on, generally loaded from the config
+#[derive(Clone, Debug, Default)]
+pub struct ServerConfig {
+    pub tcp: bool,
+    /// Port the TCP listener binds to, if not the standard one (0 picks any free port)
+    pub tcp_port: Option<u16>,
+    pub websockets: bool,
+    pub webrtc: bool,
+    /// Address the WebRTC server binds to, if not the detected non-loopback one
+    pub webrtc_bind_ip: Option<IpAddr>,
+    /// Address advertised to WebRTC clients, if not the bind address (such as when behind NAT)
+    pub webrtc_public_ip: Option<IpAddr>,
+    /// If not 0, clients number their WebRTC datagrams and they are reordered within this many
+    pub webrtc_jitter_depth: usize,
+    /// Serve HTTP over TLS on the standard port (and redirect plain HTTP to it) instead of
+    /// unencrypted HTTP on the development port
+    pub https: bool,
+    pub https_cert_path: String,
+    pub https_key_path: String,
+    /// Directory served to web clients, if any
+    pub static_content_path: Option<String>,
+    /// Open the HTTP server in a browser once it's running
+    pub open_browser: bool,
+    /// Opened instead of the HTTP server's own URL, for when that isn't how it's reached
+    pub browser_url: Option<String>,
+}
+
+impl ServerConfig {
+    /// WebSockets, WebRTC and static content are all served over HTTP
+    fn needs_http(&self) -> bool {
+        self.websockets || self.webrtc || self.static_content_path.is_some()
+    }
+
+    /// Checks that everything the config points to on the filesystem is there
+    fn validate(&self, fs: &dyn Filesystem) -> Result<(), Box<dyn Error>> {
+        if let Some(path) = &self.static_content_path {
+            fs.check_readable_dir(Path::new(path)).map_err(|e| {
+                format!(
+                    "static content path {:?} is not a readable directory: {}",
+                    path, e
+                )
+            })?;
+        }
+        Ok(())
+    }
 }
 
 impl Server {
     pub fn new(
-        enable_tcp: bool,
-        enable_websockets: bool,
-        enable_webrtc: bool,
-        enable_https: bool,
-        static_content_path: Option<&str>,
+        config: &ServerConfig,
         new_session_tx: Sender<Box<dyn SessionBuilder>>,
     ) -> Result<Self, Box<dyn Error>> {
+        config.validate(&RealFilesystem)?;
         let mut components: Vec<Box<dyn ServerComponent>> = Vec::new();
+        // If the HTTP server clients use is HTTPS, and its address
+        let mut browser_target = None;
+        let ready = Arc::new(AtomicBool::new(false));
 
-        // Is there a simpler way to make an empty warp filter?
-        let mut warp_filter = warp::any()
-            .and_then(|| async { Err::<Box<dyn warp::Reply>, _>(warp::reject::not_found()) })
-            .boxed();
+        let mut warp_filter = health_filter(ready.clone());
 
-        if enable_tcp {
+        if config.tcp {
             let ip = get_ip(None, Some(IpVersion::V4), Some(true))?;
-            let addr = SocketAddr::new(ip, TCP_PORT);
+            let addr = SocketAddr::new(ip, config.tcp_port.unwrap_or(TCP_PORT));
             let tcp = TcpListener::new(new_session_tx.clone(), addr)
                 .map_err(|e| format!("failed to create TcpListener: {}", e))?;
             components.push(Box::new(tcp));
         }
 
-        if enable_websockets {
+        if config.websockets {
             let (filter, server) = WebsocketServer::new(new_session_tx.clone())
                 .map_err(|e| format!("failed to create WebSocket server: {}", e))?;
             components.push(Box::new(server));
             warp_filter = warp_filter.or(filter).unify().boxed();
         }
 
-        if enable_webrtc {
+        if config.webrtc {
             // Firefox doesn't work when WebRTC is running on a loopback interface. This address is
             // shared automatically by webrtc_unreliable.
-            let ip = get_ip(None, Some(IpVersion::V4), Some(false))?;
-            let addr = SocketAddr::new(ip, WEB_RTC_PORT);
-            let (rtc_warp_filter, webrtc) = WebrtcServer::new(addr, new_session_tx)
-                .map_err(|e| format!("failed to create WebrtcServer: {}", e))?;
+            let bind_ip = match config.webrtc_bind_ip {
+                Some(ip) => ip,
+                None => get_ip(None, Some(IpVersion::V4), Some(false))?,
+            };
+            let bind_addr = SocketAddr::new(bind_ip, WEB_RTC_PORT);
+            let public_addr =
+                SocketAddr::new(config.webrtc_public_ip.unwrap_or(bind_ip), WEB_RTC_PORT);
+            let (rtc_warp_filter, webrtc) = WebrtcServer::new(
+                bind_addr,
+                public_addr,
+                config.webrtc_jitter_depth,
+                new_session_tx,
+            )
+            .map_err(|e| format!("failed to create WebrtcServer: {}", e))?;
             components.push(Box::new(webrtc));
             warp_filter = warp_filter.or(rtc_warp_filter).unify().boxed();
         }
 
-        if let Some(static_content_path) = static_content_path {
-            let static_content_filter: GenericFilter =
-                warp::fs::dir(static_content_path.to_string())
-                    .map(|reply| Box::new(reply) as Box<dyn warp::Reply>)
-                    .boxed();
+        if let Some(static_content_path) = &config.static_content_path {
+            let static_content_filter: GenericFilter = warp::fs::dir(static_content_path.clone())
+                .map(|reply| Box::new(reply) as Box<dyn warp::Reply>)
+                .boxed();
             warp_filter = warp_filter.or(static_content_filter).unify().boxed();
         }
 
-        if enable_https {
+        if config.https {
             let ip = get_ip(None, Some(IpVersion::V4), Some(false))?;
 
             let https_addr = SocketAddr::new(ip, HTTPS_PORT);
             let https_server = HttpServer::new_encrypted(
                 warp_filter,
                 https_addr,
-                "../ssl/cert.pem",
-                "../ssl/privkey.pem",
+                &config.https_cert_path,
+                &config.https_key_path,
             )?;
             components.push(Box::new(https_server));
+            browser_target = Some((true, https_addr));
 
             let http_addr = SocketAddr::new(ip, HTTP_PORT);
             let http_redirect_server = HttpServer::new_https_redirect(http_addr)?;
             components.push(Box::new(http_redirect_server));
-        } else {
+        } else if config.needs_http() {
             // This should resolve to localhost for testing. We need to point the web app to this
             // address (at time of writing that's done with a proxy rule in vue.config.js).
             let ip = get_ip(None, Some(IpVersion::V4), Some(true))?;
@@ -89,14 +145,77 @@ impl Server {
             let http_addr = SocketAddr::new(ip, DEVEL_HTTP_PORT);
             let http_server = HttpServer::new_unencrypted(warp_filter, http_addr)?;
             components.push(Box::new(http_server));
+            browser_target = Some((false, http_addr));
         }
 
         for component in &components {
             info!("{:?}", component);
         }
 
+        if config.open_browser {
+            open_browser(
+                &SystemBrowser,
+                config.browser_url.as_deref(),
+                browser_target,
+            );
+        }
+
         Ok(Self {
             _components: components,
+            ready,
         })
     }
+
+    /// Whether `/health` reports the server as up. Should only be set while the engine is ticking.
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tcp_only_config_starts_tcp_listener_and_no_http_server() {
+        let (tx, _rx) = channel();
+        let config = ServerConfig {
+            tcp: true,
+            // Any free port, so this doesn't collide with a running server or other tests
+            tcp_port: Some(0),
+            ..ServerConfig::default()
+        };
+        // No tokio runtime is needed, as no HTTP server is started
+        let server = Server::new(&config, tx).unwrap();
+        let components: Vec<String> = server
+            ._components
+            .iter()
+            .map(|component| format!("{:?}", component))
+            .collect();
+        assert_eq!(components.len(), 1);
+        assert!(components[0].starts_with("TcpListener"));
+    }
+
+    #[test]
+    fn missing_static_content_dir_is_an_error() {
+        let config = ServerConfig {
+            static_content_path: Some("../web/dist".to_string()),
+            ..ServerConfig::default()
+        };
+        let e = config
+            .validate(&MockFilesystem::new(&["../web"]))
+            .unwrap_err();
+        assert!(e.to_string().contains("../web/dist"));
+    }
+
+    #[test]
+    fn existing_static_content_dir_is_valid() {
+        let config = ServerConfig {
+            static_content_path: Some("../web/dist".to_string()),
+            ..ServerConfig::default()
+        };
+        assert!(config
+            .validate(&MockFilesystem::new(&["../web/dist"]))
+            .is_ok());
+    }
 }
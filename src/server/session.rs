@@ -15,6 +15,11 @@ pub trait SessionBuilder: Send + Debug {
         self: Box<Self>,
         handler: Box<dyn InboundBundleHandler>,
     ) -> Result<Box<dyn Session>, Box<dyn Error>>;
+
+    /// The data format negotiated by the session layer (ex a WebSocket subprotocol), if any
+    fn format(&self) -> Option<&str> {
+        None
+    }
 }
 
 /// Represents a low-level network connection. Abstracts over things like Unix
@@ -24,7 +29,9 @@ pub trait SessionBuilder: Send + Debug {
 pub trait Session: Send + Debug {
     /// Sends a bundle of data to the client. Bundles should be assumed to be unreliable+unordered.
     /// This errors if there's an issue with the underlying connection, or if data is longer
-    /// than max_packet_len() has ever been.
+    /// than max_packet_len() has ever been. If the session can't take more data right now it
+    /// returns an io::Error of kind WouldBlock without sending any of the bundle, and the bundle
+    /// can be retried later.
     /// TODO: this should take an Arc<[u8]> so it can be sent on channels without being copied
     fn yeet_bundle(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>>;
     /// The longest a packet should be. This may change (if, for example, long
@@ -32,6 +39,11 @@ pub trait Session: Send + Debug {
     /// error to send a packet with a previously-allowed length (this would be
     /// impossible to prevent in a thread-safe way).
     fn max_packet_len(&self) -> usize;
+    /// Sends anything the session is still holding on to from earlier bundles. Called on every
+    /// connection flush, even if there are no new bundles to send.
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
     /// Close the session, which should result in its inbound handler getting a close() (although
     /// not necessarily immediately)
     fn close(&mut self);
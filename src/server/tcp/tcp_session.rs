@@ -23,6 +23,21 @@ fn try_to_read_data(
     }
 }
 
+/// Writes as much of data as the stream will take without blocking, and returns how much that was
+fn write_without_blocking(stream: &mut TcpStream, data: &[u8]) -> std::io::Result<usize> {
+    let mut written = 0;
+    while written < data.len() {
+        match stream.write(&data[written..]) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(len) => written += len,
+            Err(ref e) if e.kind() == WouldBlock => break,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(written)
+}
+
 #[derive(Debug)]
 pub struct TcpSessionBuilder {
     stream: TcpStream,
@@ -50,6 +65,7 @@ impl SessionBuilder for TcpSessionBuilder {
             stream: self.stream,
             handler,
             mio_poll_thread: Some(thread),
+            unsent: Vec::new(),
         }))
     }
 }
@@ -59,6 +75,9 @@ struct TcpSession {
     /// Note that the mutex remains locked by the poll thread for as long as it's alive
     handler: Arc<Mutex<Box<dyn InboundBundleHandler>>>,
     mio_poll_thread: Option<Box<dyn Drop + Send>>,
+    /// The end of a bundle the stream only took part of. It is written before anything else so
+    /// bundles don't get mixed together.
+    unsent: Vec<u8>,
 }
 
 impl Debug for TcpSession {
@@ -69,7 +88,15 @@ impl Debug for TcpSession {
 
 impl Session for TcpSession {
     fn yeet_bundle(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
-        self.stream.write_all(data)?;
+        self.flush()?;
+        if !self.unsent.is_empty() {
+            return Err(Box::new(std::io::Error::from(WouldBlock)));
+        }
+        let written = write_without_blocking(&mut self.stream, data)?;
+        if written == 0 && !data.is_empty() {
+            return Err(Box::new(std::io::Error::from(WouldBlock)));
+        }
+        self.unsent.extend_from_slice(&data[written..]);
         Ok(())
     }
 
@@ -77,6 +104,14 @@ impl Session for TcpSession {
         std::usize::MAX
     }
 
+    fn flush(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.unsent.is_empty() {
+            let written = write_without_blocking(&mut self.stream, &self.unsent)?;
+            self.unsent.drain(..written);
+        }
+        Ok(())
+    }
+
     fn close(&mut self) {
         self.mio_poll_thread = None;
         self.stream
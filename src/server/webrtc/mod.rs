@@ -16,5 +16,5 @@ mod webrtc_warp_filter;
 pub use webrtc_server::WebrtcServer;
 
 use webrtc_dispatcher::{WebrtcDispatcher, WebrtcMessage};
-use webrtc_session::WebrtcSession;
+use webrtc_session::{JitterBuffer, WebrtcSession};
 use webrtc_warp_filter::webrtc_warp_filter;
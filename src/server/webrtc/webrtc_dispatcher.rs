@@ -69,10 +69,44 @@ impl Drop for DispatchTarget {
     }
 }
 
+/// The inbound side of a session. If jitter buffering is on datagrams are put back in order before
+/// being dispatched.
+struct InboundSession {
+    jitter_buffer: Option<JitterBuffer>,
+    target: DispatchTarget,
+}
+
+impl InboundSession {
+    fn new(jitter_depth: usize) -> Self {
+        Self {
+            jitter_buffer: if jitter_depth > 0 {
+                Some(JitterBuffer::new(jitter_depth))
+            } else {
+                None
+            },
+            target: DispatchTarget::new(),
+        }
+    }
+
+    fn dispatch(&mut self, data: &[u8]) {
+        match &mut self.jitter_buffer {
+            Some(jitter_buffer) => {
+                for datagram in jitter_buffer.push_datagram(data) {
+                    self.target.dispatch(&datagram);
+                }
+            }
+            None => self.target.dispatch(data),
+        }
+    }
+}
+
 struct DispatcherInner {
-    session_map: HashMap<SocketAddr, DispatchTarget>,
+    session_map: HashMap<SocketAddr, InboundSession>,
     new_session_tx: Sender<Box<dyn SessionBuilder>>,
     outbound_tx: tokio::sync::mpsc::Sender<(SocketAddr, WebrtcMessage)>,
+    /// If not 0, inbound datagrams start with a sequence number and are reordered within this many
+    /// datagrams, see JitterBuffer
+    jitter_depth: usize,
 }
 
 /// Dispatches inbound data to the correct session based on source address
@@ -89,11 +123,13 @@ impl WebrtcDispatcher {
     pub fn new(
         new_session_tx: Sender<Box<dyn SessionBuilder>>,
         outbound_tx: tokio::sync::mpsc::Sender<(SocketAddr, WebrtcMessage)>,
+        jitter_depth: usize,
     ) -> Self {
         Self(Arc::new(Mutex::new(DispatcherInner {
             session_map: HashMap::new(),
             new_session_tx,
             outbound_tx,
+            jitter_depth,
         })))
     }
 
@@ -113,6 +149,7 @@ impl WebrtcDispatcher {
             .session_map
             .get_mut(addr)
             .ok_or(format!("can not set handler for unknown address {}", addr))?
+            .target
             .set_handler(handler)
     }
 
@@ -121,7 +158,7 @@ impl WebrtcDispatcher {
             Ok(mut locked) => match locked.session_map.get_mut(addr) {
                 Some(target) => target.dispatch(data),
                 None => {
-                    let mut target = DispatchTarget::new();
+                    let mut target = InboundSession::new(locked.jitter_depth);
                     target.dispatch(data);
                     locked.session_map.insert(*addr, target);
                     let session =
@@ -166,7 +203,7 @@ mod tests {
     ) {
         let (new_session_tx, new_session_rx) = channel();
         let (outbound_tx, outbound_rx) = tokio::sync::mpsc::channel(10);
-        let dispatcher = WebrtcDispatcher::new(new_session_tx, outbound_tx);
+        let dispatcher = WebrtcDispatcher::new(new_session_tx, outbound_tx, 0);
         (new_session_rx, outbound_rx, dispatcher)
     }
 
@@ -311,4 +348,33 @@ mod tests {
             vec![MockInbound::Data(test_data(1)), MockInbound::Close]
         );
     }
+
+    #[test]
+    fn reorders_datagrams_with_jitter_buffer() {
+        let (new_session_tx, new_session) = channel();
+        let (outbound_tx, _outbound_rx) = tokio::sync::mpsc::channel(10);
+        let dispatcher = WebrtcDispatcher::new(new_session_tx, outbound_tx, 4);
+        let datagram = |sequence: u64| {
+            let mut datagram = sequence.to_be_bytes().to_vec();
+            datagram.extend(test_data(sequence as u8));
+            datagram
+        };
+        dispatcher.dispatch_inbound(&test_addr(1), &datagram(1));
+        dispatcher.dispatch_inbound(&test_addr(1), &datagram(0));
+        let builder = new_session
+            .recv_timeout(Duration::from_secs(1))
+            .expect("no session builder");
+        let inbound = MockInboundHandler::new();
+        let _ = builder.build(Box::new(inbound.clone()));
+        dispatcher.dispatch_inbound(&test_addr(1), &datagram(3));
+        dispatcher.dispatch_inbound(&test_addr(1), &datagram(2));
+        // Already released, so dropped
+        dispatcher.dispatch_inbound(&test_addr(1), &datagram(1));
+        assert_eq!(
+            inbound.get(),
+            (0..4)
+                .map(|i| MockInbound::Data(test_data(i)))
+                .collect::<Vec<_>>()
+        );
+    }
 }
@@ -107,19 +107,25 @@ async fn run_server(
 /// Accepts connections and listens for incoming data on all active connections.
 pub struct WebrtcServer {
     listen_addr: SocketAddr,
+    public_addr: SocketAddr,
     abort_handle: Option<future::AbortHandle>,
     join_handle: Option<tokio::task::JoinHandle<Result<(), future::Aborted>>>,
 }
 
 impl WebrtcServer {
+    /// Binds to `listen_addr` but tells clients (in the SDP answer) to connect to `public_addr`,
+    /// which differ when behind NAT. If jitter_depth is not 0, inbound datagrams are reordered
+    /// (see JitterBuffer).
     pub fn new(
         listen_addr: SocketAddr,
+        public_addr: SocketAddr,
+        jitter_depth: usize,
         new_session_tx: Sender<Box<dyn SessionBuilder>>,
     ) -> Result<(GenericFilter, Self), Box<dyn Error>> {
-        let webrtc_server = block_on(webrtc_unreliable::Server::new(listen_addr, listen_addr))?;
+        let webrtc_server = block_on(webrtc_unreliable::Server::new(listen_addr, public_addr))?;
         let endpoint = webrtc_server.session_endpoint();
         let (outbound_tx, outbound_rx) = tokio::sync::mpsc::channel(OUTBOUND_BUNDLE_BUFFER_SIZE);
-        let dispatcher = WebrtcDispatcher::new(new_session_tx, outbound_tx);
+        let dispatcher = WebrtcDispatcher::new(new_session_tx, outbound_tx, jitter_depth);
 
         // Use futures::future::Abortable to kill the server on command
         let (abort_handle, abort_registration) = future::AbortHandle::new_pair();
@@ -133,6 +139,7 @@ impl WebrtcServer {
             webrtc_warp_filter(endpoint),
             Self {
                 listen_addr,
+                public_addr,
                 abort_handle: Some(abort_handle),
                 join_handle: Some(join_handle),
             },
@@ -152,8 +159,47 @@ impl Drop for WebrtcServer {
 
 impl Debug for WebrtcServer {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "WebrtcServer on {:?}", self.listen_addr)
+        if self.public_addr == self.listen_addr {
+            write!(f, "WebrtcServer on {:?}", self.listen_addr)
+        } else {
+            write!(
+                f,
+                "WebrtcServer on {:?} (public {:?})",
+                self.listen_addr, self.public_addr
+            )
+        }
     }
 }
 
 impl ServerComponent for WebrtcServer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFER: &str =
+        "v=0\r\na=ice-ufrag:abcd\r\na=ice-pwd:efghijklmnopqrstuvwxyz\r\na=mid:0\r\n";
+
+    #[test]
+    fn sdp_answer_advertises_public_addr() {
+        run_with_tokio(|| {
+            let socket = provision_socket();
+            let public_addr = SocketAddr::new("203.0.113.7".parse().unwrap(), socket.port());
+            let (tx, _rx) = channel();
+            let (filter, server) = WebrtcServer::new(*socket, public_addr, 0, tx).unwrap();
+            assert_eq!(server.listen_addr, *socket);
+            let response = block_on(
+                warp::test::request()
+                    .method("POST")
+                    .path("/rtc")
+                    .body(OFFER)
+                    .reply(&filter),
+            );
+            assert_eq!(response.status(), warp::http::StatusCode::OK);
+            let answer = String::from_utf8(response.body().to_vec()).unwrap();
+            let candidate = format!("203.0.113.7 {} typ host", socket.port());
+            assert!(answer.contains(&candidate), "{}", answer);
+            assert!(!answer.contains(&socket.ip().to_string()), "{}", answer);
+        });
+    }
+}
@@ -1,4 +1,70 @@
 use super::*;
+use std::collections::BTreeMap;
+
+/// Length of the sequence number at the start of each inbound datagram when jitter buffering is on
+const SEQUENCE_LEN: usize = 8;
+
+/// Holds inbound datagrams that arrive slightly out of order so they can be released in sequence.
+/// Each datagram needs a sequence number that starts at 0 and goes up by one per datagram. Once
+/// more than depth datagrams are waiting on a gap the gap is given up on, and anything older than
+/// what has already been released is dropped.
+pub struct JitterBuffer {
+    depth: usize,
+    next: u64,
+    pending: BTreeMap<u64, Vec<u8>>,
+}
+
+impl JitterBuffer {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            next: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Takes a datagram that starts with its sequence number (a big-endian u64), and returns the
+    /// datagrams that are ready to be decoded without their sequence numbers
+    pub fn push_datagram(&mut self, datagram: &[u8]) -> Vec<Vec<u8>> {
+        if datagram.len() < SEQUENCE_LEN {
+            warn!(
+                "dropping {} byte WebRTC datagram, which is too short to have a sequence number",
+                datagram.len()
+            );
+            return Vec::new();
+        }
+        let (sequence, data) = datagram.split_at(SEQUENCE_LEN);
+        let mut bytes = [0; SEQUENCE_LEN];
+        bytes.copy_from_slice(sequence);
+        self.push(u64::from_be_bytes(bytes), data.to_vec())
+    }
+
+    /// Returns the datagrams that are ready to be decoded, in order
+    fn push(&mut self, sequence: u64, data: Vec<u8>) -> Vec<Vec<u8>> {
+        if sequence < self.next {
+            trace!(
+                "dropping datagram {} because {} has already been released",
+                sequence,
+                self.next - 1
+            );
+            return Vec::new();
+        }
+        self.pending.insert(sequence, data);
+        let mut ready = Vec::new();
+        loop {
+            while let Some(data) = self.pending.remove(&self.next) {
+                ready.push(data);
+                self.next += 1;
+            }
+            if self.pending.len() > self.depth {
+                // Waited long enough, skip ahead to the oldest datagram we have
+                self.next = *self.pending.keys().next().unwrap();
+            } else {
+                return ready;
+            }
+        }
+    }
+}
 
 /// Implements both the session and session builder (session builder turns into session when built)
 pub struct WebrtcSession {
@@ -99,3 +165,60 @@ impl Debug for WebrtcSession {
         write!(f, "WebrtcSession for {}", self.addr)
     }
 }
+
+#[cfg(test)]
+mod jitter_tests {
+    use super::*;
+
+    fn push_all(buffer: &mut JitterBuffer, sequences: &[u64]) -> Vec<Vec<u8>> {
+        sequences
+            .iter()
+            .flat_map(|seq| buffer.push(*seq, vec![*seq as u8]))
+            .collect()
+    }
+
+    #[test]
+    fn in_order_datagrams_are_released_immediately() {
+        let mut buffer = JitterBuffer::new(3);
+        assert_eq!(buffer.push(0, vec![0]), vec![vec![0]]);
+        assert_eq!(buffer.push(1, vec![1]), vec![vec![1]]);
+    }
+
+    #[test]
+    fn reordered_datagrams_within_window_come_out_in_order() {
+        let mut buffer = JitterBuffer::new(3);
+        let released = push_all(&mut buffer, &[1, 0, 3, 4, 2]);
+        assert_eq!(released, vec![vec![0], vec![1], vec![2], vec![3], vec![4]]);
+    }
+
+    #[test]
+    fn gap_is_skipped_when_window_fills() {
+        let mut buffer = JitterBuffer::new(2);
+        assert_eq!(push_all(&mut buffer, &[1, 2]), Vec::<Vec<u8>>::new());
+        assert_eq!(push_all(&mut buffer, &[3]), vec![vec![1], vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn too_late_datagram_is_dropped() {
+        let mut buffer = JitterBuffer::new(2);
+        push_all(&mut buffer, &[1, 2, 3]);
+        assert_eq!(buffer.push(0, vec![0]), Vec::<Vec<u8>>::new());
+        assert_eq!(buffer.push(4, vec![4]), vec![vec![4]]);
+    }
+
+    #[test]
+    fn sequence_number_is_read_from_datagram() {
+        let mut buffer = JitterBuffer::new(2);
+        let datagram = |sequence: u64, data: &[u8]| {
+            let mut datagram = sequence.to_be_bytes().to_vec();
+            datagram.extend_from_slice(data);
+            datagram
+        };
+        assert!(buffer.push_datagram(&datagram(1, b"b")).is_empty());
+        assert_eq!(
+            buffer.push_datagram(&datagram(0, b"a")),
+            vec![b"a".to_vec(), b"b".to_vec()]
+        );
+        assert!(buffer.push_datagram(&[1, 2, 3]).is_empty());
+    }
+}
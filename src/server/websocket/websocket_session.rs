@@ -61,11 +61,21 @@ async fn run_websocket(
 pub struct WebsocketSessionBuilder {
     addr: Option<SocketAddr>,
     websocket: warp::ws::WebSocket,
+    /// The subprotocol chosen during the handshake
+    format: Option<&'static str>,
 }
 
 impl WebsocketSessionBuilder {
-    pub fn new(addr: Option<SocketAddr>, websocket: warp::ws::WebSocket) -> Self {
-        Self { addr, websocket }
+    pub fn new(
+        addr: Option<SocketAddr>,
+        websocket: warp::ws::WebSocket,
+        format: Option<&'static str>,
+    ) -> Self {
+        Self {
+            addr,
+            websocket,
+            format,
+        }
     }
 }
 
@@ -94,6 +104,10 @@ impl SessionBuilder for WebsocketSessionBuilder {
             outbound_tx: Some(outbound_tx),
         }))
     }
+
+    fn format(&self) -> Option<&str> {
+        self.format
+    }
 }
 
 pub struct WebsocketSession {
@@ -108,7 +122,10 @@ impl Session for WebsocketSession {
             match outbound_tx.try_send(data.to_vec()) {
                 Ok(()) => Ok(()),
                 Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
-                    Err("WebSocket outbound channel is full (can't send bundle)".into())
+                    Err(Box::new(std::io::Error::new(
+                        std::io::ErrorKind::WouldBlock,
+                        "WebSocket outbound channel is full",
+                    )))
                 }
                 Err(tokio::sync::mpsc::error::TrySendError::Closed(_)) => {
                     Err("WebSocket outbound channel closed (can't send bundle)".into())
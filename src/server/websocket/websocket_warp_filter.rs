@@ -8,19 +8,35 @@ pub fn websocket_warp_filter(new_session_tx: Sender<Box<dyn SessionBuilder>>) ->
         .and(warp::addr::remote())
         // The `ws()` filter will prepare the Websocket handshake.
         .and(warp::ws())
-        .map(move |addr: Option<SocketAddr>, ws: warp::ws::Ws| {
-            let new_session_tx = new_session_tx.clone();
-            // And then our closure will be called when it completes.
-            Box::new(ws.on_upgrade(move |websocket| {
-                if let Err(e) = new_session_tx
-                    .lock()
-                    .unwrap()
-                    .send(Box::new(WebsocketSessionBuilder::new(addr, websocket)))
-                {
-                    warn!("creating WebSocket session: {}", e);
+        .and(warp::header::optional::<String>("sec-websocket-protocol"))
+        .map(
+            move |addr: Option<SocketAddr>, ws: warp::ws::Ws, protocols: Option<String>| {
+                let new_session_tx = new_session_tx.clone();
+                // Clients may request a list of subprotocols, which we use to pick the data format.
+                // If none are supported the connection falls back to JSON.
+                let format = protocols
+                    .as_deref()
+                    .and_then(|protocols| select_format(protocols.split(',').map(str::trim)));
+                // And then our closure will be called when it completes.
+                let reply =
+                    ws.on_upgrade(move |websocket| {
+                        if let Err(e) = new_session_tx.lock().unwrap().send(Box::new(
+                            WebsocketSessionBuilder::new(addr, websocket, format),
+                        )) {
+                            warn!("creating WebSocket session: {}", e);
+                        }
+                        futures::future::ready(())
+                    });
+                match format {
+                    // Browsers require the chosen subprotocol to be echoed back in the handshake
+                    Some(format) => Box::new(warp::reply::with_header(
+                        reply,
+                        "sec-websocket-protocol",
+                        format,
+                    )) as Box<dyn warp::Reply>,
+                    None => Box::new(reply) as Box<dyn warp::Reply>,
                 }
-                futures::future::ready(())
-            })) as Box<dyn warp::Reply>
-        })
+            },
+        )
         .boxed()
 }